@@ -0,0 +1,95 @@
+//! Drive the emulator's run loop with a video sink and an input source of your own.
+//! This sink draws frames as text, the input source presses Start now and then and quits after a few seconds.
+//! Without a ROM, this runs the demo cartridge.
+//!
+//!     cargo run --example custom_sink -- [ROM]
+
+use ferrum::audio::NullSink;
+use ferrum::frontend::{Hotkey, InputSource, Status, VideoSink};
+use ferrum::gb::GameBoy;
+use ferrum::joypad::Buttons;
+use ferrum::ppu::{SCREEN_HEIGHT, SCREEN_PIXELS, SCREEN_WIDTH};
+use ferrum::testrom;
+use std::{env, fs, process};
+
+/// Displayed frames to run for, 10 seconds, the boot ROM takes over 5.
+const FRAMES: u32 = 600;
+
+/// Keeps the last frame, and draws it as text on demand, a character per 2x4 pixels.
+struct TextSink {
+    frame: Vec<u32>,
+    status: String,
+}
+
+impl VideoSink for TextSink {
+    fn frame(&mut self, frame: &[u32; SCREEN_PIXELS]) {
+        self.frame.copy_from_slice(frame);
+    }
+
+    fn status(&mut self, status: &Status) {
+        self.status = status.to_string();
+    }
+}
+
+impl TextSink {
+    fn draw(&self) -> String {
+        const SHADES: [char; 4] = ['#', '+', '.', ' '];
+        let mut text = String::new();
+        for y in (0..SCREEN_HEIGHT).step_by(4) {
+            for x in (0..SCREEN_WIDTH).step_by(2) {
+                let pixel = self.frame[y * SCREEN_WIDTH + x];
+                let level = ((pixel >> 16 & 0xFF) + (pixel >> 8 & 0xFF) + (pixel & 0xFF)) / 3;
+                text.push(SHADES[(level as usize * SHADES.len() / 256).min(SHADES.len() - 1)]);
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Presses Start for a few frames every second, and quits after FRAMES displayed frames.
+struct Script {
+    polls: u32,
+    shown: u32,
+}
+
+impl InputSource for Script {
+    fn poll(&mut self) -> Buttons {
+        self.polls += 1;
+        match self.polls % 60 < 5 {
+            true => Buttons::START,
+            false => Buttons::empty(),
+        }
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        self.shown += 1;
+        match self.shown >= FRAMES {
+            true => vec![Hotkey::Quit],
+            false => Vec::new(),
+        }
+    }
+}
+
+fn main() {
+    let rom = match env::args().nth(1) {
+        Some(path) => fs::read(&path).unwrap_or_else(|e| {
+            eprintln!("Can't read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => testrom::demo(),
+    };
+
+    let mut gb = GameBoy::from_rom(rom, None);
+    // Sound goes nowhere, instead of to the sound card.
+    gb.set_audio_sink(Box::new(NullSink::default()));
+    let mut video = TextSink {
+        frame: vec![0; SCREEN_PIXELS],
+        status: String::new(),
+    };
+    let mut input = Script { polls: 0, shown: 0 };
+    gb.run_with(&mut video, &mut input);
+
+    print!("{}", video.draw());
+    println!("{}", video.status);
+}
@@ -0,0 +1,39 @@
+//! Run a ROM headless for a number of frames, and save the last one as a PNG.
+//! Without a ROM, this runs the demo cartridge.
+//!
+//!     cargo run --example render_png -- [ROM] [FRAMES] [OUT.png]
+
+use ferrum::gb::GameBoy;
+use ferrum::testrom;
+use std::path::Path;
+use std::{env, fs, process};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rom = match args.first() {
+        Some(path) => fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Can't read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => testrom::demo(),
+    };
+    // The boot ROM shows the logo for over 5 seconds first.
+    let frames = match args.get(1).map(|n| n.parse::<usize>()) {
+        Some(Ok(frames)) => frames,
+        Some(Err(_)) => {
+            eprintln!("FRAMES has to be a number");
+            process::exit(1);
+        }
+        None => 600,
+    };
+    let out = args.get(2).map_or("frame.png", String::as_str);
+
+    let mut gb = GameBoy::from_rom(rom, None);
+    gb.set_serial_output(false);
+    gb.frames().take(frames).for_each(drop);
+    if let Err(e) = gb.screenshot(Path::new(out)) {
+        eprintln!("Can't write {}: {}", out, e);
+        process::exit(1);
+    }
+    println!("Frame {} written to {}", frames, out);
+}
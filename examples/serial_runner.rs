@@ -0,0 +1,45 @@
+//! Run a test ROM that reports over the serial port, like blargg's, until it prints its verdict.
+//! Exits with 0 once the expected text is printed ("Passed" by default), 1 on "Failed", 2 if neither
+//! is printed within an emulated minute. Without a ROM, this runs the demo cartridge, expecting its greeting.
+//!
+//!     cargo run --example serial_runner -- [ROM [EXPECTED]]
+//!     cargo run --example serial_runner -- roms/test/blargg/cpu_instrs/cpu_instrs.gb
+
+use ferrum::gb::GameBoy;
+use ferrum::testrom;
+use std::{env, fs, process};
+
+/// An emulated minute.
+const MAX_FRAMES: usize = 60 * 60;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (rom, expected) = match args.first() {
+        Some(path) => {
+            let rom = fs::read(path).unwrap_or_else(|e| {
+                eprintln!("Can't read {}: {}", path, e);
+                process::exit(1);
+            });
+            (rom, args.get(1).map_or("Passed", String::as_str))
+        }
+        None => (testrom::demo(), "Hello from ferrum!"),
+    };
+
+    let mut gb = GameBoy::from_rom(rom, None);
+    gb.set_serial_output(false);
+    gb.skip_boot();
+    let mut output = String::new();
+    for frame in gb.frames().take(MAX_FRAMES) {
+        let text = String::from_utf8_lossy(&frame.serial);
+        print!("{}", text);
+        output.push_str(&text);
+        if output.contains(expected) {
+            process::exit(0);
+        }
+        if output.contains("Failed") {
+            process::exit(1);
+        }
+    }
+    eprintln!("\nNo `{}` after {} frames", expected, MAX_FRAMES);
+    process::exit(2);
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use ferrum::gb::GameBoy;
+use ferrum::testrom::fuzz_rom;
+use libfuzzer_sys::fuzz_target;
+
+/// Frames each ROM runs for. The boot ROM hands over to the cartridge around frame 335,
+/// so this leaves a couple of seconds of the cartridge's own code, while keeping runs bounded.
+const FRAMES: u32 = 480;
+
+// Arbitrary bytes as a ROM image, with a header the boot ROM accepts, run headlessly.
+// Any panic, including an out of bounds access, is a crash.
+fuzz_target!(|data: &[u8]| {
+    let mut gb = GameBoy::from_rom(fuzz_rom(data), None);
+    gb.set_serial_output(false);
+    for _ in 0..FRAMES {
+        gb.run_frame();
+    }
+});
@@ -0,0 +1,102 @@
+use crate::bus::{DmaBusPolicy, OpenBusPolicy};
+use crate::ppu::PpuAccuracy;
+use crate::state::{self, Savestate, StateReader, StateWriter};
+use serde::Deserialize;
+
+/// Accuracy profile
+/// Every setting trading emulation accuracy for speed, or for games that only work on lenient emulators,
+/// in one place, so a preset can set them all at once. The profile is saved in save states, since a state only
+/// plays back the same way with the same settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Accuracy {
+    /// Rendering pipeline, see PpuAccuracy.
+    pub ppu: PpuAccuracy,
+
+    /// Whether the PPU locks the CPU out of VRAM and OAM while it uses them.
+    pub vram_blocking: bool,
+
+    /// What reads from unmapped or inaccessible memory return, see OpenBusPolicy.
+    pub open_bus: OpenBusPolicy,
+
+    /// What the CPU sees of the buses an OAM DMA transfer is using, see DmaBusPolicy.
+    pub dma_bus: DmaBusPolicy,
+}
+
+impl Default for Accuracy {
+    fn default() -> Self {
+        AccuracyPreset::default().accuracy()
+    }
+}
+
+impl Savestate for Accuracy {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.ppu as u8);
+        w.bool(self.vram_blocking);
+        w.u8(self.open_bus as u8);
+        w.u8(self.dma_bus as u8);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.ppu = match r.u8()? {
+            0 => PpuAccuracy::Scanline,
+            1 => PpuAccuracy::Fifo,
+            _ => return Err(state::StateError::Invalid("PPU accuracy")),
+        };
+        self.vram_blocking = r.bool()?;
+        self.open_bus = match r.u8()? {
+            0 => OpenBusPolicy::Accurate,
+            1 => OpenBusPolicy::Ff,
+            2 => OpenBusPolicy::LastValue,
+            _ => return Err(state::StateError::Invalid("open bus policy")),
+        };
+        self.dma_bus = match r.u8()? {
+            0 => DmaBusPolicy::Restricted,
+            1 => DmaBusPolicy::Free,
+            _ => return Err(state::StateError::Invalid("DMA bus policy")),
+        };
+        Ok(())
+    }
+}
+
+/// Named accuracy profiles, a simple knob instead of a flag per setting.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccuracyPreset {
+    /// Scanline renderer, and nothing locked: the CPU can use VRAM, OAM, and memory during OAM DMA at any time,
+    /// and open bus reads $FF. Fast, and forgiving of games that get away with sloppy timing elsewhere.
+    Fast,
+
+    /// Scanline renderer, with the hardware's memory access rules.
+    /// Most of the speed, and only mid-scanline raster effects are off.
+    #[default]
+    Balanced,
+
+    /// The experimental pixel FIFO, with the hardware's memory access rules.
+    /// The FIFO doesn't draw the window or sprites yet, so games look worse than with Balanced, see PpuAccuracy.
+    Accurate,
+}
+
+impl AccuracyPreset {
+    pub fn accuracy(self) -> Accuracy {
+        match self {
+            AccuracyPreset::Fast => Accuracy {
+                ppu: PpuAccuracy::Scanline,
+                vram_blocking: false,
+                open_bus: OpenBusPolicy::Ff,
+                dma_bus: DmaBusPolicy::Free,
+            },
+            AccuracyPreset::Balanced => Accuracy {
+                ppu: PpuAccuracy::Scanline,
+                vram_blocking: true,
+                open_bus: OpenBusPolicy::Accurate,
+                dma_bus: DmaBusPolicy::Restricted,
+            },
+            AccuracyPreset::Accurate => Accuracy {
+                ppu: PpuAccuracy::Fifo,
+                vram_blocking: true,
+                open_bus: OpenBusPolicy::Accurate,
+                dma_bus: DmaBusPolicy::Restricted,
+            },
+        }
+    }
+}
@@ -0,0 +1,614 @@
+use crate::state::{self, StateReader, StateWriter};
+
+/// Length Timer - https://gbdev.io/pandocs/Audio_details.html#length-timer
+/// Turns the channel off after a set time, counted down at 256 Hz while enabled.
+pub(super) struct Length {
+    /// Longest length, 64 (or 256 for the wave channel).
+    max: u16,
+    counter: u16,
+    enabled: bool,
+}
+
+impl Length {
+    pub(super) fn new(max: u16) -> Self {
+        Self {
+            max,
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    /// Load the initial length (NRx1), the channel plays for max - n ticks.
+    pub(super) fn load(&mut self, n: u8) {
+        self.counter = self.max - (n as u16 & (self.max - 1));
+    }
+
+    /// Count down a tick. Returns true if the length ran out, and the channel has to be turned off.
+    pub(super) fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+
+    /// Handle a write to NRx4, which enables the length timer (bit 6) and may trigger the channel (bit 7).
+    /// extra is true if the frame sequencer's next step doesn't clock the length timer, in which case enabling it
+    /// clocks it once right away, and a trigger with a length of 0 loads one less than the maximum.
+    /// Returns true if the length ran out, and the channel has to be turned off.
+    pub(super) fn write_nrx4(&mut self, val: u8, extra: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = val & 0x40 != 0;
+        let trigger = val & 0x80 != 0;
+
+        let mut expired = false;
+        if extra && !was_enabled && self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            expired = self.counter == 0 && !trigger;
+        }
+        if trigger && self.counter == 0 {
+            self.counter = self.max;
+            if self.enabled && extra {
+                self.counter -= 1;
+            }
+        }
+        expired
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.counter);
+        w.bool(self.enabled);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.counter = r.u16()?.min(self.max);
+        self.enabled = r.bool()?;
+        Ok(())
+    }
+}
+
+/// Volume Envelope - https://gbdev.io/pandocs/Audio_Registers.html#ff12--nr12-channel-1-volume--envelope
+/// Ramps the volume up or down, one step every pace ticks of the 64 Hz envelope clock.
+#[derive(Default)]
+pub(super) struct Envelope {
+    /// NRx2 as last written, it takes effect on the next trigger.
+    nrx2: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub(super) fn write(&mut self, nrx2: u8) {
+        self.nrx2 = nrx2;
+    }
+
+    /// The DAC is on while the initial volume or the direction is set, NRx2 bits 7-3.
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.nrx2 & 0xF8 != 0
+    }
+
+    pub(super) fn trigger(&mut self) {
+        self.volume = self.nrx2 >> 4;
+        self.timer = self.nrx2 & 0x07;
+    }
+
+    pub(super) fn clock(&mut self) {
+        let pace = self.nrx2 & 0x07;
+        if pace == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = pace;
+            if self.nrx2 & 0x08 != 0 && self.volume < 15 {
+                self.volume += 1;
+            } else if self.nrx2 & 0x08 == 0 && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+
+    pub(super) fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.nrx2);
+        w.u8(self.volume);
+        w.u8(self.timer);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.nrx2 = r.u8()?;
+        self.volume = r.u8()? & 0x0F;
+        self.timer = r.u8()?;
+        Ok(())
+    }
+}
+
+/// Frequency Sweep (channel 1 only) - https://gbdev.io/pandocs/Audio_Registers.html#ff10--nr10-channel-1-sweep
+#[derive(Default)]
+struct Sweep {
+    /// NR10 as last written.
+    nr10: u8,
+    enabled: bool,
+    timer: u8,
+
+    /// Copy of the period the sweep works on.
+    shadow: u16,
+
+    /// A subtraction was calculated since the last trigger, clearing the direction bit after this disables the channel.
+    negated: bool,
+}
+
+impl Sweep {
+    fn pace(&self) -> u8 {
+        (self.nr10 >> 4) & 0x07
+    }
+
+    fn step(&self) -> u8 {
+        self.nr10 & 0x07
+    }
+
+    fn negate(&self) -> bool {
+        self.nr10 & 0x08 != 0
+    }
+
+    fn reload_timer(&mut self) {
+        // A pace of 0 is treated as 8 by the timer.
+        self.timer = if self.pace() == 0 { 8 } else { self.pace() };
+    }
+
+    /// Calculate the next period. Anything over 2047 turns the channel off.
+    fn next_period(&mut self) -> Option<u16> {
+        let delta = self.shadow >> self.step();
+        let period = if self.negate() {
+            self.negated = true;
+            self.shadow.wrapping_sub(delta)
+        } else {
+            self.shadow + delta
+        };
+        (period <= 0x7FF).then_some(period)
+    }
+}
+
+/// Duty cycle waveforms, one bit per step.
+const DUTY: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+/// Pulse channels 1 and 2 - https://gbdev.io/pandocs/Audio_Registers.html#sound-channel-1--pulse-with-period-sweep
+/// Channel 1 also has a frequency sweep.
+pub(super) struct Square {
+    pub(super) enabled: bool,
+    pub(super) length: Length,
+    pub(super) envelope: Envelope,
+    sweep: Option<Sweep>,
+
+    duty: u8,
+    duty_step: u8,
+
+    /// 11-bit period value, the channel steps every (2048 - period) * 4 T-cycles.
+    period: u16,
+    timer: u32,
+}
+
+impl Square {
+    pub(super) fn new(sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            sweep: sweep.then(Sweep::default),
+            duty: 0,
+            duty_step: 0,
+            period: 0,
+            timer: 8192,
+        }
+    }
+
+    pub(super) fn write_nr10(&mut self, val: u8) {
+        if let Some(sweep) = &mut self.sweep {
+            let was_negating = sweep.negate();
+            sweep.nr10 = val;
+            // Leaving subtraction mode after using it turns the channel off.
+            if was_negating && !sweep.negate() && sweep.negated {
+                self.enabled = false;
+            }
+        }
+    }
+
+    pub(super) fn write_nrx1(&mut self, val: u8) {
+        self.duty = val >> 6;
+        self.length.load(val & 0x3F);
+    }
+
+    pub(super) fn write_nrx2(&mut self, val: u8) {
+        self.envelope.write(val);
+        if !self.envelope.dac_enabled() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn write_nrx3(&mut self, val: u8) {
+        self.period = self.period & 0x700 | val as u16;
+    }
+
+    pub(super) fn write_nrx4(&mut self, val: u8, extra_length: bool) {
+        self.period = self.period & 0xFF | ((val as u16 & 0x07) << 8);
+        if self.length.write_nrx4(val, extra_length) {
+            self.enabled = false;
+        }
+        if val & 0x80 != 0 {
+            self.trigger();
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = (2048 - self.period as u32) * 4;
+        self.envelope.trigger();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.period;
+            sweep.negated = false;
+            sweep.reload_timer();
+            sweep.enabled = sweep.pace() != 0 || sweep.step() != 0;
+            // With a step, the next period is calculated right away, only to check for overflow.
+            if sweep.step() != 0 && sweep.next_period().is_none() {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Clocked at 128 Hz by the frame sequencer.
+    pub(super) fn clock_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+        if !sweep.enabled || sweep.pace() == 0 {
+            return;
+        }
+        match sweep.next_period() {
+            Some(period) if sweep.step() != 0 => {
+                sweep.shadow = period;
+                self.period = period;
+                // The next period is calculated again, only to check for overflow.
+                if sweep.next_period().is_none() {
+                    self.enabled = false;
+                }
+            }
+            Some(_) => {}
+            None => self.enabled = false,
+        }
+    }
+
+    pub(super) fn cycle(&mut self, ticks: u32) {
+        let mut ticks = ticks;
+        while ticks >= self.timer {
+            ticks -= self.timer;
+            self.timer = (2048 - self.period as u32) * 4;
+            self.duty_step = (self.duty_step + 1) & 0x07;
+        }
+        self.timer -= ticks;
+    }
+
+    /// Current digital output, 0-15.
+    pub(super) fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        let high = (DUTY[self.duty as usize] >> (7 - self.duty_step)) & 0x01;
+        high * self.envelope.volume()
+    }
+
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        if let Some(sweep) = &self.sweep {
+            w.u8(sweep.nr10);
+            w.bool(sweep.enabled);
+            w.u8(sweep.timer);
+            w.u16(sweep.shadow);
+            w.bool(sweep.negated);
+        }
+        w.u8(self.duty);
+        w.u8(self.duty_step);
+        w.u16(self.period);
+        w.u32(self.timer);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.enabled = r.bool()?;
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        if let Some(sweep) = &mut self.sweep {
+            sweep.nr10 = r.u8()?;
+            sweep.enabled = r.bool()?;
+            sweep.timer = r.u8()?;
+            sweep.shadow = r.u16()?;
+            sweep.negated = r.bool()?;
+        }
+        self.duty = r.u8()? & 0x03;
+        self.duty_step = r.u8()? & 0x07;
+        self.period = r.u16()? & 0x7FF;
+        self.timer = r.u32()?.clamp(1, 8192);
+        Ok(())
+    }
+}
+
+/// Wave channel 3 - https://gbdev.io/pandocs/Audio_Registers.html#sound-channel-3--wave-output
+/// Plays 32 4-bit samples from wave RAM ($FF30-$FF3F), high nibble first.
+///
+/// While it plays, the channel holds the wave RAM bus, and on DMG the CPU only gets through on the T-cycles
+/// the channel reads a byte. Then it reaches the byte being read, whatever the address, at any other time
+/// reads give $FF and writes are lost. Blargg's dmg_sound 09-12 test this, see ram_index.
+pub(super) struct Wave {
+    pub(super) enabled: bool,
+    pub(super) length: Length,
+    pub(super) ram: [u8; 16],
+    dac_enabled: bool,
+
+    /// Output level (NR32 bits 6-5), 0 mutes, 1-3 shift the sample right by 0-2.
+    level: u8,
+
+    /// Sample being played, 0-31.
+    position: u8,
+
+    /// 11-bit period value, the channel steps every (2048 - period) * 2 T-cycles.
+    period: u16,
+    timer: u32,
+
+    /// T-cycles since the channel last read wave RAM, u32::MAX if it hasn't since it was triggered.
+    since_read: u32,
+}
+
+/// T-cycles from a trigger to the channel's first read of wave RAM, on top of a step.
+const WAVE_TRIGGER_DELAY: u32 = 6;
+
+impl Wave {
+    pub(super) fn new() -> Self {
+        Self {
+            enabled: false,
+            length: Length::new(256),
+            ram: [0; 16],
+            dac_enabled: false,
+            level: 0,
+            position: 0,
+            period: 0,
+            timer: 4096,
+            since_read: u32::MAX,
+        }
+    }
+
+    pub(super) fn write_nr30(&mut self, val: u8) {
+        self.dac_enabled = val & 0x80 != 0;
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn write_nr31(&mut self, val: u8) {
+        self.length.load(val);
+    }
+
+    pub(super) fn write_nr32(&mut self, val: u8) {
+        self.level = (val >> 5) & 0x03;
+    }
+
+    pub(super) fn write_nr33(&mut self, val: u8) {
+        self.period = self.period & 0x700 | val as u16;
+    }
+
+    pub(super) fn write_nr34(&mut self, val: u8, extra_length: bool) {
+        self.period = self.period & 0xFF | ((val as u16 & 0x07) << 8);
+        if self.length.write_nrx4(val, extra_length) {
+            self.enabled = false;
+        }
+        if val & 0x80 != 0 {
+            // Triggered on the T-cycles it's about to read the next byte, the DMG's channel corrupts
+            // the first bytes of wave RAM: the first four get the four-byte block holding that byte,
+            // unless it's in the first block, then only the first byte gets it.
+            if self.enabled && self.timer <= 2 {
+                let next = ((self.position as usize + 1) & 0x1F) / 2;
+                match next {
+                    0..=3 => self.ram[0] = self.ram[next],
+                    _ => self.ram.copy_within(next & !0x03..(next & !0x03) + 4, 0),
+                }
+            }
+            self.enabled = self.dac_enabled;
+            self.position = 0;
+            self.timer = (2048 - self.period as u32) * 2 + WAVE_TRIGGER_DELAY;
+            self.since_read = u32::MAX;
+        }
+    }
+
+    /// The byte of wave RAM the CPU reaches at offset, None while the channel holds the bus.
+    pub(super) fn ram_index(&self, offset: usize) -> Option<usize> {
+        if !self.enabled {
+            return Some(offset);
+        }
+        // The channel reads at 2 MHz, a read lasts two T-cycles.
+        match self.since_read {
+            0..=1 => Some(self.position as usize / 2),
+            _ => None,
+        }
+    }
+
+    pub(super) fn cycle(&mut self, ticks: u32) {
+        let mut ticks = ticks;
+        while ticks >= self.timer {
+            ticks -= self.timer;
+            self.timer = (2048 - self.period as u32) * 2;
+            self.position = (self.position + 1) & 0x1F;
+            self.since_read = 0;
+        }
+        self.timer -= ticks;
+        self.since_read = self.since_read.saturating_add(ticks);
+    }
+
+    pub(super) fn output(&self) -> u8 {
+        if !self.enabled || self.level == 0 {
+            return 0;
+        }
+        let byte = self.ram[self.position as usize / 2];
+        let sample = if self.position & 0x01 == 0 {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+        sample >> (self.level - 1)
+    }
+
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        self.length.save_state(w);
+        w.bytes(&self.ram);
+        w.bool(self.dac_enabled);
+        w.u8(self.level);
+        w.u8(self.position);
+        w.u16(self.period);
+        w.u32(self.timer);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.enabled = r.bool()?;
+        self.length.load_state(r)?;
+        r.bytes(&mut self.ram)?;
+        self.dac_enabled = r.bool()?;
+        self.level = r.u8()? & 0x03;
+        self.position = r.u8()? & 0x1F;
+        self.period = r.u16()? & 0x7FF;
+        self.timer = r.u32()?.clamp(1, 4096 + WAVE_TRIGGER_DELAY);
+        // Not in the state, the time since the last read is as long as the timer has run, if the period held.
+        self.since_read = ((2048 - self.period as u32) * 2)
+            .checked_sub(self.timer)
+            .unwrap_or(u32::MAX);
+        Ok(())
+    }
+}
+
+/// Noise channel 4 - https://gbdev.io/pandocs/Audio_Registers.html#sound-channel-4--noise
+/// Outputs bit 0 of a linear feedback shift register, inverted.
+pub(super) struct Noise {
+    pub(super) enabled: bool,
+    pub(super) length: Length,
+    pub(super) envelope: Envelope,
+
+    /// NR43: clock shift (bits 7-4), LFSR width (bit 3, 1 = 7 bits), clock divider (bits 2-0).
+    nr43: u8,
+    lfsr: u16,
+    timer: u32,
+}
+
+impl Noise {
+    pub(super) fn new() -> Self {
+        Self {
+            enabled: false,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            nr43: 0,
+            lfsr: 0,
+            timer: 8,
+        }
+    }
+
+    /// T-cycles between LFSR steps, None if the clock shift is 14 or 15, which stops the LFSR.
+    fn period(&self) -> Option<u32> {
+        let shift = self.nr43 >> 4;
+        let divider = match self.nr43 & 0x07 {
+            0 => 8,
+            d => d as u32 * 16,
+        };
+        (shift < 14).then_some(divider << shift)
+    }
+
+    pub(super) fn write_nr41(&mut self, val: u8) {
+        self.length.load(val & 0x3F);
+    }
+
+    pub(super) fn write_nr42(&mut self, val: u8) {
+        self.envelope.write(val);
+        if !self.envelope.dac_enabled() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn write_nr43(&mut self, val: u8) {
+        self.nr43 = val;
+    }
+
+    pub(super) fn write_nr44(&mut self, val: u8, extra_length: bool) {
+        if self.length.write_nrx4(val, extra_length) {
+            self.enabled = false;
+        }
+        if val & 0x80 != 0 {
+            self.enabled = self.envelope.dac_enabled();
+            self.envelope.trigger();
+            self.lfsr = 0;
+            self.timer = self.period().unwrap_or(8);
+        }
+    }
+
+    pub(super) fn cycle(&mut self, ticks: u32) {
+        let Some(period) = self.period() else {
+            return;
+        };
+        let mut ticks = ticks;
+        while ticks >= self.timer {
+            ticks -= self.timer;
+            self.timer = period;
+
+            // Bit 15 (and bit 7 in 7-bit mode) gets the XNOR of bits 0 and 1, then the register shifts right.
+            let bit = !(self.lfsr ^ (self.lfsr >> 1)) & 0x01;
+            self.lfsr = (self.lfsr & 0x7FFF) | (bit << 15);
+            if self.nr43 & 0x08 != 0 {
+                self.lfsr = (self.lfsr & !0x80) | (bit << 7);
+            }
+            self.lfsr >>= 1;
+        }
+        self.timer -= ticks;
+    }
+
+    pub(super) fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        (self.lfsr & 0x01) as u8 * self.envelope.volume()
+    }
+
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        w.u8(self.nr43);
+        w.u16(self.lfsr);
+        w.u32(self.timer);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.enabled = r.bool()?;
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        self.nr43 = r.u8()?;
+        self.lfsr = r.u16()?;
+        self.timer = r.u32()?.max(1);
+        Ok(())
+    }
+}
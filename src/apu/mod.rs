@@ -0,0 +1,443 @@
+mod channel;
+
+use crate::state::{self, Savestate, StateReader, StateWriter};
+use serde::Deserialize;
+
+use self::channel::{Noise, Square, Wave};
+
+/// T-cycles per second.
+const CLOCK_HZ: u64 = 4194304;
+
+/// Bits of each sound register that read back as 1, because they are unused or write-only, $FF10-$FF2F.
+/// https://gbdev.io/pandocs/Audio_details.html#registers
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24 (NR20 doesn't exist)
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44 (NR40 doesn't exist)
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // $FF27-$FF2F are unused
+];
+
+/// Chunks of output per second while fast-forwarding with the pitch kept, see FastForwardAudio::Pitch.
+const FAST_FORWARD_CHUNKS: u32 = 20;
+
+/// What the sound does while fast-forwarding.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FastForwardAudio {
+    /// Silence.
+    #[default]
+    Mute,
+
+    /// Keep the pitch: play a chunk of the output (1/20 s), and drop as many chunks as the speed is over 1x,
+    /// so the music plays in snippets, at its own pitch.
+    Pitch,
+
+    /// Play it all, resampled into real time, so it plays faster and higher, like a tape on fast-forward.
+    Raw,
+}
+
+/// Volume settings of the mixer.
+/// These are user settings applied on top of the game's own volume (NR50), they aren't part of the machine.
+pub struct Mixer {
+    /// Master volume, 0.0 - 1.0.
+    pub volume: f32,
+
+    /// Gain of each channel (pulse 1, pulse 2, wave, noise), 0.0 - 1.0.
+    pub gains: [f32; 4],
+
+    pub muted: bool,
+
+    /// While fast-forwarding, how many times faster than a real Gameboy, and what the sound does.
+    /// Raw sound is resampled by whoever sets the sample rate, the mixer only mutes, or drops chunks.
+    pub fast_forward: Option<(u32, FastForwardAudio)>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            gains: [1.0; 4],
+            muted: false,
+            fast_forward: None,
+        }
+    }
+}
+
+/// Audio Processing Unit - https://gbdev.io/pandocs/Audio.html
+/// Four channels, two pulse channels (the first with a frequency sweep), a wave channel, and a noise channel,
+/// each with a DAC, mixed into a stereo output.
+///
+/// The channels' length timers, envelopes, and sweep are clocked by the frame sequencer, which steps at 512 Hz,
+/// on the falling edge of bit 4 of DIV (DIV-APU).
+///
+/// Step   Length Ctr  Vol Env     Sweep
+/// ---------------------------------------
+/// 0      Clock       -           -
+/// 1      -           -           -
+/// 2      Clock       -           Clock
+/// 3      -           -           -
+/// 4      Clock       -           -
+/// 5      -           -           -
+/// 6      Clock       -           Clock
+/// 7      -           Clock       -
+/// ---------------------------------------
+/// Rate   256 Hz      64 Hz       128 Hz
+///
+/// The output is averaged down to the sample rate of the audio sink, and passed through a high-pass filter,
+/// like the capacitor on the real output, to remove the DAC's DC offset.
+pub struct Apu {
+    ch1: Square,
+    ch2: Square,
+    ch3: Wave,
+    ch4: Noise,
+
+    /// Sound registers as last written, $FF10-$FF2F.
+    regs: [u8; 0x20],
+
+    /// NR52 bit 7, all sound on/off.
+    power: bool,
+
+    /// Next frame sequencer step, 0-7.
+    frame_step: u8,
+
+    pub mixer: Mixer,
+
+    /// Output sample rate, 0 while nothing is listening.
+    sample_rate: u32,
+
+    /// T-cycles times the sample rate, towards the next sample.
+    sample_clock: u64,
+
+    /// Sum of the output, left and right, since the last sample, and the T-cycles it covers.
+    sum: (f32, f32),
+    sum_ticks: u32,
+
+    /// High-pass filter capacitors, left and right, and how much charge they keep per sample.
+    capacitor: (f32, f32),
+    charge_factor: f32,
+
+    /// Interleaved stereo samples, waiting to be taken.
+    samples: Vec<i16>,
+
+    /// Stereo samples into the cycle of a kept chunk and the dropped ones, while fast-forwarding with the pitch kept.
+    chunk_pos: u32,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            ch1: Square::new(true),
+            ch2: Square::new(false),
+            ch3: Wave::new(),
+            ch4: Noise::new(),
+            regs: [0; 0x20],
+            power: false,
+            frame_step: 0,
+            mixer: Mixer::default(),
+            sample_rate: 0,
+            sample_clock: 0,
+            sum: (0.0, 0.0),
+            sum_ticks: 0,
+            capacitor: (0.0, 0.0),
+            charge_factor: 1.0,
+            samples: Vec::new(),
+            chunk_pos: 0,
+        }
+    }
+
+    /// Produce samples at the given rate, 0 stops producing samples.
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = rate;
+        self.sample_clock = 0;
+        self.sum = (0.0, 0.0);
+        self.sum_ticks = 0;
+        self.samples.clear();
+        if rate > 0 {
+            // The capacitor keeps 0.999958 of its charge per T-cycle.
+            self.charge_factor = 0.999958f32.powf(CLOCK_HZ as f32 / rate as f32);
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Samples produced since the last call, interleaved stereo.
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            // NR52: power, and which channels are on.
+            0xFF26 => {
+                0x70 | if self.power { 0x80 } else { 0x00 }
+                    | self.ch1.enabled as u8
+                    | (self.ch2.enabled as u8) << 1
+                    | (self.ch3.enabled as u8) << 2
+                    | (self.ch4.enabled as u8) << 3
+            }
+            0xFF10..=0xFF2F => {
+                let i = addr as usize - 0xFF10;
+                self.regs[i] | READ_MASKS[i]
+            }
+            0xFF30..=0xFF3F => self
+                .ch3
+                .ram_index(addr as usize - 0xFF30)
+                .map_or(0xFF, |i| self.ch3.ram[i]),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xFF26 => {
+                let power = val & 0x80 != 0;
+                if self.power && !power {
+                    self.power_off();
+                } else if !self.power && power {
+                    // The frame sequencer starts over at step 0.
+                    self.frame_step = 0;
+                }
+                self.power = power;
+                return;
+            }
+            0xFF30..=0xFF3F => {
+                if let Some(i) = self.ch3.ram_index(addr as usize - 0xFF30) {
+                    self.ch3.ram[i] = val;
+                }
+                return;
+            }
+            _ => (),
+        }
+
+        // While powered off, the registers ignore writes, except for the length timers on DMG.
+        if !self.power {
+            match addr {
+                0xFF11 => self.ch1.length.load(val & 0x3F),
+                0xFF16 => self.ch2.length.load(val & 0x3F),
+                0xFF1B => self.ch3.length.load(val),
+                0xFF20 => self.ch4.length.load(val & 0x3F),
+                _ => (),
+            }
+            return;
+        }
+
+        if (0xFF10..=0xFF2F).contains(&addr) {
+            self.regs[addr as usize - 0xFF10] = val;
+        }
+
+        // Enabling a length timer (or triggering) has extra effects in the half of the frame sequencer's
+        // period where the next step doesn't clock the length timers.
+        let extra_length = self.frame_step & 0x01 == 1;
+        match addr {
+            0xFF10 => self.ch1.write_nr10(val),
+            0xFF11 => self.ch1.write_nrx1(val),
+            0xFF12 => self.ch1.write_nrx2(val),
+            0xFF13 => self.ch1.write_nrx3(val),
+            0xFF14 => self.ch1.write_nrx4(val, extra_length),
+            0xFF16 => self.ch2.write_nrx1(val),
+            0xFF17 => self.ch2.write_nrx2(val),
+            0xFF18 => self.ch2.write_nrx3(val),
+            0xFF19 => self.ch2.write_nrx4(val, extra_length),
+            0xFF1A => self.ch3.write_nr30(val),
+            0xFF1B => self.ch3.write_nr31(val),
+            0xFF1C => self.ch3.write_nr32(val),
+            0xFF1D => self.ch3.write_nr33(val),
+            0xFF1E => self.ch3.write_nr34(val, extra_length),
+            0xFF20 => self.ch4.write_nr41(val),
+            0xFF21 => self.ch4.write_nr42(val),
+            0xFF22 => self.ch4.write_nr43(val),
+            0xFF23 => self.ch4.write_nr44(val, extra_length),
+            _ => (),
+        }
+    }
+
+    /// Turning the APU off clears every sound register, and turns off every channel.
+    /// Wave RAM, and on DMG the length timers, are kept.
+    fn power_off(&mut self) {
+        for addr in 0xFF10..=0xFF25 {
+            match addr {
+                // Writing NRx1 would reload the length timers, which keep counting from where they were.
+                0xFF11 | 0xFF16 | 0xFF1B | 0xFF20 => self.regs[addr as usize - 0xFF10] = 0,
+                _ => self.write(addr, 0x00),
+            }
+        }
+        self.ch1.enabled = false;
+        self.ch2.enabled = false;
+        self.ch3.enabled = false;
+        self.ch4.enabled = false;
+    }
+
+    /// Step the frame sequencer, on the falling edge of DIV bit 4.
+    pub fn div_apu(&mut self) {
+        if !self.power {
+            return;
+        }
+        let step = self.frame_step;
+        self.frame_step = (self.frame_step + 1) & 0x07;
+
+        if step & 0x01 == 0 {
+            if self.ch1.length.clock() {
+                self.ch1.enabled = false;
+            }
+            if self.ch2.length.clock() {
+                self.ch2.enabled = false;
+            }
+            if self.ch3.length.clock() {
+                self.ch3.enabled = false;
+            }
+            if self.ch4.length.clock() {
+                self.ch4.enabled = false;
+            }
+        }
+        if step == 2 || step == 6 {
+            self.ch1.clock_sweep();
+        }
+        if step == 7 {
+            self.ch1.envelope.clock();
+            self.ch2.envelope.clock();
+            self.ch4.envelope.clock();
+        }
+    }
+
+    /// NR51, which sides each channel (pulse 1, pulse 2, wave, noise) is routed to, left and right.
+    /// Bits 4-7 enable the channels on the left, bits 0-3 on the right.
+    fn panning(&self, channel: usize) -> (bool, bool) {
+        let nr51 = self.regs[0x15];
+        (nr51 & (0x10 << channel) != 0, nr51 & (0x01 << channel) != 0)
+    }
+
+    /// NR50 bits 4-6 and 0-2, the volume of the left and right, 0-7 for 1/8 - 8/8, there's no muting a side.
+    /// Bits 7 and 3 mix the cartridge's VIN pin into the left and right. No cartridge ferrum emulates drives VIN,
+    /// so it adds nothing to the output, but the bits are kept and read back like the rest of the register.
+    fn master_volume(&self) -> (u8, u8) {
+        let nr50 = self.regs[0x14];
+        ((nr50 >> 4) & 0x07, nr50 & 0x07)
+    }
+
+    /// Mix the channels into a left and right output, -1.0 - 1.0.
+    /// A DAC that's on turns the channel's 0-15 into -1.0 - 1.0 (so silence has a DC offset the filter removes),
+    /// a DAC that's off outputs 0. NR51 routes each channel to the left and/or right, and NR50 sets the volume
+    /// of each side.
+    fn mix(&self) -> (f32, f32) {
+        let dac = |enabled: bool, output: u8| {
+            if enabled {
+                output as f32 / 7.5 - 1.0
+            } else {
+                0.0
+            }
+        };
+        let channels = [
+            dac(self.ch1.dac_enabled(), self.ch1.output()),
+            dac(self.ch2.dac_enabled(), self.ch2.output()),
+            dac(self.ch3.dac_enabled(), self.ch3.output()),
+            dac(self.ch4.dac_enabled(), self.ch4.output()),
+        ];
+
+        let (mut left, mut right) = (0.0, 0.0);
+        for (n, &output) in channels.iter().enumerate() {
+            let output = output * self.mixer.gains[n];
+            let (to_left, to_right) = self.panning(n);
+            if to_left {
+                left += output;
+            }
+            if to_right {
+                right += output;
+            }
+        }
+        let (left_volume, right_volume) = self.master_volume();
+        (
+            left * (left_volume + 1) as f32 / 32.0,
+            right * (right_volume + 1) as f32 / 32.0,
+        )
+    }
+
+    /// Advance the channels by the given T-cycles, and produce samples.
+    pub fn cycle(&mut self, ticks: u32) {
+        if self.power {
+            self.ch1.cycle(ticks);
+            self.ch2.cycle(ticks);
+            self.ch3.cycle(ticks);
+            self.ch4.cycle(ticks);
+        }
+        if self.sample_rate == 0 {
+            return;
+        }
+
+        let (left, right) = self.mix();
+        self.sum.0 += left * ticks as f32;
+        self.sum.1 += right * ticks as f32;
+        self.sum_ticks += ticks;
+
+        self.sample_clock += ticks as u64 * self.sample_rate as u64;
+        while self.sample_clock >= CLOCK_HZ {
+            self.sample_clock -= CLOCK_HZ;
+            let n = self.sum_ticks.max(1) as f32;
+            let (left, right) = (self.sum.0 / n, self.sum.1 / n);
+            self.sum = (0.0, 0.0);
+            self.sum_ticks = 0;
+
+            let left = self.high_pass(left, true);
+            let right = self.high_pass(right, false);
+            if let Some((speed, FastForwardAudio::Pitch)) = self.mixer.fast_forward {
+                let chunk = (self.sample_rate / FAST_FORWARD_CHUNKS).max(1);
+                let kept = self.chunk_pos < chunk;
+                self.chunk_pos = (self.chunk_pos + 1) % (chunk * speed.max(1));
+                if !kept {
+                    continue;
+                }
+            }
+            let volume = match self.mixer.fast_forward {
+                Some((_, FastForwardAudio::Mute)) => 0.0,
+                _ if self.mixer.muted => 0.0,
+                _ => self.mixer.volume,
+            };
+            self.samples.push((left * volume * i16::MAX as f32) as i16);
+            self.samples.push((right * volume * i16::MAX as f32) as i16);
+        }
+    }
+
+    fn high_pass(&mut self, input: f32, left: bool) -> f32 {
+        let capacitor = if left {
+            &mut self.capacitor.0
+        } else {
+            &mut self.capacitor.1
+        };
+        let output = input - *capacitor;
+        *capacitor = input - output * self.charge_factor;
+        output
+    }
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Only the machine is saved, the output (mixer settings, sample rate, pending samples) belongs to the host.
+impl Savestate for Apu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.regs);
+        w.bool(self.power);
+        w.u8(self.frame_step);
+        self.ch1.save_state(w);
+        self.ch2.save_state(w);
+        self.ch3.save_state(w);
+        self.ch4.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        r.bytes(&mut self.regs)?;
+        self.power = r.bool()?;
+        self.frame_step = r.u8()? & 0x07;
+        self.ch1.load_state(r)?;
+        self.ch2.load_state(r)?;
+        self.ch3.load_state(r)?;
+        self.ch4.load_state(r)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::input::{self, Binding};
+use crate::palette::Palette;
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A boot ROM overlays $0000-$00FF.
+pub const BOOT_ROM_SIZE: usize = 0x100;
+
+/// External assets
+/// Files the user can edit while a game runs: a custom boot ROM, a palette, and key bindings.
+/// They are reloaded with the reload hotkey, or when one of them changes on disk,
+/// so tweaking them doesn't take a restart and replaying to the point of interest.
+#[derive(Default)]
+pub struct Assets {
+    boot_rom: Option<PathBuf>,
+    palette: Option<PathBuf>,
+    keys: Option<PathBuf>,
+
+    /// Modification times of the files when they were last loaded, in the same order.
+    modified: [Option<SystemTime>; 3],
+}
+
+/// The assets that loaded, a file that is missing or invalid gives None, and the asset in use stays.
+#[derive(Default)]
+pub struct Loaded {
+    pub boot_rom: Option<Vec<u8>>,
+    pub palette: Option<Palette>,
+    pub keys: Option<Vec<(String, Binding)>>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a boot ROM file, 256 bytes, instead of the model's built-in one.
+    pub fn set_boot_rom(&mut self, path: PathBuf) {
+        self.boot_rom = Some(path);
+    }
+
+    /// Show the screen in a palette file's colors, see Palette.
+    pub fn set_palette(&mut self, path: PathBuf) {
+        self.palette = Some(path);
+    }
+
+    /// Bind keys from a file, in place of the keys bound to the buttons it lists, see input::parse_bindings.
+    pub fn set_keys(&mut self, path: PathBuf) {
+        self.keys = Some(path);
+    }
+
+    fn paths(&self) -> impl Iterator<Item = Option<&Path>> {
+        [&self.boot_rom, &self.palette, &self.keys]
+            .into_iter()
+            .map(|path| path.as_deref())
+    }
+
+    fn modified(path: Option<&Path>) -> Option<SystemTime> {
+        fs::metadata(path?).and_then(|m| m.modified()).ok()
+    }
+
+    /// Has any file changed since the assets were last loaded?
+    pub fn changed(&self) -> bool {
+        self.paths()
+            .zip(self.modified)
+            .any(|(path, modified)| Self::modified(path) != modified)
+    }
+
+    /// Load every file, problems are logged.
+    pub fn load(&mut self) -> Loaded {
+        let modified: Vec<_> = self.paths().map(Self::modified).collect();
+        self.modified.copy_from_slice(&modified);
+
+        let mut loaded = Loaded::default();
+        if let Some(path) = &self.boot_rom {
+            loaded.boot_rom = match fs::read(path) {
+                Ok(rom) if rom.len() == BOOT_ROM_SIZE => Some(rom),
+                Ok(rom) => {
+                    warn!(
+                        "Ignoring {}, a boot ROM is {} bytes, not {}",
+                        path.display(),
+                        rom.len(),
+                        BOOT_ROM_SIZE
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!("Failed to read {}: {}", path.display(), e);
+                    None
+                }
+            };
+        }
+        if let Some(path) = &self.palette {
+            loaded.palette = Palette::load(path)
+                .map_err(|e| warn!("Failed to load the palette {}: {}", path.display(), e))
+                .ok();
+        }
+        if let Some(path) = &self.keys {
+            loaded.keys = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| input::parse_bindings(&text))
+                .map_err(|e| warn!("Failed to load the key bindings {}: {}", path.display(), e))
+                .ok();
+        }
+        loaded
+    }
+}
@@ -0,0 +1,153 @@
+use super::{AudioSink, BufferStats, DEFAULT_LATENCY};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BufferSize, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Samples waiting for the sound card, shared with its callback.
+#[derive(Default)]
+struct Queue {
+    /// Interleaved stereo samples waiting to be played.
+    samples: VecDeque<i16>,
+
+    /// Samples were pushed since the queue last ran dry, so running dry again is a new underrun.
+    playing: bool,
+
+    underruns: u64,
+    overruns: u64,
+}
+
+/// Audio output to the host's default sound card, through cpal.
+/// Samples are queued here, and the sound card's callback drains the queue from its own thread.
+/// When the queue runs dry, silence is played.
+/// The queue aims to hold the target latency's worth of samples, and holds at most twice that:
+/// if the emulator runs ahead of the sound card, older samples are dropped instead of building up latency.
+pub struct CpalSink {
+    /// Output stream, playing as long as it's alive.
+    _stream: Stream,
+
+    queue: Arc<Mutex<Queue>>,
+
+    /// Stereo frames the queue aims to hold.
+    target: usize,
+
+    sample_rate: u32,
+}
+
+impl CpalSink {
+    /// Open the default output device, at its default sample rate and buffer size.
+    pub fn new() -> Result<Self, String> {
+        Self::with_buffer(None)
+    }
+
+    /// Open the default output device, at its default sample rate, asking for a buffer of the given
+    /// stereo frames, or the device's default. Smaller buffers cut latency, until the sound card underruns.
+    pub fn with_buffer(frames: Option<u32>) -> Result<Self, String> {
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or("no audio output device")?;
+        let supported = device.default_output_config().map_err(|e| e.to_string())?;
+        let sample_rate = supported.sample_rate().0;
+        let mut config = supported.config();
+        if let Some(frames) = frames {
+            config.buffer_size = BufferSize::Fixed(frames);
+        }
+
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        let stream = match supported.sample_format() {
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, queue.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, queue.clone()),
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, queue.clone()),
+            format => return Err(format!("unsupported sample format {}", format)),
+        }
+        .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        let mut sink = Self {
+            _stream: stream,
+            queue,
+            target: 0,
+            sample_rate,
+        };
+        sink.set_latency(DEFAULT_LATENCY);
+        Ok(sink)
+    }
+
+    /// Aim to keep this much audio queued for the sound card.
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.target = (latency.as_secs_f64() * self.sample_rate as f64).round() as usize;
+    }
+}
+
+/// Build an output stream, converting the queued samples to the device's sample format and channel count.
+/// Mono devices get the average of both channels, extra channels get silence.
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    queue: Arc<Mutex<Queue>>,
+) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<i16>,
+{
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut queue = queue.lock().unwrap();
+            for frame in data.chunks_mut(channels) {
+                let (left, right) = match (queue.samples.pop_front(), queue.samples.pop_front()) {
+                    (Some(left), Some(right)) => (left, right),
+                    _ => {
+                        if std::mem::take(&mut queue.playing) {
+                            queue.underruns += 1;
+                        }
+                        (0, 0)
+                    }
+                };
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    let value = match (channels, channel) {
+                        (1, _) => ((left as i32 + right as i32) / 2) as i16,
+                        (_, 0) => left,
+                        (_, 1) => right,
+                        _ => 0,
+                    };
+                    *sample = T::from_sample(value);
+                }
+            }
+        },
+        |e| warn!("Audio output error: {}", e),
+        None,
+    )
+}
+
+impl AudioSink for CpalSink {
+    fn push_samples(&mut self, samples: &[i16]) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.samples.extend(samples);
+        queue.playing |= !samples.is_empty();
+        // Twice the target, two samples per stereo frame.
+        let max = self.target * 2 * 2;
+        if queue.samples.len() > max {
+            let excess = queue.samples.len() - max;
+            queue.samples.drain(..excess);
+            queue.overruns += 1;
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn buffer_stats(&self) -> Option<BufferStats> {
+        let queue = self.queue.lock().unwrap();
+        Some(BufferStats {
+            queued: queue.samples.len() / 2,
+            target: self.target,
+            underruns: queue.underruns,
+            overruns: queue.overruns,
+        })
+    }
+}
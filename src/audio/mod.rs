@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg(feature = "cpal")]
+mod cpal;
+
+#[cfg(feature = "cpal")]
+pub use self::cpal::CpalSink;
+
+/// Sample rate used when the front-end doesn't ask for a specific one.
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+
+/// Audio queued for the sound card when the front-end doesn't ask for a specific latency.
+pub const DEFAULT_LATENCY: Duration = Duration::from_millis(50);
+
+/// Most emulation speed is nudged to keep a sink's buffer at its target, too little to hear as a change of pitch.
+const MAX_RATE_NUDGE: f64 = 0.005;
+
+/// Audio output
+/// The emulator core pushes its samples to a sink, and doesn't care where they end up: a sound card, a file,
+/// or a Vec for a headless test. Front-ends provide their own sinks for their audio backends.
+///
+/// Samples are signed 16-bit, interleaved stereo (left, right, left, right, ...), at the sink's sample rate.
+pub trait AudioSink {
+    /// Queue samples for output.
+    fn push_samples(&mut self, samples: &[i16]);
+
+    /// Sample rate the sink plays at, in Hz.
+    fn sample_rate(&self) -> u32;
+
+    /// How full the sink's buffer is, for sinks that play samples in real time from a buffer of their own.
+    fn buffer_stats(&self) -> Option<BufferStats> {
+        None
+    }
+}
+
+/// How full a sink's buffer is, in stereo frames, and how often it ran dry or spilled over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    /// Frames queued, and how many the sink aims to keep queued.
+    pub queued: usize,
+    pub target: usize,
+
+    /// Times the sound card found the buffer empty, so silence played (a crackle), since the sink opened.
+    pub underruns: u64,
+
+    /// Times samples were dropped because the buffer was full, since the sink opened.
+    pub overruns: u64,
+}
+
+impl BufferStats {
+    /// Short lines for the frame time HUD, e.g. "Audio 48/50 ms +0.2%" and "2 underruns 0 overruns".
+    pub fn lines(&self, sample_rate: u32) -> Vec<String> {
+        let ms = |frames: usize| frames as u64 * 1000 / sample_rate.max(1) as u64;
+        vec![
+            format!(
+                "Audio {}/{} ms {:+.1}%",
+                ms(self.queued),
+                ms(self.target),
+                (rate_nudge(self) - 1.0) * 100.0
+            ),
+            format!("{} underruns {} overruns", self.underruns, self.overruns),
+        ]
+    }
+}
+
+/// Dynamic rate control
+/// The display (or the host's timer) and the sound card run on clocks of their own, which never quite agree,
+/// so over a long session the buffer between them slowly fills up and overruns, or runs dry and crackles.
+/// Returns how much faster to emulate to keep the buffer at its target: above 1 while it's below, under 1
+/// while it's above, in proportion to how far off it is, by at most 0.5%.
+pub fn rate_nudge(stats: &BufferStats) -> f64 {
+    if stats.target == 0 {
+        return 1.0;
+    }
+    let off = (stats.target as f64 - stats.queued as f64) / stats.target as f64;
+    1.0 + off.clamp(-1.0, 1.0) * MAX_RATE_NUDGE
+}
+
+/// A sink that drops every sample, for running without sound.
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { sample_rate }
+    }
+}
+
+impl Default for NullSink {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl AudioSink for NullSink {
+    fn push_samples(&mut self, _: &[i16]) {}
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// A sink that keeps every sample, so the APU's output can be inspected headlessly.
+pub struct CaptureSink {
+    sample_rate: u32,
+
+    /// Samples pushed so far, interleaved stereo.
+    pub samples: Vec<i16>,
+}
+
+impl CaptureSink {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl AudioSink for CaptureSink {
+    fn push_samples(&mut self, samples: &[i16]) {
+        self.samples.extend_from_slice(samples);
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// Captured samples are shared, so they can still be read after the sink is handed to the GameBoy.
+impl AudioSink for Rc<RefCell<CaptureSink>> {
+    fn push_samples(&mut self, samples: &[i16]) {
+        self.borrow_mut().push_samples(samples);
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.borrow().sample_rate()
+    }
+}
@@ -0,0 +1,125 @@
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Hash a save state (or any byte buffer) with 64-bit FNV-1a.
+/// FNV is simple and stable across platforms and Rust versions, so hash files stay comparable.
+pub fn hash(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Determinism audit
+/// The whole machine state is hashed at the end of every frame. A run can record its hashes to a file,
+/// and a later run can verify against them, flagging the first frame where the two runs diverge.
+///
+/// Hash files are plain text, one frame per line:
+/// <frame> <hash>
+pub struct HashAudit {
+    /// Hashes of this run, by frame.
+    hashes: BTreeMap<u64, u64>,
+
+    /// Hashes of the recorded run, when verifying.
+    expected: Option<BTreeMap<u64, u64>>,
+
+    /// First frame where this run didn't match the recorded run.
+    divergence: Option<u64>,
+}
+
+impl HashAudit {
+    /// Record the hashes of this run.
+    pub fn record() -> Self {
+        Self {
+            hashes: BTreeMap::new(),
+            expected: None,
+            divergence: None,
+        }
+    }
+
+    /// Verify this run against the hashes of a recorded run.
+    pub fn verify(expected: BTreeMap<u64, u64>) -> Self {
+        Self {
+            expected: Some(expected),
+            ..Self::record()
+        }
+    }
+
+    /// Read a hash file.
+    pub fn read(path: &Path) -> io::Result<BTreeMap<u64, u64>> {
+        let invalid = |line: usize| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid hash on line {}", line + 1),
+            )
+        };
+        let mut hashes = BTreeMap::new();
+        for (n, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let (Some(frame), Some(hash), None) = (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid(n));
+            };
+            let frame = frame.parse().map_err(|_| invalid(n))?;
+            let hash = u64::from_str_radix(hash, 16).map_err(|_| invalid(n))?;
+            hashes.insert(frame, hash);
+        }
+        Ok(hashes)
+    }
+
+    /// Record the state hash at the end of a frame, and check it against the recorded run, if verifying.
+    /// A frame can be checked again, e.g. after a run-ahead rollback, the latest hash wins.
+    pub fn check(&mut self, frame: u64, hash: u64) {
+        self.hashes.insert(frame, hash);
+
+        let Some(expected) = &self.expected else {
+            return;
+        };
+        match expected.get(&frame) {
+            Some(&recorded) if recorded != hash && self.divergence.is_none() => {
+                warn!(
+                    "Frame {} diverged, state hash {:016x} doesn't match the recorded {:016x}",
+                    frame, hash, recorded
+                );
+                self.divergence = Some(frame);
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether this run is being recorded, rather than verified.
+    pub fn is_recording(&self) -> bool {
+        self.expected.is_none()
+    }
+
+    /// First frame where this run diverged from the recorded run.
+    pub fn divergence(&self) -> Option<u64> {
+        self.divergence
+    }
+
+    /// Number of frames of the recorded run this run didn't reach.
+    pub fn unverified(&self) -> usize {
+        let last = self.hashes.keys().next_back().copied();
+        self.expected.as_ref().map_or(0, |expected| {
+            expected
+                .keys()
+                .filter(|&&frame| last.is_none_or(|last| frame > last))
+                .count()
+        })
+    }
+
+    /// Write the hashes of this run to a hash file.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        for (frame, hash) in &self.hashes {
+            let _ = writeln!(text, "{} {:016x}", frame, hash);
+        }
+        fs::write(path, text)
+    }
+}
@@ -0,0 +1,265 @@
+use crate::audio::CaptureSink;
+use crate::gb::GameBoy;
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+/// T-cycles per video frame, and per second.
+const FRAME_TICKS: u64 = 154 * 456;
+const CLOCK_HZ: u64 = 4194304;
+
+/// Timestamps are in microseconds.
+const TIMESTAMP_SCALE: u64 = 1000;
+
+/// Matroska element IDs, https://www.matroska.org/technical/elements.html
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549A966;
+const TIMESTAMP_SCALE_ID: u32 = 0x2AD7B1;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const DEFAULT_DURATION: u32 = 0x23E383;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const COLOUR_SPACE: u32 = 0x2EB524;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const BIT_DEPTH: u32 = 0x6264;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+/// Track numbers.
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+
+/// An EBML element, its ID, size, and data.
+fn element(id: u32, data: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = id
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|&byte| byte == 0)
+        .collect();
+    // Sizes are variable length integers, the length is the number of leading zero bits + 1.
+    let len = (1..=8)
+        .find(|len| (data.len() as u64) < (1 << (7 * len)) - 1)
+        .unwrap();
+    let size = (1u64 << (7 * len)) | data.len() as u64;
+    bytes.extend_from_slice(&size.to_be_bytes()[8 - len..]);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+fn uint(id: u32, val: u64) -> Vec<u8> {
+    let bytes = val.to_be_bytes();
+    let first = bytes.iter().position(|&byte| byte != 0).unwrap_or(7);
+    element(id, &bytes[first..])
+}
+
+fn float(id: u32, val: f64) -> Vec<u8> {
+    element(id, &val.to_be_bytes())
+}
+
+fn string(id: u32, val: &str) -> Vec<u8> {
+    element(id, val.as_bytes())
+}
+
+/// Audio/video dump
+/// Writes a Matroska file with uncompressed video (RGB24, 160x144) and audio (16-bit stereo PCM), for encoding later,
+/// e.g. `ffmpeg -i dump.mkv -c:v libx264 -c:a aac dump.mp4`.
+///
+/// Every video frame covers exactly 70224 T-cycles, and carries the audio of those same T-cycles, so audio and video
+/// can't drift apart however long the dump. Both outputs are placed on the machine's T-cycle counter:
+/// the APU produces sample n at T-cycle n * 4194304 / sample rate (counting from when the sample rate was set),
+/// and a video frame shows the last frame the PPU finished by the end of its 70224 T-cycles.
+/// The video runs at 4194304 / 70224 (~59.73) frames per second, like a real Gameboy.
+pub struct AvDump {
+    out: BufWriter<File>,
+    sample_rate: u32,
+
+    /// Frames written so far.
+    frames: u64,
+}
+
+impl AvDump {
+    /// Create the file, and write the headers.
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&element(
+            EBML,
+            &[
+                uint(EBML_VERSION, 1),
+                uint(EBML_READ_VERSION, 1),
+                uint(EBML_MAX_ID_LENGTH, 4),
+                uint(EBML_MAX_SIZE_LENGTH, 8),
+                string(DOC_TYPE, "matroska"),
+                uint(DOC_TYPE_VERSION, 4),
+                uint(DOC_TYPE_READ_VERSION, 2),
+            ]
+            .concat(),
+        ))?;
+
+        // The segment's size isn't known until the end, an all ones size means unknown.
+        out.write_all(&SEGMENT.to_be_bytes())?;
+        out.write_all(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF])?;
+        let app = concat!("ferrum ", env!("CARGO_PKG_VERSION"));
+        out.write_all(&element(
+            INFO,
+            &[
+                uint(TIMESTAMP_SCALE_ID, TIMESTAMP_SCALE),
+                string(MUXING_APP, app),
+                string(WRITING_APP, app),
+            ]
+            .concat(),
+        ))?;
+
+        let video = element(
+            TRACK_ENTRY,
+            &[
+                uint(TRACK_NUMBER, VIDEO_TRACK as u64),
+                uint(TRACK_UID, VIDEO_TRACK as u64),
+                uint(TRACK_TYPE, 1),
+                string(CODEC_ID, "V_UNCOMPRESSED"),
+                uint(DEFAULT_DURATION, FRAME_TICKS * 1_000_000_000 / CLOCK_HZ),
+                element(
+                    VIDEO,
+                    &[
+                        uint(PIXEL_WIDTH, SCREEN_WIDTH as u64),
+                        uint(PIXEL_HEIGHT, SCREEN_HEIGHT as u64),
+                        element(COLOUR_SPACE, &[b'R', b'G', b'B', 24]),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        );
+        let audio = element(
+            TRACK_ENTRY,
+            &[
+                uint(TRACK_NUMBER, AUDIO_TRACK as u64),
+                uint(TRACK_UID, AUDIO_TRACK as u64),
+                uint(TRACK_TYPE, 2),
+                string(CODEC_ID, "A_PCM/INT/LIT"),
+                element(
+                    AUDIO,
+                    &[
+                        float(SAMPLING_FREQUENCY, sample_rate as f64),
+                        uint(CHANNELS, 2),
+                        uint(BIT_DEPTH, 16),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        );
+        out.write_all(&element(TRACKS, &[video, audio].concat()))?;
+
+        Ok(Self {
+            out,
+            sample_rate,
+            frames: 0,
+        })
+    }
+
+    /// Write a video frame (160x144 pixels, 0x00RRGGBB) and its audio (interleaved stereo samples),
+    /// as a cluster of their own.
+    fn write_frame(&mut self, pixels: &[u32], samples: &[i16]) -> io::Result<()> {
+        let timestamp =
+            (self.frames * FRAME_TICKS * 1_000_000_000 / TIMESTAMP_SCALE + CLOCK_HZ / 2) / CLOCK_HZ;
+
+        // Track number, timestamp relative to the cluster, and flags (keyframe).
+        let mut video = vec![0x80 | VIDEO_TRACK, 0x00, 0x00, 0x80];
+        video.extend(pixels.iter().flat_map(|pixel| {
+            let [_, r, g, b] = pixel.to_be_bytes();
+            [r, g, b]
+        }));
+        let mut audio = vec![0x80 | AUDIO_TRACK, 0x00, 0x00, 0x80];
+        audio.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+
+        self.out.write_all(&element(
+            CLUSTER,
+            &[
+                uint(TIMESTAMP, timestamp),
+                element(SIMPLE_BLOCK, &video),
+                element(SIMPLE_BLOCK, &audio),
+            ]
+            .concat(),
+        ))?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Run a machine for the given number of video frames, dumping them.
+    /// The machine's audio sink is set aside while dumping, and put back after.
+    pub fn record(&mut self, gb: &mut GameBoy, frames: u64) -> io::Result<()> {
+        let sink = gb.take_audio_sink();
+        let capture = Rc::new(RefCell::new(CaptureSink::new(self.sample_rate)));
+        gb.set_audio_sink(Box::new(capture.clone()));
+        let result = self.dump(gb, &capture, frames);
+        gb.take_audio_sink();
+        if let Some(sink) = sink {
+            gb.set_audio_sink(sink);
+        }
+        result
+    }
+
+    fn dump(
+        &mut self,
+        gb: &mut GameBoy,
+        capture: &Rc<RefCell<CaptureSink>>,
+        frames: u64,
+    ) -> io::Result<()> {
+        // The APU counts samples from when the sample rate was set, just now.
+        let start = gb.counters().cycles;
+        let mut written = 0;
+        let mut shown = gb.viewport();
+        let mut next = None;
+
+        for n in 1..=frames {
+            let end = start + n * FRAME_TICKS;
+
+            // A frame the PPU finishes past the end of this one is shown in the next.
+            while gb.counters().cycles < end {
+                if gb.run_frame() {
+                    if gb.counters().cycles <= end {
+                        shown = gb.viewport();
+                    } else {
+                        next = Some(gb.viewport());
+                    }
+                }
+            }
+
+            // Samples up to the end of the frame, the rest stay for the next.
+            let samples = ((end - start) * self.sample_rate as u64 / CLOCK_HZ) as usize * 2;
+            let audio: Vec<i16> = capture
+                .borrow_mut()
+                .samples
+                .drain(..samples - written)
+                .collect();
+            written = samples;
+            self.write_frame(&shown, &audio)?;
+            if let Some(frame) = next.take() {
+                shown = frame;
+            }
+        }
+        self.out.flush()
+    }
+}
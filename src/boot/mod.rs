@@ -54,3 +54,65 @@ pub static BOOTROM: &[u8] = &[
     0x21, 0x04, 0x01, 0x11, 0xa8, 0x00, 0x1a, 0x13, 0xbe, 0x20, 0xfe, 0x23, 0x7d, 0xfe, 0x34, 0x20,
     0xf5, 0x06, 0x19, 0x78, 0x86, 0x23, 0x05, 0x20, 0xfb, 0x86, 0x20, 0xfe, 0x3e, 0x01, 0xe0, 0x50,
 ];
+
+/// The early DMG boot ROM, only found in the first Japanese DMG-01 units.
+/// It checks the header before drawing the logo, and flashes the screen instead of hanging with the logo on screen
+/// if the check fails. It also leaves different values in the CPU registers.
+pub static DMG0_BOOTROM: &[u8] = &[
+    0x31, 0xfe, 0xff, 0xaf, 0x21, 0xff, 0x9f, 0x32, 0xcb, 0x7c, 0x20, 0xfb, 0x21, 0x26, 0xff, 0x0e,
+    0x11, 0x3e, 0x80, 0x32, 0xe2, 0x0c, 0x3e, 0xf3, 0xe2, 0x32, 0x3e, 0x77, 0x77, 0x3e, 0xfc, 0xe0,
+    0x47, 0x21, 0x04, 0x01, 0xe5, 0x11, 0xcb, 0x00, 0x1a, 0x13, 0xbe, 0x20, 0x6b, 0x23, 0x7d, 0xfe,
+    0x34, 0x20, 0xf5, 0x06, 0x19, 0x78, 0x86, 0x23, 0x05, 0x20, 0xfb, 0x86, 0x20, 0x5a, 0xd1, 0x21,
+    0x10, 0x80, 0x1a, 0xcd, 0xa9, 0x00, 0xcd, 0xaa, 0x00, 0x13, 0x7b, 0xfe, 0x34, 0x20, 0xf3, 0x3e,
+    0x18, 0x21, 0x2f, 0x99, 0x0e, 0x0c, 0x32, 0x3d, 0x28, 0x09, 0x0d, 0x20, 0xf9, 0x11, 0xec, 0xff,
+    0x19, 0x18, 0xf1, 0x67, 0x3e, 0x64, 0x57, 0xe0, 0x42, 0x3e, 0x91, 0xe0, 0x40, 0x04, 0x1e, 0x02,
+    0xcd, 0xbc, 0x00, 0x0e, 0x13, 0x24, 0x7c, 0x1e, 0x83, 0xfe, 0x62, 0x28, 0x06, 0x1e, 0xc1, 0xfe,
+    0x64, 0x20, 0x06, 0x7b, 0xe2, 0x0c, 0x3e, 0x87, 0xe2, 0xf0, 0x42, 0x90, 0xe0, 0x42, 0x15, 0x20,
+    0xdd, 0x05, 0x20, 0x69, 0x16, 0x20, 0x18, 0xd6, 0x3e, 0x91, 0xe0, 0x40, 0x1e, 0x14, 0xcd, 0xbc,
+    0x00, 0xf0, 0x47, 0xee, 0xff, 0xe0, 0x47, 0x18, 0xf3, 0x4f, 0x06, 0x04, 0xc5, 0xcb, 0x11, 0x17,
+    0xc1, 0xcb, 0x11, 0x17, 0x05, 0x20, 0xf5, 0x22, 0x23, 0x22, 0x23, 0xc9, 0x0e, 0x0c, 0xf0, 0x44,
+    0xfe, 0x90, 0x20, 0xfa, 0x0d, 0x20, 0xf7, 0x1d, 0x20, 0xf2, 0xc9, 0xce, 0xed, 0x66, 0x66, 0xcc,
+    0x0d, 0x00, 0x0b, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0c, 0x00, 0x0d, 0x00, 0x08, 0x11, 0x1f, 0x88,
+    0x89, 0x00, 0x0e, 0xdc, 0xcc, 0x6e, 0xe6, 0xdd, 0xdd, 0xd9, 0x99, 0xbb, 0xbb, 0x67, 0x63, 0x6e,
+    0x0e, 0xec, 0xcc, 0xdd, 0xdc, 0x99, 0x9f, 0xbb, 0xb9, 0x33, 0x3e, 0xff, 0xff, 0x3c, 0xe0, 0x50,
+];
+
+/// The Gameboy Pocket (MGB) boot ROM.
+/// Identical to the DMG boot ROM, except it loads $FF into A instead of $01 before handing control to the cartridge,
+/// which games can use to detect the Pocket.
+pub static MGB_BOOTROM: &[u8] = &[
+    0x31, 0xfe, 0xff, 0xaf, 0x21, 0xff, 0x9f, 0x32, 0xcb, 0x7c, 0x20, 0xfb, 0x21, 0x26, 0xff, 0x0e,
+    0x11, 0x3e, 0x80, 0x32, 0xe2, 0x0c, 0x3e, 0xf3, 0xe2, 0x32, 0x3e, 0x77, 0x77, 0x3e, 0xfc, 0xe0,
+    0x47, 0x11, 0x04, 0x01, 0x21, 0x10, 0x80, 0x1a, 0xcd, 0x95, 0x00, 0xcd, 0x96, 0x00, 0x13, 0x7b,
+    0xfe, 0x34, 0x20, 0xf3, 0x11, 0xd8, 0x00, 0x06, 0x08, 0x1a, 0x13, 0x22, 0x23, 0x05, 0x20, 0xf9,
+    0x3e, 0x19, 0xea, 0x10, 0x99, 0x21, 0x2f, 0x99, 0x0e, 0x0c, 0x3d, 0x28, 0x08, 0x32, 0x0d, 0x20,
+    0xf9, 0x2e, 0x0f, 0x18, 0xf3, 0x67, 0x3e, 0x64, 0x57, 0xe0, 0x42, 0x3e, 0x91, 0xe0, 0x40, 0x04,
+    0x1e, 0x02, 0x0e, 0x0c, 0xf0, 0x44, 0xfe, 0x90, 0x20, 0xfa, 0x0d, 0x20, 0xf7, 0x1d, 0x20, 0xf2,
+    0x0e, 0x13, 0x24, 0x7c, 0x1e, 0x83, 0xfe, 0x62, 0x28, 0x06, 0x1e, 0xc1, 0xfe, 0x64, 0x20, 0x06,
+    0x7b, 0xe2, 0x0c, 0x3e, 0x87, 0xe2, 0xf0, 0x42, 0x90, 0xe0, 0x42, 0x15, 0x20, 0xd2, 0x05, 0x20,
+    0x4f, 0x16, 0x20, 0x18, 0xcb, 0x4f, 0x06, 0x04, 0xc5, 0xcb, 0x11, 0x17, 0xc1, 0xcb, 0x11, 0x17,
+    0x05, 0x20, 0xf5, 0x22, 0x23, 0x22, 0x23, 0xc9, 0xce, 0xed, 0x66, 0x66, 0xcc, 0x0d, 0x00, 0x0b,
+    0x03, 0x73, 0x00, 0x83, 0x00, 0x0c, 0x00, 0x0d, 0x00, 0x08, 0x11, 0x1f, 0x88, 0x89, 0x00, 0x0e,
+    0xdc, 0xcc, 0x6e, 0xe6, 0xdd, 0xdd, 0xd9, 0x99, 0xbb, 0xbb, 0x67, 0x63, 0x6e, 0x0e, 0xec, 0xcc,
+    0xdd, 0xdc, 0x99, 0x9f, 0xbb, 0xb9, 0x33, 0x3e, 0x3c, 0x42, 0xb9, 0xa5, 0xb9, 0xa5, 0x42, 0x3c,
+    0x21, 0x04, 0x01, 0x11, 0xa8, 0x00, 0x1a, 0x13, 0xbe, 0x20, 0xfe, 0x23, 0x7d, 0xfe, 0x34, 0x20,
+    0xf5, 0x06, 0x19, 0x78, 0x86, 0x23, 0x05, 0x20, 0xfb, 0x86, 0x20, 0xfe, 0x3e, 0xff, 0xe0, 0x50,
+];
+
+/// IO registers as the boot ROM leaves them, written in the order it writes them, to skip it.
+/// Only the registers it writes (and IF, whose V-Blank flag the logo animation leaves set) are here,
+/// the others are still as they were at power on when it hands control to the cartridge.
+/// https://gbdev.io/pandocs/Power_Up_Sequence.html#hardware-registers
+pub const POST_BOOT_IO: [(u16, u8); 11] = [
+    (0xFF26, 0x80), // NR52, APU on
+    (0xFF11, 0x80), // NR11
+    (0xFF12, 0xF3), // NR12
+    (0xFF25, 0xF3), // NR51
+    (0xFF24, 0x77), // NR50
+    (0xFF47, 0xFC), // BGP
+    (0xFF42, 0x00), // SCY, the logo scrolled into place
+    (0xFF40, 0x91), // LCDC
+    (0xFF13, 0xC1), // NR13, the second note of the chime
+    (0xFF14, 0x87), // NR14, trigger
+    (0xFF0F, 0xE1), // IF
+];
@@ -0,0 +1,126 @@
+use std::cell::Cell;
+
+/// What reads from unmapped or inaccessible memory return.
+/// When nothing drives the data bus, the value read depends on the region, and on the hardware revision.
+/// https://gbdev.io/pandocs/Memory_Map.html
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OpenBusPolicy {
+    /// Return what the DMG returns for the region, e.g. $FF for VRAM during Drawing, $00 for $FEA0-$FEFF.
+    #[default]
+    Accurate,
+
+    /// Always return $FF.
+    Ff,
+
+    /// Return the last value seen on the data bus.
+    LastValue,
+}
+
+/// What the CPU sees of the buses an OAM DMA transfer is using.
+/// While DMA copies to OAM, it drives the external bus (ROM, cartridge RAM, WRAM) and the VRAM bus,
+/// so the CPU can only use HRAM and the IO registers, which is why games run their DMA routine from HRAM.
+/// https://gbdev.io/pandocs/OAM_DMA_Transfer.html
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DmaBusPolicy {
+    /// Reads from those buses return the byte being transferred, and writes are lost, like on a real Gameboy.
+    #[default]
+    Restricted,
+
+    /// The CPU keeps access to all of memory, for games that get away with it elsewhere.
+    Free,
+}
+
+/// Open bus state, shared between the MMU and the components that can leave the bus undriven.
+pub struct OpenBus {
+    policy: Cell<OpenBusPolicy>,
+
+    /// Last value read from, or written to, the data bus.
+    last: Cell<u8>,
+}
+
+impl OpenBus {
+    pub fn new() -> Self {
+        Self {
+            policy: Cell::new(OpenBusPolicy::default()),
+            last: Cell::new(0xFF),
+        }
+    }
+
+    pub fn set_policy(&self, policy: OpenBusPolicy) {
+        self.policy.set(policy);
+    }
+
+    pub fn policy(&self) -> OpenBusPolicy {
+        self.policy.get()
+    }
+
+    /// Record the value on the data bus after a read or write.
+    pub fn latch(&self, val: u8) {
+        self.last.set(val);
+    }
+
+    pub fn last(&self) -> u8 {
+        self.last.get()
+    }
+
+    /// The value of an open bus read, accurate is what the DMG returns for the region being read.
+    pub fn read(&self, accurate: u8) -> u8 {
+        match self.policy.get() {
+            OpenBusPolicy::Accurate => accurate,
+            OpenBusPolicy::Ff => 0xFF,
+            OpenBusPolicy::LastValue => self.last.get(),
+        }
+    }
+}
+
+impl Default for OpenBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmu::memory::Memory;
+    use crate::mmu::Mmu;
+
+    #[test]
+    fn policies() {
+        let bus = OpenBus::new();
+        bus.latch(0x42);
+        for (policy, expected) in [
+            (OpenBusPolicy::Accurate, 0x00),
+            (OpenBusPolicy::Ff, 0xFF),
+            (OpenBusPolicy::LastValue, 0x42),
+        ] {
+            bus.set_policy(policy);
+            assert_eq!(bus.read(0x00), expected, "{:?}", policy);
+        }
+    }
+
+    /// Write a byte to WRAM, then read the missing cartridge RAM, which leaves the bus open.
+    fn after_write(policy: OpenBusPolicy, val: u8) -> u8 {
+        let mut mmu = Mmu::from_rom(vec![0x00; 0x8000], None);
+        mmu.set_open_bus_policy(policy);
+        mmu.write8(0xC000, val);
+        mmu.read8(0xA000)
+    }
+
+    /// The last value is whatever went over the bus last, written or read.
+    #[test]
+    fn last_value() {
+        assert_eq!(after_write(OpenBusPolicy::LastValue, 0x5A), 0x5A);
+        assert_eq!(after_write(OpenBusPolicy::LastValue, 0x3C), 0x3C);
+        assert_eq!(after_write(OpenBusPolicy::Ff, 0x5A), 0xFF);
+        // The DMG reads $FF from a cartridge without RAM.
+        assert_eq!(after_write(OpenBusPolicy::Accurate, 0x5A), 0xFF);
+
+        let mut mmu = Mmu::from_rom(vec![0x00; 0x8000], None);
+        mmu.set_open_bus_policy(OpenBusPolicy::LastValue);
+        mmu.write8(0xC000, 0x77);
+        mmu.write8(0xC001, 0x11);
+        mmu.read8(0xC000);
+        assert_eq!(mmu.read8(0xA000), 0x77, "a read didn't latch the bus");
+    }
+}
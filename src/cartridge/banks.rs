@@ -0,0 +1,140 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The banks a cartridge has mapped into the address space.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Banks {
+    /// ROM bank at $0000-$3FFF, 0 except on MBC1 in advanced banking mode.
+    pub rom0: usize,
+
+    /// ROM bank at $4000-$7FFF.
+    pub rom: usize,
+
+    /// RAM bank (or MBC3 RTC register, $08-$0C) at $A000-$BFFF, None without RAM banking.
+    pub ram: Option<usize>,
+
+    /// Banking mode, None if the mapper has only one, e.g. MBC1's 0 (simple) or 1 (advanced).
+    pub mode: Option<u8>,
+
+    /// Whether external RAM is enabled, None if the mapper doesn't gate it.
+    pub ram_enabled: Option<bool>,
+}
+
+impl Banks {
+    /// The bank behind a CPU address, None outside the cartridge's banked areas.
+    pub fn bank_of(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x0000..=0x3FFF => Some(self.rom0),
+            0x4000..=0x7FFF => Some(self.rom),
+            0xA000..=0xBFFF => self.ram,
+            _ => None,
+        }
+    }
+}
+
+/// A bank in hex, or "-" for None.
+fn write_bank(f: &mut fmt::Formatter, bank: Option<usize>) -> fmt::Result {
+    match bank {
+        Some(bank) => write!(f, "{:02X}", bank),
+        None => write!(f, "-"),
+    }
+}
+
+/// "rom0=00 rom=05 ram=01 mode=0 ram-enabled=1", what the mapper doesn't have is "-".
+impl fmt::Display for Banks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rom0={:02X} rom={:02X} ram=", self.rom0, self.rom)?;
+        write_bank(f, self.ram)?;
+        write!(f, " mode=")?;
+        write_bank(f, self.mode.map(usize::from))?;
+        write!(f, " ram-enabled=")?;
+        write_bank(f, self.ram_enabled.map(usize::from))
+    }
+}
+
+/// Banks forced from a debugger, whatever the mapper's registers say, e.g. to tell whether a game stuck in the
+/// wrong bank is let down by ferrum's mapper. None leaves the mapper in charge. The mapper still takes the game's
+/// bank switches, they show again once the override is cleared.
+/// A RAM override maps external RAM banks only, never an MBC3's RTC registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BankOverride {
+    /// ROM bank at $4000-$7FFF.
+    pub rom: Option<usize>,
+
+    /// RAM bank at $A000-$BFFF.
+    pub ram: Option<usize>,
+    pub ram_enabled: Option<bool>,
+}
+
+impl BankOverride {
+    /// Whether the override leaves RAM to the mapper.
+    pub fn ram_is_mapped(&self) -> bool {
+        self.ram.is_none() && self.ram_enabled.is_none()
+    }
+
+    /// The banks the CPU sees, with the override on top of the mapper's.
+    pub fn apply(&self, banks: Banks) -> Banks {
+        Banks {
+            rom: self.rom.unwrap_or(banks.rom),
+            ram: self.ram.or(banks.ram),
+            ram_enabled: self.ram_enabled.or(banks.ram_enabled),
+            ..banks
+        }
+    }
+}
+
+/// "rom=07 ram=- ram-enabled=1", "-" is left to the mapper.
+impl fmt::Display for BankOverride {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rom=")?;
+        write_bank(f, self.rom)?;
+        write!(f, " ram=")?;
+        write_bank(f, self.ram)?;
+        write!(f, " ram-enabled=")?;
+        write_bank(f, self.ram_enabled.map(usize::from))
+    }
+}
+
+/// A CPU address qualified with the bank mapped there, e.g. 03:4F20.
+/// With a mapper, the address alone doesn't say which code or data it is, the same $4F20 is in every ROM bank.
+/// Addresses outside the cartridge's banked areas (and the boot ROM) have no bank.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BankedAddr {
+    pub bank: Option<u16>,
+    pub addr: u16,
+}
+
+impl BankedAddr {
+    pub fn new(bank: Option<u16>, addr: u16) -> Self {
+        Self { bank, addr }
+    }
+}
+
+/// e.g. "03:4F20", or "C000" without a bank.
+impl fmt::Display for BankedAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(bank) = self.bank {
+            write!(f, "{:02X}:", bank)?;
+        }
+        write!(f, "{:04X}", self.addr)
+    }
+}
+
+/// BANK:ADDR or ADDR in hex, e.g. 03:4F20, $4F20, or 0x4F20.
+impl FromStr for BankedAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = |s: &str, what: &str| {
+            let digits = s.trim_start_matches("0x").trim_start_matches('$');
+            u16::from_str_radix(digits, 16).map_err(|_| format!("`{}` isn't {}", s, what))
+        };
+        match s.split_once(':') {
+            Some((bank, addr)) => Ok(Self::new(
+                Some(hex(bank, "a bank")?),
+                hex(addr, "an address")?,
+            )),
+            None => Ok(Self::new(None, hex(s, "an address")?)),
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use crate::boot::BOOTROM;
 use num_enum::IntoPrimitive;
 use num_enum::TryFromPrimitive;
+use std::fmt;
 /*
  The cartridge header contains the following information:
  https://gbdev.io/pandocs/The_Cartridge_Header.html#the-cartridge-header
@@ -62,7 +64,7 @@ pub enum CartridgeType {
 /// The ROM size is usually defined by the following formula:
 /// 32KiB x (1 << value).
 /// The number of banks is then calculated by dividing the ROM size by 16KiB.
-#[derive(Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum RomSize {
     Rom32Kb = 0x00,
@@ -99,6 +101,52 @@ pub enum RamSize {
     Kb64 = 0x05,
 }
 
+impl CartridgeType {
+    /// Does the cartridge have a battery to keep its external RAM (and RTC) alive while powered off?
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self,
+            CartridgeType::Mbc1RamBattery
+                | CartridgeType::Mbc2Battery
+                | CartridgeType::RomRamBattery
+                | CartridgeType::Mmm01RamBattery
+                | CartridgeType::Mbc3TimerBattery
+                | CartridgeType::Mbc3TimerRamBattery
+                | CartridgeType::Mbc3RamBattery
+                | CartridgeType::Mbc5RamBattery
+                | CartridgeType::Mbc5RumbleRamBattery
+                | CartridgeType::Mbc7SensorRumbleRamBattery
+                | CartridgeType::HuC1RamBattery
+        )
+    }
+}
+
+impl RomSize {
+    /// Size of the ROM in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            RomSize::Rom1_1Mb => 72 * 0x4000,
+            RomSize::Rom1_2Mb => 80 * 0x4000,
+            RomSize::Rom1_5Mb => 96 * 0x4000,
+            size => 0x8000 << (*size as u8),
+        }
+    }
+}
+
+impl RamSize {
+    /// Size of the external RAM in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            RamSize::None => 0,
+            RamSize::Kb2Unused => 0x800,
+            RamSize::Kb8 => 0x2000,
+            RamSize::Kb32 => 0x8000,
+            RamSize::Kb128 => 0x20000,
+            RamSize::Kb64 => 0x10000,
+        }
+    }
+}
+
 /// Destination Code
 /// This is used to determine if the game is for the Japanese market or the international market.
 #[derive(Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
@@ -330,3 +378,242 @@ pub enum OldLicenseeCode {
     ExtremeEntertainment = 0xF3,
     LjnFF = 0xFF,
 }
+
+/// Cartridge header ends at $014F.
+pub const HEADER_END: usize = 0x150;
+
+/// The Nintendo logo, which the boot ROM compares against the copy in the cartridge header.
+/// The boot ROM keeps its own copy at $00A8-$00D7.
+pub fn logo() -> &'static [u8] {
+    &BOOTROM[0xA8..0xD8]
+}
+
+/// Header checksum, over $0134-$014C, checked by the boot ROM.
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[0x134..=0x14C]
+        .iter()
+        .fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1))
+}
+
+/// Global checksum, over every byte but itself, not checked by the hardware.
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|&(i, _)| i != 0x14E && i != 0x14F)
+        .fold(0u16, |sum, (_, &b)| sum.wrapping_add(b as u16))
+}
+
+/// Fill in the header checksum and global checksum of a ROM image, which has to hold at least a header.
+/// The header checksum goes first, the global checksum covers it.
+pub fn fix_checksums(rom: &mut [u8]) {
+    rom[0x14D] = header_checksum(rom);
+    let global = global_checksum(rom);
+    rom[0x14E..0x150].copy_from_slice(&global.to_be_bytes());
+}
+
+/// New Licensee Code, two ASCII characters that read as the hex value of the code, e.g. "31" for Nintendo.
+pub fn new_licensee_code(code: [u8; 2]) -> Option<NewLicenseeCode> {
+    let code = std::str::from_utf8(&code).ok()?;
+    NewLicenseeCode::try_from(u8::from_str_radix(code, 16).ok()?).ok()
+}
+
+/// The header of a ROM image, decoded straight from the file, without loading the cartridge.
+/// Unlike the cartridge, this doesn't assume the header is valid, unknown codes decode to None.
+pub struct Header<'a> {
+    rom: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    /// None if the image is too short to hold a header.
+    pub fn new(rom: &'a [u8]) -> Option<Self> {
+        (rom.len() >= HEADER_END).then_some(Self { rom })
+    }
+
+    /// Printable text of a header field, up to the first $00.
+    fn text(&self, start: usize, end: usize) -> String {
+        self.rom[start..end]
+            .iter()
+            .take_while(|&&b| b != 0x00)
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+
+    /// Title, the CGB flag takes the place of its last character in CGB aware cartridges.
+    pub fn title(&self) -> String {
+        if self.cgb_flag() & 0x80 != 0 {
+            self.text(0x134, 0x143)
+        } else {
+            self.text(0x134, 0x144)
+        }
+    }
+
+    /// Manufacturer Code, only in newer cartridges, where it takes the place of the end of the title.
+    pub fn manufacturer_code(&self) -> Option<String> {
+        let code = &self.rom[0x13F..0x143];
+        (self.cgb_flag() & 0x80 != 0 && code.iter().all(u8::is_ascii_alphanumeric))
+            .then(|| self.text(0x13F, 0x143))
+    }
+
+    pub fn cgb_flag(&self) -> u8 {
+        self.rom[0x143]
+    }
+
+    pub fn sgb_flag(&self) -> u8 {
+        self.rom[0x146]
+    }
+
+    pub fn cartridge_type(&self) -> Option<CartridgeType> {
+        CartridgeType::try_from(self.rom[0x147]).ok()
+    }
+
+    pub fn rom_size(&self) -> Option<RomSize> {
+        RomSize::try_from(self.rom[0x148]).ok()
+    }
+
+    pub fn ram_size(&self) -> Option<RamSize> {
+        RamSize::try_from(self.rom[0x149]).ok()
+    }
+
+    pub fn destination_code(&self) -> Option<DestinationCode> {
+        DestinationCode::try_from(self.rom[0x14A]).ok()
+    }
+
+    pub fn old_licensee_code(&self) -> Option<OldLicenseeCode> {
+        OldLicenseeCode::try_from(self.rom[0x14B]).ok()
+    }
+
+    pub fn new_licensee_code(&self) -> Option<NewLicenseeCode> {
+        new_licensee_code([self.rom[0x144], self.rom[0x145]])
+    }
+
+    pub fn version(&self) -> u8 {
+        self.rom[0x14C]
+    }
+
+    /// Whether the logo matches the boot ROM's copy, otherwise the boot ROM locks up.
+    pub fn logo_valid(&self) -> bool {
+        &self.rom[0x104..0x134] == logo()
+    }
+
+    /// Header checksum in the header, and the one computed from the header.
+    pub fn header_checksum(&self) -> (u8, u8) {
+        (self.rom[0x14D], header_checksum(self.rom))
+    }
+
+    /// Global checksum in the header, and the one computed from the image.
+    pub fn global_checksum(&self) -> (u16, u16) {
+        (
+            u16::from_be_bytes([self.rom[0x14E], self.rom[0x14F]]),
+            global_checksum(self.rom),
+        )
+    }
+}
+
+/// Name of a header code, or its raw value if it isn't a known code.
+pub(super) fn describe<T: fmt::Debug>(code: Option<T>, raw: impl fmt::UpperHex) -> String {
+    code.map_or(format!("Unknown (${:02X})", raw), |code| {
+        format!("{:?}", code)
+    })
+}
+
+/// Whether a checksum in the header matches the computed one.
+fn check(valid: bool) -> &'static str {
+    if valid {
+        "OK"
+    } else {
+        "BAD"
+    }
+}
+
+/// Every field of the header, decoded, one per line.
+impl fmt::Display for Header<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rom = self.rom;
+        writeln!(f, "Title:              {}", self.title())?;
+        if let Some(code) = self.manufacturer_code() {
+            writeln!(f, "Manufacturer Code:  {}", code)?;
+        }
+        let cgb = match self.cgb_flag() {
+            0x80 => "CGB enhanced, works on DMG",
+            0xC0 => "CGB only",
+            _ => "DMG",
+        };
+        writeln!(f, "CGB Flag:           ${:02X} ({})", self.cgb_flag(), cgb)?;
+        let sgb = if self.sgb_flag() == 0x03 {
+            "SGB functions"
+        } else {
+            "none"
+        };
+        writeln!(f, "SGB Flag:           ${:02X} ({})", self.sgb_flag(), sgb)?;
+        writeln!(
+            f,
+            "Cartridge Type:     {}",
+            describe(self.cartridge_type(), rom[0x147])
+        )?;
+        match self.rom_size() {
+            Some(size) => writeln!(
+                f,
+                "ROM Size:           {:?} ({} bytes, file is {} bytes)",
+                size,
+                size.bytes(),
+                rom.len()
+            )?,
+            None => writeln!(
+                f,
+                "ROM Size:           {}",
+                describe(None::<RomSize>, rom[0x148])
+            )?,
+        }
+        match self.ram_size() {
+            Some(size) => writeln!(f, "RAM Size:           {:?} ({} bytes)", size, size.bytes())?,
+            None => writeln!(
+                f,
+                "RAM Size:           {}",
+                describe(None::<RamSize>, rom[0x149])
+            )?,
+        }
+        writeln!(
+            f,
+            "Destination Code:   {}",
+            describe(self.destination_code(), rom[0x14A])
+        )?;
+        writeln!(
+            f,
+            "Old Licensee Code:  {}",
+            describe(self.old_licensee_code(), rom[0x14B])
+        )?;
+        if self.old_licensee_code() == Some(OldLicenseeCode::UseNewLicenseeCode) {
+            writeln!(
+                f,
+                "New Licensee Code:  {} (\"{}\")",
+                self.new_licensee_code()
+                    .map_or("Unknown".to_string(), |code| format!("{:?}", code)),
+                self.text(0x144, 0x146)
+            )?;
+        }
+        writeln!(f, "Version:            ${:02X}", self.version())?;
+        writeln!(f, "Nintendo Logo:      {}", check(self.logo_valid()))?;
+        let (stored, computed) = self.header_checksum();
+        writeln!(
+            f,
+            "Header Checksum:    ${:02X} (computed ${:02X}, {})",
+            stored,
+            computed,
+            check(stored == computed)
+        )?;
+        let (stored, computed) = self.global_checksum();
+        write!(
+            f,
+            "Global Checksum:    ${:04X} (computed ${:04X}, {})",
+            stored,
+            computed,
+            check(stored == computed)
+        )
+    }
+}
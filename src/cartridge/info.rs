@@ -0,0 +1,108 @@
+use super::header::{describe, Header, OldLicenseeCode};
+use super::Mapper;
+use serde::Serialize;
+
+/// Whether a cartridge makes use of the Gameboy Color, from the CGB flag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CgbSupport {
+    /// A DMG cartridge, the CGB runs it with its DMG palettes.
+    None,
+
+    /// CGB enhanced, and it works on a DMG too.
+    Enhanced,
+
+    /// CGB only.
+    Only,
+}
+
+/// ROM info
+/// What the cartridge header says about a ROM image, decoded without loading the cartridge or starting an emulator,
+/// for listing games, and for tools built on ferrum. Serializes, e.g. to JSON with `ferrum info --json`.
+/// Codes the header doesn't hold a known value for read "Unknown ($XX)", sizes None.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RomInfo {
+    pub title: String,
+
+    /// Manufacturer Code, only in newer cartridges.
+    pub manufacturer_code: Option<String>,
+    pub cgb: CgbSupport,
+
+    /// Whether the cartridge uses the Super Gameboy's functions.
+    pub sgb: bool,
+
+    /// Cartridge type from the header, e.g. "Mbc1RamBattery".
+    pub cartridge_type: String,
+
+    /// The mapper ferrum emulates the cartridge with, None if it doesn't emulate this cartridge type.
+    pub mapper: Option<Mapper>,
+
+    /// Whether a battery keeps the cartridge RAM, and clock, when powered off.
+    pub battery: bool,
+
+    /// ROM and cartridge RAM sizes from the header, in bytes.
+    pub rom_size: Option<usize>,
+    pub ram_size: Option<usize>,
+
+    /// Size of the image itself, in bytes, which doesn't always match the header.
+    pub file_size: usize,
+
+    /// e.g. "Japan" or "Overseas".
+    pub destination: String,
+
+    /// Who published the game, from the Old Licensee Code, or the New Licensee Code when it says to use that.
+    pub licensee: String,
+
+    /// Mask ROM version number, 0 for the first release.
+    pub version: u8,
+
+    /// Whether the logo, header checksum, and global checksum are right. A real Gameboy only boots cartridges
+    /// with the first two right, it doesn't check the global checksum.
+    pub logo_valid: bool,
+    pub header_checksum_valid: bool,
+    pub global_checksum_valid: bool,
+}
+
+/// Decode the header of a ROM image, None if the image is too short to hold one.
+pub fn rom_info(rom: &[u8]) -> Option<RomInfo> {
+    let header = Header::new(rom)?;
+    let cgb = match header.cgb_flag() {
+        0x80 => CgbSupport::Enhanced,
+        0xC0 => CgbSupport::Only,
+        _ => CgbSupport::None,
+    };
+    let cartridge_type = header.cartridge_type();
+    let licensee = match header.old_licensee_code() {
+        Some(OldLicenseeCode::UseNewLicenseeCode) => header.new_licensee_code().map_or_else(
+            || {
+                format!(
+                    "Unknown (\"{}\")",
+                    String::from_utf8_lossy(&rom[0x144..0x146])
+                )
+            },
+            |code| format!("{:?}", code),
+        ),
+        code => describe(code, rom[0x14B]),
+    };
+    let (stored, computed) = header.header_checksum();
+    let header_checksum_valid = stored == computed;
+    let (stored, computed) = header.global_checksum();
+    Some(RomInfo {
+        title: header.title(),
+        manufacturer_code: header.manufacturer_code(),
+        cgb,
+        sgb: header.sgb_flag() == 0x03,
+        cartridge_type: describe(cartridge_type.as_ref(), rom[0x147]),
+        mapper: cartridge_type.as_ref().and_then(Mapper::from_type),
+        battery: cartridge_type.as_ref().is_some_and(|t| t.has_battery()),
+        rom_size: header.rom_size().map(|size| size.bytes()),
+        ram_size: header.ram_size().map(|size| size.bytes()),
+        file_size: rom.len(),
+        destination: describe(header.destination_code(), rom[0x14A]),
+        licensee,
+        version: header.version(),
+        logo_valid: header.logo_valid(),
+        header_checksum_valid,
+        global_checksum_valid: stored == computed,
+    })
+}
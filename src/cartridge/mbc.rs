@@ -1,36 +1,63 @@
 use super::Cartridge;
 use crate::mmu::memory::Memory;
+use crate::state::{self, Savestate, StateReader, StateWriter};
+use log::warn;
 
 /// No MBC (ROM Only) - https://gbdev.io/pandocs/nombc.html
 /// Small games of not more than 32 KiB ROM do not require a MBC chip for ROM banking.
 /// The ROM is directly mapped to memory at $0000-7FFF.
 /// Optionally up to 8 KiB of RAM could be connected at $A000-BFFF, using a discrete logic decoder in place of a full MBC chip.
+///
+/// Writes to ROM do nothing, but a write selecting a ROM bank past the first one, as if to an MBC1, hints at a ROM
+/// under-declaring its mapper, which is worth a warning (once).
 pub struct RomOnly {
     rom: Vec<u8>,
+    warned: bool,
 }
 
 impl RomOnly {
     pub fn new(rom: Vec<u8>) -> Self {
-        Self { rom }
+        Self { rom, warned: false }
     }
 }
 
 impl Memory for RomOnly {
     fn read8(&self, addr: u16) -> u8 {
-        self.rom[addr as usize]
+        match addr {
+            0x0000..=0x7FFF => self.rom[addr as usize],
+            _ => self.read_ram(addr).unwrap_or(0xFF),
+        }
     }
 
-    fn write8(&mut self, _: u16, _: u8) {}
-
-    fn read16(&self, addr: u16) -> u16 {
-        u16::from(self.read8(addr)) | (u16::from(self.read8(addr + 1)) << 8)
+    fn write8(&mut self, addr: u16, val: u8) {
+        if (0x2000..=0x3FFF).contains(&addr) && val & 0x1F > 0x01 && !self.warned {
+            warn!(
+                "ROM only cartridge selected ROM bank {:02X} at {:04X}, it may need a mapper. Try forcing MBC1.",
+                val, addr
+            );
+            self.warned = true;
+        }
     }
 
-    fn write16(&mut self, _: u16, _: u16) {}
-
     fn cycle(&mut self, _: u32) -> u32 {
         0
     }
 }
 
-impl Cartridge for RomOnly {}
+impl Savestate for RomOnly {
+    fn save_state(&self, _: &mut StateWriter) {}
+
+    fn load_state(&mut self, _: &mut StateReader) -> state::Result<()> {
+        Ok(())
+    }
+}
+
+impl Cartridge for RomOnly {
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+}
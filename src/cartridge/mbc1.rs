@@ -1,5 +1,6 @@
 use super::Cartridge;
 use crate::mmu::memory::Memory;
+use crate::state::{self, Savestate, StateReader, StateWriter};
 
 // TODO: Implement saving and loading of battery backed RAM.(Save RAM state to a file, etc).
 
@@ -7,6 +8,7 @@ use crate::mmu::memory::Memory;
 /// MBC1 has two bank modes:
 ///   ROM Banking Mode (up to 8KByte RAM, 2MByte ROM) (default)
 ///   RAM Banking Mode (up to 32KByte RAM, 512KByte ROM)
+#[derive(Clone, Copy)]
 enum BankMode {
     Rom,
     Ram,
@@ -97,7 +99,8 @@ impl Mbc1 {
             BankMode::Rom => self.bank & 0x7f,
             BankMode::Ram => self.bank & 0x1f,
         };
-        bank as usize
+        // Bank bits past the size of the ROM aren't wired up, so banks wrap around.
+        bank as usize % (self.rom.len() / 0x4000)
     }
 
     fn ram_bank(&self) -> usize {
@@ -118,16 +121,8 @@ impl Memory for Mbc1 {
                 let offset = addr as usize - 0x4000;
                 self.rom[bank * 0x4000 + offset]
             }
-            0xa000..=0xbfff => {
-                if self.ram_enabled {
-                    let bank = self.ram_bank();
-                    let offset = addr as usize - 0xa000;
-                    self.ram[bank * 0x2000 + offset]
-                } else {
-                    0x00
-                }
-            }
-            _ => 0x00,
+            0xa000..=0xbfff => self.read_ram(addr).unwrap_or(0xff),
+            _ => 0xff,
         }
     }
 
@@ -144,35 +139,107 @@ impl Memory for Mbc1 {
                 self.bank = self.bank & 0x9f | ((val & 0x03) << 5);
             }
             0x6000..=0x7fff => {
-                self.bank_mode = match val {
+                // Only bit 0 is wired up, the other bits are ignored.
+                self.bank_mode = match val & 0x01 {
                     0x00 => BankMode::Rom,
-                    0x01 => BankMode::Ram,
-                    _ => panic!("Invalid bank mode: {:#04x}", val),
+                    _ => BankMode::Ram,
                 };
             }
             0xa000..=0xbfff => {
+                // Writes past the end of the RAM (or with no RAM at all) go nowhere.
                 if self.ram_enabled {
                     let bank = self.ram_bank();
                     let offset = addr as usize - 0xa000;
-                    self.ram[bank * 0x2000 + offset] = val;
+                    if let Some(byte) = self.ram.get_mut(bank * 0x2000 + offset) {
+                        *byte = val;
+                    }
                 }
             }
             _ => {}
         }
     }
 
-    fn read16(&self, addr: u16) -> u16 {
-        u16::from(self.read8(addr)) | (u16::from(self.read8(addr + 1)) << 8)
+    fn cycle(&mut self, _: u32) -> u32 {
+        0
     }
+}
 
-    fn write16(&mut self, addr: u16, val: u16) {
-        self.write8(addr, (val & 0xFF) as u8);
-        self.write8(addr + 1, (val >> 8) as u8);
+impl Savestate for Mbc1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.bank_mode as u8);
+        w.u8(self.bank);
+        w.bool(self.ram_enabled);
+        w.bytes(&self.ram);
     }
 
-    fn cycle(&mut self, _: u32) -> u32 {
-        0
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.bank_mode = match r.u8()? {
+            0 => BankMode::Rom,
+            1 => BankMode::Ram,
+            _ => return Err(state::StateError::Invalid("MBC1 bank mode")),
+        };
+        self.bank = r.u8()?;
+        self.ram_enabled = r.bool()?;
+        r.bytes(&mut self.ram)?;
+        Ok(())
     }
 }
 
-impl Cartridge for Mbc1 {}
+impl Cartridge for Mbc1 {
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram_enabled(&self) -> Option<bool> {
+        Some(self.ram_enabled)
+    }
+
+    fn banking_mode(&self) -> Option<u8> {
+        Some(self.bank_mode as u8)
+    }
+
+    fn selected_ram_bank(&self) -> Option<usize> {
+        (!self.ram.is_empty()).then(|| self.ram_bank())
+    }
+
+    fn read_ram(&self, addr: u16) -> Option<u8> {
+        if !self.ram_enabled {
+            return None;
+        }
+        let bank = self.ram_bank();
+        let offset = addr as usize - 0xa000;
+        self.ram.get(bank * 0x2000 + offset).copied()
+    }
+
+    fn rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x0000..=0x3fff => Some(addr as usize),
+            0x4000..=0x7fff => Some(self.rom_bank() * 0x4000 + (addr as usize - 0x4000)),
+            _ => None,
+        }
+    }
+
+    fn ram(&self) -> Option<&[u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&self.ram)
+        }
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&mut self.ram)
+        }
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        super::load_ram(&mut self.ram, data);
+    }
+}
@@ -0,0 +1,220 @@
+use super::rtc::Rtc;
+use super::Cartridge;
+use crate::mmu::memory::Memory;
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
+/// https://gbdev.io/pandocs/MBC3.html
+/// Beside for the ability to access up to 2 MiB ROM (128 banks), and 32 KiB RAM (4 banks), the MBC3 also includes
+/// a built-in Real Time Clock (RTC). The RTC requires an external 32.768 kHz Quartz Oscillator, and an external battery
+/// (if it should continue to tick when the Game Boy is turned off).
+///
+/// 0000-3FFF - ROM Bank 00 (Read Only)
+/// Contains the first 16 KiB of the ROM.
+///
+/// 4000-7FFF - ROM Bank 01-7F (Read Only)
+/// Same as for MBC1, except that accessing banks $20, $40, and $60 is supported now.
+///
+/// A000-BFFF - RAM Bank 00-03, if any (Read/Write)
+/// A000-BFFF - RTC Register 08-0C (Read/Write)
+/// Depending on the current Bank Number/RTC Register selection (see below), this memory space is used to access an
+/// 8 KiB external RAM Bank, or a single RTC Register.
+///
+/// Registers:
+/// 0000-1FFF - RAM and Timer Enable (Write Only)
+/// Mostly the same as for MBC1, a value of $0A will enable reading and writing to external RAM - and to the RTC
+/// Registers! A value of $00 will disable either.
+///
+/// 2000-3FFF - ROM Bank Number (Write Only)
+/// Same as for MBC1, except that the whole 7 bits of the ROM Bank Number are written directly to this address.
+/// As for the MBC1, writing a value of $00 will select Bank $01 instead.
+///
+/// 4000-5FFF - RAM Bank Number - or - RTC Register Select (Write Only)
+/// Writing a value in range for $00-$03 maps the corresponding external RAM Bank (if any) into memory at A000-BFFF.
+/// When writing a value of $08-$0C, this will map the corresponding RTC register into memory at A000-BFFF.
+///
+/// 6000-7FFF - Latch Clock Data (Write Only)
+/// When writing $00, and then $01 to this register, the current time becomes latched into the RTC registers.
+/// The latched data will not change until it becomes latched again, by repeating the write $00->$01 procedure.
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    /// Real Time Clock, if the cartridge has one.
+    rtc: Option<Rtc>,
+
+    rom_bank: u8,
+
+    /// RAM bank ($00-$03), or RTC register ($08-$0C), mapped at $A000-$BFFF.
+    ram_bank: u8,
+    ram_enabled: bool,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, rtc: bool) -> Self {
+        Self {
+            rom,
+            ram,
+            rtc: rtc.then(Rtc::new),
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            ram_enabled: false,
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        // Bank bits past the size of the ROM aren't wired up, so banks wrap around.
+        self.rom_bank as usize % (self.rom.len() / 0x4000)
+    }
+
+    /// Offset in external RAM of addr ($A000-$BFFF), in the current RAM bank.
+    fn ram_offset(&self, addr: u16) -> usize {
+        self.ram_bank as usize * 0x2000 + (addr as usize - 0xA000)
+    }
+}
+
+impl Memory for Mbc3 {
+    fn read8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom[addr as usize],
+            0x4000..=0x7FFF => self.rom[self.rom_bank() * 0x4000 + (addr as usize - 0x4000)],
+            0xA000..=0xBFFF => self.read_ram(addr).unwrap_or(0xFF),
+            _ => 0xFF,
+        }
+    }
+
+    fn write8(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                let bank = val & 0x7F;
+                self.rom_bank = if bank == 0x00 { 0x01 } else { bank };
+            }
+            0x4000..=0x5FFF => self.ram_bank = val & 0x0F,
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(val);
+                }
+            }
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return;
+                }
+                match self.ram_bank {
+                    0x00..=0x03 => {
+                        let offset = self.ram_offset(addr);
+                        if let Some(byte) = self.ram.get_mut(offset) {
+                            *byte = val;
+                        }
+                    }
+                    0x08..=0x0C => {
+                        if let Some(rtc) = &mut self.rtc {
+                            rtc.write(self.ram_bank, val);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cycle(&mut self, ticks: u32) -> u32 {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.cycle(ticks);
+        }
+        0
+    }
+}
+
+impl Savestate for Mbc3 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.rom_bank);
+        w.u8(self.ram_bank);
+        w.bool(self.ram_enabled);
+        w.bytes(&self.ram);
+        if let Some(rtc) = &self.rtc {
+            rtc.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.rom_bank = r.u8()?;
+        self.ram_bank = r.u8()?;
+        self.ram_enabled = r.bool()?;
+        r.bytes(&mut self.ram)?;
+        if let Some(rtc) = &mut self.rtc {
+            rtc.load_state(r)?;
+        }
+        Ok(())
+    }
+}
+
+impl Cartridge for Mbc3 {
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram_enabled(&self) -> Option<bool> {
+        Some(self.ram_enabled)
+    }
+
+    fn selected_ram_bank(&self) -> Option<usize> {
+        Some(self.ram_bank as usize)
+    }
+
+    fn read_ram(&self, addr: u16) -> Option<u8> {
+        if !self.ram_enabled {
+            return None;
+        }
+        match self.ram_bank {
+            0x00..=0x03 => self.ram.get(self.ram_offset(addr)).copied(),
+            0x08..=0x0C => self.rtc.as_ref().map(|rtc| rtc.read(self.ram_bank)),
+            _ => None,
+        }
+    }
+
+    fn rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x0000..=0x3FFF => Some(addr as usize),
+            0x4000..=0x7FFF => Some(self.rom_bank() * 0x4000 + (addr as usize - 0x4000)),
+            _ => None,
+        }
+    }
+
+    fn ram(&self) -> Option<&[u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&self.ram)
+        }
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&mut self.ram)
+        }
+    }
+
+    /// A save with an RTC footer after the RAM sets the clock too.
+    fn load_ram(&mut self, data: &[u8]) {
+        super::load_ram(&mut self.ram, data);
+        if let (Some(rtc), Some(footer)) = (&mut self.rtc, super::rtc_footer(data, self.ram.len()))
+        {
+            rtc.load_footer(footer, super::rtc::unix_time());
+        }
+    }
+
+    fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+}
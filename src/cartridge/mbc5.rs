@@ -0,0 +1,192 @@
+use super::Cartridge;
+use crate::mmu::memory::Memory;
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
+/// https://gbdev.io/pandocs/MBC5.html
+/// The first MBC that is guaranteed to work properly with GBC Double Speed mode. It can map up to 8 MiB of ROM
+/// (512 banks), and 128 KiB of RAM (16 banks).
+///
+/// 0000-3FFF - ROM Bank 00 (Read Only)
+/// Contains the first 16 KiB of the ROM.
+///
+/// 4000-7FFF - ROM Bank 00-1FF (Read Only)
+/// Same as for MBC1, except that bank 0 can be mapped here too, and banks are 9 bits.
+///
+/// A000-BFFF - RAM Bank 00-0F, if any (Read/Write)
+///
+/// Registers:
+/// 0000-1FFF - RAM Enable (Write Only)
+/// Same as for MBC1, $0A enables RAM, $00 disables it.
+///
+/// 2000-2FFF - 8 least significant bits of ROM bank number (Write Only)
+/// 3000-3FFF - 9th bit of ROM bank number (Write Only)
+/// Writing $00 selects bank 0, unlike MBC1 and MBC3.
+///
+/// 4000-5FFF - RAM bank number (Write Only)
+/// Writing a value in range for $00-$0F maps the corresponding external RAM Bank (if any) into memory at A000-BFFF.
+///
+/// Rumble:
+/// On rumble cartridges, bit 3 of the RAM bank number drives the rumble motor instead of a RAM bank line,
+/// so those have at most 8 RAM banks. Games run the motor in bursts for weaker rumble.
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    /// 9 bit ROM bank number.
+    rom_bank: u16,
+
+    /// RAM bank number, without the motor bit on rumble cartridges.
+    ram_bank: u8,
+    ram_enabled: bool,
+
+    /// Whether the motor is running, and the T-cycles it ran for since they were last taken,
+    /// None if the cartridge has no motor.
+    motor: Option<bool>,
+    motor_ticks: u32,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, rumble: bool) -> Self {
+        Self {
+            rom,
+            ram,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            ram_enabled: false,
+            motor: rumble.then_some(false),
+            motor_ticks: 0,
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        // Bank bits past the size of the ROM aren't wired up, so banks wrap around.
+        self.rom_bank as usize % (self.rom.len() / 0x4000)
+    }
+
+    /// Offset in external RAM of addr ($A000-$BFFF), in the current RAM bank.
+    fn ram_offset(&self, addr: u16) -> usize {
+        let banks = (self.ram.len() / 0x2000).max(1);
+        (self.ram_bank as usize % banks) * 0x2000 + (addr as usize - 0xA000)
+    }
+}
+
+impl Memory for Mbc5 {
+    fn read8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom[addr as usize],
+            0x4000..=0x7FFF => self.rom[self.rom_bank() * 0x4000 + (addr as usize - 0x4000)],
+            0xA000..=0xBFFF => self.read_ram(addr).unwrap_or(0xFF),
+            _ => 0xFF,
+        }
+    }
+
+    fn write8(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | val as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((val as u16 & 0x01) << 8),
+            0x4000..=0x5FFF => match &mut self.motor {
+                Some(motor) => {
+                    *motor = val & 0x08 != 0;
+                    self.ram_bank = val & 0x07;
+                }
+                None => self.ram_bank = val & 0x0F,
+            },
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let offset = self.ram_offset(addr);
+                if let Some(byte) = self.ram.get_mut(offset) {
+                    *byte = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cycle(&mut self, ticks: u32) -> u32 {
+        if self.motor == Some(true) {
+            self.motor_ticks = self.motor_ticks.saturating_add(ticks);
+        }
+        0
+    }
+}
+
+impl Savestate for Mbc5 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.rom_bank);
+        w.u8(self.ram_bank);
+        w.bool(self.ram_enabled);
+        w.bytes(&self.ram);
+        if let Some(motor) = self.motor {
+            w.bool(motor);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.rom_bank = r.u16()?;
+        self.ram_bank = r.u8()?;
+        self.ram_enabled = r.bool()?;
+        r.bytes(&mut self.ram)?;
+        if self.motor.is_some() {
+            self.motor = Some(r.bool()?);
+        }
+        Ok(())
+    }
+}
+
+impl Cartridge for Mbc5 {
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram_enabled(&self) -> Option<bool> {
+        Some(self.ram_enabled)
+    }
+
+    fn selected_ram_bank(&self) -> Option<usize> {
+        (!self.ram.is_empty()).then_some(self.ram_bank as usize)
+    }
+
+    fn read_ram(&self, addr: u16) -> Option<u8> {
+        if !self.ram_enabled {
+            return None;
+        }
+        self.ram.get(self.ram_offset(addr)).copied()
+    }
+
+    fn rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x0000..=0x3FFF => Some(addr as usize),
+            0x4000..=0x7FFF => Some(self.rom_bank() * 0x4000 + (addr as usize - 0x4000)),
+            _ => None,
+        }
+    }
+
+    fn ram(&self) -> Option<&[u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&self.ram)
+        }
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&mut self.ram)
+        }
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        super::load_ram(&mut self.ram, data);
+    }
+
+    fn take_rumble(&mut self) -> Option<u32> {
+        self.motor?;
+        Some(std::mem::take(&mut self.motor_ticks))
+    }
+}
@@ -0,0 +1,244 @@
+use super::Cartridge;
+use crate::mmu::memory::Memory;
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
+/// https://gbdev.io/pandocs/MMM01.html
+/// The MMM01 is a mapper for multi-game compilation cartridges, e.g. Momotarou Collection 2 and Taito Variety Pack.
+/// It boots into a menu, which picks a game, and then maps that game's slice of the ROM as if it was the whole
+/// cartridge, with its own MBC1 style banking, so the game runs unmodified.
+///
+/// Unmapped mode:
+/// On power-up the MMM01 is unmapped, and the last 32 KiB of the ROM (the menu, whose header says MMM01)
+/// is at 0000-7FFF whatever the registers hold. The menu sets up the registers for a game, then sets the map
+/// enable bit, which maps the game and locks most of the configuration until the next power cycle.
+///
+/// 0000-3FFF - ROM Bank "00" (Read Only)
+/// Mapped, the first bank of the game: the ROM bank number with its low bits zeroed, except the masked ones.
+///
+/// 4000-7FFF - ROM Bank 01-1FF (Read Only)
+/// Mapped, the selected ROM bank. As for MBC1, when the low bits the game can change are all 0, it reads
+/// the bank as if bit 0 was set.
+///
+/// A000-BFFF - RAM Bank 00-0F, if any (Read/Write)
+///
+/// Registers:
+/// 0000-1FFF - RAM Enable (Write Only)
+///   Bits 0-3: $A enables RAM, as on MBC1.
+///   Bits 4-5: RAM bank mask, RAM bank bits 0-1 the game can't change. (unmapped only)
+///   Bit 6: Map enable, maps the game in. (unmapped only)
+///
+/// 2000-3FFF - ROM Bank Number (Write Only)
+///   Bits 0-4: ROM bank bits 0-4, except the masked ones once mapped.
+///   Bits 5-6: ROM bank bits 5-6. (unmapped only)
+///
+/// 4000-5FFF - RAM Bank Number (Write Only)
+///   Bits 0-1: RAM bank bits 0-1, except the masked ones once mapped.
+///   Bits 2-3: RAM bank bits 2-3. (unmapped only)
+///   Bits 4-5: ROM bank bits 7-8. (unmapped only)
+///
+/// 6000-7FFF - Mode Select (Write Only)
+///   Bits 2-5: ROM bank mask, ROM bank bits 1-4 the game can't change. (unmapped only)
+pub struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    /// Set once the menu maps a game in, which locks the configuration.
+    mapped: bool,
+
+    /// 9 bit ROM bank number.
+    rom_bank: u16,
+
+    /// ROM bank bits the game can't change once mapped, keeping it in its slice of the ROM.
+    rom_mask: u16,
+
+    ram_bank: u8,
+
+    /// RAM bank bits the game can't change once mapped.
+    ram_mask: u8,
+    ram_enabled: bool,
+}
+
+impl Mmm01 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self {
+        Self {
+            rom,
+            ram,
+            mapped: false,
+            rom_bank: 0x00,
+            rom_mask: 0x00,
+            ram_bank: 0x00,
+            ram_mask: 0x00,
+            ram_enabled: false,
+        }
+    }
+
+    /// The ROM banks at 0000-3FFF and 4000-7FFF.
+    fn rom_banks(&self) -> (usize, usize) {
+        let banks = self.rom.len() / 0x4000;
+        if !self.mapped {
+            // The menu, in the last 32 KiB.
+            return (banks.saturating_sub(2), banks - 1);
+        }
+        let low = self.rom_bank & 0x1F;
+        let rom0 = (self.rom_bank & !0x1F) | (low & self.rom_mask);
+        let rom = if low & !self.rom_mask == 0 {
+            self.rom_bank | 0x01
+        } else {
+            self.rom_bank
+        };
+        // Bank bits past the size of the ROM aren't wired up, so banks wrap around.
+        (rom0 as usize % banks, rom as usize % banks)
+    }
+
+    fn ram_offset(&self, addr: u16) -> usize {
+        let banks = (self.ram.len() / 0x2000).max(1);
+        (self.ram_bank as usize % banks) * 0x2000 + (addr as usize - 0xA000)
+    }
+
+    /// Write the bits of val outside mask into reg, or all of them before the game is mapped.
+    fn write_masked(mapped: bool, reg: u8, val: u8, mask: u8) -> u8 {
+        if mapped {
+            (reg & mask) | (val & !mask)
+        } else {
+            val
+        }
+    }
+}
+
+impl Memory for Mmm01 {
+    fn read8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self
+                .rom_offset(addr)
+                .map_or(0xFF, |offset| self.rom[offset]),
+            0xA000..=0xBFFF => self.read_ram(addr).unwrap_or(0xFF),
+            _ => 0xFF,
+        }
+    }
+
+    fn write8(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = val & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.ram_mask = (val >> 4) & 0x03;
+                    self.mapped = val & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let mask = self.rom_mask as u8;
+                let low =
+                    Self::write_masked(self.mapped, self.rom_bank as u8 & 0x1F, val & 0x1F, mask);
+                let mid = if self.mapped {
+                    self.rom_bank & 0x60
+                } else {
+                    val as u16 & 0x60
+                };
+                self.rom_bank = (self.rom_bank & 0x180) | mid | low as u16;
+            }
+            0x4000..=0x5FFF => {
+                let low = Self::write_masked(
+                    self.mapped,
+                    self.ram_bank & 0x03,
+                    val & 0x03,
+                    self.ram_mask,
+                );
+                if self.mapped {
+                    self.ram_bank = (self.ram_bank & 0x0C) | low;
+                } else {
+                    self.ram_bank = (val & 0x0C) | low;
+                    self.rom_bank = (self.rom_bank & 0x7F) | ((val as u16 & 0x30) << 3);
+                }
+            }
+            0x6000..=0x7FFF if !self.mapped => self.rom_mask = (val as u16 & 0x3C) >> 1,
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let offset = self.ram_offset(addr);
+                if let Some(byte) = self.ram.get_mut(offset) {
+                    *byte = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cycle(&mut self, _: u32) -> u32 {
+        0
+    }
+}
+
+impl Savestate for Mmm01 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.mapped);
+        w.u16(self.rom_bank);
+        w.u16(self.rom_mask);
+        w.u8(self.ram_bank);
+        w.u8(self.ram_mask);
+        w.bool(self.ram_enabled);
+        w.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.mapped = r.bool()?;
+        self.rom_bank = r.u16()?;
+        self.rom_mask = r.u16()?;
+        self.ram_bank = r.u8()?;
+        self.ram_mask = r.u8()?;
+        self.ram_enabled = r.bool()?;
+        r.bytes(&mut self.ram)?;
+        Ok(())
+    }
+}
+
+impl Cartridge for Mmm01 {
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram_enabled(&self) -> Option<bool> {
+        Some(self.ram_enabled)
+    }
+
+    fn selected_ram_bank(&self) -> Option<usize> {
+        (!self.ram.is_empty()).then(|| self.ram_offset(0xA000) / 0x2000)
+    }
+
+    fn read_ram(&self, addr: u16) -> Option<u8> {
+        if !self.ram_enabled {
+            return None;
+        }
+        self.ram.get(self.ram_offset(addr)).copied()
+    }
+
+    fn rom_offset(&self, addr: u16) -> Option<usize> {
+        let (rom0, rom) = self.rom_banks();
+        match addr {
+            0x0000..=0x3FFF => Some(rom0 * 0x4000 + addr as usize),
+            0x4000..=0x7FFF => Some(rom * 0x4000 + (addr as usize - 0x4000)),
+            _ => None,
+        }
+    }
+
+    fn ram(&self) -> Option<&[u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&self.ram)
+        }
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some(&mut self.ram)
+        }
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        super::load_ram(&mut self.ram, data);
+    }
+}
@@ -1,13 +1,33 @@
+pub mod banks;
 pub mod header;
+pub mod info;
 pub mod mbc;
 pub mod mbc1;
+pub mod mbc3;
+pub mod mbc5;
+pub mod mmm01;
+pub mod rtc;
 
 use crate::mmu::memory::Memory;
+use crate::state::Savestate;
+use log::{info, warn};
+use serde::Serialize;
 
-use self::{header::*, mbc::*, mbc1::*};
+use self::{
+    banks::Banks,
+    header::*,
+    mbc::*,
+    mbc1::*,
+    mbc3::*,
+    mbc5::*,
+    mmm01::*,
+    rtc::{Rtc, RtcTime},
+};
 
 /// Cartridge represents a Gameboy ROM
-pub trait Cartridge: Memory {
+/// The save state of a cartridge covers its mapper registers (banks, RAM enable, banking mode, the RTC with
+/// its latch, and the rumble motor) and external RAM, not the ROM itself.
+pub trait Cartridge: Memory + Savestate {
     /// Cartridge Tile
     fn title(&self) -> String {
         let mut title = String::new();
@@ -20,58 +40,471 @@ pub trait Cartridge: Memory {
         title
     }
 
-    /// Cartridge Type
-    fn mbc(&self) -> CartridgeType {
-        CartridgeType::try_from(self.read8(0x147)).unwrap()
+    /// Size of the ROM image the cartridge maps, which can differ from its header when the mapper was detected.
+    fn rom_len(&self) -> usize;
+
+    /// The ROM image the cartridge maps.
+    fn rom(&self) -> &[u8];
+
+    /// Offset in the ROM image of the byte mapped at a CPU address ($0000-$7FFF), with the current banking.
+    fn rom_offset(&self, addr: u16) -> Option<usize> {
+        (addr <= 0x7FFF).then_some(addr as usize)
+    }
+
+    /// The RAM bank mapped at $A000-$BFFF (or MBC3 RTC register, $08-$0C), None without RAM banking.
+    fn selected_ram_bank(&self) -> Option<usize> {
+        None
+    }
+
+    /// The banks mapped right now, for bank qualified addresses (e.g. 03:4F20) in debugging tools.
+    fn current_banks(&self) -> Banks {
+        Banks {
+            rom0: self.rom_offset(0x0000).map_or(0, |offset| offset / 0x4000),
+            rom: self.rom_offset(0x4000).map_or(1, |offset| offset / 0x4000),
+            ram: self.selected_ram_bank(),
+            mode: self.banking_mode(),
+            ram_enabled: self.ram_enabled(),
+        }
     }
 
-    /// ROM Size
-    fn rom_size(&self) -> RomSize {
-        RomSize::try_from(self.read8(0x148)).unwrap()
+    /// The banking mode, None if the mapper has only one.
+    fn banking_mode(&self) -> Option<u8> {
+        None
     }
 
-    /// RAM Size
-    fn ram_size(&self) -> RamSize {
-        RamSize::try_from(self.read8(0x149)).unwrap()
+    /// Whether external RAM is enabled, None if the mapper doesn't gate it.
+    fn ram_enabled(&self) -> Option<bool> {
+        None
     }
 
-    /// Destination Code
-    fn destination_code(&self) -> DestinationCode {
-        DestinationCode::try_from(self.read8(0x14A)).unwrap()
+    /// Read external RAM ($A000-$BFFF).
+    /// None if nothing drives the data bus, because there is no RAM or it is disabled.
+    fn read_ram(&self, _addr: u16) -> Option<u8> {
+        None
     }
 
-    /// New Licensee Code
-    fn new_licensee_code(&self) -> NewLicenseeCode {
-        NewLicenseeCode::try_from(
-            ((self.read8(0x144) as u16) << 8 | self.read8(0x145) as u16) as u8,
-        )
-        .unwrap()
+    /// External RAM, if the cartridge has any.
+    fn ram(&self) -> Option<&[u8]> {
+        None
     }
 
-    /// Old Licensee Code
-    fn old_licensee_code(&self) -> OldLicenseeCode {
-        OldLicenseeCode::try_from(self.read8(0x14B)).unwrap()
+    fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Replace the contents of the external RAM, e.g. with a save file, see load_ram.
+    /// Cartridges without RAM ignore it.
+    fn load_ram(&mut self, _data: &[u8]) {}
+
+    /// Does a battery keep the external RAM (and RTC) alive while powered off, so it's worth saving?
+    fn has_battery(&self) -> bool {
+        self.mbc().is_some_and(|cart_type| cart_type.has_battery())
+    }
+
+    /// What the battery keeps alive, for the save file: the external RAM, then the RTC footer if the cartridge
+    /// has a clock, see Rtc::footer. None without a battery, or with nothing for it to keep.
+    fn battery_save(&self) -> Option<Vec<u8>> {
+        if !self.has_battery() {
+            return None;
+        }
+        let mut save = self.ram().map(<[u8]>::to_vec).unwrap_or_default();
+        if let Some(rtc) = self.rtc() {
+            save.extend(rtc.footer(rtc::unix_time()));
+        }
+        (!save.is_empty()).then_some(save)
+    }
+
+    /// Current time of the Real Time Clock, if the cartridge has one.
+    fn rtc_state(&self) -> Option<RtcTime> {
+        self.rtc().map(Rtc::time)
+    }
+
+    /// Real Time Clock, if the cartridge has one.
+    fn rtc(&self) -> Option<&Rtc> {
+        None
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+
+    /// T-cycles the rumble motor ran for since they were last taken, None if the cartridge has no motor.
+    fn take_rumble(&mut self) -> Option<u32> {
+        None
+    }
+
+    /// Cartridge Type, None if it isn't a known type.
+    fn mbc(&self) -> Option<CartridgeType> {
+        CartridgeType::try_from(self.read8(0x147)).ok()
+    }
+
+    /// ROM Size, None if it isn't a known size.
+    fn rom_size(&self) -> Option<RomSize> {
+        RomSize::try_from(self.read8(0x148)).ok()
+    }
+
+    /// RAM Size, None if it isn't a known size.
+    fn ram_size(&self) -> Option<RamSize> {
+        RamSize::try_from(self.read8(0x149)).ok()
+    }
+
+    /// Destination Code, None if it isn't a known code.
+    fn destination_code(&self) -> Option<DestinationCode> {
+        DestinationCode::try_from(self.read8(0x14A)).ok()
+    }
+
+    /// New Licensee Code, None if it isn't a known code.
+    fn new_licensee_code(&self) -> Option<NewLicenseeCode> {
+        header::new_licensee_code([self.read8(0x144), self.read8(0x145)])
+    }
+
+    /// Old Licensee Code, None if it isn't a known code.
+    fn old_licensee_code(&self) -> Option<OldLicenseeCode> {
+        OldLicenseeCode::try_from(self.read8(0x14B)).ok()
+    }
+}
+
+/// Sizes of the RTC footer other emulators (VBA-M, BGB, mGBA) append to the save of an MBC3 with a timer,
+/// with a 32 or 64 bit timestamp.
+const RTC_FOOTERS: [usize; 2] = [44, 48];
+
+/// The RTC footer after size bytes of RAM in a save, if it has one, see Rtc::load_footer.
+pub fn rtc_footer(data: &[u8], size: usize) -> Option<&[u8]> {
+    let extra = data.len().checked_sub(size)?;
+    RTC_FOOTERS.contains(&extra).then(|| &data[size..])
+}
+
+/// Fit a save file to a cartridge with size bytes of RAM, so saves from other emulators and flash carts load.
+/// An RTC footer after the RAM is left for the clock, see rtc_footer. Any other larger image is truncated,
+/// and a smaller one padded with $00, as ferrum's RAM powers on, with a warning.
+pub fn fit_save(data: &[u8], size: usize) -> Vec<u8> {
+    match data.len() {
+        len if len == size => {}
+        len if rtc_footer(data, size).is_some() => {
+            info!("The save has a {} byte RTC footer.", len - size);
+        }
+        len if len > size => warn!(
+            "Save is {} bytes, truncating it to the cartridge's {} bytes of RAM.",
+            len, size
+        ),
+        len => warn!(
+            "Save is {} bytes, padding it to the cartridge's {} bytes of RAM.",
+            len, size
+        ),
+    }
+    let mut ram = data[..data.len().min(size)].to_vec();
+    ram.resize(size, 0x00);
+    ram
+}
+
+/// Copy a RAM image into a cartridge's external RAM, for Cartridge::load_ram, fitted to its size with fit_save.
+fn load_ram(ram: &mut [u8], data: &[u8]) {
+    ram.copy_from_slice(&fit_save(data, ram.len()));
+}
+
+/// Name of a header code, for the cartridge info.
+fn describe<T: std::fmt::Debug>(code: Option<T>) -> String {
+    code.map_or("Unknown".to_string(), |code| format!("{:?}", code))
+}
+
+/// Memory Bank Controllers ferrum emulates, to override the one in the cartridge header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc3,
+    Mbc5,
+    Mmm01,
+}
+
+impl Mapper {
+    /// The mapper of a cartridge type, None if it isn't emulated.
+    fn from_type(cart_type: &CartridgeType) -> Option<Self> {
+        match cart_type {
+            CartridgeType::RomOnly => Some(Mapper::RomOnly),
+            CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery => {
+                Some(Mapper::Mbc1)
+            }
+            CartridgeType::Mbc3
+            | CartridgeType::Mbc3Ram
+            | CartridgeType::Mbc3RamBattery
+            | CartridgeType::Mbc3TimerBattery
+            | CartridgeType::Mbc3TimerRamBattery => Some(Mapper::Mbc3),
+            CartridgeType::Mbc5
+            | CartridgeType::Mbc5Ram
+            | CartridgeType::Mbc5RamBattery
+            | CartridgeType::Mbc5Rumble
+            | CartridgeType::Mbc5RumbleRam
+            | CartridgeType::Mbc5RumbleRamBattery => Some(Mapper::Mbc5),
+            CartridgeType::Mmm01 | CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery => {
+                Some(Mapper::Mmm01)
+            }
+            _ => None,
+        }
     }
 }
 
-/// Initialize a new Cartridge.
+/// Initialize a new Cartridge from a ROM file on disk.
 pub fn new(path: String) -> Box<dyn Cartridge> {
-    let rom_data = std::fs::read(path.clone()).unwrap();
-    let cart: Box<dyn Cartridge> = match CartridgeType::try_from(rom_data[0x147]).unwrap() {
-        CartridgeType::RomOnly => Box::new(RomOnly::new(rom_data)),
-        CartridgeType::Mbc1 => Box::new(Mbc1::new(rom_data, vec![])),
-        //TODO: Implement other cartridge types.
-        _ => todo!("Unsupported cartridge type: {:?}", path),
+    let rom_data = std::fs::read(path).unwrap();
+    from_rom(rom_data, None)
+}
+
+/// Size of the ROM chip holding an image, the next power of two, and at least the 32 KiB ROM only space.
+fn chip_size(rom: &[u8]) -> usize {
+    rom.len().next_power_of_two().max(0x8000)
+}
+
+/// Offset of the cartridge header in a ROM image, the start of it, except on MMM01 multi-game cartridges.
+/// Those boot into a menu in the last 32 KiB of the ROM, and the header there is the cartridge's, the one at the
+/// start belongs to the first game.
+fn header_offset(rom: &[u8]) -> usize {
+    let Some(menu) = rom.len().checked_sub(0x8000).filter(|&menu| menu > 0) else {
+        return 0;
+    };
+    match CartridgeType::try_from(rom[menu + 0x147]) {
+        Ok(CartridgeType::Mmm01 | CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery) => menu,
+        _ => 0,
+    }
+}
+
+/// Pick the mapper of a ROM image, and the size of the ROM it maps.
+///
+/// Usually both come from the header, but some homebrew ROMs under-declare their mapper: an image larger than
+/// 32 KiB can only be reached through bank switching, so a ROM only (or unknown) cartridge type that big
+/// is taken as MBC1, the most common mapper, and the ROM is sized from the image rather than the header.
+fn detect_mapper(rom: &[u8]) -> (Mapper, usize) {
+    let header = header_offset(rom);
+    let cart_type = CartridgeType::try_from(rom[header + 0x147]).ok();
+    let mapper = match &cart_type {
+        Some(cart_type) => match Mapper::from_type(cart_type) {
+            Some(mapper) => mapper,
+            None => {
+                warn!(
+                    "Unsupported cartridge type {:?}, loading it as ROM only, force the mapper if it is wrong.",
+                    cart_type
+                );
+                Mapper::RomOnly
+            }
+        },
+        None => {
+            warn!("Unknown cartridge type {:02X}.", rom[header + 0x147]);
+            Mapper::RomOnly
+        }
+    };
+
+    if mapper == Mapper::RomOnly && rom.len() > 0x8000 {
+        warn!(
+            "ROM image is {} bytes, too large for a cartridge without a mapper. Assuming MBC1, force the mapper if it is wrong.",
+            rom.len()
+        );
+        return (Mapper::Mbc1, chip_size(rom));
+    }
+    match (cart_type, RomSize::try_from(rom[header + 0x148])) {
+        (Some(_), Ok(size)) => (mapper, size.bytes()),
+        _ => (mapper, chip_size(rom)),
+    }
+}
+
+/// Fit a ROM image to the size of the ROM the cartridge maps, so every address the cartridge can map is backed by data.
+///
+/// A ROM chip smaller than the address space leaves the upper address lines unconnected, so a power of two sized
+/// image (e.g. a 16 KiB homebrew ROM in the 32 KiB ROM only space) is mirrored to fill it.
+/// Anything else missing, such as the end of a truncated file, is undriven and reads as $FF.
+/// Data past the size in the header is dropped, the mapper can't reach it.
+fn fit_rom(mut rom: Vec<u8>, size: usize) -> Vec<u8> {
+    if rom.len() > size {
+        warn!(
+            "ROM image is {} bytes, larger than the {} bytes in its header. Ignoring the extra data.",
+            rom.len(),
+            size
+        );
+        rom.truncate(size);
+    } else if rom.len() < size {
+        warn!(
+            "ROM image is {} bytes, smaller than the {} bytes of its ROM.",
+            rom.len(),
+            size
+        );
+        let len = rom.len();
+        if len.is_power_of_two() {
+            rom = (0..size).map(|i| rom[i % len]).collect();
+        } else {
+            rom.resize(size, 0xFF);
+        }
+    }
+    rom
+}
+
+/// Initialize a new Cartridge from an in-memory ROM image.
+/// An optional RAM image can be given to restore the cartridge's external RAM,
+/// otherwise the external RAM is sized from the cartridge header.
+/// The mapper comes from the header, or is detected when the header can't be right, see detect_mapper.
+pub fn from_rom(rom: impl Into<Vec<u8>>, ram: Option<Vec<u8>>) -> Box<dyn Cartridge> {
+    load(rom.into(), ram, None)
+}
+
+/// Initialize a new Cartridge from an in-memory ROM image, with the given mapper whatever its header says.
+/// The ROM is sized from the image.
+pub fn from_rom_as(
+    rom: impl Into<Vec<u8>>,
+    ram: Option<Vec<u8>>,
+    mapper: Mapper,
+) -> Box<dyn Cartridge> {
+    load(rom.into(), ram, Some(mapper))
+}
+
+fn load(mut rom: Vec<u8>, ram: Option<Vec<u8>>, mapper: Option<Mapper>) -> Box<dyn Cartridge> {
+    // An image too short to hold a header is padded with $00 up to the end of the header, a 32 KiB ROM only cartridge.
+    if rom.len() < HEADER_END {
+        warn!(
+            "ROM image is only {} bytes, too small to hold a cartridge header.",
+            rom.len()
+        );
+        rom.resize(HEADER_END, 0x00);
+    }
+
+    let (mapper, size) = match mapper {
+        Some(mapper) => {
+            info!("Forcing the {:?} mapper.", mapper);
+            (mapper, chip_size(&rom))
+        }
+        None => detect_mapper(&rom),
+    };
+    let rom_data = fit_rom(rom, size);
+    let header = header_offset(&rom_data);
+    let cart_type = CartridgeType::try_from(rom_data[header + 0x147]);
+    let rtc = matches!(
+        cart_type,
+        Ok(CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery)
+    );
+    // A save is fitted to the RAM size in the header, unless the header says there's no RAM,
+    // then the save is the best guess at how much RAM there is, short of the RTC footer.
+    let ram_size = RamSize::try_from(rom_data[header + 0x149]).map_or(0, |size| size.bytes());
+    let footer = ram
+        .as_deref()
+        .filter(|_| rtc)
+        .and_then(|save| rtc_footer(save, ram_size))
+        .map(<[u8]>::to_vec);
+    let ram_data = match ram {
+        Some(_) if ram_size == 0 && footer.is_some() => Vec::new(),
+        Some(ram) if ram_size == 0 => ram,
+        Some(ram) => fit_save(&ram, ram_size),
+        None => vec![0x00; ram_size],
     };
+    let rumble = matches!(
+        cart_type,
+        Ok(CartridgeType::Mbc5Rumble
+            | CartridgeType::Mbc5RumbleRam
+            | CartridgeType::Mbc5RumbleRamBattery)
+    );
+    let mut cart: Box<dyn Cartridge> = match mapper {
+        Mapper::RomOnly => Box::new(RomOnly::new(rom_data)),
+        Mapper::Mbc1 => Box::new(Mbc1::new(rom_data, ram_data)),
+        Mapper::Mbc3 => Box::new(Mbc3::new(rom_data, ram_data, rtc)),
+        Mapper::Mbc5 => Box::new(Mbc5::new(rom_data, ram_data, rumble)),
+        Mapper::Mmm01 => Box::new(Mmm01::new(rom_data, ram_data)),
+    };
+    if let (Some(rtc), Some(footer)) = (cart.rtc_mut(), footer) {
+        rtc.load_footer(&footer, rtc::unix_time());
+    }
 
-    println!("\nCartridge Info:");
-    println!("\tCartridge Title: {}", cart.title());
-    println!("\tCartridge Type: {:?}", cart.mbc());
-    println!("\tROM Size: {:?}", cart.rom_size());
-    println!("\tRAM Size: {:?}", cart.ram_size());
-    println!("\tDestination Code: {:?}", cart.destination_code());
-    println!("\tNew Licensee Code: {:?}", cart.new_licensee_code());
-    println!("\tOld Licensee Code: {:?}\n", cart.old_licensee_code());
+    // Logged rather than printed, the core doesn't write to stdout on its own, a host may run several machines.
+    info!(
+        "Cartridge Info:\n\tCartridge Title: {}\n\tCartridge Type: {}\n\tROM Size: {}\n\tRAM Size: {}\n\tDestination Code: {}\n\tNew Licensee Code: {}\n\tOld Licensee Code: {}",
+        cart.title(),
+        describe(cart.mbc()),
+        describe(cart.rom_size()),
+        describe(cart.ram_size()),
+        describe(cart.destination_code()),
+        describe(cart.new_licensee_code()),
+        describe(cart.old_licensee_code())
+    );
 
     cart
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ROM image of len bytes with a ROM only, 32 KiB header, as far as it reaches.
+    /// No two bytes of a 64 KiB image repeat at a power of two apart, so mirroring shows.
+    fn image(len: usize) -> Vec<u8> {
+        let mut rom: Vec<u8> = (0..len).map(|i| i as u8 ^ (i >> 8) as u8).collect();
+        for addr in 0x147..=0x149 {
+            if let Some(byte) = rom.get_mut(addr) {
+                *byte = 0x00;
+            }
+        }
+        rom
+    }
+
+    #[test]
+    fn full_image() {
+        assert!(fit_rom(image(0x8000), 0x8000) == image(0x8000));
+    }
+
+    /// An 8 KiB image is a smaller chip, mirrored over the ROM, as its upper address lines aren't wired up.
+    #[test]
+    fn small_chip_mirrored() {
+        let rom = fit_rom(image(0x2000), 0x8000);
+        assert_eq!(rom.len(), 0x8000);
+        for chip in rom.chunks(0x2000) {
+            assert!(chip == image(0x2000), "not mirrored");
+        }
+    }
+
+    /// A truncated image, not the size of any chip, is padded with $FF, as if the rest of the ROM was erased.
+    #[test]
+    fn truncated_image_padded() {
+        let rom = fit_rom(image(0x5000), 0x8000);
+        assert_eq!(rom.len(), 0x8000);
+        assert!(rom[..0x5000] == image(0x5000), "the image changed");
+        assert!(rom[0x5000..].iter().all(|&byte| byte == 0xFF), "not padded");
+    }
+
+    /// An image larger than the ROM size in its header is truncated to it.
+    #[test]
+    fn oversized_image_truncated() {
+        assert!(fit_rom(image(0x10000), 0x8000) == image(0x8000));
+    }
+
+    /// Cartridge types without a mapper in ferrum, like MBC2 or the Pocket Camera, load as ROM only.
+    #[test]
+    fn unsupported_type_loads() {
+        for cart_type in [0x05, 0x20, 0xFC] {
+            let mut rom = image(0x8000);
+            rom[0x147] = cart_type;
+            assert_eq!(detect_mapper(&rom), (Mapper::RomOnly, 0x8000));
+            from_rom(rom, None);
+        }
+    }
+
+    /// Images of any size load, down to none at all, and read from anywhere in ROM without panicking.
+    #[test]
+    fn any_size_loads() {
+        for len in [
+            0,
+            0x100,
+            HEADER_END - 1,
+            0x2000,
+            0x5000,
+            0x8000,
+            0x8001,
+            0x10000,
+        ] {
+            let cart = from_rom(image(len), None);
+            assert!(
+                cart.rom_len() >= 0x8000,
+                "{} bytes mapped as {}",
+                len,
+                cart.rom_len()
+            );
+            for addr in (0x0000..0x8000).step_by(0x100) {
+                cart.read8(addr);
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::state::{self, Savestate, StateReader, StateWriter};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// T-cycles per second, the RTC's 32.768 kHz crystal is emulated off the system clock.
+const TICKS_PER_SECOND: u32 = 4194304;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Size of the RTC footer written after the cartridge RAM in the save, see Rtc::footer.
+pub const FOOTER_SIZE: usize = 48;
+
+/// Host time as seconds since the Unix epoch, for the timestamp in the RTC footer.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// A time on the RTC's counters.
+/// The day counter is 9 bits, so it counts up to 511 days before it overflows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RtcTime {
+    pub days: u16,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl fmt::Display for RtcTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}d {:02}:{:02}:{:02}",
+            self.days, self.hours, self.minutes, self.seconds
+        )
+    }
+}
+
+/// Parse a time as D:HH:MM:SS, e.g. 12:08:30:00 for day 12, 8:30 AM.
+impl FromStr for RtcTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let [days, hours, minutes, seconds] = fields[..] else {
+            return Err(format!("'{}' isn't a D:HH:MM:SS time", s));
+        };
+        let field = |value: &str, max: u16| match value.parse::<u16>() {
+            Ok(n) if n <= max => Ok(n),
+            _ => Err(format!("'{}' isn't in 0-{}", value, max)),
+        };
+        Ok(Self {
+            days: field(days, 511)?,
+            hours: field(hours, 23)? as u8,
+            minutes: field(minutes, 59)? as u8,
+            seconds: field(seconds, 59)? as u8,
+        })
+    }
+}
+
+/// MBC3 Real Time Clock - https://gbdev.io/pandocs/MBC3.html#the-clock-counter-registers
+/// The clock keeps counting seconds, minutes, hours, and days, which the game reads through a latched copy.
+///
+/// 08h  RTC S   Seconds   0-59 (0-3Bh)
+/// 09h  RTC M   Minutes   0-59 (0-3Bh)
+/// 0Ah  RTC H   Hours     0-23 (0-17h)
+/// 0Bh  RTC DL  Lower 8 bits of Day Counter (0-FFh)
+/// 0Ch  RTC DH  Upper 1 bit of Day Counter, Carry Bit, Halt Flag
+///       Bit 0  Most significant bit of Day Counter (Bit 8)
+///       Bit 6  Halt (0=Active, 1=Stop Timer)
+///       Bit 7  Day Counter Carry Bit (1=Counter Overflow)
+///
+/// The clock runs off the emulated system clock, not the host's, so it stops while emulation is paused,
+/// and its speed can be changed to make time based events happen sooner.
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    day_carry: bool,
+
+    /// Registers as of the last latch, what the game reads.
+    latched: [u8; 5],
+
+    /// A $00 was written to the latch register, a $01 next latches the clock.
+    latch_armed: bool,
+
+    /// T-cycles into the current second.
+    ticks: u32,
+
+    /// Emulated seconds per second, 0 stops the clock regardless of the halt flag.
+    speed: u32,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            day_carry: false,
+            latched: [0; 5],
+            latch_armed: false,
+            ticks: 0,
+            speed: 1,
+        }
+    }
+
+    /// Live value of an RTC register ($08-$0C).
+    fn register(&self, reg: u8) -> u8 {
+        match reg {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days as u8,
+            _ => {
+                (self.days >> 8) as u8 & 0x01
+                    | if self.halt { 0x40 } else { 0x00 }
+                    | if self.day_carry { 0x80 } else { 0x00 }
+            }
+        }
+    }
+
+    /// Read a latched RTC register ($08-$0C).
+    pub fn read(&self, reg: u8) -> u8 {
+        self.latched[(reg - 0x08) as usize]
+    }
+
+    /// Write a live RTC register ($08-$0C).
+    pub fn write(&mut self, reg: u8, val: u8) {
+        match reg {
+            0x08 => {
+                // Writing the seconds restarts the current second.
+                self.seconds = val & 0x3F;
+                self.ticks = 0;
+            }
+            0x09 => self.minutes = val & 0x3F,
+            0x0A => self.hours = val & 0x1F,
+            0x0B => self.days = self.days & 0x100 | val as u16,
+            _ => {
+                self.days = self.days & 0xFF | ((val as u16 & 0x01) << 8);
+                self.halt = val & 0x40 != 0;
+                self.day_carry = val & 0x80 != 0;
+            }
+        }
+    }
+
+    /// Write the latch register ($6000-$7FFF), $00 then $01 copies the clock into the latched registers.
+    pub fn write_latch(&mut self, val: u8) {
+        if self.latch_armed && val == 0x01 {
+            for reg in 0x08..=0x0C {
+                self.latched[(reg - 0x08) as usize] = self.register(reg);
+            }
+        }
+        self.latch_armed = val == 0x00;
+    }
+
+    /// Advance the clock by the given T-cycles.
+    pub fn cycle(&mut self, ticks: u32) {
+        if self.halt || self.speed == 0 {
+            return;
+        }
+        self.ticks += ticks * self.speed;
+        while self.ticks >= TICKS_PER_SECOND {
+            self.ticks -= TICKS_PER_SECOND;
+            self.tick();
+        }
+    }
+
+    /// Count a second.
+    /// Counters only roll over when they reach their normal limit, a counter set out of range by the game
+    /// counts up to the limit of its bits, and wraps to 0 without carrying.
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.days = (self.days + 1) & 0x1FF;
+        if self.days == 0 {
+            self.day_carry = true;
+        }
+    }
+
+    /// Current time on the clock's counters.
+    pub fn time(&self) -> RtcTime {
+        RtcTime {
+            days: self.days,
+            hours: self.hours,
+            minutes: self.minutes,
+            seconds: self.seconds,
+        }
+    }
+
+    /// Set the clock's counters, e.g. to jump ahead to a time based event.
+    pub fn set_time(&mut self, time: RtcTime) {
+        self.seconds = time.seconds & 0x3F;
+        self.minutes = time.minutes & 0x3F;
+        self.hours = time.hours & 0x1F;
+        self.days = time.days & 0x1FF;
+        self.ticks = 0;
+    }
+
+    /// Set how many emulated seconds pass per second, 0 stops the clock.
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed;
+    }
+
+    /// Emulated seconds per second, 0 if the clock is stopped.
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// The footer VBA-M, BGB and mGBA write after the RAM in the save, so the clock keeps its time between sessions.
+    /// The live, then the latched registers, each a 32 bit little endian word, then the Unix time it was saved at.
+    pub fn footer(&self, now: u64) -> [u8; FOOTER_SIZE] {
+        let mut footer = [0; FOOTER_SIZE];
+        let regs = (0x08..=0x0C)
+            .map(|reg| self.register(reg))
+            .chain(self.latched);
+        for (word, reg) in footer.chunks_exact_mut(4).zip(regs) {
+            word.copy_from_slice(&(reg as u32).to_le_bytes());
+        }
+        footer[40..].copy_from_slice(&now.to_le_bytes());
+        footer
+    }
+
+    /// Restore the clock from a footer, see footer, or the older 44 byte one with a 32 bit timestamp.
+    /// The battery kept the clock running while the game was off, so it's advanced by the time since it was saved,
+    /// unless it was halted.
+    pub fn load_footer(&mut self, footer: &[u8], now: u64) {
+        let word = |i: usize| u32::from_le_bytes(footer[i * 4..i * 4 + 4].try_into().unwrap());
+        for reg in 0x08..=0x0C {
+            self.write(reg, word((reg - 0x08) as usize) as u8);
+        }
+        for (i, latched) in self.latched.iter_mut().enumerate() {
+            *latched = word(5 + i) as u8;
+        }
+        let saved = match footer.get(40..48) {
+            Some(time) => u64::from_le_bytes(time.try_into().unwrap()),
+            None => word(10) as u64,
+        };
+        self.advance(now.saturating_sub(saved));
+    }
+
+    /// Count the given seconds at once, unless the clock is halted.
+    fn advance(&mut self, seconds: u64) {
+        if self.halt {
+            return;
+        }
+        // Whole days don't change the time of day, they only count up the days.
+        let days = self.days as u64 + seconds / SECONDS_PER_DAY;
+        if days > 0x1FF {
+            self.day_carry = true;
+        }
+        self.days = (days & 0x1FF) as u16;
+        for _ in 0..seconds % SECONDS_PER_DAY {
+            self.tick();
+        }
+    }
+}
+
+impl Default for Rtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The speed is a user setting, not part of the machine, so it isn't saved.
+impl Savestate for Rtc {
+    fn save_state(&self, w: &mut StateWriter) {
+        for reg in 0x08..=0x0C {
+            w.u8(self.register(reg));
+        }
+        w.bytes(&self.latched);
+        w.bool(self.latch_armed);
+        w.u32(self.ticks);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        for reg in 0x08..=0x0C {
+            let val = r.u8()?;
+            self.write(reg, val);
+        }
+        r.bytes(&mut self.latched)?;
+        self.latch_armed = r.bool()?;
+        self.ticks = r.u32()?;
+        Ok(())
+    }
+}
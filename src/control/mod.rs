@@ -0,0 +1,235 @@
+use crate::cartridge::banks::BankedAddr;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Longest command line accepted, a client sending more without a newline is disconnected.
+const MAX_LINE: usize = 1024;
+
+/// Most bytes a single peek returns.
+const MAX_PEEK: usize = 0x1000;
+
+/// A command from a control client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Read len bytes starting at addr, an address with a bank fails unless that bank is mapped.
+    Peek {
+        addr: BankedAddr,
+        len: usize,
+    },
+
+    /// Write a byte, as if the CPU did.
+    Poke {
+        addr: BankedAddr,
+        val: u8,
+    },
+    Pause,
+    Resume,
+
+    /// Write the last frame to a PNG.
+    Screenshot(PathBuf),
+
+    /// Load a save state slot.
+    LoadState(u8),
+
+    /// Read the emulated time counters.
+    Counters,
+
+    /// Read the banks the cartridge has mapped.
+    Banks,
+
+    /// Force a bank, or RAM enable, whatever the mapper's registers say, None hands it back to the mapper.
+    OverrideBank(BankKind, Option<usize>),
+
+    /// Hand every bank back to the mapper.
+    ClearBankOverride,
+}
+
+/// What a bank override forces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BankKind {
+    Rom,
+    Ram,
+    RamEnabled,
+}
+
+/// Parse a number in hex, with an optional 0x or $ prefix, e.g. C000, 0xC000, or $C000.
+fn parse_hex(s: &str) -> Result<u32, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches('$');
+    u32::from_str_radix(digits, 16).map_err(|_| format!("`{}` isn't a hex number", s))
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let addr = |s: &str| s.parse::<BankedAddr>();
+        match words[..] {
+            ["peek", a] => Ok(Command::Peek {
+                addr: addr(a)?,
+                len: 1,
+            }),
+            ["peek", a, len] => match len.parse() {
+                Ok(len @ 1..=MAX_PEEK) => Ok(Command::Peek {
+                    addr: addr(a)?,
+                    len,
+                }),
+                _ => Err(format!("`{}` isn't a length (1-{})", len, MAX_PEEK)),
+            },
+            ["poke", a, val] => match parse_hex(val)? {
+                val @ 0x00..=0xFF => Ok(Command::Poke {
+                    addr: addr(a)?,
+                    val: val as u8,
+                }),
+                _ => Err(format!("`{}` isn't a byte", val)),
+            },
+            ["pause"] => Ok(Command::Pause),
+            ["resume"] => Ok(Command::Resume),
+            ["counters"] => Ok(Command::Counters),
+            ["banks"] => Ok(Command::Banks),
+            ["bank", "clear"] => Ok(Command::ClearBankOverride),
+            ["bank", kind, bank] => {
+                let kind = match kind {
+                    "rom" => BankKind::Rom,
+                    "ram" => BankKind::Ram,
+                    "ram-enabled" => BankKind::RamEnabled,
+                    _ => return Err(format!("`{}` isn't rom, ram, or ram-enabled", kind)),
+                };
+                // 9 bits of ROM bank, as on MMM01.
+                let max = match kind {
+                    BankKind::RamEnabled => 1,
+                    _ => 0x1FF,
+                };
+                match bank {
+                    "-" => Ok(Command::OverrideBank(kind, None)),
+                    _ => match parse_hex(bank)? {
+                        val if val <= max => Ok(Command::OverrideBank(kind, Some(val as usize))),
+                        _ => Err(format!("`{}` isn't 0-{:X}, or -", bank, max)),
+                    },
+                }
+            }
+            ["screenshot", path] => Ok(Command::Screenshot(PathBuf::from(path))),
+            ["loadstate", slot] => match slot.parse() {
+                Ok(slot) => Ok(Command::LoadState(slot)),
+                Err(_) => Err(format!("`{}` isn't a slot", slot)),
+            },
+            _ => Err(format!("unknown command `{}`", s.trim())),
+        }
+    }
+}
+
+/// A connected client, and the part of its next command line received so far.
+struct Client {
+    stream: TcpStream,
+    line: Vec<u8>,
+}
+
+/// Control socket
+/// Lets scripts and external tools drive a running emulator over TCP, one command per line, one reply per line:
+///
+/// peek ADDR [LEN]         Read bytes, replies with them in hex, e.g. "3C 00 FF"
+/// poke ADDR VAL           Write a byte, replies "ok"
+/// pause, resume           Pause or resume emulation, replies "ok"
+/// screenshot PATH         Write the last frame to a PNG, replies "ok"
+/// loadstate SLOT          Load a save state slot, replies "ok"
+/// counters                Read the emulated time, replies e.g. "cycles=4194304 frames=60 rendered=59 seconds=1.000000"
+/// banks                   Read the cartridge's mapped banks, replies e.g. "rom0=00 rom=05 ram=01 mode=0 ram-enabled=1",
+///                         followed by the override, if any, e.g. "override rom=07 ram=- ram-enabled=-"
+/// bank rom|ram BANK       Force a ROM or RAM bank whatever the mapper's registers say, until cleared, replies "ok"
+/// bank ram-enabled 0|1    Force external RAM disabled or enabled, replies "ok"
+/// bank rom|ram|ram-enabled -
+///                         Hand one back to the mapper, replies "ok"
+/// bank clear              Hand every bank back to the mapper, replies "ok"
+///
+/// Addresses and values are in hex. Addresses can name a bank, e.g. 03:4F20, peek and poke fail
+/// if it isn't mapped. Failed commands reply "error: <reason>".
+/// $ echo "peek C000 4" | nc -q1 localhost 7777
+///
+/// The server never blocks, it's polled once per displayed frame.
+pub struct ControlServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl ControlServer {
+    /// Listen on the given address, e.g. 127.0.0.1:7777.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Accept new clients, and run the commands received since the last poll through handle,
+    /// which returns the reply (or the reason the command failed).
+    pub fn poll(&mut self, mut handle: impl FnMut(Command) -> Result<String, String>) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.clients.push(Client {
+                            stream,
+                            line: Vec::new(),
+                        });
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("Failed to accept a control client: {}", e);
+                    break;
+                }
+            }
+        }
+
+        self.clients
+            .retain_mut(|client| client.poll(&mut handle).is_ok());
+    }
+}
+
+impl Client {
+    /// Run the client's complete command lines. An error means the client is gone.
+    fn poll(
+        &mut self,
+        handle: &mut impl FnMut(Command) -> Result<String, String>,
+    ) -> io::Result<()> {
+        // Commands sent right before the client closed its end still get run, and replied to.
+        let mut buf = [0u8; 256];
+        let mut closed = false;
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => self.line.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        while let Some(end) = self.line.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.line.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match line.parse().and_then(&mut *handle) {
+                Ok(reply) => reply,
+                Err(e) => format!("error: {}", e),
+            };
+            writeln!(self.stream, "{}", reply)?;
+        }
+
+        if closed {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        if self.line.len() > MAX_LINE {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        Ok(())
+    }
+}
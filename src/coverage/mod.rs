@@ -0,0 +1,99 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// ROM banks are 16 KiB.
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// Execution coverage of the cartridge ROM.
+/// Every ROM byte the CPU fetched an opcode from is marked, by its offset in the ROM image,
+/// so the same address is tracked separately for each bank mapped into it.
+pub struct Coverage {
+    executed: Vec<bool>,
+}
+
+/// JSON coverage report.
+#[derive(Serialize)]
+struct Report {
+    rom_size: usize,
+    executed: usize,
+    banks: Vec<BankReport>,
+}
+
+/// Coverage of a single ROM bank.
+/// Ranges are inclusive, in the addresses the CPU sees the bank at ($0000-$3FFF for bank 0, $4000-$7FFF otherwise).
+#[derive(Serialize)]
+struct BankReport {
+    bank: usize,
+    executed: usize,
+    ranges: Vec<[u16; 2]>,
+}
+
+impl Coverage {
+    pub fn new(rom_size: usize) -> Self {
+        Self {
+            executed: vec![false; rom_size],
+        }
+    }
+
+    /// Mark the ROM byte at offset as executed.
+    pub fn mark(&mut self, offset: usize) {
+        // Mappers can address past the size given in the header, grow to fit.
+        if offset >= self.executed.len() {
+            self.executed.resize(offset + 1, false);
+        }
+        self.executed[offset] = true;
+    }
+
+    /// Was the ROM byte at offset executed?
+    pub fn executed(&self, offset: usize) -> bool {
+        self.executed.get(offset).copied().unwrap_or(false)
+    }
+
+    /// Binary coverage map, one byte per ROM byte, 0x01 if it was executed and 0x00 otherwise.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.executed.iter().map(|&e| e as u8).collect()
+    }
+
+    /// JSON coverage report, with the executed address ranges of each bank.
+    pub fn to_json(&self) -> String {
+        let banks = self
+            .executed
+            .chunks(ROM_BANK_SIZE)
+            .enumerate()
+            .map(|(bank, executed)| {
+                let base = if bank == 0 { 0x0000 } else { 0x4000 };
+                let mut ranges: Vec<[u16; 2]> = Vec::new();
+                for (i, _) in executed.iter().enumerate().filter(|(_, &e)| e) {
+                    let addr = (base + i) as u16;
+                    match ranges.last_mut() {
+                        Some(range) if range[1] + 1 == addr => range[1] = addr,
+                        _ => ranges.push([addr, addr]),
+                    }
+                }
+                BankReport {
+                    bank,
+                    executed: executed.iter().filter(|&&e| e).count(),
+                    ranges,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let report = Report {
+            rom_size: self.executed.len(),
+            executed: banks.iter().map(|b| b.executed).sum(),
+            banks,
+        };
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+
+    /// Export the coverage, as a JSON report if the file name ends in .json, otherwise as a binary map.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            fs::write(path, self.to_json())
+        } else {
+            fs::write(path, self.to_bytes())
+        }
+    }
+}
@@ -3,6 +3,7 @@ use super::{
     registers::{Reg16, Reg8},
     Cpu,
 };
+use crate::fault::Fault;
 use log::{info, warn};
 use std::collections::HashMap;
 
@@ -27,23 +28,30 @@ impl Cpu {
             0x00 => {}
 
             // 0x10 - STOP
-            0x10 => {}
+            // STOP is 2 bytes, the byte after the opcode is skipped.
+            0x10 => {
+                self.imm8();
+            }
 
             // 0x76 - HALT
+            // With IME off and an interrupt already pending, the CPU doesn't halt, and trips over the HALT bug instead.
             0x76 => {
-                self.halt = true;
+                if !self.ime && self.interrupt_pending() {
+                    self.halt_bug = true;
+                } else {
+                    self.halt = true;
+                }
             }
 
             // 0xF3 - DI - Disable interrupts
-            // NOTE: The IME should be changed not immediately, but after this instruction executes.
             0xF3 => {
                 self.ime = false;
             }
 
             // 0xFB - EI - Enable interrupts
-            // NOTE: The IME should be changed not immediately, but after this instruction executes.
+            // NOTE: IME is set after the next instruction executes, see Cpu::cycle.
             0xFB => {
-                self.ime = true;
+                self.ei_pending = true;
             }
 
             // LD r8, d8
@@ -105,8 +113,12 @@ impl Cpu {
                     0x22 | 0x32 => {
                         self.ld8(self.reg.read16(Reg16::HL), self.reg.read8(Reg8::A));
                         match op {
-                            0x22 => self.reg.write16(Reg16::HL, self.reg.read16(Reg16::HL) + 1),
-                            0x32 => self.reg.write16(Reg16::HL, self.reg.read16(Reg16::HL) - 1),
+                            0x22 => self
+                                .reg
+                                .write16(Reg16::HL, self.reg.read16(Reg16::HL).wrapping_add(1)),
+                            0x32 => self
+                                .reg
+                                .write16(Reg16::HL, self.reg.read16(Reg16::HL).wrapping_sub(1)),
                             _ => {}
                         }
                     }
@@ -127,8 +139,12 @@ impl Cpu {
                     _ => 0x00,
                 };
                 match op {
-                    0x2A => self.reg.write16(Reg16::HL, self.reg.read16(Reg16::HL) + 1),
-                    0x3A => self.reg.write16(Reg16::HL, self.reg.read16(Reg16::HL) - 1),
+                    0x2A => self
+                        .reg
+                        .write16(Reg16::HL, self.reg.read16(Reg16::HL).wrapping_add(1)),
+                    0x3A => self
+                        .reg
+                        .write16(Reg16::HL, self.reg.read16(Reg16::HL).wrapping_sub(1)),
                     _ => {}
                 }
                 self.ldr8(Reg8::A, val);
@@ -368,12 +384,6 @@ impl Cpu {
             0xE0 => {
                 let addr = 0xFF00 | (self.imm8() as u16);
                 self.ld8(addr, self.reg.read8(Reg8::A));
-
-                // Gameboy Boot ROM will write to 0xFF50 to disable itself
-                if addr == 0xFF50 {
-                    self.boot_rom_enabled = false;
-                    println!("Boot ROM disabled");
-                }
             }
 
             // 0xF0 - LDH A, (a8) - Load memory at address 0xFF00 + a8 into register A
@@ -1156,6 +1166,10 @@ impl Cpu {
 
             _ => {
                 warn!("Illegal opcode: {:#02x}.", op);
+                self.fault = Some(Fault::IllegalOpcode {
+                    pc: self.pc().wrapping_sub(1),
+                    opcode: op,
+                });
             }
         }
 
@@ -1561,6 +1575,7 @@ impl Cpu {
     pub(super) fn stack_push(&mut self, val: u16) {
         self.reg.dec_sp(2);
         let sp = self.reg.read16(Reg16::SP);
+        self.pushed_over_ie |= sp >= 0xFFFE;
         self.mem.borrow_mut().write16(sp, val);
         //self.ld16(sp - 2, val);
         //self.reg.dec_sp(2);
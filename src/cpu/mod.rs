@@ -1,12 +1,43 @@
+use crate::fault::Fault;
 use crate::mmu::memory::Memory;
-use log::info;
+use crate::state::{self, Savestate, StateReader, StateWriter};
+use log::{info, warn};
 use std::cell::RefCell;
 use std::rc::Rc;
+use trace::{Executed, TraceRing};
 
 mod execute;
 pub mod interrupts;
+#[cfg(test)]
+mod opcode_audit;
 mod opcodes;
 mod registers;
+pub mod trace;
+
+/// Every register, in the order they are stored in save states.
+const REGISTERS16: [registers::Reg16; 6] = [
+    registers::Reg16::AF,
+    registers::Reg16::BC,
+    registers::Reg16::DE,
+    registers::Reg16::HL,
+    registers::Reg16::SP,
+    registers::Reg16::PC,
+];
+
+/// The CPU registers, as seen by debugging tools.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuState {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+
+    /// Interrupt Master Enable Flag (IME)
+    pub ime: bool,
+    pub halted: bool,
+}
 
 /// The DMG-01 had a Sharp LR35902 CPU (speculated to be a SM83 core), which is a hybrid of the Z80 and the 8080
 /// https://gbdev.io/gb-opcodes/optables/errata
@@ -17,14 +48,33 @@ pub struct Cpu {
     /// Memory
     mem: Rc<RefCell<dyn Memory>>,
 
-    /// Keeps track of the Boot ROM being enabled or disabled.
-    boot_rom_enabled: bool,
-
     /// Interrupt Master Enable Flag (IME)
     ime: bool,
 
     /// Halt flag, for stopping CPU operation.
     halt: bool,
+
+    /// EI was just executed, IME is set after the next instruction.
+    ei_pending: bool,
+
+    /// HALT was executed with IME off and an interrupt already pending, so the CPU didn't halt,
+    /// and the next opcode fetch fails to increment PC. The byte after HALT is read twice.
+    halt_bug: bool,
+
+    /// Interrupt (IF bit) dispatched since it was last taken, for tracing.
+    dispatched: Option<u8>,
+
+    /// Illegal opcode executed (or stack fault) since it was last taken, for strict mode.
+    fault: Option<Fault>,
+
+    /// Check where SP points, see set_stack_check, whether it's in ROM or IO already,
+    /// and whether a push wrote IE since the last check.
+    stack_check: bool,
+    sp_stray: bool,
+    pushed_over_ie: bool,
+
+    /// The last instructions executed, for crash reports.
+    trace: TraceRing,
 }
 
 impl Cpu {
@@ -33,9 +83,19 @@ impl Cpu {
         /*self.mem
         .borrow()
         .read8(self.reg.read16(registers::Reg16::PC))*/
+        if self.halt_bug {
+            self.halt_bug = false;
+            return self.mem.borrow().read8(self.pc());
+        }
         self.imm8()
     }
 
+    /// Is an enabled interrupt requested? (IE & IF)
+    fn interrupt_pending(&self) -> bool {
+        let mem = self.mem.borrow();
+        mem.read8(0xFFFF) & mem.read8(0xFF0F) & 0x1F != 0
+    }
+
     /// Handles CPU Interrupts and returns the number of cycles the interrupt took.
     fn handle_interrupts(&mut self) -> u32 {
         // Interrupts are handled by the CPU, not the MMU.
@@ -55,7 +115,7 @@ impl Cpu {
         // Get Interrupt Enable and Interrupt Flag registers
         let ie = self.mem.borrow().read8(0xFFFF);
         let if_ = self.mem.borrow().read8(0xFF0F);
-        let triggered = ie & if_;
+        let triggered = ie & if_ & 0x1F;
 
         // If interrupts are enabled, but none are pending, do nothing.
         if triggered == 0x00 {
@@ -63,17 +123,19 @@ impl Cpu {
         }
 
         // If we get here, we have an interrupt to handle.
-        // Reset IME and CPU halt.
+        // Reset IME and CPU halt. Leaving HALT takes an extra M-cycle.
+        let wake = if self.halt { 4 } else { 0 };
         self.halt = false;
 
         if !self.ime {
-            return 0;
+            return wake;
         }
         self.ime = false;
 
         // Consume the interrupt, and write the remaining interrupts back to the IF register.
         let i = triggered.trailing_zeros();
         self.mem.borrow_mut().write8(0xFF0F, if_ & !(1 << i));
+        self.dispatched = Some(i as u8);
 
         // Push the current PC onto the stack
         let pc = self.reg.read16(registers::Reg16::PC);
@@ -83,7 +145,8 @@ impl Cpu {
         self.reg
             .write16(registers::Reg16::PC, 0x0040 | ((i as u16) << 3));
 
-        16
+        // Dispatch takes 5 M-cycles: 2 wait states, 2 to push PC, 1 to jump.
+        wake + 20
     }
 
     /// Prints the current CPU state to the console.
@@ -101,9 +164,9 @@ impl Cpu {
         let h = self.reg.read8(registers::Reg8::H);
         let l = self.reg.read8(registers::Reg8::L);
         let m = self.mem.borrow().read8(pc);
-        let n = self.mem.borrow().read8(pc + 1);
-        let o = self.mem.borrow().read8(pc + 2);
-        let p = self.mem.borrow().read8(pc + 3);
+        let n = self.mem.borrow().read8(pc.wrapping_add(1));
+        let o = self.mem.borrow().read8(pc.wrapping_add(2));
+        let p = self.mem.borrow().read8(pc.wrapping_add(3));
 
         // Print using the following format
         // [registers] (mem[pc] mem[pc+1] mem[pc+2] mem[pc+3])
@@ -136,9 +199,16 @@ impl Cpu {
             */
             reg: registers::Registers::new(),
             mem,
-            boot_rom_enabled: true,
             ime: false,
             halt: false,
+            ei_pending: false,
+            halt_bug: false,
+            dispatched: None,
+            fault: None,
+            stack_check: false,
+            sp_stray: false,
+            pushed_over_ie: false,
+            trace: TraceRing::default(),
         }
     }
 
@@ -146,23 +216,179 @@ impl Cpu {
     pub fn cycle(&mut self) -> u32 {
         //self._debug_print_state();
         let mut ticks = 0;
+        let pc = self.pc();
 
         // If CPU is halted, do nothing.
         if !self.halt {
+            // EI takes effect after the instruction following it, so EI followed by DI never lets an interrupt in.
+            let ei_pending = std::mem::take(&mut self.ei_pending);
+            let state = self.state();
+            let bank = self.mem.borrow().banked(state.pc).bank;
             let op = self.fetch();
+            self.trace.push(Executed {
+                state,
+                opcode: op,
+                bank,
+            });
             ticks += self.op_execute(op);
+            if ei_pending && op != 0xF3 {
+                self.ime = true;
+            }
         } else {
             info!("CPU halted!");
-            ticks += 1;
+            ticks += 4;
         }
 
         ticks += self.handle_interrupts();
+        if self.stack_check {
+            self.check_stack(pc);
+        }
         //println!("Ticks: {}", ticks);
         self.mem.borrow_mut().cycle(ticks)
     }
 
+    /// Address of the next instruction.
+    pub fn pc(&self) -> u16 {
+        self.reg.read16(registers::Reg16::PC)
+    }
+
+    /// Is the CPU halted, waiting for an interrupt?
+    pub fn halted(&self) -> bool {
+        self.halt
+    }
+
+    /// Snapshot of the registers.
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            af: self.reg.read16(registers::Reg16::AF),
+            bc: self.reg.read16(registers::Reg16::BC),
+            de: self.reg.read16(registers::Reg16::DE),
+            hl: self.reg.read16(registers::Reg16::HL),
+            sp: self.reg.read16(registers::Reg16::SP),
+            pc: self.pc(),
+            ime: self.ime,
+            halted: self.halt,
+        }
+    }
+
+    /// Overwrite the registers, e.g. to start where the boot ROM would have left them.
+    pub fn set_state(&mut self, state: CpuState) {
+        let values = [state.af, state.bc, state.de, state.hl, state.sp, state.pc];
+        for (reg, val) in REGISTERS16.into_iter().zip(values) {
+            self.reg.write16(reg, val);
+        }
+        self.ime = state.ime;
+        self.halt = state.halted;
+    }
+
+    /// The interrupt (IF bit) dispatched since the last call, if any.
+    pub fn take_dispatched(&mut self) -> Option<u8> {
+        self.dispatched.take()
+    }
+
+    /// The illegal opcode executed (or stack fault) since the last call, if any.
+    pub fn take_fault(&mut self) -> Option<Fault> {
+        self.fault.take()
+    }
+
+    /// Warn when SP moves into ROM or the IO registers, and when the stack is pushed over IE,
+    /// faults in strict mode. Off by default, some code points SP at ROM on purpose.
+    pub fn set_stack_check(&mut self, enabled: bool) {
+        self.stack_check = enabled;
+        self.sp_stray = false;
+        self.pushed_over_ie = false;
+    }
+
+    /// Check the stack after the instruction at pc, reporting SP as it moves into ROM or IO, not while it stays there.
+    /// $0000 is the top of a stack that wraps around to $FFFF, its first push is reported instead.
+    fn check_stack(&mut self, pc: u16) {
+        let sp = self.reg.read16(registers::Reg16::SP);
+        let stray = matches!(sp, 0x0001..=0x7FFF | 0xFF00..=0xFF7F);
+        let fault = if std::mem::take(&mut self.pushed_over_ie) {
+            Some(Fault::StackOverIe { pc })
+        } else if stray && !self.sp_stray {
+            Some(Fault::StackPointer { pc, sp })
+        } else {
+            None
+        };
+        self.sp_stray = stray;
+        if let Some(fault) = fault {
+            warn!("{}", fault);
+            self.fault.get_or_insert(fault);
+        }
+    }
+
+    /// Keep the last len instructions executed, 0 keeps none.
+    pub fn set_trace_len(&mut self, len: usize) {
+        self.trace = TraceRing::new(len);
+    }
+
+    /// The last instructions executed, see TraceRing.
+    pub fn trace(&self) -> &TraceRing {
+        &self.trace
+    }
+
+    /// Put back a trace ring, e.g. from before a frame that was rolled back.
+    pub fn set_trace(&mut self, trace: TraceRing) {
+        self.trace = trace;
+    }
+
     /// Dumps the current CPU Register values at the info Log level.
     pub fn dump_registers(&self) {
         info!("CPU Registers{}", self.reg);
     }
 }
+
+impl Savestate for Cpu {
+    fn save_state(&self, w: &mut StateWriter) {
+        for reg in REGISTERS16 {
+            w.u16(self.reg.read16(reg));
+        }
+        w.bool(self.ime);
+        w.bool(self.halt);
+        w.bool(self.ei_pending);
+        w.bool(self.halt_bug);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        for reg in REGISTERS16 {
+            self.reg.write16(reg, r.u16()?);
+        }
+        self.ime = r.bool()?;
+        self.halt = r.bool()?;
+        self.ei_pending = r.bool()?;
+        self.halt_bug = r.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmu::memory::FlatRam;
+
+    /// LD BC,d16 at the end of the address space, its immediate word fetched across $FFFF to $0000,
+    /// and the PC wrapping around after it.
+    #[test]
+    fn immediate_wraps() {
+        for pc in [0xFFFDu16, 0xFFFE, 0xFFFF] {
+            let mut ram = FlatRam::new();
+            ram.0[pc as usize] = 0x01;
+            ram.0[pc.wrapping_add(1) as usize] = 0x34;
+            ram.0[pc.wrapping_add(2) as usize] = 0x12;
+            let mut cpu = Cpu::power_on(Rc::new(RefCell::new(ram)));
+            cpu.set_state(CpuState {
+                pc,
+                ..Default::default()
+            });
+            cpu.cycle();
+            let state = cpu.state();
+            assert_eq!(
+                (state.bc, state.pc),
+                (0x1234, pc.wrapping_add(3)),
+                "at ${:04X}",
+                pc
+            );
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::opcodes::{OpCode, CB_OP_CODES, CPU_OP_CODES};
+use super::{Cpu, CpuState};
+use crate::mmu::memory::Memory;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Where the instruction under audit is placed, and where the registers point, away from it and from each other,
+/// so the only reads right after the opcode are its immediate bytes.
+const PC: u16 = 0xC000;
+const POINTER: u16 = 0xD000;
+const SP: u16 = 0xDFF0;
+
+/// Immediate bytes, not 0, so a relative jump lands somewhere else than the next instruction.
+const IMMEDIATE: u8 = 0x10;
+
+/// The interrupt registers, the CPU reads them around every instruction, which isn't the instruction's doing.
+const INTERRUPT_REGISTERS: [u16; 2] = [0xFF0F, 0xFFFF];
+
+/// Flat RAM over the whole address space, logging every access.
+struct AuditBus {
+    ram: Vec<u8>,
+    accesses: Vec<u16>,
+    reads: RefCell<Vec<u16>>,
+}
+
+impl Memory for AuditBus {
+    fn read8(&self, addr: u16) -> u8 {
+        self.reads.borrow_mut().push(addr);
+        self.ram[addr as usize]
+    }
+
+    fn write8(&mut self, addr: u16, val: u8) {
+        self.accesses.push(addr);
+        self.ram[addr as usize] = val;
+    }
+
+    fn cycle(&mut self, ticks: u32) -> u32 {
+        ticks
+    }
+}
+
+/// What one run of an instruction did: its length as executed (the opcode and the immediate bytes it fetched),
+/// the T-cycles it took, the bus accesses it made, and whether it jumped.
+struct Run {
+    length: u8,
+    cycles: u32,
+    accesses: u32,
+    jumped: bool,
+}
+
+/// Run an instruction once, with the flags given. bytes is the opcode, after a CB prefix.
+fn run(bytes: &[u8], flags: u8) -> Run {
+    let mut ram = vec![IMMEDIATE; 0x10000];
+    ram[PC as usize..PC as usize + bytes.len()].copy_from_slice(bytes);
+    // Interrupts stay off, and RET pops $0000.
+    for addr in INTERRUPT_REGISTERS
+        .iter()
+        .map(|&addr| addr as usize)
+        .chain(SP as usize..SP as usize + 2)
+    {
+        ram[addr] = 0;
+    }
+    let bus = Rc::new(RefCell::new(AuditBus {
+        ram,
+        accesses: Vec::new(),
+        reads: RefCell::new(Vec::new()),
+    }));
+    let mut cpu = Cpu::power_on(bus.clone());
+    cpu.set_state(CpuState {
+        af: u16::from(flags),
+        bc: POINTER,
+        de: POINTER,
+        hl: POINTER,
+        sp: SP,
+        pc: PC,
+        ime: false,
+        halted: false,
+    });
+
+    let cycles = cpu.cycle();
+    let mut bus = bus.borrow_mut();
+    let reads = bus.reads.take();
+    bus.accesses.extend(reads);
+    bus.accesses
+        .retain(|addr| !INTERRUPT_REGISTERS.contains(addr));
+    let length = 1
+        + (1..=2)
+            .take_while(|&i| bus.accesses.contains(&(PC + i)))
+            .count() as u8;
+    Run {
+        length,
+        cycles,
+        accesses: bus.accesses.len() as u32,
+        jumped: cpu.pc() != PC + u16::from(length),
+    }
+}
+
+/// T-cycles of a branch taken, and not taken. The table writes both in one number for conditional branches,
+/// e.g. 1612 for 16 taken and 12 not, 128 for 12 and 8.
+fn branch_cycles(cycles: u32) -> (u32, u32) {
+    match cycles {
+        c if c >= 1000 => (c / 100, c % 100),
+        c if c >= 100 => (c / 10, c % 10),
+        c => (c, c),
+    }
+}
+
+/// Check a table entry against runs of its instruction, bytes being the opcode (after a CB prefix).
+/// Conditional branches are run with every flag reset, then set, to take them both ways.
+fn check(entry: &OpCode, bytes: &[u8], mismatches: &mut Vec<String>) {
+    let name = match bytes {
+        [0xCB, ..] => format!("CB ${:02X} {}", entry.op, entry.mnemonic),
+        _ => format!("${:02X} {}", entry.op, entry.mnemonic),
+    };
+    let runs = [run(bytes, 0x00), run(bytes, 0xF0)];
+    if runs[0].length != entry.length {
+        mismatches.push(format!(
+            "{}: {} bytes in the table, executes as {}",
+            name, entry.length, runs[0].length
+        ));
+    }
+
+    let (taken, not_taken) = branch_cycles(entry.cycles);
+    let mut cycles = Vec::new();
+    for run in &runs {
+        let (expected, way) = match (taken == not_taken, run.jumped) {
+            (true, _) => (taken, ""),
+            (false, true) => (taken, " taken"),
+            (false, false) => (not_taken, " not taken"),
+        };
+        if run.cycles != expected {
+            cycles.push(format!(
+                "{}: {} cycles{} in the table, executes in {}",
+                name, expected, way, run.cycles
+            ));
+        }
+        // Every bus access takes a machine cycle, 4 T-cycles. Except STOP's, the byte after it is fetched
+        // as the clock stops.
+        if run.cycles < run.accesses * 4 && bytes != [0x10] {
+            cycles.push(format!(
+                "{}: {} cycles, too few for its {} bus accesses",
+                name, run.cycles, run.accesses
+            ));
+        }
+    }
+    if taken != not_taken && runs[0].jumped == runs[1].jumped {
+        cycles.push(format!(
+            "{}: taken and not taken cycles in the table, but it branches the same way whatever the flags",
+            name
+        ));
+    }
+    cycles.dedup();
+    mismatches.append(&mut cycles);
+}
+
+/// Opcode table audit
+/// Cross-check the opcode tables' lengths and cycles against what the instructions do when executed:
+/// how many immediate bytes they fetch, the T-cycles they take (both ways for a conditional branch),
+/// and whether those cover their bus accesses. A wrong length or cycle count otherwise only shows up in test ROMs.
+/// Returns a line per mismatch, e.g. "$E2 LD: 2 bytes in the table, executes as 1", none if the tables hold.
+/// Illegal opcodes aren't checked, they lock up the CPU, nor the CB prefix on its own, the CB table covers it.
+fn audit() -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for entry in CPU_OP_CODES.iter() {
+        if entry.op != 0xCB && !entry.mnemonic.starts_with("ILLEGAL") {
+            check(entry, &[entry.op], &mut mismatches);
+        }
+    }
+    for entry in CB_OP_CODES.iter() {
+        check(entry, &[0xCB, entry.op], &mut mismatches);
+    }
+    mismatches
+}
+
+#[test]
+fn tables_match_execution() {
+    let mismatches = audit();
+    assert!(
+        mismatches.is_empty(),
+        "{} mismatches: {}",
+        mismatches.len(),
+        mismatches.join(", ")
+    );
+}
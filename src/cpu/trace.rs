@@ -0,0 +1,93 @@
+use super::CpuState;
+use crate::cartridge::banks::BankedAddr;
+use std::fmt;
+
+/// Instructions kept by default, enough to see how a game got where it crashed.
+pub const DEFAULT_TRACE_LEN: usize = 64;
+
+/// An instruction the CPU executed, with the registers from before it ran.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Executed {
+    pub state: CpuState,
+    pub opcode: u8,
+
+    /// The bank PC was in, if it was in a banked area.
+    pub bank: Option<u16>,
+}
+
+impl Executed {
+    /// Where the instruction was, e.g. 03:4F20.
+    pub fn addr(&self) -> BankedAddr {
+        BankedAddr::new(self.bank, self.state.pc)
+    }
+}
+
+/// e.g. "PC:01:4150 op:C3 AF:01B0 BC:0013 DE:00D8 HL:014D SP:FFFE"
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = &self.state;
+        write!(
+            f,
+            "PC:{} op:{:02X} AF:{:04X} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X}",
+            self.addr(),
+            self.opcode,
+            s.af,
+            s.bc,
+            s.de,
+            s.hl,
+            s.sp
+        )?;
+        if s.ime {
+            write!(f, " IME")?;
+        }
+        Ok(())
+    }
+}
+
+/// Trace ring
+/// The last few instructions the CPU executed, always recorded, so a crash can be reported with how it came about.
+/// Recording is a copy of the registers into a fixed buffer, cheap enough to leave on.
+#[derive(Clone, Debug)]
+pub struct TraceRing {
+    entries: Vec<Executed>,
+
+    /// Where the next instruction goes, and how many of the entries are recorded.
+    next: usize,
+    len: usize,
+}
+
+impl TraceRing {
+    /// A ring keeping the last len instructions, 0 records nothing.
+    pub fn new(len: usize) -> Self {
+        Self {
+            entries: vec![Executed::default(); len],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, executed: Executed) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.entries[self.next] = executed;
+        self.next = (self.next + 1) % self.entries.len();
+        self.len = (self.len + 1).min(self.entries.len());
+    }
+
+    /// The recorded instructions, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Executed> {
+        let start = (self.next + self.entries.len() - self.len) % self.entries.len().max(1);
+        self.entries.iter().cycle().skip(start).take(self.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Default for TraceRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRACE_LEN)
+    }
+}
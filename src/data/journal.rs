@@ -0,0 +1,98 @@
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Pages of cartridge RAM are written to the save file individually, this many bytes each.
+pub const PAGE_SIZE: usize = 0x200;
+
+/// Displayed frames without a write to cartridge RAM before it's saved, half a second.
+/// Games write a save a byte at a time over a few frames, this waits until they're done.
+const SETTLE_FRAMES: u32 = 30;
+
+/// Displayed frames RAM can go unsaved while the game keeps writing, 10 seconds.
+/// Games using cartridge RAM as work RAM never stop writing, their RAM is saved this often.
+const MAX_PENDING_FRAMES: u32 = 600;
+
+/// Save journal
+/// Writes battery backed RAM to the save file shortly after the game stops writing to it, like a flash cart does,
+/// instead of only when ferrum exits, so a crash or power loss doesn't lose an in-game save.
+/// Only the pages that changed since the last write are written, in place, then synced to disk.
+pub struct SaveJournal {
+    path: PathBuf,
+
+    /// The RAM as the save file has it.
+    saved: Vec<u8>,
+
+    /// Displayed frames since the first RAM write that isn't saved yet, and since the last one,
+    /// None if nothing was written since the last save.
+    pending: Option<(u32, u32)>,
+}
+
+impl SaveJournal {
+    /// Journal to the save file at path, which holds ram (or will, when the game writes to it).
+    pub fn new(path: PathBuf, ram: &[u8]) -> Self {
+        Self {
+            path,
+            saved: ram.to_vec(),
+            pending: None,
+        }
+    }
+
+    /// Whether RAM was written since the last save, and will be saved once it settles.
+    pub fn pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Call once per displayed frame, with whether the game wrote to cartridge RAM since the last call.
+    /// Once RAM settles, the changed pages are written, returns how many, 0 if none were.
+    pub fn frame(&mut self, written: bool, ram: &[u8]) -> io::Result<usize> {
+        let (age, quiet) = match (self.pending, written) {
+            (None, false) => return Ok(0),
+            (None, true) => (0, 0),
+            (Some((age, _)), true) => (age + 1, 0),
+            (Some((age, quiet)), false) => (age + 1, quiet + 1),
+        };
+        if quiet < SETTLE_FRAMES && age < MAX_PENDING_FRAMES {
+            self.pending = Some((age, quiet));
+            return Ok(0);
+        }
+        self.pending = None;
+        self.flush(ram)
+    }
+
+    /// Write the pages of ram that differ from the save file, returns how many.
+    /// A save file of another size is rewritten whole.
+    pub fn flush(&mut self, ram: &[u8]) -> io::Result<usize> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        if file.metadata()?.len() != ram.len() as u64 || self.saved.len() != ram.len() {
+            file.write_all(ram)?;
+            file.set_len(ram.len() as u64)?;
+            file.sync_data()?;
+            self.saved = ram.to_vec();
+            return Ok(ram.len().div_ceil(PAGE_SIZE));
+        }
+
+        let mut pages = 0;
+        for (i, (page, saved)) in ram
+            .chunks(PAGE_SIZE)
+            .zip(self.saved.chunks_mut(PAGE_SIZE))
+            .enumerate()
+        {
+            if page == saved {
+                continue;
+            }
+            file.seek(SeekFrom::Start((i * PAGE_SIZE) as u64))?;
+            file.write_all(page)?;
+            saved.copy_from_slice(page);
+            pages += 1;
+        }
+        if pages > 0 {
+            file.sync_data()?;
+        }
+        Ok(pages)
+    }
+}
@@ -0,0 +1,306 @@
+use crate::accuracy::AccuracyPreset;
+use crate::gb::{FastForwardAudio, FastForwardSpeed, Presentation, Rumble};
+use crate::model::Model;
+use crate::ppu::{LcdOffPolicy, PpuAccuracy};
+use log::{info, warn};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod journal;
+mod playtime;
+
+pub use journal::{SaveJournal, PAGE_SIZE};
+pub use playtime::{format_playtime, Playtime, PlaytimeClock};
+
+/// Number of save state slots per game.
+pub const STATE_SLOTS: u8 = 10;
+
+/// Per-game settings, read from the game's config.toml.
+/// Every setting is optional, options given on the command line take precedence.
+///
+/// model = "mgb"
+/// accuracy = "balanced"
+/// ppu-accuracy = "scanline"
+/// turbo-rate = 3
+/// run-ahead = true
+/// volume = 70
+/// channel-volume = [100, 100, 50, 100]
+/// scale = 3
+/// scale-key = "Tab"
+/// palette = "high-contrast"
+/// widescreen = true
+/// interlaced = false
+/// lcd-off = "hold"
+/// playtime-clock = "emulated"
+/// fast-forward = "8x"
+/// fast-forward-audio = "pitch"
+/// presentation = "triple-buffer"
+/// rumble = "off"
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GameConfig {
+    pub model: Option<Model>,
+    pub accuracy: Option<AccuracyPreset>,
+
+    /// Rendering pipeline, see PpuAccuracy, the pixel FIFO is experimental.
+    pub ppu_accuracy: Option<PpuAccuracy>,
+    pub turbo_rate: Option<u32>,
+    pub run_ahead: Option<bool>,
+
+    /// Master volume, in percent.
+    pub volume: Option<u8>,
+
+    /// Volume of each sound channel (pulse 1, pulse 2, wave, noise), in percent.
+    pub channel_volume: Option<[u8; 4]>,
+
+    /// Audio kept queued for the sound card, in milliseconds.
+    pub audio_latency: Option<u32>,
+
+    /// Size of the sound card's buffer, in samples per channel.
+    pub audio_buffer: Option<u32>,
+
+    /// Window scale (1-4), changing it with the scale hotkey updates this setting.
+    pub scale: Option<usize>,
+
+    /// Key that switches to the next window scale in place of F12, e.g. "Tab" or "Ctrl+S", as in key bindings files.
+    pub scale_key: Option<String>,
+
+    /// Built-in palette, e.g. "dmg" or "red-green", changing it with the palette hotkey updates this setting.
+    pub palette: Option<String>,
+
+    /// Experimental widescreen, only for games it works with, see GameBoy::set_widescreen.
+    pub widescreen: Option<bool>,
+
+    /// Render every other scanline per frame, see GameBoy::set_interlaced, the interlace hotkey updates this setting.
+    pub interlaced: Option<bool>,
+
+    /// What the screen shows while the game has the LCD off, see LcdOffPolicy.
+    pub lcd_off: Option<LcdOffPolicy>,
+
+    /// What counts towards the game's playtime, real or emulated time, see PlaytimeClock.
+    pub playtime_clock: Option<PlaytimeClock>,
+
+    /// How fast fast-forwarding runs, and what the sound does meanwhile.
+    pub fast_forward: Option<FastForwardSpeed>,
+    pub fast_forward_audio: Option<FastForwardAudio>,
+
+    /// How the window presents frames, see Presentation.
+    pub presentation: Option<Presentation>,
+
+    /// What a rumble cartridge's motor does, see Rumble.
+    pub rumble: Option<Rumble>,
+}
+
+/// ferrum's data directory.
+/// Every game gets its own directory, named after the title and global checksum from the cartridge header,
+/// so saves, states, and screenshots from different games never collide.
+///
+/// <data-dir>/<TITLE>-<checksum>/
+///     config.toml     Per-game settings
+///     playtime.toml   Time played
+///     saves/          Battery backed cartridge RAM
+///     states/         Save states
+///     screenshots/    Screenshots
+pub struct DataDir {
+    root: PathBuf,
+}
+
+impl DataDir {
+    /// Use the given directory, or the platform's data directory (e.g. ~/.local/share/ferrum on Linux).
+    pub fn new(root: Option<PathBuf>) -> Self {
+        let root = root.unwrap_or_else(|| {
+            dirs::data_dir()
+                .map(|dir| dir.join("ferrum"))
+                .unwrap_or_else(|| PathBuf::from("ferrum-data"))
+        });
+        Self { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directories of every game that has one.
+    pub fn games(&self) -> io::Result<Vec<GameDir>> {
+        let mut games = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            games.push(GameDir {
+                root: entry.path(),
+                key: entry.file_name().to_string_lossy().into_owned(),
+            });
+        }
+        games.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(games)
+    }
+
+    /// The data directory of the game in the given ROM image.
+    pub fn game(&self, rom: &[u8]) -> GameDir {
+        let key = game_key(rom);
+        GameDir {
+            root: self.root.join(&key),
+            key,
+        }
+    }
+}
+
+/// Build the directory name of a game from its header, e.g. TETRIS-BF9B.
+/// Characters that aren't safe in file names are replaced with '_'.
+fn game_key(rom: &[u8]) -> String {
+    let title: String = rom
+        .get(0x134..0x143)
+        .unwrap_or_default()
+        .iter()
+        .take_while(|&&c| c != 0x00)
+        .map(|&c| {
+            if c.is_ascii_alphanumeric() {
+                c as char
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let title = if title.is_empty() {
+        "UNTITLED".to_string()
+    } else {
+        title
+    };
+
+    let checksum = match rom.get(0x14E..=0x14F) {
+        Some(&[hi, lo]) => u16::from_be_bytes([hi, lo]),
+        _ => 0x0000,
+    };
+
+    format!("{}-{:04X}", title, checksum)
+}
+
+/// The data directory of a single game.
+pub struct GameDir {
+    root: PathBuf,
+    key: String,
+}
+
+impl GameDir {
+    /// Name of the game's directory, also used to name its files.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.root.join("config.toml")
+    }
+
+    pub fn playtime_path(&self) -> PathBuf {
+        self.root.join("playtime.toml")
+    }
+
+    /// Time spent playing the game, over every session, see Playtime.
+    pub fn playtime(&self) -> io::Result<Duration> {
+        playtime::read(&self.playtime_path())
+    }
+
+    pub fn save_path(&self) -> PathBuf {
+        self.root.join("saves").join(format!("{}.sav", self.key))
+    }
+
+    pub fn state_path(&self, slot: u8) -> PathBuf {
+        self.root
+            .join("states")
+            .join(format!("{}.ss{}", self.key, slot))
+    }
+
+    pub fn screenshots_dir(&self) -> PathBuf {
+        self.root.join("screenshots")
+    }
+
+    /// Create the game's directory layout, if it doesn't exist yet.
+    pub fn create(&self) -> io::Result<()> {
+        for dir in ["saves", "states", "screenshots"] {
+            fs::create_dir_all(self.root.join(dir))?;
+        }
+        Ok(())
+    }
+
+    /// Load the per-game settings.
+    /// A missing config.toml gives the default settings, an invalid one is reported and ignored.
+    pub fn load_config(&self) -> GameConfig {
+        self.read_config()
+            .unwrap_or_else(|e| {
+                warn!("Ignoring {}: {}", self.config_path().display(), e);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    /// Read the per-game settings, None if there's no config.toml.
+    pub fn read_config(&self) -> Result<Option<GameConfig>, String> {
+        match fs::read_to_string(self.config_path()) {
+            Ok(text) => toml::from_str(&text).map(Some).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Change a single per-game setting, keeping the others.
+    /// An invalid config.toml is left alone, rather than overwritten.
+    pub fn set_config_value(&self, key: &str, value: impl Into<toml::Value>) -> io::Result<()> {
+        let path = self.config_path();
+        let mut config = match fs::read_to_string(&path) {
+            Ok(text) => text
+                .parse::<toml::Table>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e),
+        };
+        config.insert(key.to_string(), value.into());
+        fs::write(&path, config.to_string())
+    }
+
+    /// Load the battery backed cartridge RAM, if the game has been saved before.
+    /// The save is as GameBoy::battery_save has it, with the RTC footer after the RAM of a cartridge with a clock,
+    /// for GameBoy::load_ram, or to power on with.
+    pub fn load_save(&self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.save_path()) {
+            Ok(save) => Ok(Some(save)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the battery backed cartridge RAM, and the RTC footer, from GameBoy::battery_save.
+    pub fn write_save(&self, save: &[u8]) -> io::Result<()> {
+        fs::write(self.save_path(), save)
+    }
+
+    /// Move a loose .sav file next to the ROM (e.g. roms/tetris.sav) into the game's saves directory.
+    /// A save already in the data directory is never overwritten.
+    /// Returns true if a save was migrated.
+    pub fn migrate_save(&self, rom_path: &Path) -> io::Result<bool> {
+        let loose = rom_path.with_extension("sav");
+        if !loose.is_file() {
+            return Ok(false);
+        }
+
+        let save = self.save_path();
+        if save.exists() {
+            warn!(
+                "Not migrating {}, {} already exists.",
+                loose.display(),
+                save.display()
+            );
+            return Ok(false);
+        }
+
+        // Copy, then remove, as the data directory may be on another file system.
+        fs::copy(&loose, &save)?;
+        fs::remove_file(&loose)?;
+        info!("Migrated {} to {}", loose.display(), save.display());
+        Ok(true)
+    }
+}
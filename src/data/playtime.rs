@@ -0,0 +1,102 @@
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Playtime not yet written to the game's directory is written this often, so a crash loses at most this much.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What counts as time played.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaytimeClock {
+    /// Real time spent playing, a minute fast-forwarded is a minute played.
+    #[default]
+    Wall,
+
+    /// Emulated time, a minute fast-forwarded at 4x is four minutes played, as if on a real Gameboy.
+    Emulated,
+}
+
+/// playtime.toml
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlaytimeFile {
+    seconds: u64,
+}
+
+/// Playtime
+/// Time spent playing a game, over every session, kept in playtime.toml in the game's directory.
+/// Time paused, or in the background with the background policy pausing, isn't played.
+pub struct Playtime {
+    path: PathBuf,
+    clock: PlaytimeClock,
+    total: Duration,
+
+    /// Played since playtime.toml was last written.
+    unsaved: Duration,
+}
+
+impl Playtime {
+    /// Load the playtime from the file at path, a missing file is a game never played.
+    pub fn load(path: PathBuf, clock: PlaytimeClock) -> io::Result<Self> {
+        let total = read(&path)?;
+        Ok(Self {
+            path,
+            clock,
+            total,
+            unsaved: Duration::ZERO,
+        })
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn set_clock(&mut self, clock: PlaytimeClock) {
+        self.clock = clock;
+    }
+
+    /// Count time played, wall is the real time it took to emulate frames at the speed of a real Gameboy.
+    /// Written to the file every minute or so, returns whether it was written this time.
+    pub fn add(&mut self, wall: Duration, emulated: Duration) -> io::Result<bool> {
+        let played = match self.clock {
+            PlaytimeClock::Wall => wall,
+            PlaytimeClock::Emulated => emulated,
+        };
+        self.total += played;
+        self.unsaved += played;
+        if self.unsaved < SAVE_INTERVAL {
+            return Ok(false);
+        }
+        self.save().map(|()| true)
+    }
+
+    /// Write the playtime to the file.
+    pub fn save(&mut self) -> io::Result<()> {
+        fs::write(&self.path, format!("seconds = {}\n", self.total.as_secs()))?;
+        self.unsaved = Duration::ZERO;
+        Ok(())
+    }
+}
+
+/// Read a playtime.toml, zero if there's none.
+pub(super) fn read(path: &Path) -> io::Result<Duration> {
+    match fs::read_to_string(path) {
+        Ok(text) => toml::from_str::<PlaytimeFile>(&text)
+            .map(|file| Duration::from_secs(file.seconds))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Duration::ZERO),
+        Err(e) => Err(e),
+    }
+}
+
+/// Playtime the way game libraries show it, e.g. "12h 05m", or "42m" under an hour.
+pub fn format_playtime(playtime: Duration) -> String {
+    let minutes = playtime.as_secs() / 60;
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
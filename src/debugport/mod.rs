@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+
+/// Debug argument register (DBGARG), queues an argument for the next message.
+pub const DBGARG: u16 = 0xFF7E;
+
+/// Debug output register (DBGOUT), appends a character to the message, $00 prints it.
+pub const DBGOUT: u16 = 0xFF7F;
+
+/// Longest message kept, characters past this are dropped, in case a game writes to DBGOUT without ever ending the message.
+const MAX_MESSAGE: usize = 256;
+
+/// Debug printf port
+/// A logging channel for homebrew developers, separate from the serial port so it doesn't get in the way
+/// of link cable emulation. Nothing like it exists on real hardware, so it is only enabled on request,
+/// otherwise $FF7E and $FF7F are the unused registers they normally are.
+///
+/// The game writes the characters of a format string to DBGOUT, then $00 to print it. Arguments are written
+/// to DBGARG beforehand, and consumed in order by the format string:
+///     %d - byte, in decimal        %D - word (low byte first), in decimal
+///     %x - byte, in hex            %X - word (low byte first), in hex
+///     %b - byte, in binary         %c - byte, as an ASCII character
+///     %% - a literal %
+///
+/// ld a, [wScore]
+/// ldh [$FF7E], a
+/// ld hl, .msg          ; "score=%d", 0
+/// .loop
+/// ld a, [hl+]
+/// ldh [$FF7F], a
+/// and a
+/// jr nz, .loop
+pub struct DebugPort {
+    /// Format string of the message being written.
+    message: Vec<u8>,
+
+    /// Arguments queued for the next message.
+    args: VecDeque<u8>,
+}
+
+impl DebugPort {
+    pub fn new() -> Self {
+        Self {
+            message: Vec::new(),
+            args: VecDeque::new(),
+        }
+    }
+
+    /// Queue an argument for the next message.
+    pub fn write_arg(&mut self, val: u8) {
+        self.args.push_back(val);
+    }
+
+    /// Append a character to the message, returning the formatted message once it ends.
+    pub fn write_char(&mut self, val: u8) -> Option<String> {
+        if val != 0x00 {
+            if self.message.len() < MAX_MESSAGE {
+                self.message.push(val);
+            }
+            return None;
+        }
+
+        let message = std::mem::take(&mut self.message);
+        let text = self.format(&message);
+        self.args.clear();
+        Some(text)
+    }
+
+    /// Format the message with its arguments, missing arguments show up as ?.
+    fn format(&mut self, message: &[u8]) -> String {
+        let mut text = String::new();
+        let mut chars = message.iter().map(|&c| c as char);
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let spec = chars.next();
+            if spec == Some('%') {
+                text.push('%');
+                continue;
+            }
+            let arg = match spec {
+                Some('d' | 'x' | 'b' | 'c') => self.args.pop_front().map(u16::from),
+                Some('D' | 'X') => self.word(),
+                Some(other) => {
+                    text.push('%');
+                    text.push(other);
+                    continue;
+                }
+                None => {
+                    text.push('%');
+                    continue;
+                }
+            };
+            let Some(arg) = arg else {
+                text.push('?');
+                continue;
+            };
+            match spec {
+                Some('x') => text.push_str(&format!("{:02X}", arg)),
+                Some('X') => text.push_str(&format!("{:04X}", arg)),
+                Some('b') => text.push_str(&format!("{:08b}", arg)),
+                Some('c') => text.push(arg as u8 as char),
+                _ => text.push_str(&arg.to_string()),
+            }
+        }
+        text
+    }
+
+    /// Take a word argument, low byte first.
+    fn word(&mut self) -> Option<u16> {
+        let lo = self.args.pop_front()?;
+        let hi = self.args.pop_front()?;
+        Some(u16::from_le_bytes([lo, hi]))
+    }
+}
+
+impl Default for DebugPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,131 @@
+use crate::frontend;
+use crate::gb::GameBoy;
+use crate::ppu::{PpuAccuracy, SCREEN_HEIGHT, SCREEN_PIXELS, SCREEN_WIDTH};
+use log::warn;
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+/// Color of pixels that differ between the two renderers.
+const DIFF_COLOR: u32 = 0x00FF0000;
+
+/// PPU accuracy diff
+/// Runs the scanline renderer and the experimental pixel FIFO renderer in lockstep, and compares their frames.
+/// The FIFO doesn't draw the window or sprites, nor scroll horizontally by less than a tile, so those differ.
+/// Before every frame, the FIFO machine's state is copied into the scanline machine, so both render the frame
+/// from the exact same state, and a difference in timing can't snowball into unrelated differences later on.
+pub struct PpuDiff {
+    fifo: GameBoy,
+    scanline: GameBoy,
+
+    /// Pixels that differed in the last frame.
+    diff: Vec<bool>,
+}
+
+impl PpuDiff {
+    pub fn new(rom: Vec<u8>) -> Self {
+        let mut fifo = GameBoy::from_rom(rom.clone(), None);
+        let mut scanline = GameBoy::from_rom(rom, None);
+        fifo.set_ppu_accuracy(PpuAccuracy::Fifo);
+        scanline.set_ppu_accuracy(PpuAccuracy::Scanline);
+
+        // Only the FIFO machine prints serial output, the scanline machine replays the same frames.
+        scanline.set_serial_output(false);
+        Self {
+            fifo,
+            scanline,
+            diff: vec![false; SCREEN_PIXELS],
+        }
+    }
+
+    /// Run a frame on both renderers, from the same state.
+    /// Returns the number of pixels that differ.
+    pub fn step(&mut self) -> usize {
+        let state = self.fifo.save_state();
+        self.scanline
+            .load_state(&state)
+            .expect("Failed to copy the machine state to the scanline renderer");
+
+        self.fifo.run_frame();
+        self.scanline.run_frame();
+
+        let fifo = self.fifo.viewport();
+        let scanline = self.scanline.viewport();
+        for (i, diff) in self.diff.iter_mut().enumerate() {
+            *diff = fifo[i] != scanline[i];
+        }
+        self.diff.iter().filter(|&&d| d).count()
+    }
+
+    /// Draw the scanline frame, the FIFO frame, and the differences side by side into a 3x wide buffer.
+    /// Differing pixels are red, on top of a faded FIFO frame.
+    fn draw(&self, buffer: &mut [u32]) {
+        let panes = [
+            self.scanline.viewport(),
+            self.fifo.viewport(),
+            self.fifo
+                .viewport()
+                .iter()
+                .zip(&self.diff)
+                .map(|(&pixel, &diff)| {
+                    if diff {
+                        DIFF_COLOR
+                    } else {
+                        // Fade the frame, so the differences stand out.
+                        0x00C0C0C0 | ((pixel >> 2) & 0x003F3F3F)
+                    }
+                })
+                .collect(),
+        ];
+        for y in 0..SCREEN_HEIGHT {
+            for (n, pane) in panes.iter().enumerate() {
+                let row = y * SCREEN_WIDTH;
+                let out = y * SCREEN_WIDTH * 3 + n * SCREEN_WIDTH;
+                buffer[out..out + SCREEN_WIDTH].copy_from_slice(&pane[row..row + SCREEN_WIDTH]);
+            }
+        }
+    }
+
+    /// Open a window showing scanline | FIFO | differences, and run until it is closed.
+    /// Returns the frames with differences, and how many pixels differed in each.
+    /// Escape - Quit, P - Pause, N - Step a frame while paused
+    pub fn run(&mut self) -> Vec<(u64, usize)> {
+        let option = WindowOptions {
+            resize: false,
+            scale: minifb::Scale::X2,
+            ..Default::default()
+        };
+        let mut window = Window::new(
+            "ferrum - PPU diff (scanline | fifo | diff)",
+            SCREEN_WIDTH * 3,
+            SCREEN_HEIGHT,
+            option,
+        )
+        .unwrap();
+        window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+
+        let mut buffer: Vec<u32> = vec![0; SCREEN_PIXELS * 3];
+        let mut keymap = frontend::minifb::default_keymap();
+        let mut paused = false;
+        let mut differences = Vec::new();
+        while window.is_open() && !window.is_key_down(Key::Escape) {
+            let step = window.is_key_pressed(Key::N, KeyRepeat::Yes);
+            if window.is_key_pressed(Key::P, KeyRepeat::No) {
+                paused = !paused;
+            }
+
+            if !paused || step {
+                self.fifo.set_buttons(keymap.update(&window.get_keys()));
+                let differing = self.step();
+                if differing > 0 {
+                    differences.push((self.fifo.frame(), differing));
+                }
+                self.draw(&mut buffer);
+            }
+
+            if let Err(e) = window.update_with_buffer(&buffer, SCREEN_WIDTH * 3, SCREEN_HEIGHT) {
+                warn!("Failed to update the window: {}", e);
+                break;
+            }
+        }
+        differences
+    }
+}
@@ -0,0 +1,307 @@
+use crate::assets::BOOT_ROM_SIZE;
+use crate::data::DataDir;
+use crate::frontend;
+use crate::gb::{CpuState, GameBoy};
+use crate::model::Model;
+use crate::reftrace::{self, Outcome, TraceLine};
+use crate::testrom::{self, TestRom};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How a doctor check came out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+
+    /// ferrum runs, but something is off or missing, e.g. no sound.
+    Warn,
+
+    /// ferrum won't run as it normally does.
+    Fail,
+}
+
+/// What one check of the environment found.
+pub struct Finding {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Finding {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// e.g. "OK    audio      48000 Hz output on the default device"
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self.status {
+            Status::Ok => "OK",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        write!(f, "{:<5} {:<10} {}", status, self.name, self.detail)
+    }
+}
+
+fn window() -> Finding {
+    match frontend::minifb::probe() {
+        Ok(()) => Finding::new("window", Status::Ok, "a window opens"),
+        Err(e) => Finding::new(
+            "window",
+            Status::Fail,
+            format!(
+                "a window doesn't open ({}), --audio-only still plays without one",
+                e
+            ),
+        ),
+    }
+}
+
+#[cfg(feature = "cpal")]
+fn audio() -> Finding {
+    use crate::audio::AudioSink;
+    match crate::audio::CpalSink::new() {
+        Ok(sink) => Finding::new(
+            "audio",
+            Status::Ok,
+            format!("{} Hz output on the default device", sink.sample_rate()),
+        ),
+        Err(e) => Finding::new(
+            "audio",
+            Status::Warn,
+            format!("no audio output ({}), games play silently", e),
+        ),
+    }
+}
+
+#[cfg(not(feature = "cpal"))]
+fn audio() -> Finding {
+    Finding::new(
+        "audio",
+        Status::Warn,
+        "built without the cpal feature, games play silently",
+    )
+}
+
+/// The data directory can be created, and written to.
+fn data_dir(data_dir: &DataDir) -> Finding {
+    let root = data_dir.root();
+    let probe = root.join(".ferrum-doctor");
+    let written = fs::create_dir_all(root)
+        .and_then(|()| fs::write(&probe, b"ferrum"))
+        .and_then(|()| fs::remove_file(&probe));
+    match written {
+        Ok(()) => Finding::new(
+            "data dir",
+            Status::Ok,
+            format!("{} is writable", root.display()),
+        ),
+        Err(e) => Finding::new(
+            "data dir",
+            Status::Fail,
+            format!(
+                "{} isn't writable ({}), saves and states would be lost, see --data-dir",
+                root.display(),
+                e
+            ),
+        ),
+    }
+}
+
+/// Every game's config.toml parses.
+fn configs(data_dir: &DataDir) -> Finding {
+    let games = match data_dir.games() {
+        Ok(games) => games,
+        Err(e) => {
+            return Finding::new("configs", Status::Warn, format!("can't list games ({})", e))
+        }
+    };
+    let mut configs = 0;
+    let mut invalid = Vec::new();
+    for game in &games {
+        match game.read_config() {
+            Ok(Some(_)) => configs += 1,
+            Ok(None) => (),
+            // TOML errors quote the line, only where it is and what's wrong are kept.
+            Err(e) => invalid.push(format!(
+                "{}: {}, {}",
+                game.config_path().display(),
+                e.lines().next().unwrap_or_default(),
+                e.lines().last().unwrap_or_default()
+            )),
+        }
+    }
+    match invalid.is_empty() {
+        true => Finding::new(
+            "configs",
+            Status::Ok,
+            format!("{} games, {} config.toml, all valid", games.len(), configs),
+        ),
+        false => Finding::new(
+            "configs",
+            Status::Warn,
+            format!("ignored, as they're invalid: {}", invalid.join("; ")),
+        ),
+    }
+}
+
+/// A custom boot ROM, if one is given, is 256 bytes and looks like one.
+fn boot_rom(path: Option<&Path>) -> Finding {
+    let Some(path) = path else {
+        return Finding::new("boot ROM", Status::Ok, "built in");
+    };
+    match fs::read(path) {
+        Ok(rom) if rom.len() != BOOT_ROM_SIZE => Finding::new(
+            "boot ROM",
+            Status::Fail,
+            format!(
+                "{} is {} bytes, not {}, the built-in one would run",
+                path.display(),
+                rom.len(),
+                BOOT_ROM_SIZE
+            ),
+        ),
+        // Boot ROMs start by setting up the stack, LD SP,$FFFE.
+        Ok(rom) if rom[..3] != [0x31, 0xFE, 0xFF] => Finding::new(
+            "boot ROM",
+            Status::Warn,
+            format!(
+                "{} doesn't start like a boot ROM, LD SP,$FFFE",
+                path.display()
+            ),
+        ),
+        Ok(_) => Finding::new("boot ROM", Status::Ok, path.display().to_string()),
+        Err(e) => Finding::new(
+            "boot ROM",
+            Status::Fail,
+            format!("can't read {} ({})", path.display(), e),
+        ),
+    }
+}
+
+/// The CPU registers, and every IO register, as the machine starts the cartridge.
+fn post_boot(model: Model, skip: bool) -> (CpuState, Vec<u8>) {
+    let mut rom = TestRom::new("DOCTOR");
+    rom.end();
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_model(model);
+    if skip {
+        gb.skip_boot();
+    } else {
+        gb.finish_boot();
+    }
+    let io = (0xFF00..=0xFF7F).chain([0xFFFF]).map(|addr| gb.peek(addr));
+    (gb.cpu_state(), io.collect())
+}
+
+/// Running the model's boot ROM to the end leaves the documented registers, the ones skipping it starts with.
+/// Where the PPU is in its frame (LY, and the mode and coincidence bits of STAT) isn't compared,
+/// it depends on how long the boot ROM took, down to the dot, skipping it starts the frame over.
+fn boot_matches(model: Model) -> Result<(), String> {
+    let (booted, io) = post_boot(model, false);
+    let (skipped, skipped_io) = post_boot(model, true);
+    if booted != skipped {
+        return Err(format!(
+            "the boot ROM left {:04X?}, skipping it starts with {:04X?}",
+            booted, skipped
+        ));
+    }
+    (0xFF00..=0xFF7F)
+        .chain([0xFFFF])
+        .zip(io.into_iter().zip(skipped_io))
+        .filter(|&(addr, _)| addr != 0xFF44)
+        .map(|(addr, (read, skipped))| match addr {
+            0xFF41 => (addr, (read & !0x07, skipped & !0x07)),
+            _ => (addr, (read, skipped)),
+        })
+        .find(|(_, (read, skipped))| read != skipped)
+        .map_or(Ok(()), |(addr, (read, skipped))| {
+            Err(format!(
+                "${:04X} is ${:02X} after the boot ROM, ${:02X} skipping it",
+                addr, read, skipped
+            ))
+        })
+}
+
+/// Instructions traced by trace_matches.
+const TRACE_LINES: usize = 200;
+
+/// The demo cartridge, from its entry point.
+fn trace_machine() -> GameBoy {
+    let mut gb = GameBoy::from_rom(testrom::demo(), None);
+    gb.set_serial_output(false);
+    gb.skip_boot();
+    gb
+}
+
+/// A trace of ferrum running the demo cartridge matches a second run, line by line.
+fn trace_matches() -> Result<(), String> {
+    let mut gb = trace_machine();
+    let mut lines = Vec::new();
+    for _ in 0..TRACE_LINES {
+        lines.push(TraceLine::capture(&gb).to_string());
+        gb.step();
+    }
+    match reftrace::compare(&mut trace_machine(), lines.join("\n").as_bytes()) {
+        Ok(Outcome::Matched(TRACE_LINES)) => Ok(()),
+        Ok(Outcome::Matched(n)) => Err(format!("matched {} of {} lines", n, TRACE_LINES)),
+        Ok(Outcome::Diverged(d)) => Err(format!("diverged at line {}", d.line)),
+        Ok(Outcome::Malformed { line, error }) => Err(format!("line {}: {}", line, error)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A quick run of the CPU: the DMG boot ROM to the end, and a trace of itself.
+fn cpu() -> Finding {
+    match boot_matches(Model::Dmg)
+        .map_err(|e| format!("boot ROM: {}", e))
+        .and_then(|()| trace_matches().map_err(|e| format!("trace: {}", e)))
+    {
+        Ok(()) => Finding::new(
+            "CPU",
+            Status::Ok,
+            "the boot ROM and a trace ran as expected",
+        ),
+        Err(e) => Finding::new("CPU", Status::Fail, e),
+    }
+}
+
+/// Environment checks
+/// Whether ferrum can run here: a window, audio, the data directory and the configs in it,
+/// a custom boot ROM if one is used, and a quick run of the emulated CPU.
+/// Run them with `ferrum doctor`.
+pub fn examine(dir: &DataDir, boot: Option<&Path>) -> Vec<Finding> {
+    vec![
+        window(),
+        audio(),
+        data_dir(dir),
+        configs(dir),
+        boot_rom(boot),
+        cpu(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_rom_leaves_the_registers_skipping_it_starts_with() {
+        for model in [Model::Dmg0, Model::Dmg, Model::Mgb] {
+            assert_eq!(boot_matches(model), Ok(()), "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn cpu_runs() {
+        let finding = cpu();
+        assert_eq!(finding.status, Status::Ok, "{}", finding);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+/// Something a game did that a real Gameboy doesn't support, and a correct game shouldn't do.
+/// These are only logged normally, strict mode stops emulation on the first one,
+/// to catch bugs in homebrew (or gaps in the emulator) where they happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// One of the 11 unused opcodes, which lock up a real CPU.
+    IllegalOpcode {
+        pc: u16,
+        opcode: u8,
+    },
+
+    /// Access to $FEA0-$FEFF, which Nintendo prohibits.
+    ProhibitedRead(u16),
+    ProhibitedWrite(u16),
+
+    /// OAM DMA from $E000-$FFFF, which DMG can't read, it reads WRAM instead.
+    DmaSource(u16),
+
+    /// Access to memory other than HRAM and the IO registers during OAM DMA.
+    DmaConflict(u16),
+
+    /// Access to an address in $FF00-$FF7F with no IO register behind it (or none emulated yet).
+    UnknownIoRead(u16),
+    UnknownIoWrite(u16),
+
+    /// SP moved into ROM or the IO registers, where the stack can't work, usually a crashed game.
+    /// Only with the stack check, since some code points SP at ROM on purpose, to read tables with POP.
+    StackPointer {
+        pc: u16,
+        sp: u16,
+    },
+
+    /// A push (or an interrupt) wrote the stack over IE at $FFFF, only with the stack check.
+    StackOverIe {
+        pc: u16,
+    },
+}
+
+/// e.g. "Illegal op $D3 at $0150", short enough for a line of the OSD.
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::IllegalOpcode { pc, opcode } => {
+                write!(f, "Illegal op ${:02X} at ${:04X}", opcode, pc)
+            }
+            Fault::ProhibitedRead(addr) => write!(f, "Read of prohibited ${:04X}", addr),
+            Fault::ProhibitedWrite(addr) => write!(f, "Write to prohibited ${:04X}", addr),
+            Fault::DmaSource(addr) => write!(f, "DMA from prohibited ${:04X}", addr),
+            Fault::DmaConflict(addr) => write!(f, "Access to ${:04X} during DMA", addr),
+            Fault::UnknownIoRead(addr) => write!(f, "Read of unknown IO ${:04X}", addr),
+            Fault::UnknownIoWrite(addr) => write!(f, "Write to unknown IO ${:04X}", addr),
+            Fault::StackPointer { pc, sp } => write!(
+                f,
+                "SP in {} ${:04X} at ${:04X}",
+                if *sp <= 0x7FFF { "ROM" } else { "IO" },
+                sp,
+                pc
+            ),
+            Fault::StackOverIe { pc } => write!(f, "Stack pushed over IE at ${:04X}", pc),
+        }
+    }
+}
+
+/// What happens on a fault.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultPolicy {
+    /// Log it, and keep going, like a real Gameboy would (mostly).
+    #[default]
+    Ignore,
+
+    /// Stop the frame right after the faulting instruction, and pause, showing the fault on the OSD.
+    Pause,
+
+    /// Stop the frame right after the faulting instruction, and quit, for headless runs.
+    Exit,
+}
@@ -0,0 +1,178 @@
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use log::warn;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Frames kept for the graph, one column each.
+const HISTORY: usize = SCREEN_WIDTH;
+
+/// Height of the graph, in pixels. The top is two frame times, so the middle is a real Gameboy's frame time.
+const GRAPH_HEIGHT: usize = 32;
+
+/// A real Gameboy shows a frame every 70224 T-cycles, ~16.74 ms.
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 * 70224 / 4194304);
+
+/// Colors of the graph: emulation, presenting, the rest of the loop, and the frame time line.
+const EMULATE_COLOR: u32 = 0x0000C000;
+const PRESENT_COLOR: u32 = 0x004080FF;
+const OTHER_COLOR: u32 = 0x00A0A0A0;
+const LINE_COLOR: u32 = 0x00FF4040;
+
+/// Host time spent on one displayed frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameTime {
+    /// Emulating the frame(s), including run-ahead.
+    pub emulate: Duration,
+
+    /// Handing the frame to the video sink, which includes waiting for vsync (or the window's rate limit).
+    pub present: Duration,
+
+    /// The whole loop, from the start of this frame to the start of the next one.
+    pub total: Duration,
+}
+
+/// Frame time graph and stutter diagnostics
+/// Host timing of every displayed frame, to tell emulation that can't keep up apart from present stalls,
+/// when a game stutters. The last frames are kept for a graph drawn along the bottom of the screen:
+/// emulation in green, presenting in blue, the rest of the loop in grey, and a red line at a real Gameboy's frame time.
+/// Every frame can also be written to a CSV file:
+///
+/// frame,emulate_us,present_us,total_us
+/// 1,2210,14380,16702
+#[derive(Default)]
+pub struct FrameTimes {
+    history: VecDeque<FrameTime>,
+
+    /// Frames recorded so far.
+    frames: u64,
+
+    /// Frames that took longer than 1.5 frame times, in total, and when emulation alone did.
+    stutters: u64,
+    slow_emulation: u64,
+
+    /// Longest and summed frame times, for the summary.
+    max: Duration,
+    sum: FrameTime,
+
+    csv: Option<BufWriter<File>>,
+}
+
+impl FrameTimes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also write every frame to a CSV file.
+    pub fn set_csv(&mut self, path: &Path) -> io::Result<()> {
+        let mut csv = BufWriter::new(File::create(path)?);
+        writeln!(csv, "frame,emulate_us,present_us,total_us")?;
+        self.csv = Some(csv);
+        Ok(())
+    }
+
+    /// Add the timing of a displayed frame.
+    pub fn record(&mut self, time: FrameTime) {
+        self.frames += 1;
+        if time.total > FRAME_TIME * 3 / 2 {
+            self.stutters += 1;
+            if time.emulate > FRAME_TIME {
+                self.slow_emulation += 1;
+            }
+        }
+        self.max = self.max.max(time.total);
+        self.sum.emulate += time.emulate;
+        self.sum.present += time.present;
+        self.sum.total += time.total;
+
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(time);
+
+        if let Some(csv) = &mut self.csv {
+            if let Err(e) = writeln!(
+                csv,
+                "{},{},{},{}",
+                self.frames,
+                time.emulate.as_micros(),
+                time.present.as_micros(),
+                time.total.as_micros()
+            ) {
+                warn!("Failed to write frame times, stopping: {}", e);
+                self.csv = None;
+            }
+        }
+    }
+
+    /// Draw the graph of the last frames along the bottom of a 160x144 frame, newest on the right.
+    pub fn draw(&self, frame: &mut [u32]) {
+        let top = SCREEN_HEIGHT - GRAPH_HEIGHT;
+        let scale = (FRAME_TIME * 2).as_secs_f64() / GRAPH_HEIGHT as f64;
+        let height = |d: Duration| ((d.as_secs_f64() / scale).round() as usize).min(GRAPH_HEIGHT);
+
+        // Darken the game behind the graph, so the bars stand out.
+        for pixel in &mut frame[top * SCREEN_WIDTH..] {
+            *pixel = (*pixel >> 2) & 0x003F3F3F;
+        }
+
+        let x0 = SCREEN_WIDTH - self.history.len();
+        for (i, time) in self.history.iter().enumerate() {
+            let emulate = height(time.emulate);
+            let present = height(time.emulate + time.present);
+            let total = height(time.total);
+            for h in 0..total.max(present) {
+                let color = if h < emulate {
+                    EMULATE_COLOR
+                } else if h < present {
+                    PRESENT_COLOR
+                } else {
+                    OTHER_COLOR
+                };
+                frame[(SCREEN_HEIGHT - 1 - h) * SCREEN_WIDTH + x0 + i] = color;
+            }
+        }
+
+        let line = SCREEN_HEIGHT - 1 - GRAPH_HEIGHT / 2;
+        for x in (0..SCREEN_WIDTH).step_by(2) {
+            frame[line * SCREEN_WIDTH + x] = LINE_COLOR;
+        }
+    }
+
+    /// Is every frame written to a CSV file?
+    pub fn is_recording(&self) -> bool {
+        self.csv.is_some()
+    }
+
+    /// Flush the CSV file, if any.
+    pub fn flush(&mut self) {
+        if let Some(csv) = &mut self.csv {
+            if let Err(e) = csv.flush() {
+                warn!("Failed to write frame times: {}", e);
+            }
+        }
+    }
+}
+
+/// e.g. "3600 frames, avg 16.74 ms (emulate 2.10 ms, present 14.52 ms), max 50.12 ms,
+///       4 stutters, 1 with slow emulation"
+impl fmt::Display for FrameTimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frames = self.frames.max(1) as u32;
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{} frames, avg {:.2} ms (emulate {:.2} ms, present {:.2} ms), max {:.2} ms, {} stutters, {} with slow emulation",
+            self.frames,
+            ms(self.sum.total / frames),
+            ms(self.sum.emulate / frames),
+            ms(self.sum.present / frames),
+            ms(self.max),
+            self.stutters,
+            self.slow_emulation
+        )
+    }
+}
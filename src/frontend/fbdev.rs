@@ -0,0 +1,553 @@
+use super::{Hotkey, InputSource, PixelFormat, VideoSink};
+use crate::input::{Binding, Chord, HotkeyMap, InputMap, Modifiers};
+use crate::joypad::Buttons;
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_PIXELS, SCREEN_WIDTH};
+use log::{info, warn};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::raw::c_long;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// O_NONBLOCK on Linux, reads from an input device return WouldBlock instead of waiting for an event.
+const O_NONBLOCK: i32 = 0o4000;
+
+/// struct input_event: a timeval (two longs), then the type and code (u16s), and the value (i32).
+const EVENT_SIZE: usize = 2 * std::mem::size_of::<c_long>() + 8;
+
+/// Event types and the absolute axes of a D-pad reported as a hat, from linux/input-event-codes.h.
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+/// A key or button code of an evdev device, e.g. 45 for X, or 0x130 for a controller's south face button.
+/// Formats as its name in key bindings files, see parse_key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EvKey(pub u16);
+
+/// Key names, as minifb names the same keys where it has them, and controller buttons, as Linux names them.
+const KEYS: [(&str, u16); 97] = [
+    ("ESCAPE", 1),
+    ("1", 2),
+    ("2", 3),
+    ("3", 4),
+    ("4", 5),
+    ("5", 6),
+    ("6", 7),
+    ("7", 8),
+    ("8", 9),
+    ("9", 10),
+    ("0", 11),
+    ("MINUS", 12),
+    ("EQUAL", 13),
+    ("BACKSPACE", 14),
+    ("TAB", 15),
+    ("Q", 16),
+    ("W", 17),
+    ("E", 18),
+    ("R", 19),
+    ("T", 20),
+    ("Y", 21),
+    ("U", 22),
+    ("I", 23),
+    ("O", 24),
+    ("P", 25),
+    ("LEFTBRACKET", 26),
+    ("RIGHTBRACKET", 27),
+    ("ENTER", 28),
+    ("LEFTCTRL", 29),
+    ("A", 30),
+    ("S", 31),
+    ("D", 32),
+    ("F", 33),
+    ("G", 34),
+    ("H", 35),
+    ("J", 36),
+    ("K", 37),
+    ("L", 38),
+    ("SEMICOLON", 39),
+    ("APOSTROPHE", 40),
+    ("BACKQUOTE", 41),
+    ("LEFTSHIFT", 42),
+    ("BACKSLASH", 43),
+    ("Z", 44),
+    ("X", 45),
+    ("C", 46),
+    ("V", 47),
+    ("B", 48),
+    ("N", 49),
+    ("M", 50),
+    ("COMMA", 51),
+    ("PERIOD", 52),
+    ("SLASH", 53),
+    ("RIGHTSHIFT", 54),
+    ("LEFTALT", 56),
+    ("SPACE", 57),
+    ("F1", 59),
+    ("F2", 60),
+    ("F3", 61),
+    ("F4", 62),
+    ("F5", 63),
+    ("F6", 64),
+    ("F7", 65),
+    ("F8", 66),
+    ("F9", 67),
+    ("F10", 68),
+    ("NUMPADMINUS", 74),
+    ("NUMPADPLUS", 78),
+    ("F11", 87),
+    ("F12", 88),
+    ("RIGHTCTRL", 97),
+    ("RIGHTALT", 100),
+    ("HOME", 102),
+    ("UP", 103),
+    ("PAGEUP", 104),
+    ("LEFT", 105),
+    ("RIGHT", 106),
+    ("END", 107),
+    ("DOWN", 108),
+    ("PAGEDOWN", 109),
+    ("INSERT", 110),
+    ("DELETE", 111),
+    ("BTN_SOUTH", 0x130),
+    ("BTN_EAST", 0x131),
+    ("BTN_NORTH", 0x133),
+    ("BTN_WEST", 0x134),
+    ("BTN_TL", 0x136),
+    ("BTN_TR", 0x137),
+    ("BTN_TL2", 0x138),
+    ("BTN_TR2", 0x139),
+    ("BTN_SELECT", 0x13A),
+    ("BTN_START", 0x13B),
+    ("BTN_MODE", 0x13C),
+    ("BTN_DPAD_UP", 0x220),
+    ("BTN_DPAD_DOWN", 0x221),
+    ("BTN_DPAD_LEFT", 0x222),
+    ("BTN_DPAD_RIGHT", 0x223),
+];
+
+impl EvKey {
+    fn named(name: &str) -> Self {
+        parse_key(name).expect("a key in KEYS")
+    }
+}
+
+/// e.g. "X", "BTN_SOUTH", or "KEY_240" for a key without a name.
+impl fmt::Debug for EvKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match KEYS.iter().find(|(_, code)| *code == self.0) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => write!(f, "KEY_{}", self.0),
+        }
+    }
+}
+
+/// Parse the name of a key or controller button, e.g. "X", "Up", "BTN_START", or a code, "KEY_240".
+pub fn parse_key(name: &str) -> Option<EvKey> {
+    let name = name.to_ascii_uppercase();
+    if let Some(code) = name.strip_prefix("KEY_").and_then(|code| code.parse().ok()) {
+        return Some(EvKey(code));
+    }
+    KEYS.iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, code)| EvKey(*code))
+}
+
+/// Default bindings, for a keyboard, and a controller (or a handheld's buttons wired up as one).
+/// Keyboard: Arrow keys - D-Pad, X - A, Z - B, Backspace - Select, Enter - Start, S - Turbo A, A - Turbo B
+/// Controller: D-pad - D-Pad, East - A, South - B, Select - Select, Start - Start, North - Turbo A, West - Turbo B
+pub fn default_keymap() -> InputMap<EvKey> {
+    let mut keymap = InputMap::new();
+    for (key, binding) in [
+        ("RIGHT", Binding::Button(Buttons::RIGHT)),
+        ("LEFT", Binding::Button(Buttons::LEFT)),
+        ("UP", Binding::Button(Buttons::UP)),
+        ("DOWN", Binding::Button(Buttons::DOWN)),
+        ("X", Binding::Button(Buttons::A)),
+        ("Z", Binding::Button(Buttons::B)),
+        ("BACKSPACE", Binding::Button(Buttons::SELECT)),
+        ("ENTER", Binding::Button(Buttons::START)),
+        ("S", Binding::Turbo(Buttons::A)),
+        ("A", Binding::Turbo(Buttons::B)),
+        ("BTN_DPAD_RIGHT", Binding::Button(Buttons::RIGHT)),
+        ("BTN_DPAD_LEFT", Binding::Button(Buttons::LEFT)),
+        ("BTN_DPAD_UP", Binding::Button(Buttons::UP)),
+        ("BTN_DPAD_DOWN", Binding::Button(Buttons::DOWN)),
+        ("BTN_EAST", Binding::Button(Buttons::A)),
+        ("BTN_SOUTH", Binding::Button(Buttons::B)),
+        ("BTN_SELECT", Binding::Button(Buttons::SELECT)),
+        ("BTN_START", Binding::Button(Buttons::START)),
+        ("BTN_NORTH", Binding::Turbo(Buttons::A)),
+        ("BTN_WEST", Binding::Turbo(Buttons::B)),
+    ] {
+        keymap.bind(EvKey::named(key), binding);
+    }
+    keymap
+}
+
+/// Default hotkeys.
+/// Keyboard: Escape - Quit, P - Pause, F5 - Save state, F6/F7 - Previous/next state slot, F9 - Load state,
+/// +/- - Volume up/down, M - Mute, Tab - Fast-forward, F4 - Show the emulated time counters, F3 - Show the frame
+/// time graph, C - Next palette
+/// Controller: Mode (home) - Quit, TR - Save state, TL - Load state, TR2 - Fast-forward, TL2 - Pause
+pub fn default_hotkeys() -> HotkeyMap<EvKey> {
+    let mut hotkeys = HotkeyMap::new();
+    for (key, hotkey) in [
+        ("ESCAPE", Hotkey::Quit),
+        ("P", Hotkey::Pause),
+        ("F5", Hotkey::SaveState),
+        ("F6", Hotkey::PrevStateSlot),
+        ("F7", Hotkey::NextStateSlot),
+        ("F9", Hotkey::LoadState),
+        ("EQUAL", Hotkey::VolumeUp),
+        ("NUMPADPLUS", Hotkey::VolumeUp),
+        ("MINUS", Hotkey::VolumeDown),
+        ("NUMPADMINUS", Hotkey::VolumeDown),
+        ("M", Hotkey::Mute),
+        ("TAB", Hotkey::FastForward),
+        ("F4", Hotkey::Counters),
+        ("F3", Hotkey::FrameTimes),
+        ("C", Hotkey::NextPalette),
+        ("BTN_MODE", Hotkey::Quit),
+        ("BTN_TR", Hotkey::SaveState),
+        ("BTN_TL", Hotkey::LoadState),
+        ("BTN_TR2", Hotkey::FastForward),
+        ("BTN_TL2", Hotkey::Pause),
+    ] {
+        hotkeys.bind(Chord::key(EvKey::named(key)), hotkey);
+    }
+    hotkeys
+}
+
+/// Open the framebuffer device, e.g. /dev/fb0, and every evdev input device.
+/// The framebuffer doesn't wait for vsync, so frames are paced to refresh_rate with a timer.
+pub fn open(
+    device: &Path,
+    refresh_rate: f64,
+    keymap: InputMap<EvKey>,
+    hotkeys: HotkeyMap<EvKey>,
+) -> io::Result<(FbdevVideo, EvdevInput)> {
+    let video = FbdevVideo::open(device, refresh_rate)?;
+    let input = EvdevInput::open(keymap, hotkeys)?;
+    Ok((video, input))
+}
+
+/// Read a line of a framebuffer's attributes in sysfs, e.g. bits_per_pixel.
+fn read_attr(sysfs: &Path, attr: &str) -> io::Result<String> {
+    fs::read_to_string(sysfs.join(attr)).map(|text| text.trim().to_string())
+}
+
+fn parse_attr<T: std::str::FromStr>(text: &str, attr: &str) -> io::Result<T> {
+    text.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected {} `{}`", attr, text),
+        )
+    })
+}
+
+/// Frames shown on the Linux framebuffer, without a desktop, e.g. on a Raspberry Pi handheld.
+/// The screen is scaled up by the largest integer factor that fits the display, and centered.
+/// 16 bit framebuffers are taken as RGB565, 32 bit ones as XRGB8888, what Linux's drivers (the Pi's included) use.
+pub struct FbdevVideo {
+    fb: File,
+    format: PixelFormat,
+
+    /// Size of the display in pixels, and of a line of the framebuffer in bytes, which can be padded.
+    width: usize,
+    height: usize,
+    stride: usize,
+
+    /// Width of the frames last shown, the border around them is cleared when it changes.
+    frame_width: usize,
+
+    /// When the next frame is due, and the time between frames.
+    next: Instant,
+    frame_time: Duration,
+
+    /// A line of the frame, scaled up.
+    line: Vec<u8>,
+}
+
+impl FbdevVideo {
+    /// Open a framebuffer device, finding its size and pixel format in sysfs.
+    pub fn open(device: &Path, refresh_rate: f64) -> io::Result<Self> {
+        let name = device.file_name().unwrap_or_default();
+        let sysfs = PathBuf::from("/sys/class/graphics").join(name);
+        let size = read_attr(&sysfs, "virtual_size")?;
+        let (width, height) = size.split_once(',').unwrap_or((&size, ""));
+        let (width, height) = (
+            parse_attr(width, "virtual_size")?,
+            parse_attr(height, "virtual_size")?,
+        );
+        let bits: u32 = parse_attr(&read_attr(&sysfs, "bits_per_pixel")?, "bits_per_pixel")?;
+        let format = match bits {
+            16 => PixelFormat::Rgb565,
+            32 => PixelFormat::Bgra8888,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "{} bit framebuffers aren't supported, only 16 and 32 bit",
+                        bits
+                    ),
+                ))
+            }
+        };
+        let stride = parse_attr(&read_attr(&sysfs, "stride")?, "stride")?;
+        let fb = OpenOptions::new().read(true).write(true).open(device)?;
+        info!(
+            "Framebuffer {}: {}x{}, {} bit",
+            device.display(),
+            width,
+            height,
+            bits
+        );
+
+        // The console's cursor would blink on top of the screen.
+        if io::stdout().is_terminal() {
+            print!("\x1b[?25l");
+            let _ = io::stdout().flush();
+        }
+        let mut video = Self {
+            fb,
+            format,
+            width,
+            height,
+            stride,
+            frame_width: 0,
+            next: Instant::now(),
+            frame_time: Duration::from_secs_f64(1.0 / refresh_rate),
+            line: Vec::new(),
+        };
+        video.clear()?;
+        Ok(video)
+    }
+
+    /// Fill the framebuffer with black.
+    fn clear(&mut self) -> io::Result<()> {
+        self.fb.write_all_at(&vec![0; self.stride * self.height], 0)
+    }
+
+    /// Wait until the next frame is due.
+    fn pace(&mut self) {
+        self.next += self.frame_time;
+        let now = Instant::now();
+        if self.next > now {
+            std::thread::sleep(self.next - now);
+        } else {
+            // Running behind, don't try to catch up with a burst of frames.
+            self.next = now;
+        }
+    }
+}
+
+impl Drop for FbdevVideo {
+    fn drop(&mut self) {
+        let _ = self.clear();
+        if io::stdout().is_terminal() {
+            print!("\x1b[?25h");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+impl VideoSink for FbdevVideo {
+    fn frame(&mut self, frame: &[u32; SCREEN_PIXELS]) {
+        let mut encoded = Vec::new();
+        self.format.encode(frame, &mut encoded);
+        self.encoded_frame(&encoded, SCREEN_WIDTH);
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn encoded_frame(&mut self, pixels: &[u8], width: usize) {
+        if width != self.frame_width {
+            if let Err(e) = self.clear() {
+                warn!("Failed to clear the framebuffer: {}", e);
+            }
+            self.frame_width = width;
+        }
+        let bytes = self.format.bytes_per_pixel();
+        let scale = (self.width / width).min(self.height / SCREEN_HEIGHT).max(1);
+
+        // A display smaller than the frame shows its top left.
+        let left = self.width.saturating_sub(width * scale) / 2;
+        let top = self.height.saturating_sub(SCREEN_HEIGHT * scale) / 2;
+        let visible = (width * scale).min(self.width) * bytes;
+        for (y, row) in pixels.chunks_exact(width * bytes).enumerate() {
+            self.line.clear();
+            for pixel in row.chunks_exact(bytes) {
+                for _ in 0..scale {
+                    self.line.extend_from_slice(pixel);
+                }
+            }
+            for dy in 0..scale {
+                let line = top + y * scale + dy;
+                if line >= self.height {
+                    break;
+                }
+                let offset = line * self.stride + left * bytes;
+                if let Err(e) = self.fb.write_all_at(&self.line[..visible], offset as u64) {
+                    warn!("Failed to write to the framebuffer: {}", e);
+                    return;
+                }
+            }
+        }
+        self.pace();
+    }
+
+    fn refresh_rate(&self) -> Option<f64> {
+        Some(1.0 / self.frame_time.as_secs_f64())
+    }
+}
+
+/// Keyboards and controllers, read straight from their evdev devices, /dev/input/event*, without a desktop.
+/// Reading them needs the input group (or root). Devices are found when opened, so plug them in first.
+/// A D-pad reported as a hat presses the BTN_DPAD_* buttons.
+pub struct EvdevInput {
+    devices: Vec<(PathBuf, File)>,
+    keymap: InputMap<EvKey>,
+    hotkeys: HotkeyMap<EvKey>,
+
+    /// Keys held down, and pressed since hotkeys were last read.
+    held: Vec<EvKey>,
+    pressed: Vec<EvKey>,
+
+    /// Position of the hat, -1 to 1 on each axis.
+    hat: (i32, i32),
+}
+
+impl EvdevInput {
+    /// Open every input device that can be read.
+    pub fn open(keymap: InputMap<EvKey>, hotkeys: HotkeyMap<EvKey>) -> io::Result<Self> {
+        let mut devices = Vec::new();
+        for entry in fs::read_dir("/dev/input")? {
+            let path = entry?.path();
+            let is_event = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"));
+            if !is_event {
+                continue;
+            }
+            match OpenOptions::new()
+                .read(true)
+                .custom_flags(O_NONBLOCK)
+                .open(&path)
+            {
+                Ok(device) => devices.push((path, device)),
+                Err(e) => warn!("Failed to open {}: {}", path.display(), e),
+            }
+        }
+        if devices.is_empty() {
+            warn!("No input devices could be opened, is the user in the input group?");
+        }
+        Ok(Self {
+            devices,
+            keymap,
+            hotkeys,
+            held: Vec::new(),
+            pressed: Vec::new(),
+            hat: (0, 0),
+        })
+    }
+
+    /// Read the events waiting on every device. Devices that went away, e.g. unplugged, are dropped.
+    fn read_events(&mut self) {
+        let mut buffer = [0u8; EVENT_SIZE * 64];
+        let mut events = Vec::new();
+        self.devices.retain_mut(|(path, device)| loop {
+            match device.read(&mut buffer) {
+                Ok(0) => return true,
+                Ok(read) => events.extend(buffer[..read].chunks_exact(EVENT_SIZE).map(|event| {
+                    let event = &event[EVENT_SIZE - 8..];
+                    (
+                        u16::from_ne_bytes([event[0], event[1]]),
+                        u16::from_ne_bytes([event[2], event[3]]),
+                        i32::from_ne_bytes([event[4], event[5], event[6], event[7]]),
+                    )
+                })),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) => {
+                    warn!("Stopped reading {}: {}", path.display(), e);
+                    return false;
+                }
+            }
+        });
+        for (kind, code, value) in events {
+            match (kind, code, value) {
+                (EV_KEY, code, 1) => {
+                    if !self.held.contains(&EvKey(code)) {
+                        self.held.push(EvKey(code));
+                    }
+                    self.pressed.push(EvKey(code));
+                }
+                (EV_KEY, code, 0) => self.held.retain(|key| *key != EvKey(code)),
+                (EV_ABS, ABS_HAT0X, value) => self.hat.0 = value.signum(),
+                (EV_ABS, ABS_HAT0Y, value) => self.hat.1 = value.signum(),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl InputSource for EvdevInput {
+    fn poll(&mut self) -> Buttons {
+        self.read_events();
+        let mut held = self.held.clone();
+        for (axis, negative, positive) in [
+            (self.hat.0, "BTN_DPAD_LEFT", "BTN_DPAD_RIGHT"),
+            (self.hat.1, "BTN_DPAD_UP", "BTN_DPAD_DOWN"),
+        ] {
+            match axis {
+                -1 => held.push(EvKey::named(negative)),
+                1 => held.push(EvKey::named(positive)),
+                _ => (),
+            }
+        }
+        self.keymap.update(&held)
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        self.read_events();
+        let mut modifiers = Modifiers::empty();
+        for key in &self.held {
+            // Left and right Ctrl, Shift, and Alt.
+            modifiers |= match key.0 {
+                29 | 97 => Modifiers::CTRL,
+                42 | 54 => Modifiers::SHIFT,
+                56 | 100 => Modifiers::ALT,
+                _ => Modifiers::empty(),
+            };
+        }
+        let pressed = std::mem::take(&mut self.pressed);
+        self.hotkeys.resolve(&pressed, modifiers)
+    }
+
+    /// Hotkeys can be bound to chords, e.g. "Ctrl+S", Joypad buttons to keys and controller buttons.
+    fn rebind(&mut self, bindings: &[(String, Binding)]) {
+        for (name, binding) in bindings {
+            match binding {
+                Binding::Hotkey(hotkey) => match Chord::parse(name, parse_key) {
+                    Ok(chord) => self.hotkeys.rebind(chord, *hotkey),
+                    Err(e) => warn!("Ignoring the {} hotkey: {}", hotkey.name(), e),
+                },
+                _ => match parse_key(name) {
+                    Some(key) => self.keymap.rebind(key, *binding),
+                    None => warn!("Ignoring unknown key `{}`", name),
+                },
+            }
+        }
+    }
+
+    fn conflicts(&self) -> Vec<String> {
+        let joypad: Vec<EvKey> = self.keymap.inputs().into_iter().copied().collect();
+        self.hotkeys.conflicts(&joypad)
+    }
+}
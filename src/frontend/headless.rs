@@ -0,0 +1,139 @@
+use super::{Hotkey, InputSource, VideoSink};
+use crate::joypad::Buttons;
+use crate::ppu::SCREEN_PIXELS;
+use log::warn;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// A real Gameboy shows a frame every 70224 T-cycles, ~59.73 times per second.
+pub(super) const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 * 70224 / 4194304);
+
+/// Frames a button typed on the console is held down for, long enough for games polling every few frames.
+const PRESS_FRAMES: u32 = 6;
+
+/// Frames aren't shown anywhere, but emulation is paced to the speed of a real Gameboy, e.g. to play its music.
+pub struct PacedVideo {
+    /// When the next frame is due.
+    next: Instant,
+}
+
+impl PacedVideo {
+    pub fn new() -> Self {
+        Self {
+            next: Instant::now(),
+        }
+    }
+}
+
+impl Default for PacedVideo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VideoSink for PacedVideo {
+    fn refresh_rate(&self) -> Option<f64> {
+        Some(1.0 / FRAME_TIME.as_secs_f64())
+    }
+
+    fn frame(&mut self, _frame: &[u32; SCREEN_PIXELS]) {
+        self.next += FRAME_TIME;
+        let now = Instant::now();
+        if self.next > now {
+            std::thread::sleep(self.next - now);
+        } else {
+            // Running behind, don't try to catch up with a burst of frames.
+            self.next = now;
+        }
+    }
+}
+
+/// Joypad presses typed on the console, one command per line:
+///     n, next     Press Right, the next track in most sound tests
+///     p, prev     Press Left, the previous track
+///     <button>    Press a button: a, b, start, select, up, down, left, or right
+///     q, quit     Quit
+/// Each press holds the button down for a few frames, then releases it.
+pub struct ConsoleInput {
+    lines: Receiver<String>,
+
+    /// Button being pressed, and for how many more frames.
+    press: Option<(Buttons, u32)>,
+    quit: bool,
+}
+
+impl ConsoleInput {
+    /// Start reading commands from stdin.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            lines: rx,
+            press: None,
+            quit: false,
+        }
+    }
+
+    /// Run the commands typed since the last call.
+    fn read_commands(&mut self) {
+        // Once stdin is closed (or if it isn't a console), this just keeps playing.
+        while let Ok(line) = self.lines.try_recv() {
+            let command = line.trim().to_ascii_lowercase();
+            let button = match command.as_str() {
+                "" => continue,
+                "q" | "quit" => {
+                    self.quit = true;
+                    continue;
+                }
+                "n" | "next" => Some(Buttons::RIGHT),
+                "p" | "prev" => Some(Buttons::LEFT),
+                name => Buttons::from_name(&name.to_ascii_uppercase()),
+            };
+            match button {
+                Some(button) => self.press = Some((button, PRESS_FRAMES)),
+                None => warn!("Unknown command `{}`", command),
+            }
+        }
+    }
+}
+
+impl Default for ConsoleInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for ConsoleInput {
+    fn poll(&mut self) -> Buttons {
+        self.read_commands();
+        match &mut self.press {
+            Some((button, frames)) if *frames > 0 => {
+                *frames -= 1;
+                *button
+            }
+            _ => {
+                self.press = None;
+                Buttons::empty()
+            }
+        }
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        self.read_commands();
+        if self.quit {
+            vec![Hotkey::Quit]
+        } else {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,709 @@
+use super::{Hotkey, InputSource, Status, VideoSink};
+use crate::input::{Binding, Chord, HotkeyMap, InputMap, Modifiers};
+use crate::joypad::Buttons;
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_PIXELS, SCREEN_WIDTH};
+use log::warn;
+use minifb::{Key, KeyRepeat, MouseMode, Scale, Window, WindowOptions};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Default keyboard bindings.
+/// Arrow keys - D-Pad, X - A, Z - B, Backspace - Select, Enter - Start, S - Turbo A, A - Turbo B
+pub fn default_keymap() -> InputMap<Key> {
+    let mut keymap = InputMap::new();
+    keymap.bind(Key::Right, Binding::Button(Buttons::RIGHT));
+    keymap.bind(Key::Left, Binding::Button(Buttons::LEFT));
+    keymap.bind(Key::Up, Binding::Button(Buttons::UP));
+    keymap.bind(Key::Down, Binding::Button(Buttons::DOWN));
+    keymap.bind(Key::X, Binding::Button(Buttons::A));
+    keymap.bind(Key::Z, Binding::Button(Buttons::B));
+    keymap.bind(Key::Backspace, Binding::Button(Buttons::SELECT));
+    keymap.bind(Key::Enter, Binding::Button(Buttons::START));
+    keymap.bind(Key::S, Binding::Turbo(Buttons::A));
+    keymap.bind(Key::A, Binding::Turbo(Buttons::B));
+    keymap
+}
+
+/// Default hotkeys.
+/// Escape - Quit, P - Pause, F5 - Save state, F6/F7 - Previous/next state slot, F8 - Export maps, F9 - Load state,
+/// +/- - Volume up/down, M - Mute, F10 - Show the RTC, F11 - Change the RTC speed, F12 - Change the scale,
+/// F4 - Show the emulated time counters, F3 - Show the frame time graph, F2 - Show the OAM viewer,
+/// [/] - Previous/next sprite in the OAM viewer, I - Show the IO viewer, C - Next palette, L - Interlace,
+/// R/Shift+R - Reset to/set the race anchor, Tab - Fast-forward,
+/// F1 - Reload the boot ROM, palette, and key bindings
+pub fn default_hotkeys() -> HotkeyMap<Key> {
+    let mut hotkeys = HotkeyMap::new();
+    for (key, hotkey) in [
+        (Key::Escape, Hotkey::Quit),
+        (Key::P, Hotkey::Pause),
+        (Key::F5, Hotkey::SaveState),
+        (Key::F6, Hotkey::PrevStateSlot),
+        (Key::F7, Hotkey::NextStateSlot),
+        (Key::F8, Hotkey::ExportMaps),
+        (Key::F9, Hotkey::LoadState),
+        (Key::Equal, Hotkey::VolumeUp),
+        (Key::NumPadPlus, Hotkey::VolumeUp),
+        (Key::Minus, Hotkey::VolumeDown),
+        (Key::NumPadMinus, Hotkey::VolumeDown),
+        (Key::M, Hotkey::Mute),
+        (Key::F10, Hotkey::ShowRtc),
+        (Key::F11, Hotkey::RtcSpeed),
+        (Key::F12, Hotkey::Scale),
+        (Key::F4, Hotkey::Counters),
+        (Key::F3, Hotkey::FrameTimes),
+        (Key::F2, Hotkey::OamViewer),
+        (Key::I, Hotkey::IoViewer),
+        (Key::F1, Hotkey::ReloadAssets),
+        (Key::C, Hotkey::NextPalette),
+        (Key::L, Hotkey::Interlace),
+        (Key::R, Hotkey::RaceReset),
+        (Key::Tab, Hotkey::FastForward),
+        (Key::LeftBracket, Hotkey::PrevSprite),
+        (Key::RightBracket, Hotkey::NextSprite),
+    ] {
+        hotkeys.bind(Chord::key(key), hotkey);
+    }
+    hotkeys.bind(
+        Chord {
+            modifiers: Modifiers::SHIFT,
+            key: Key::R,
+        },
+        Hotkey::RaceAnchor,
+    );
+    hotkeys
+}
+
+/// Application icon, a Gameboy, one character per pixel.
+/// # - case, o - screen border, = - screen, + - buttons, . - transparent
+#[cfg(all(unix, not(target_os = "macos")))]
+const ICON: [&str; 16] = [
+    "..############..",
+    ".##############.",
+    ".#oooooooooooo#.",
+    ".#o==========o#.",
+    ".#o==========o#.",
+    ".#o==========o#.",
+    ".#o==========o#.",
+    ".#o==========o#.",
+    ".#oooooooooooo#.",
+    ".##############.",
+    ".#+####+###++##.",
+    ".+++####++#++##.",
+    ".#+####++######.",
+    ".##############.",
+    ".####+#+#######.",
+    "..############..",
+];
+
+/// The icon as ARGB pixels, in X11's _NET_WM_ICON layout (width, height, then the pixels).
+#[cfg(all(unix, not(target_os = "macos")))]
+fn icon() -> Vec<u64> {
+    let mut icon = vec![ICON[0].len() as u64, ICON.len() as u64];
+    icon.extend(ICON.iter().flat_map(|row| {
+        row.chars().map(|c| match c {
+            '#' => 0xFFC0C0B8,
+            'o' => 0xFF505060,
+            '=' => 0xFF8BAC0F,
+            '+' => 0xFF8B1D50,
+            _ => 0x00000000,
+        })
+    }));
+    icon
+}
+
+/// Parse the name of a key, e.g. "F12", "Tab", "Q", "Up", or "1".
+pub fn parse_key(name: &str) -> Option<Key> {
+    let key = match name.to_ascii_uppercase().as_str() {
+        "A" => Key::A,
+        "B" => Key::B,
+        "C" => Key::C,
+        "D" => Key::D,
+        "E" => Key::E,
+        "F" => Key::F,
+        "G" => Key::G,
+        "H" => Key::H,
+        "I" => Key::I,
+        "J" => Key::J,
+        "K" => Key::K,
+        "L" => Key::L,
+        "M" => Key::M,
+        "N" => Key::N,
+        "O" => Key::O,
+        "P" => Key::P,
+        "Q" => Key::Q,
+        "R" => Key::R,
+        "S" => Key::S,
+        "T" => Key::T,
+        "U" => Key::U,
+        "V" => Key::V,
+        "W" => Key::W,
+        "X" => Key::X,
+        "Y" => Key::Y,
+        "Z" => Key::Z,
+        "0" => Key::Key0,
+        "1" => Key::Key1,
+        "2" => Key::Key2,
+        "3" => Key::Key3,
+        "4" => Key::Key4,
+        "5" => Key::Key5,
+        "6" => Key::Key6,
+        "7" => Key::Key7,
+        "8" => Key::Key8,
+        "9" => Key::Key9,
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        "TAB" => Key::Tab,
+        "BACKQUOTE" => Key::Backquote,
+        "INSERT" => Key::Insert,
+        "DELETE" => Key::Delete,
+        "HOME" => Key::Home,
+        "END" => Key::End,
+        "PAGEUP" => Key::PageUp,
+        "PAGEDOWN" => Key::PageDown,
+        "UP" => Key::Up,
+        "DOWN" => Key::Down,
+        "LEFT" => Key::Left,
+        "RIGHT" => Key::Right,
+        "ENTER" => Key::Enter,
+        "BACKSPACE" => Key::Backspace,
+        "SPACE" => Key::Space,
+        "LEFTSHIFT" => Key::LeftShift,
+        "RIGHTSHIFT" => Key::RightShift,
+        "COMMA" => Key::Comma,
+        "PERIOD" => Key::Period,
+        "SLASH" => Key::Slash,
+        "SEMICOLON" => Key::Semicolon,
+        "ESCAPE" => Key::Escape,
+        "EQUAL" => Key::Equal,
+        "MINUS" => Key::Minus,
+        "LEFTBRACKET" => Key::LeftBracket,
+        "RIGHTBRACKET" => Key::RightBracket,
+        "NUMPADPLUS" => Key::NumPadPlus,
+        "NUMPADMINUS" => Key::NumPadMinus,
+        "LEFTCTRL" => Key::LeftCtrl,
+        "RIGHTCTRL" => Key::RightCtrl,
+        "LEFTALT" => Key::LeftAlt,
+        "RIGHTALT" => Key::RightAlt,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Open a window and close it again, to check one can be opened at all, e.g. that there's a display.
+pub fn probe() -> Result<(), String> {
+    Window::new(
+        "ferrum",
+        SCREEN_WIDTH,
+        SCREEN_HEIGHT,
+        WindowOptions::default(),
+    )
+    .map(drop)
+    .map_err(|e| e.to_string())
+}
+
+/// Create a window showing frames of the given width (the screen's, or a widescreen frame's) at the given integer scale,
+/// updated at most refresh_rate times per second.
+/// minifb can't scale by 3, so the window is always at 1x, and frames are scaled up before they're shown.
+fn create_window(title: &str, width: usize, scale: usize, refresh_rate: f64) -> Window {
+    let option = WindowOptions {
+        resize: false,
+        scale: Scale::X1,
+        ..Default::default()
+    };
+    let mut window = Window::new(title, width * scale, SCREEN_HEIGHT * scale, option).unwrap();
+    window.limit_update_rate(Some(std::time::Duration::from_secs_f64(1.0 / refresh_rate)));
+
+    // X11 copies the icon, so it only has to outlive the call.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let icon = icon();
+        window.set_icon(minifb::Icon::Buffer(icon.as_ptr(), icon.len() as u32));
+    }
+    window
+}
+
+/// Open a minifb window, which is both the video sink and the input source.
+/// The window limits updates to the display's refresh rate, which paces emulation.
+pub fn open(
+    title: &str,
+    scale: usize,
+    refresh_rate: f64,
+    keymap: InputMap<Key>,
+    hotkeys: HotkeyMap<Key>,
+) -> (MinifbVideo, MinifbInput) {
+    let window = Rc::new(RefCell::new(create_window(
+        title,
+        SCREEN_WIDTH,
+        scale,
+        refresh_rate,
+    )));
+    (
+        MinifbVideo {
+            window: window.clone(),
+            title: title.to_string(),
+            width: SCREEN_WIDTH,
+            scale,
+            refresh_rate,
+            buffer: vec![0; SCREEN_PIXELS * scale * scale],
+        },
+        MinifbInput {
+            window,
+            keymap,
+            hotkeys,
+        },
+    )
+}
+
+/// Scale a frame, width pixels wide, up into buffer, sized for it.
+fn scale_up(frame: &[u32], width: usize, scale: usize, buffer: &mut Vec<u32>) {
+    buffer.resize(width * SCREEN_HEIGHT * scale * scale, 0);
+    for (y, row) in frame.chunks_exact(width).enumerate() {
+        for (x, &pixel) in row.iter().enumerate() {
+            for dy in 0..scale {
+                let start = ((y * scale + dy) * width + x) * scale;
+                buffer[start..start + scale].fill(pixel);
+            }
+        }
+    }
+}
+
+/// Update the window with a frame scaled up by scale_up.
+fn show(window: &mut Window, buffer: &[u32], width: usize, scale: usize) {
+    if let Err(e) = window.update_with_buffer(buffer, width * scale, SCREEN_HEIGHT * scale) {
+        warn!("Failed to update the window: {}", e);
+    }
+}
+
+/// Frames shown in a minifb window.
+pub struct MinifbVideo {
+    /// Shared with the input, the window is replaced when the scale, or the width of frames, changes.
+    window: Rc<RefCell<Window>>,
+
+    /// Current title, to carry over to a new window.
+    title: String,
+
+    /// Width of the frames shown, the screen's or a widescreen frame's.
+    width: usize,
+    scale: usize,
+
+    /// Most updates per second, the refresh rate of the display.
+    refresh_rate: f64,
+
+    /// The frame scaled up to the window's size.
+    buffer: Vec<u32>,
+}
+
+impl MinifbVideo {
+    /// Recreate the window for frames of another width, or another scale.
+    fn resize(&mut self, width: usize, scale: usize) {
+        *self.window.borrow_mut() = create_window(&self.title, width, scale, self.refresh_rate);
+        self.width = width;
+        self.scale = scale;
+        self.buffer = vec![0; width * SCREEN_HEIGHT * scale * scale];
+    }
+}
+
+impl VideoSink for MinifbVideo {
+    fn refresh_rate(&self) -> Option<f64> {
+        Some(self.refresh_rate)
+    }
+
+    fn frame(&mut self, frame: &[u32; SCREEN_PIXELS]) {
+        self.wide_frame(frame, SCREEN_WIDTH);
+    }
+
+    fn wide_frame(&mut self, frame: &[u32], width: usize) {
+        if width != self.width {
+            self.resize(width, self.scale);
+        }
+        scale_up(frame, width, self.scale, &mut self.buffer);
+        show(
+            &mut self.window.borrow_mut(),
+            &self.buffer,
+            width,
+            self.scale,
+        );
+    }
+
+    fn status(&mut self, status: &Status) {
+        self.title = status.to_string();
+        self.window.borrow_mut().set_title(&self.title);
+    }
+
+    fn set_scale(&mut self, scale: usize) -> bool {
+        if scale == self.scale {
+            return true;
+        }
+        self.resize(self.width, scale);
+        true
+    }
+}
+
+/// Keyboard input from a minifb window, the Joypad and hotkeys, see default_keymap and default_hotkeys.
+pub struct MinifbInput {
+    window: Rc<RefCell<Window>>,
+    keymap: InputMap<Key>,
+    hotkeys: HotkeyMap<Key>,
+}
+
+impl InputSource for MinifbInput {
+    fn poll(&mut self) -> Buttons {
+        self.keymap.update(&self.window.borrow().get_keys())
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        let window = self.window.borrow();
+        let mut hotkeys = resolve_hotkeys(
+            &self.hotkeys,
+            &window.get_keys(),
+            &window.get_keys_pressed(KeyRepeat::No),
+        );
+        if !window.is_open() {
+            hotkeys.push(Hotkey::Quit);
+        }
+        hotkeys
+    }
+
+    /// Hotkeys can be bound to chords, e.g. "Ctrl+S", Joypad buttons to keys.
+    fn rebind(&mut self, bindings: &[(String, Binding)]) {
+        rebind(&mut self.keymap, &mut self.hotkeys, bindings);
+    }
+
+    fn conflicts(&self) -> Vec<String> {
+        conflicts(&self.keymap, &self.hotkeys)
+    }
+
+    fn pointer(&mut self) -> Option<(usize, usize)> {
+        let window = self.window.borrow();
+        pointer(window.get_mouse_pos(MouseMode::Discard)?, window.get_size())
+    }
+
+    /// minifb reports a minimized window as inactive too.
+    fn focused(&mut self) -> bool {
+        self.window.borrow_mut().is_active()
+    }
+}
+
+/// The hotkeys pressed, with the keys held down for their modifiers.
+fn resolve_hotkeys(hotkeys: &HotkeyMap<Key>, keys: &[Key], pressed: &[Key]) -> Vec<Hotkey> {
+    if pressed.contains(&Key::Space) {
+        println!("hemlo <3");
+    }
+    let mut modifiers = Modifiers::empty();
+    for key in keys {
+        modifiers |= match key {
+            Key::LeftCtrl | Key::RightCtrl => Modifiers::CTRL,
+            Key::LeftShift | Key::RightShift => Modifiers::SHIFT,
+            Key::LeftAlt | Key::RightAlt => Modifiers::ALT,
+            _ => Modifiers::empty(),
+        };
+    }
+    hotkeys.resolve(pressed, modifiers)
+}
+
+/// Hotkeys can be bound to chords, e.g. "Ctrl+S", Joypad buttons to keys.
+fn rebind(
+    keymap: &mut InputMap<Key>,
+    hotkeys: &mut HotkeyMap<Key>,
+    bindings: &[(String, Binding)],
+) {
+    for (name, binding) in bindings {
+        match binding {
+            Binding::Hotkey(hotkey) => match Chord::parse(name, parse_key) {
+                Ok(chord) => hotkeys.rebind(chord, *hotkey),
+                Err(e) => warn!("Ignoring the {} hotkey: {}", hotkey.name(), e),
+            },
+            _ => match parse_key(name) {
+                Some(key) => keymap.rebind(key, *binding),
+                None => warn!("Ignoring unknown key `{}`", name),
+            },
+        }
+    }
+}
+
+fn conflicts(keymap: &InputMap<Key>, hotkeys: &HotkeyMap<Key>) -> Vec<String> {
+    let joypad: Vec<Key> = keymap.inputs().into_iter().copied().collect();
+    hotkeys.conflicts(&joypad)
+}
+
+/// The window can be at any scale, so the mouse position is scaled down to the screen.
+/// A window wider than the screen shows a widescreen frame, with the screen in the middle.
+fn pointer((x, y): (f32, f32), (width, height): (usize, usize)) -> Option<(usize, usize)> {
+    let frame_width = width * SCREEN_HEIGHT / height.max(1);
+    let border = frame_width.saturating_sub(SCREEN_WIDTH) / 2;
+    let x = (x as usize * frame_width / width.max(1)).checked_sub(border)?;
+    (x < SCREEN_WIDTH).then_some((x, y as usize * SCREEN_HEIGHT / height.max(1)))
+}
+
+/// A frame handed from emulation to the presenter thread, width pixels wide.
+#[derive(Default)]
+struct Frame {
+    pixels: Vec<u32>,
+    width: usize,
+}
+
+/// What emulation and the presenter thread share, the presenter owns the window.
+#[derive(Default)]
+struct Shared {
+    /// The newest complete frame, and whether the presenter has yet to show it.
+    ready: Frame,
+    fresh: bool,
+
+    title: String,
+    scale: usize,
+
+    /// Keys held down, and keys pressed since emulation last took them.
+    keys: Vec<Key>,
+    pressed: Vec<Key>,
+
+    /// The mouse position, and the window's size, for pointer.
+    mouse: Option<(f32, f32)>,
+    size: (usize, usize),
+    active: bool,
+
+    /// The window was closed (or couldn't be opened), or emulation is done with it.
+    closed: bool,
+}
+
+/// The shared state, and the condition the presenter waits on for the next frame.
+type Presenter = Arc<(Mutex<Shared>, Condvar)>;
+
+/// Marks the window closed when the presenter thread ends, even if it panics, so emulation quits.
+struct Closing(Presenter);
+
+impl Drop for Closing {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.0 .0.lock() {
+            shared.closed = true;
+        }
+    }
+}
+
+/// Open a minifb window on a presenter thread, triple buffered: emulation draws a frame while the presenter shows
+/// the newest complete one, and a third holds the frame in between, so neither waits for the other.
+/// Emulation is paced by its own timer at refresh_rate, with absolute deadlines, and the window is updated as soon as
+/// a frame is ready, rather than by minifb's limiter, which sleeps from one update to the next and drifts against
+/// the compositor, showing some frames twice and skipping others (judder).
+/// Not on macOS, where windows have to live on the main thread.
+pub fn open_threaded(
+    title: &str,
+    scale: usize,
+    refresh_rate: f64,
+    keymap: InputMap<Key>,
+    hotkeys: HotkeyMap<Key>,
+) -> (ThreadedVideo, ThreadedInput) {
+    let shared: Presenter = Arc::new((
+        Mutex::new(Shared {
+            title: title.to_string(),
+            scale,
+            active: true,
+            ..Default::default()
+        }),
+        Condvar::new(),
+    ));
+    let thread = {
+        let shared = shared.clone();
+        thread::spawn(move || present(shared, refresh_rate))
+    };
+    (
+        ThreadedVideo {
+            shared: shared.clone(),
+            thread: Some(thread),
+            back: Frame::default(),
+            period: Duration::from_secs_f64(1.0 / refresh_rate),
+            next: Instant::now(),
+        },
+        ThreadedInput {
+            shared,
+            keymap,
+            hotkeys,
+        },
+    )
+}
+
+/// The presenter thread, showing each frame as it's ready, until the window is closed or emulation is done.
+fn present(shared: Presenter, refresh_rate: f64) {
+    let _closing = Closing(shared.clone());
+    let (lock, ready) = &*shared;
+    let (mut title, mut scale) = {
+        let shared = lock.lock().unwrap();
+        (shared.title.clone(), shared.scale)
+    };
+    let mut width = SCREEN_WIDTH;
+    let mut window = create_window(&title, width, scale, refresh_rate);
+    window.limit_update_rate(None);
+    let mut front = Frame::default();
+    let mut buffer = Vec::new();
+    loop {
+        let mut shared = lock.lock().unwrap();
+        if !shared.fresh && !shared.closed {
+            // Keep handling window events while emulation is paused, or behind.
+            let timeout = Duration::from_secs_f64(1.0 / refresh_rate);
+            shared = ready.wait_timeout(shared, timeout).unwrap().0;
+        }
+        if shared.closed {
+            return;
+        }
+        let fresh = std::mem::take(&mut shared.fresh);
+        if fresh {
+            std::mem::swap(&mut front, &mut shared.ready);
+        }
+        let retitle = shared.title != title;
+        if retitle {
+            title.clone_from(&shared.title);
+        }
+        let rescale = shared.scale != scale;
+        scale = shared.scale;
+        drop(shared);
+
+        if rescale || (fresh && front.width != width) {
+            width = if fresh { front.width } else { width };
+            window = create_window(&title, width, scale, refresh_rate);
+            window.limit_update_rate(None);
+        } else if retitle {
+            window.set_title(&title);
+        }
+        if fresh {
+            scale_up(&front.pixels, width, scale, &mut buffer);
+            show(&mut window, &buffer, width, scale);
+        } else {
+            window.update();
+        }
+
+        let mut shared = lock.lock().unwrap();
+        shared.keys = window.get_keys();
+        shared
+            .pressed
+            .extend(window.get_keys_pressed(KeyRepeat::No));
+        shared.mouse = window.get_mouse_pos(MouseMode::Discard);
+        shared.size = window.get_size();
+        shared.active = window.is_active();
+        if !window.is_open() {
+            shared.closed = true;
+            return;
+        }
+    }
+}
+
+/// Frames handed to the presenter thread of open_threaded.
+pub struct ThreadedVideo {
+    shared: Presenter,
+    thread: Option<JoinHandle<()>>,
+
+    /// The frame emulation draws into.
+    back: Frame,
+
+    /// Time between frames, at the display's refresh rate, and when the next one is due.
+    period: Duration,
+    next: Instant,
+}
+
+impl VideoSink for ThreadedVideo {
+    fn refresh_rate(&self) -> Option<f64> {
+        Some(1.0 / self.period.as_secs_f64())
+    }
+
+    fn frame(&mut self, frame: &[u32; SCREEN_PIXELS]) {
+        self.wide_frame(frame, SCREEN_WIDTH);
+    }
+
+    fn wide_frame(&mut self, frame: &[u32], width: usize) {
+        self.back.pixels.clear();
+        self.back.pixels.extend_from_slice(frame);
+        self.back.width = width;
+        {
+            let (lock, ready) = &*self.shared;
+            let mut shared = lock.lock().unwrap();
+            // A frame the presenter didn't get to is dropped, emulation never waits on it.
+            std::mem::swap(&mut self.back, &mut shared.ready);
+            shared.fresh = true;
+            ready.notify_one();
+        }
+
+        self.next += self.period;
+        let now = Instant::now();
+        if self.next > now {
+            thread::sleep(self.next - now);
+        } else {
+            // Running behind, don't try to catch up with a burst of frames.
+            self.next = now;
+        }
+    }
+
+    fn status(&mut self, status: &Status) {
+        self.shared.0.lock().unwrap().title = status.to_string();
+    }
+
+    fn set_scale(&mut self, scale: usize) -> bool {
+        self.shared.0.lock().unwrap().scale = scale;
+        true
+    }
+}
+
+impl Drop for ThreadedVideo {
+    /// Close the window, and wait for the presenter thread to be done with it.
+    fn drop(&mut self) {
+        let (lock, ready) = &*self.shared;
+        if let Ok(mut shared) = lock.lock() {
+            shared.closed = true;
+        }
+        ready.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Keyboard input from the window of open_threaded, as the presenter thread last saw it.
+pub struct ThreadedInput {
+    shared: Presenter,
+    keymap: InputMap<Key>,
+    hotkeys: HotkeyMap<Key>,
+}
+
+impl InputSource for ThreadedInput {
+    fn poll(&mut self) -> Buttons {
+        let keys = self.shared.0.lock().unwrap().keys.clone();
+        self.keymap.update(&keys)
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        let mut shared = self.shared.0.lock().unwrap();
+        let pressed = std::mem::take(&mut shared.pressed);
+        let mut hotkeys = resolve_hotkeys(&self.hotkeys, &shared.keys, &pressed);
+        if shared.closed {
+            hotkeys.push(Hotkey::Quit);
+        }
+        hotkeys
+    }
+
+    fn rebind(&mut self, bindings: &[(String, Binding)]) {
+        rebind(&mut self.keymap, &mut self.hotkeys, bindings);
+    }
+
+    fn conflicts(&self) -> Vec<String> {
+        conflicts(&self.keymap, &self.hotkeys)
+    }
+
+    fn pointer(&mut self) -> Option<(usize, usize)> {
+        let shared = self.shared.0.lock().unwrap();
+        pointer(shared.mouse?, shared.size)
+    }
+
+    fn focused(&mut self) -> bool {
+        self.shared.0.lock().unwrap().active
+    }
+}
@@ -0,0 +1,276 @@
+#[cfg(all(feature = "fbdev", target_os = "linux"))]
+pub mod fbdev;
+pub mod headless;
+pub mod minifb;
+mod pixel;
+pub mod terminal;
+
+pub use pixel::PixelFormat;
+
+use crate::data::format_playtime;
+use crate::input::Binding;
+use crate::joypad::Buttons;
+use crate::ppu::{SCREEN_PIXELS, SCREEN_WIDTH};
+use std::fmt;
+use std::time::Duration;
+
+/// Where finished frames go, a window, a texture, a canvas, etc.
+/// Frames are 160x144 pixels, row by row, as 0x00RRGGBB, or encoded in the sink's pixel format.
+pub trait VideoSink {
+    /// Present a frame, with the OSD already drawn on top.
+    /// This is called once per displayed frame, so a sink that waits for vsync (or a timer) paces emulation.
+    fn frame(&mut self, frame: &[u32; SCREEN_PIXELS]);
+
+    /// Present a frame wider than the screen, e.g. a widescreen frame (256x144), centered on the screen.
+    /// Sinks that can only show 160x144 get the middle of it.
+    fn wide_frame(&mut self, frame: &[u32], width: usize) {
+        let border = (width - SCREEN_WIDTH) / 2;
+        let mut screen = [0u32; SCREEN_PIXELS];
+        for (row, wide_row) in screen
+            .chunks_exact_mut(SCREEN_WIDTH)
+            .zip(frame.chunks_exact(width))
+        {
+            row.copy_from_slice(&wide_row[border..border + SCREEN_WIDTH]);
+        }
+        self.frame(&screen);
+    }
+
+    /// The pixel format the sink takes frames in. Sinks taking anything but Xrgb8888 get their frames
+    /// through encoded_frame, instead of frame and wide_frame.
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Xrgb8888
+    }
+
+    /// Present a frame encoded in the sink's pixel format, width pixels wide (160, or wider, see wide_frame).
+    fn encoded_frame(&mut self, _pixels: &[u8], _width: usize) {}
+
+    /// Frames per second the sink presents, if it paces emulation, e.g. the refresh rate of a window's display.
+    fn refresh_rate(&self) -> Option<f64> {
+        None
+    }
+
+    /// Show the emulator's status, e.g. in the window title.
+    /// This is called about once per second, and whenever emulation is paused or resumed.
+    fn status(&mut self, _status: &Status) {}
+
+    /// Show frames scaled up by an integer factor.
+    /// Returns false if the sink can't be scaled.
+    fn set_scale(&mut self, _scale: usize) -> bool {
+        false
+    }
+}
+
+/// Present a frame of 0x00RRGGBB pixels, width pixels wide, in the sink's pixel format.
+/// encoded is kept between frames, for sinks that take frames encoded.
+pub fn present(video: &mut dyn VideoSink, frame: &[u32], width: usize, encoded: &mut Vec<u8>) {
+    match video.pixel_format() {
+        PixelFormat::Xrgb8888 if width == SCREEN_WIDTH => {
+            video.frame(frame.try_into().expect("a 160x144 frame"))
+        }
+        PixelFormat::Xrgb8888 => video.wide_frame(frame, width),
+        format => {
+            format.encode(frame, encoded);
+            video.encoded_frame(encoded, width);
+        }
+    }
+}
+
+/// Live emulator status, for a front-end to show.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    /// Title of the game, from the cartridge header.
+    pub title: String,
+
+    /// Frames shown per second.
+    pub fps: f64,
+
+    /// Emulation speed, in percent of a real Gameboy.
+    pub speed: f64,
+
+    pub paused: bool,
+
+    /// Whether the run is being recorded, e.g. to a timeline or a hash file.
+    pub recording: bool,
+
+    /// Time spent playing the game, over every session, if it's kept.
+    pub playtime: Option<Duration>,
+}
+
+/// e.g. "ferrum - TETRIS - 3h 05m played - 60 FPS (100%) [REC]"
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ferrum")?;
+        if !self.title.is_empty() {
+            write!(f, " - {}", self.title)?;
+        }
+        if let Some(playtime) = self.playtime {
+            write!(f, " - {} played", format_playtime(playtime))?;
+        }
+        if self.paused {
+            write!(f, " - Paused")?;
+        } else {
+            write!(f, " - {:.0} FPS ({:.0}%)", self.fps, self.speed)?;
+        }
+        if self.recording {
+            write!(f, " [REC]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Emulator commands a front-end can trigger, besides the Joypad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    /// Stop emulation, e.g. the window was closed.
+    Quit,
+    Pause,
+    SaveState,
+    LoadState,
+    PrevStateSlot,
+    NextStateSlot,
+    ExportMaps,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+
+    /// Show the cartridge's Real Time Clock.
+    ShowRtc,
+
+    /// Switch the Real Time Clock to its next speed (normal, faster, paused).
+    RtcSpeed,
+
+    /// Switch to the next window scale.
+    Scale,
+
+    /// Show or hide the emulated time counters.
+    Counters,
+
+    /// Show or hide the frame time graph.
+    FrameTimes,
+
+    /// Show or hide the OAM viewer, and select the previous or next OAM entry in it.
+    OamViewer,
+    PrevSprite,
+    NextSprite,
+
+    /// Show or hide the IO viewer, every IO register, highlighted when it changed since the last frame.
+    IoViewer,
+
+    /// Reload the external assets, a custom boot ROM, palette, and key bindings.
+    ReloadAssets,
+
+    /// Switch to the next built-in palette.
+    NextPalette,
+
+    /// Turn interlaced rendering on or off.
+    Interlace,
+
+    /// Make the machine as it is the race anchor, and reset to it, see gb::Race.
+    RaceAnchor,
+    RaceReset,
+
+    /// Start or stop fast-forwarding, at the speed set with GameBoy::set_fast_forward.
+    FastForward,
+}
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 25] = [
+        Hotkey::Quit,
+        Hotkey::Pause,
+        Hotkey::SaveState,
+        Hotkey::LoadState,
+        Hotkey::PrevStateSlot,
+        Hotkey::NextStateSlot,
+        Hotkey::ExportMaps,
+        Hotkey::VolumeUp,
+        Hotkey::VolumeDown,
+        Hotkey::Mute,
+        Hotkey::ShowRtc,
+        Hotkey::RtcSpeed,
+        Hotkey::Scale,
+        Hotkey::Counters,
+        Hotkey::FrameTimes,
+        Hotkey::OamViewer,
+        Hotkey::PrevSprite,
+        Hotkey::NextSprite,
+        Hotkey::IoViewer,
+        Hotkey::ReloadAssets,
+        Hotkey::NextPalette,
+        Hotkey::Interlace,
+        Hotkey::RaceAnchor,
+        Hotkey::RaceReset,
+        Hotkey::FastForward,
+    ];
+
+    /// Name of the hotkey in key bindings files, e.g. "save-state".
+    pub fn name(self) -> &'static str {
+        match self {
+            Hotkey::Quit => "quit",
+            Hotkey::Pause => "pause",
+            Hotkey::SaveState => "save-state",
+            Hotkey::LoadState => "load-state",
+            Hotkey::PrevStateSlot => "prev-state-slot",
+            Hotkey::NextStateSlot => "next-state-slot",
+            Hotkey::ExportMaps => "export-maps",
+            Hotkey::VolumeUp => "volume-up",
+            Hotkey::VolumeDown => "volume-down",
+            Hotkey::Mute => "mute",
+            Hotkey::ShowRtc => "show-rtc",
+            Hotkey::RtcSpeed => "rtc-speed",
+            Hotkey::Scale => "scale",
+            Hotkey::Counters => "counters",
+            Hotkey::FrameTimes => "frame-times",
+            Hotkey::OamViewer => "oam-viewer",
+            Hotkey::PrevSprite => "prev-sprite",
+            Hotkey::NextSprite => "next-sprite",
+            Hotkey::IoViewer => "io-viewer",
+            Hotkey::ReloadAssets => "reload-assets",
+            Hotkey::NextPalette => "next-palette",
+            Hotkey::Interlace => "interlace",
+            Hotkey::RaceAnchor => "race-anchor",
+            Hotkey::RaceReset => "race-reset",
+            Hotkey::FastForward => "fast-forward",
+        }
+    }
+
+    /// The hotkey with a name, None if there's none.
+    pub fn from_name(name: &str) -> Option<Hotkey> {
+        Hotkey::ALL.into_iter().find(|hotkey| hotkey.name() == name)
+    }
+}
+
+/// Where Joypad input comes from, a keyboard, a controller, a touch screen, etc.
+pub trait InputSource {
+    /// The Joypad buttons held down for the next frame.
+    /// This is called once per emulated frame.
+    fn poll(&mut self) -> Buttons;
+
+    /// Hotkeys triggered since the last call.
+    /// This is called once per displayed frame, after the frame is presented.
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        Vec::new()
+    }
+
+    /// Bind host inputs, by name, in place of the inputs bound to the same buttons so far, see input::parse_bindings.
+    /// Sources without named inputs ignore this.
+    fn rebind(&mut self, _bindings: &[(String, Binding)]) {}
+
+    /// Bindings that get in each other's way, e.g. a key bound to two hotkeys, see HotkeyMap::conflicts.
+    /// This is called when emulation starts, and after rebinding.
+    fn conflicts(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The screen pixel (160x144) under the mouse pointer, or a finger, if any.
+    /// Beside the screen of a widescreen frame is None.
+    /// This is called once per displayed frame, the OAM viewer selects the sprite being hovered.
+    fn pointer(&mut self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Is the emulator in the foreground? False while its window is unfocused or minimized.
+    /// This is called once per displayed frame, front-ends without a window are always in the foreground.
+    fn focused(&mut self) -> bool {
+        true
+    }
+}
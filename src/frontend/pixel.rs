@@ -0,0 +1,46 @@
+/// How a video sink wants pixels laid out in memory.
+/// Frames are rendered as 0x00RRGGBB u32s, which is what minifb takes, other formats are encoded once per
+/// presented frame, after the palette and the OSD, so the sink can hand them straight to a texture or a display.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 0x00RRGGBB u32s, the frames as rendered.
+    #[default]
+    Xrgb8888,
+
+    /// 4 bytes per pixel, R G B A in memory, alpha always $FF, e.g. a WASM canvas's ImageData.
+    Rgba8888,
+
+    /// 4 bytes per pixel, B G R A in memory, alpha always $FF, e.g. an SDL ARGB8888 texture on little-endian hosts.
+    Bgra8888,
+
+    /// 2 bytes per pixel, a little-endian u16 of 5 bits red, 6 green, and 5 blue, e.g. a small SPI display.
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            _ => 4,
+        }
+    }
+
+    /// Encode a frame of 0x00RRGGBB pixels into out, replacing what it held, row by row with no padding.
+    /// Xrgb8888 pixels are written as little-endian u32s.
+    pub fn encode(self, frame: &[u32], out: &mut Vec<u8>) {
+        out.clear();
+        out.reserve(frame.len() * self.bytes_per_pixel());
+        for &pixel in frame {
+            let [b, g, r, _] = pixel.to_le_bytes();
+            match self {
+                PixelFormat::Xrgb8888 => out.extend_from_slice(&pixel.to_le_bytes()),
+                PixelFormat::Rgba8888 => out.extend_from_slice(&[r, g, b, 0xFF]),
+                PixelFormat::Bgra8888 => out.extend_from_slice(&[b, g, r, 0xFF]),
+                PixelFormat::Rgb565 => {
+                    let rgb565 = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | (b as u16 >> 3);
+                    out.extend_from_slice(&rgb565.to_le_bytes());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use super::headless::FRAME_TIME;
+use super::{Status, VideoSink};
+use crate::ppu::{SCREEN_PIXELS, SCREEN_WIDTH};
+use log::warn;
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::time::Instant;
+
+/// Frames between drawings in a terminal, 15 per second, enough to watch without flooding an SSH connection.
+pub const LIVE_INTERVAL: u32 = 4;
+
+/// Frames between drawings into a log, when stdout isn't a terminal, about one per second.
+pub const LOG_INTERVAL: u32 = 60;
+
+/// Frames drawn in the terminal, with ANSI 24-bit colors and half block characters (▀),
+/// the top pixel in the foreground color and the bottom one in the background, so 160x144 takes 160x72 characters.
+/// On a terminal, frames are drawn in place, e.g. to check rendering over SSH. Otherwise, e.g. in a CI log,
+/// they're printed one after another, each under its frame number. Emulation is paced to a real Gameboy.
+pub struct TerminalVideo {
+    /// Drawn in place on a terminal, or one after another.
+    live: bool,
+
+    /// Frames between drawings, and frames presented so far.
+    interval: u32,
+    frames: u64,
+
+    /// When the next frame is due.
+    next: Instant,
+
+    /// Status shown under the frame on a terminal.
+    status: String,
+
+    /// The frame drawn, as text.
+    text: String,
+}
+
+impl TerminalVideo {
+    /// Draw every interval-th frame, by default LIVE_INTERVAL on a terminal, LOG_INTERVAL otherwise.
+    pub fn new(interval: Option<u32>) -> Self {
+        let live = io::stdout().is_terminal();
+        let interval = interval.unwrap_or(if live { LIVE_INTERVAL } else { LOG_INTERVAL });
+        if live {
+            // Clear the screen, and hide the cursor.
+            print!("\x1b[2J\x1b[?25l");
+        }
+        Self {
+            live,
+            interval: interval.max(1),
+            frames: 0,
+            next: Instant::now(),
+            status: String::new(),
+            text: String::new(),
+        }
+    }
+
+    fn draw(&mut self, frame: &[u32], width: usize) {
+        self.text.clear();
+        if self.live {
+            self.text.push_str("\x1b[H");
+        } else {
+            let _ = writeln!(self.text, "Frame {}", self.frames);
+        }
+        half_blocks(frame, width, &mut self.text);
+        if self.live {
+            let _ = writeln!(self.text, "{}\x1b[K", self.status);
+        }
+        let mut stdout = io::stdout().lock();
+        if let Err(e) = stdout
+            .write_all(self.text.as_bytes())
+            .and_then(|()| stdout.flush())
+        {
+            warn!("Failed to draw in the terminal: {}", e);
+        }
+    }
+}
+
+impl Drop for TerminalVideo {
+    fn drop(&mut self) {
+        if self.live {
+            // Show the cursor again.
+            print!("\x1b[?25h");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+impl VideoSink for TerminalVideo {
+    fn frame(&mut self, frame: &[u32; SCREEN_PIXELS]) {
+        self.wide_frame(frame, SCREEN_WIDTH);
+    }
+
+    /// Widescreen frames are drawn whole, wider than 160 characters.
+    fn wide_frame(&mut self, frame: &[u32], width: usize) {
+        if self.frames.is_multiple_of(self.interval as u64) {
+            self.draw(frame, width);
+        }
+        self.frames += 1;
+
+        self.next += FRAME_TIME;
+        let now = Instant::now();
+        if self.next > now {
+            std::thread::sleep(self.next - now);
+        } else {
+            // Running behind, don't try to catch up with a burst of frames.
+            self.next = now;
+        }
+    }
+
+    fn refresh_rate(&self) -> Option<f64> {
+        Some(1.0 / FRAME_TIME.as_secs_f64())
+    }
+
+    fn status(&mut self, status: &Status) {
+        self.status = status.to_string();
+    }
+}
+
+/// Append a frame of 0x00RRGGBB pixels, width pixels wide, to out as lines of half blocks, two rows of pixels each,
+/// ending in a reset of the colors. Colors are only given where they change along a line.
+pub fn half_blocks(frame: &[u32], width: usize, out: &mut String) {
+    for rows in frame.chunks(width * 2) {
+        let (top, bottom) = rows.split_at(width.min(rows.len()));
+        let (mut fg, mut bg) = (None, None);
+        for (x, &upper) in top.iter().enumerate() {
+            let lower = bottom.get(x).copied().unwrap_or(0);
+            if fg != Some(upper) {
+                let [b, g, r, _] = upper.to_le_bytes();
+                let _ = write!(out, "\x1b[38;2;{};{};{}m", r, g, b);
+                fg = Some(upper);
+            }
+            if bg != Some(lower) {
+                let [b, g, r, _] = lower.to_le_bytes();
+                let _ = write!(out, "\x1b[48;2;{};{};{}m", r, g, b);
+                bg = Some(lower);
+            }
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\n");
+    }
+}
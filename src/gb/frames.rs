@@ -0,0 +1,86 @@
+use super::{Counters, GameBoy};
+use crate::audio::{AudioSink, CaptureSink, DEFAULT_SAMPLE_RATE};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Everything one emulated frame produced.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The last complete frame, 160x144 pixels, row by row.
+    /// With the LCD off, it is the last frame the PPU finished.
+    pub pixels: Vec<u32>,
+
+    /// Did the PPU finish a new frame? Only while the LCD is on.
+    pub rendered: bool,
+
+    /// Audio samples, interleaved stereo, at the sample rate of the machine's audio sink (or 48 kHz without one).
+    pub samples: Vec<i16>,
+
+    /// Bytes written to the serial port (SB).
+    pub serial: Vec<u8>,
+
+    /// Emulated time, once the frame is done.
+    pub counters: Counters,
+}
+
+/// Frame iterator
+/// Runs the machine one video frame per call to next, for tests and tools that don't need the run loop:
+///
+/// ```
+/// # let mut gb = ferrum::gb::GameBoy::from_rom(ferrum::testrom::demo(), None);
+/// let screenshot = gb.frames().nth(59).unwrap().pixels;
+/// assert_eq!(screenshot.len(), 160 * 144);
+/// ```
+///
+/// The iterator never ends. While it's alive, the machine's samples are captured, and still passed on to its audio sink,
+/// if it has one. Dropping the iterator puts the machine back as it was, ready for more frames or the run loop.
+pub struct Frames<'a> {
+    gb: &'a mut GameBoy,
+
+    /// The machine's own audio sink, if any, while the capture sink replaces it.
+    sink: Option<Box<dyn AudioSink>>,
+    capture: Rc<RefCell<CaptureSink>>,
+}
+
+impl<'a> Frames<'a> {
+    pub(super) fn new(gb: &'a mut GameBoy) -> Self {
+        let sink = gb.take_audio_sink();
+        let sample_rate = sink
+            .as_ref()
+            .map_or(DEFAULT_SAMPLE_RATE, |sink| sink.sample_rate());
+        let capture = Rc::new(RefCell::new(CaptureSink::new(sample_rate)));
+        gb.set_audio_sink(Box::new(capture.clone()));
+        gb.mmu.borrow_mut().set_serial_capture(true);
+        Self { gb, sink, capture }
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let rendered = self.gb.run_frame();
+        let samples = std::mem::take(&mut self.capture.borrow_mut().samples);
+        if let Some(sink) = &mut self.sink {
+            sink.push_samples(&samples);
+        }
+        let serial = self.gb.mmu.borrow_mut().take_serial_bytes();
+        Some(Frame {
+            pixels: self.gb.viewport(),
+            rendered,
+            samples,
+            serial,
+            counters: self.gb.counters(),
+        })
+    }
+}
+
+impl Drop for Frames<'_> {
+    fn drop(&mut self) {
+        self.gb.mmu.borrow_mut().set_serial_capture(false);
+        self.gb.take_audio_sink();
+        if let Some(sink) = self.sink.take() {
+            self.gb.set_audio_sink(sink);
+        }
+    }
+}
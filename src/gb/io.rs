@@ -0,0 +1,65 @@
+use crate::osd;
+
+/// Number of IO registers, $FF00-$FF7F.
+pub const IO_REGISTERS: usize = 0x80;
+
+/// Registers per row of the IO viewer, 16 rows of 8 fill the screen.
+const ROW_REGISTERS: usize = 8;
+
+/// Colors of registers in the IO viewer, changed ones stand out.
+const VALUE_COLOR: u32 = 0x00C0C0C0;
+const CHANGED_COLOR: u32 = 0x00FFD040;
+const LABEL_COLOR: u32 = 0x0060A0FF;
+
+/// IO snapshot
+/// Every IO register ($FF00-$FF7F) as the CPU would read it, at one point in time.
+/// Comparing snapshots of consecutive frames shows which registers a game is writing, see IoSnapshot::changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IoSnapshot {
+    /// $FF00-$FF7F, in address order.
+    pub regs: [u8; IO_REGISTERS],
+}
+
+impl IoSnapshot {
+    /// The value of the register at addr, which must be in $FF00-$FF7F.
+    pub fn get(&self, addr: u16) -> u8 {
+        self.regs[addr as usize - 0xFF00]
+    }
+
+    /// Addresses of the registers that read differently in previous.
+    pub fn changed(&self, previous: &IoSnapshot) -> Vec<u16> {
+        (0xFF00..)
+            .zip(self.regs.iter().zip(&previous.regs))
+            .filter(|(_, (now, then))| now != then)
+            .map(|(addr, _)| addr)
+            .collect()
+    }
+
+    /// Draw every register over a 160x144 frame, eight per row, each row labeled with the low byte of its first
+    /// address, e.g. "40" for $FF40-$FF47. Registers that changed since previous are highlighted.
+    pub fn draw(&self, previous: Option<&IoSnapshot>, frame: &mut [u32]) {
+        // Darken the game behind the registers, so they stand out.
+        for pixel in frame.iter_mut() {
+            *pixel = (*pixel >> 2) & 0x003F3F3F;
+        }
+
+        let changed = previous
+            .map(|previous| self.changed(previous))
+            .unwrap_or_default();
+        let (cell_width, cell_height) = osd::CELL_SIZE;
+        for (row, regs) in self.regs.chunks_exact(ROW_REGISTERS).enumerate() {
+            let y = row * (cell_height + 1);
+            let first = row * ROW_REGISTERS;
+            osd::text(frame, 0, y, &format!("{:02X}", first), LABEL_COLOR);
+            for (i, val) in regs.iter().enumerate() {
+                let addr = 0xFF00 + (first + i) as u16;
+                let x = (3 + i * 3) * cell_width;
+                let color = match changed.contains(&addr) {
+                    true => CHANGED_COLOR,
+                    false => VALUE_COLOR,
+                };
+                osd::text(frame, x, y, &format!("{:02X}", val), color);
+            }
+        }
+    }
+}
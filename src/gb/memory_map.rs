@@ -0,0 +1,131 @@
+use crate::mmu::{MapEntry, Region};
+use std::fmt;
+
+/// Pixels per 64 bytes of address space in the SVG, 1024 for the whole of it.
+const SVG_BYTES_PER_PIXEL: u32 = 64;
+
+/// Height of the smallest areas in the SVG, so their text fits.
+const SVG_MIN_ROW: u32 = 20;
+
+const SVG_WIDTH: u32 = 560;
+
+/// Memory map
+/// The 64K address space as the CPU sees it at one point in time: which area maps where, the banks switched in,
+/// and whether cartridge RAM is enabled. Taken from the MMU's dispatch table, so it's the map ferrum emulates.
+/// Prints as text, one area per line, or draws as an SVG, see MemoryMap::svg.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// Title from the cartridge header.
+    pub title: String,
+
+    /// Cartridge type from the header, e.g. "Mbc1RamBattery", "Unknown" if it isn't a known type.
+    pub cartridge: String,
+
+    /// Size of the cartridge ROM, in bytes.
+    pub rom_size: usize,
+
+    /// Every area of the address space, in address order.
+    pub entries: Vec<MapEntry>,
+}
+
+impl MemoryMap {
+    /// e.g. "TETRIS, RomOnly, 32 KiB ROM"
+    fn header(&self) -> String {
+        format!(
+            "{}, {}, {} KiB ROM",
+            self.title,
+            self.cartridge,
+            self.rom_size / 0x400
+        )
+    }
+
+    /// The map as an SVG image, address space top to bottom, each area as tall as it's big, but for the small
+    /// ones, colored by what's there.
+    pub fn svg(&self) -> String {
+        let rows: Vec<u32> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let size = entry.end as u32 - entry.start as u32 + 1;
+                (size / SVG_BYTES_PER_PIXEL).max(SVG_MIN_ROW)
+            })
+            .collect();
+        let height = 30 + rows.iter().sum::<u32>();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" \
+             font-family=\"monospace\" font-size=\"12\">\n",
+            SVG_WIDTH, height
+        );
+        svg += &format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"#FFFFFF\"/>\n",
+            SVG_WIDTH, height
+        );
+        svg += &format!(
+            "<text x=\"8\" y=\"18\" font-weight=\"bold\">{}</text>\n",
+            escape(&self.header())
+        );
+        let mut y = 30;
+        for (entry, row) in self.entries.iter().zip(rows) {
+            svg += &format!(
+                "<rect x=\"110\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#404040\"/>\n",
+                y,
+                SVG_WIDTH - 118,
+                row,
+                color(entry.region)
+            );
+            svg += &format!(
+                "<text x=\"8\" y=\"{}\">${:04X}-${:04X}</text>\n",
+                y + 14,
+                entry.start,
+                entry.end
+            );
+            svg += &format!(
+                "<text x=\"118\" y=\"{}\"><tspan font-weight=\"bold\">{}</tspan> {}</text>\n",
+                y + 14,
+                escape(entry.name),
+                escape(&entry.detail)
+            );
+            y += row;
+        }
+        svg += "</svg>\n";
+        svg
+    }
+}
+
+/// e.g.
+/// TETRIS, RomOnly, 32 KiB ROM
+/// $0000-$00FF  Boot ROM  built-in boot ROM, until $FF50 is written
+/// $0100-$3FFF  ROM 0     ROM bank 00 of 02
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.header())?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "${:04X}-${:04X}  {:<9} {}",
+                entry.start, entry.end, entry.name, entry.detail
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Fill color of a region in the SVG, cartridge areas warm, the Gameboy's own RAM cool, registers gray.
+fn color(region: Region) -> &'static str {
+    match region {
+        Region::Rom0 | Region::RomX => "#F4C27A",
+        Region::CartRam => "#F29E8E",
+        Region::Vram | Region::Oam => "#A8D8A0",
+        Region::Wram0 | Region::WramX | Region::Hram => "#9CC4EC",
+        Region::Echo0 | Region::EchoX => "#D4E4F4",
+        Region::Prohibited => "#E0E0E0",
+        Region::Io | Region::Ie => "#C8C8C8",
+    }
+}
+
+/// Text for SVG, with the characters XML reserves escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -1,13 +1,248 @@
+use crate::accuracy::Accuracy;
+pub use crate::apu::FastForwardAudio;
+use crate::assets::Assets;
+use crate::audio::{self, AudioSink, DEFAULT_LATENCY};
+use crate::audit::{self, HashAudit};
+use crate::bus::{DmaBusPolicy, OpenBusPolicy};
+use crate::cartridge::banks::{BankOverride, BankedAddr, Banks};
+use crate::cartridge::rtc::RtcTime;
+use crate::cartridge::Mapper;
+use crate::control::{BankKind, Command, ControlServer};
+use crate::coverage::Coverage;
 use crate::cpu;
-use crate::mmu;
-use crate::ppu::{SCREEN_HEIGHT, SCREEN_PIXELS, SCREEN_WIDTH};
-use log::warn;
-use minifb::KeyRepeat;
-use minifb::{Key, Window, WindowOptions};
+pub use crate::cpu::trace::{Executed, DEFAULT_TRACE_LEN};
+pub use crate::cpu::CpuState;
+use crate::data::{GameDir, Playtime, PlaytimeClock, SaveJournal, STATE_SLOTS};
+use crate::fault::{Fault, FaultPolicy};
+use crate::frametime::{FrameTime, FrameTimes};
+use crate::frontend::{self, Hotkey, InputSource, PixelFormat, Status, VideoSink};
+use crate::input::{Binding, DEFAULT_TURBO_RATE};
+use crate::joypad::Buttons;
+use crate::mmu::{self, memory::Memory};
+pub use crate::mmu::{IoSupport, MapEntry, Region};
+use crate::model::Model;
+use crate::osd::{self, Osd};
+use crate::palette::{Palette, PRESETS};
+use crate::ppu::debug::{self, IndexedImage, Layer, OamEntry};
+use crate::ppu::watchpoint::{WatchHit, Watchpoint};
+use crate::ppu::{
+    LcdOffPolicy, PpuAccuracy, SpritePriority, SCREEN_PIXELS, SCREEN_WIDTH, WIDESCREEN_BORDER,
+    WIDESCREEN_WIDTH,
+};
+use crate::serial::{NullDevice, SerialDevice};
+use crate::state::{self, Savestate, StateReader, StateWriter, Thumbnail, THUMBNAIL_WIDTH};
+use crate::timeline::Timeline;
+pub use frames::{Frame, Frames};
+pub use io::{IoSnapshot, IO_REGISTERS};
+use log::{info, warn};
+pub use memory_map::MemoryMap;
+pub use poke::Poke;
+pub use race::Race;
+use serde::Deserialize;
+use stall::StallWatch;
+pub use stall::{Stall, StallReport};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
-use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+pub use watch::RomWatch;
+
+mod frames;
+mod io;
+mod memory_map;
+mod poke;
+mod race;
+mod stall;
+mod watch;
+
+/// What the clock multiplier applies to.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClockScope {
+    /// Only the CPU runs faster (or slower). The PPU and timer keep their normal speed,
+    /// so the CPU gets more (or less) time per frame. Useful for games prone to slowdown.
+    #[default]
+    Cpu,
+
+    /// The whole Gameboy runs faster (or slower), more (or fewer) frames are emulated per second.
+    System,
+}
+
+/// What happens to emulation while the window is in the background, unfocused or minimized.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BackgroundPolicy {
+    /// Keep emulating, as if the window was in the foreground.
+    #[default]
+    Run,
+
+    /// Keep emulating, without sound.
+    Mute,
+
+    /// Pause, and only check for the window coming back a few times per second, to leave the CPU idle.
+    Pause,
+}
+
+/// How emulation is paced to the display, which refreshes at its own rate (usually 60 Hz), not the Gameboy's ~59.73 Hz.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pacing {
+    /// One frame per display refresh, so scrolling is smooth, running a little fast (or slow) to match the display.
+    /// Audio is the master clock, it's resampled by the same ratio, so the sound card gets the samples
+    /// it plays and the two don't drift apart.
+    #[default]
+    Host,
+
+    /// Exactly the Gameboy's frame rate, audio plays at its true pitch. At 60 Hz, every ~220th refresh
+    /// shows the previous frame again.
+    Exact,
+}
+
+/// How the window presents frames.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Presentation {
+    /// Emulation updates the window, and minifb's limiter holds it to the refresh rate.
+    #[default]
+    Limiter,
+
+    /// Triple buffered, the window is updated from its own thread, and emulation is paced by a timer,
+    /// to stop judder from the limiter drifting against the compositor. Not on macOS.
+    TripleBuffer,
+}
+
+/// What a rumble cartridge's motor does on the host.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rumble {
+    /// The screen shakes.
+    #[default]
+    Shake,
+
+    /// Nothing, the motor is ignored.
+    Off,
+}
+
+/// Pixels the screen moves at full rumble, one way then the other on alternate frames.
+const SHAKE_PIXELS: f32 = 3.0;
+
+/// Move each row of a screen width pixels wide sideways by offset pixels, right if positive,
+/// filling the edge it leaves with the pixels that were there.
+fn shake(screen: &mut [u32], width: usize, offset: isize) {
+    let shift = offset.unsigned_abs().min(width);
+    for row in screen.chunks_exact_mut(width) {
+        if offset > 0 {
+            row.copy_within(..width - shift, shift);
+        } else {
+            row.copy_within(shift.., 0);
+        }
+    }
+}
+
+/// How fast fast-forwarding runs.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum FastForwardSpeed {
+    #[serde(rename = "2x")]
+    X2,
+    #[default]
+    #[serde(rename = "4x")]
+    X4,
+    #[serde(rename = "8x")]
+    X8,
+
+    /// As fast as the host can emulate, filling each display refresh with frames.
+    #[serde(rename = "uncapped")]
+    Uncapped,
+}
+
+impl FastForwardSpeed {
+    /// Times the speed of a real Gameboy, None uncapped.
+    pub fn factor(self) -> Option<u32> {
+        match self {
+            FastForwardSpeed::X2 => Some(2),
+            FastForwardSpeed::X4 => Some(4),
+            FastForwardSpeed::X8 => Some(8),
+            FastForwardSpeed::Uncapped => None,
+        }
+    }
+}
+
+/// Refresh rate assumed for a window, minifb can't ask the display.
+pub const DEFAULT_REFRESH_RATE: f64 = 60.0;
+
+/// A frame is 154 scanlines of 456 T-cycles each.
+/// This bounds a frame while the LCD is off, and the PPU isn't producing any.
+const FRAME_TICKS: u32 = 154 * 456;
+
+/// T-cycles per second of a real Gameboy.
+const CLOCK_HZ: f64 = 4194304.0;
+
+/// Frames per second of a real Gameboy, ~59.73.
+const FRAME_RATE: f64 = CLOCK_HZ / FRAME_TICKS as f64;
+
+/// How often the front-end's status is updated.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Real Time Clock speeds the RTC hotkey cycles through, 0 pauses the clock.
+const RTC_SPEEDS: [u32; 4] = [1, 60, 3600, 0];
+
+/// Window scales the scale hotkey cycles through.
+const SCALES: [usize; 4] = [1, 2, 3, 4];
+
+/// How often a window paused in the background checks whether it's back in the foreground.
+const BACKGROUND_POLL: Duration = Duration::from_millis(100);
+
+/// Step of the volume hotkeys.
+const VOLUME_STEP: f32 = 0.1;
+
+/// Entries in OAM, and the color of the sprite selected in the OAM viewer.
+const OAM_ENTRIES: usize = 40;
+const SPRITE_OUTLINE_COLOR: u32 = 0x00FF00FF;
+
+/// Emulated time since power on, for speedrun timers, autosplitters, and TAS tools.
+/// The counters only move forward while emulating, but they are part of the machine's state,
+/// so loading a save state (or rolling back a run-ahead frame) takes them back to when it was saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// T-cycles, as seen by the timer and PPU.
+    pub cycles: u64,
+
+    /// Frames emulated, including frames with the LCD off.
+    pub frames: u64,
+
+    /// Frames the PPU finished, only counts frames with the LCD on.
+    pub rendered: u64,
+}
+
+impl Counters {
+    /// Emulated seconds, at the speed of a real Gameboy.
+    pub fn seconds(&self) -> f64 {
+        self.cycles as f64 / CLOCK_HZ
+    }
+
+    /// Emulated time as h:mm:ss.mmm, the way speedrun timers show it.
+    pub fn clock(&self) -> String {
+        let millis = self.cycles * 1000 / CLOCK_HZ as u64;
+        format!(
+            "{}:{:02}:{:02}.{:03}",
+            millis / 3_600_000,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000
+        )
+    }
+}
+
+/// e.g. "cycles=4194304 frames=60 rendered=59 seconds=1.000000"
+impl std::fmt::Display for Counters {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "cycles={} frames={} rendered={} seconds={:.6}",
+            self.cycles,
+            self.frames,
+            self.rendered,
+            self.seconds()
+        )
+    }
+}
 
 /// The GameBoy DMG-01 (non-color).
 pub struct GameBoy {
@@ -20,112 +255,2031 @@ pub struct GameBoy {
     /// To make emulation easier, we will define a MMU.
     /// The MMU is responsible for mapping memory addresses to actual memory locations.
     mmu: Rc<RefCell<mmu::Mmu>>,
+
+    /// Frames turbo buttons stay on, and then off, while held.
+    turbo_rate: u32,
+
+    /// Window scale.
+    scale: usize,
+
+    /// Host inputs bound by name, e.g. from the game's config, before the key bindings file.
+    bindings: Vec<(String, Binding)>,
+
+    /// On-Screen Display for status messages.
+    osd: Osd,
+
+    /// Emulate one frame ahead of the displayed one, to hide a frame of input latency.
+    run_ahead: bool,
+
+    /// What happens while the window is in the background.
+    background: BackgroundPolicy,
+
+    /// How emulation is paced to the display, and the refresh rate of the window's display.
+    pacing: Pacing,
+    refresh_rate: f64,
+    presentation: Presentation,
+
+    /// Fast-forward speed, and what the sound does meanwhile, and whether it's fast-forwarding.
+    fast_forward: FastForwardSpeed,
+    fast_forward_audio: FastForwardAudio,
+    fast_forwarding: bool,
+
+    /// Where the game's saves and states are kept, if anywhere.
+    game_dir: Option<GameDir>,
+
+    /// Writes battery backed RAM to the game's save as the game saves, if it has any.
+    journal: Option<SaveJournal>,
+
+    /// Time spent playing the game, kept in its data directory, and what counts towards it.
+    playtime: Option<Playtime>,
+    playtime_clock: PlaytimeClock,
+
+    /// Save state slot used by the save/load state hotkeys.
+    state_slot: u8,
+
+    /// Emulated clock speed, relative to a real Gameboy, and what it applies to.
+    clock_multiplier: f64,
+    clock_scope: ClockScope,
+
+    /// Execution coverage of the cartridge ROM, if enabled.
+    coverage: Option<Coverage>,
+
+    /// Determinism audit of the state hash at the end of every frame, if enabled.
+    hash_audit: Option<HashAudit>,
+
+    /// Number of frames emulated since power on.
+    frame: u64,
+
+    /// Number of frames the PPU finished since power on.
+    rendered: u64,
+
+    /// Whether the counters are shown on the OSD.
+    show_counters: bool,
+
+    /// Host timing of displayed frames, and whether its graph is shown.
+    frame_times: FrameTimes,
+    show_frame_times: bool,
+
+    /// The OAM entry selected in the OAM viewer, while it's shown, and where the pointer was last frame,
+    /// so a pointer resting on the screen doesn't fight the hotkeys.
+    oam_viewer: Option<usize>,
+    pointer: Option<(usize, usize)>,
+
+    /// Whether the IO viewer is shown, and the registers it showed last frame, to highlight the ones that changed.
+    show_io: bool,
+    last_io: Option<IoSnapshot>,
+
+    /// External assets, reloaded while running, and the palette frames are shown in.
+    assets: Assets,
+    palette: Palette,
+
+    /// What happens on a fault, and the fault that stopped the last frame, if any.
+    fault_policy: FaultPolicy,
+    fault: Option<Fault>,
+
+    /// The watchpoint hit that stopped the last frame, if any.
+    watch_hit: Option<WatchHit>,
+
+    /// Looks for black screens that won't come back after each frame, None if disabled,
+    /// and the stall that started in the last frame, if any.
+    stall_watch: Option<StallWatch>,
+    stall: Option<StallReport>,
+
+    /// Whether the trace ring was dumped already, only the first crash is reported.
+    trace_dumped: bool,
+
+    /// Joypad input queued with set_input, by the frame it applies to.
+    input: BTreeMap<u64, Buttons>,
+
+    /// The ROM file to reload when it changes, if watched.
+    watch: Option<RomWatch>,
+
+    /// Speedrun practice, once an anchor is set.
+    race: Option<Race>,
+
+    /// What the rumble motor does on the host, and how hard it ran over the last frame, None without a motor.
+    rumble: Rumble,
+    motor: Option<f32>,
+
+    /// Memory patches not written yet, and whether they are written again every frame after.
+    pokes: Vec<Poke>,
+    poke_hold: bool,
+
+    /// Where audio samples go, if anywhere.
+    audio: Option<Box<dyn AudioSink>>,
+
+    /// Audio kept queued for the default sound card, and the size of the sound card's own buffer in stereo frames,
+    /// None for its default.
+    #[cfg_attr(not(feature = "cpal"), allow(dead_code))]
+    audio_latency: Duration,
+    #[cfg_attr(not(feature = "cpal"), allow(dead_code))]
+    audio_buffer: Option<u32>,
+
+    /// Control socket for external tools, if enabled.
+    control: Option<ControlServer>,
 }
 
 impl GameBoy {
+    /// Produce samples at ratio times the sink's sample rate, so emulation running 1 / ratio times as fast
+    /// as a real Gameboy still fills the sink as fast as it plays.
+    fn resample_audio(&mut self, ratio: f64) {
+        if let Some(audio) = &self.audio {
+            let rate = (audio.sample_rate() as f64 * ratio).round() as u32;
+            let mut mmu = self.mmu.borrow_mut();
+            if mmu.audio_sample_rate() != rate {
+                mmu.set_audio_sample_rate(rate);
+            }
+        }
+    }
+
     /// Initialize Gameboy Audio Hardware (APU)
+    /// Unless the front-end gave its own sink, audio goes to the default sound card, if built with cpal.
     fn init_audio(&mut self) {
-        // TODO: Look at using cpal for audio output, spin up a thread to handle audio, etc.
-        warn!("Audio is not implemented yet.");
+        if self.audio.is_some() {
+            return;
+        }
+
+        #[cfg(feature = "cpal")]
+        match crate::audio::CpalSink::with_buffer(self.audio_buffer) {
+            Ok(mut sink) => {
+                sink.set_latency(self.audio_latency);
+                self.set_audio_sink(Box::new(sink));
+            }
+            Err(e) => warn!("Failed to open audio output: {}", e),
+        }
+
+        #[cfg(not(feature = "cpal"))]
+        warn!("Built without the cpal feature, audio is disabled.");
     }
 }
 impl GameBoy {
     /// Initialize Gameboy Hardware
     pub fn power_on(rom_path: String) -> Self {
-        let mmu = Rc::new(RefCell::new(mmu::Mmu::new(rom_path)));
+        Self::with_mmu(mmu::Mmu::new(rom_path))
+    }
+
+    /// Initialize Gameboy Hardware from an in-memory ROM image.
+    /// An optional RAM image can be given to restore the cartridge's external RAM.
+    pub fn from_rom(rom: impl Into<Vec<u8>>, ram: Option<Vec<u8>>) -> Self {
+        Self::with_mmu(mmu::Mmu::from_rom(rom, ram))
+    }
+
+    /// Initialize Gameboy Hardware from an in-memory ROM image, with the given mapper whatever the cartridge header says.
+    pub fn from_rom_as(rom: impl Into<Vec<u8>>, ram: Option<Vec<u8>>, mapper: Mapper) -> Self {
+        Self::with_mmu(mmu::Mmu::from_rom_as(rom, ram, mapper))
+    }
+
+    fn with_mmu(mmu: mmu::Mmu) -> Self {
+        let mmu = Rc::new(RefCell::new(mmu));
         let cpu = cpu::Cpu::power_on(mmu.clone());
 
-        Self { cpu, mmu }
+        Self {
+            cpu,
+            mmu,
+            turbo_rate: DEFAULT_TURBO_RATE,
+            scale: 2,
+            bindings: Vec::new(),
+            osd: Osd::new(),
+            run_ahead: false,
+            background: BackgroundPolicy::default(),
+            pacing: Pacing::default(),
+            presentation: Presentation::default(),
+            refresh_rate: DEFAULT_REFRESH_RATE,
+            fast_forward: FastForwardSpeed::default(),
+            fast_forward_audio: FastForwardAudio::default(),
+            fast_forwarding: false,
+            game_dir: None,
+            journal: None,
+            playtime: None,
+            playtime_clock: PlaytimeClock::default(),
+            state_slot: 0,
+            clock_multiplier: 1.0,
+            clock_scope: ClockScope::default(),
+            coverage: None,
+            hash_audit: None,
+            frame: 0,
+            rendered: 0,
+            show_counters: false,
+            frame_times: FrameTimes::new(),
+            show_frame_times: false,
+            oam_viewer: None,
+            pointer: None,
+            show_io: false,
+            last_io: None,
+            assets: Assets::new(),
+            palette: Palette::default(),
+            fault_policy: FaultPolicy::default(),
+            fault: None,
+            watch_hit: None,
+            stall_watch: Some(StallWatch::default()),
+            stall: None,
+            trace_dumped: false,
+            input: BTreeMap::new(),
+            watch: None,
+            race: None,
+            rumble: Rumble::default(),
+            motor: None,
+            pokes: Vec::new(),
+            poke_hold: false,
+            audio: None,
+            audio_latency: DEFAULT_LATENCY,
+            audio_buffer: None,
+            control: None,
+        }
     }
 
-    /// Run Gameboy emulation
-    pub fn run(&mut self) {
-        warn!("Emulation loop is a work in progress, no threading or event handling.");
+    /// Emulate the given hardware revision.
+    /// The model is part of the power on state, so this has to be called before running the first frame.
+    pub fn set_model(&mut self, model: Model) {
+        self.mmu.borrow_mut().set_model(model);
+    }
 
-        // The Gameboy runs at 4.194304 MHz.
-        // 4194304 Hz / 1000 ms * 16 ms = 67108.8
-        let waitticks = (4194304f64 / 1000.0 * 16.0).round() as u32;
-        let mut ticks = 0;
+    /// Reload the ROM when its file changes, while running, see RomWatch.
+    pub fn set_rom_watch(&mut self, watch: RomWatch) {
+        self.watch = Some(watch);
+    }
 
-        // Initialize Audio
-        self.init_audio();
+    /// Practice a speedrun from a save state: load it as the anchor of race mode, and start the first attempt,
+    /// see Race. If the state can't be loaded, the machine and the race so far are left as they were.
+    pub fn set_race_anchor(&mut self, anchor: Vec<u8>) -> state::Result<()> {
+        self.load_state(&anchor)?;
+        self.race = Some(Race::new(anchor, self.counters()));
+        Ok(())
+    }
 
-        // Setup window for rendering
-        let render_scale = 2;
-        let option = WindowOptions {
-            resize: false,
-            scale: match render_scale {
-                1 => minifb::Scale::X1,
-                2 => minifb::Scale::X2,
-                4 => minifb::Scale::X4,
-                8 => minifb::Scale::X8,
-                _ => panic!("Invalid render scale: {}", render_scale),
-            },
-            ..Default::default()
+    /// Reset to the race anchor, starting the next attempt with the timer at zero. Does nothing without an anchor.
+    pub fn race_reset(&mut self) -> state::Result<()> {
+        let Some(mut race) = self.race.take() else {
+            return Ok(());
         };
-        let rom_title = self.mmu.borrow().rom_title();
-        let mut window = Window::new(
-            format!("ferrum - {}", rom_title).as_str(),
-            SCREEN_WIDTH,
-            SCREEN_HEIGHT,
-            option,
-        )
-        .unwrap();
-        window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+        let result = self.load_state(race.anchor());
+        if result.is_ok() {
+            race.next_attempt(self.counters());
+        }
+        self.race = Some(race);
+        result
+    }
 
-        // Initialize window buffer
-        let mut buffer: Vec<u32> = vec![0; SCREEN_PIXELS];
-        window
-            .update_with_buffer(buffer.as_slice(), SCREEN_WIDTH, SCREEN_HEIGHT)
-            .unwrap();
+    pub fn race(&self) -> Option<&Race> {
+        self.race.as_ref()
+    }
 
-        // Emulation loop
-        let mut emulate = true;
-        while emulate {
-            // Stop emulation if window is closed.
-            if !window.is_open() {
-                emulate = false;
-            }
+    /// Hard reset with a new ROM image, as if the cartridge were swapped with the power off.
+    /// Battery backed RAM carries over (and is saved first), the rest of the machine starts over from power on.
+    /// Settings, like the model or the palette, stay as they were.
+    pub fn reload(&mut self, rom: Vec<u8>, mapper: Option<Mapper>) {
+        self.write_battery_save();
+        let save = self.battery_save();
+        let mut fresh = match mapper {
+            Some(mapper) => GameBoy::from_rom_as(rom, save, mapper),
+            None => GameBoy::from_rom(rom, save),
+        };
+        fresh.set_model(self.mmu.borrow().model());
+        fresh.set_accuracy(self.accuracy());
+        let state = fresh.save_state();
+        self.mmu
+            .borrow_mut()
+            .swap_cartridge(&mut fresh.mmu.borrow_mut());
+        self.load_state(&state)
+            .expect("Failed to power on with the new ROM");
+    }
 
-            // Simulate correct CPU speed.
-            while ticks < waitticks {
-                self.cpu.dump_registers();
-                ticks += self.cpu.cycle();
-            }
+    /// Keep the game's battery saves, save states, and playtime in the given data directory.
+    /// Battery backed RAM is saved there as the game writes it, see SaveJournal, and when emulation stops.
+    pub fn set_game_dir(&mut self, game_dir: GameDir) {
+        self.journal = self
+            .battery_save()
+            .map(|save| SaveJournal::new(game_dir.save_path(), &save));
+        self.playtime = Playtime::load(game_dir.playtime_path(), self.playtime_clock)
+            .map_err(|e| {
+                warn!(
+                    "Failed to read {}: {}",
+                    game_dir.playtime_path().display(),
+                    e
+                )
+            })
+            .ok();
+        self.game_dir = Some(game_dir);
+    }
 
-            // Is the PPU ready to render?
-            let updated = self.mmu.borrow_mut().ppu_updated();
-            if updated {
-                // Update window buffer
-                let viewport = self.mmu.borrow_mut().ppu_get_viewport().clone();
-                for y in 0..SCREEN_HEIGHT {
-                    for x in 0..SCREEN_WIDTH {
-                        let pixel = viewport[y][x];
-                        buffer[y * SCREEN_WIDTH + x] = pixel;
-                    }
-                }
+    /// Count real time played towards the game's playtime, or emulated time, see PlaytimeClock.
+    pub fn set_playtime_clock(&mut self, clock: PlaytimeClock) {
+        self.playtime_clock = clock;
+        if let Some(playtime) = &mut self.playtime {
+            playtime.set_clock(clock);
+        }
+    }
 
-                window
-                    .update_with_buffer(buffer.as_slice(), SCREEN_WIDTH, SCREEN_HEIGHT)
-                    .unwrap();
+    /// Time spent playing the game, over every session, None without a data directory.
+    pub fn playtime(&self) -> Option<Duration> {
+        self.playtime.as_ref().map(Playtime::total)
+    }
+
+    /// The cartridge's battery backed RAM, which should be persisted between sessions,
+    /// followed by the RTC footer on a cartridge with a clock. None if the cartridge has no battery.
+    pub fn battery_save(&self) -> Option<Vec<u8>> {
+        self.mmu.borrow().battery_save()
+    }
+
+    /// Replace the contents of the cartridge's external RAM, e.g. with a save file from another emulator.
+    /// An RTC footer after the RAM sets the cartridge's clock.
+    pub fn load_ram(&mut self, data: &[u8]) {
+        self.mmu.borrow_mut().load_ram(data);
+    }
+
+    /// Set the Joypad buttons currently held down.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.mmu.borrow_mut().set_buttons(buttons);
+    }
+
+    /// Queue the Joypad buttons held down during the given frame, counted from power on.
+    /// The buttons are set at the start of the frame, and stay held until they are changed.
+    /// Queued input is part of the machine's timeline, so it replays the same way after a save state is loaded.
+    pub fn set_input(&mut self, frame: u64, buttons: Buttons) {
+        self.input.insert(frame, buttons);
+    }
+
+    /// Queue a memory patch, written at the start of its frame.
+    pub fn add_poke(&mut self, poke: Poke) {
+        self.pokes.push(poke);
+    }
+
+    /// Keep writing memory patches at the start of every frame once they are due, so the game can't change them,
+    /// e.g. to hold a lives counter.
+    pub fn set_poke_hold(&mut self, hold: bool) {
+        self.poke_hold = hold;
+    }
+
+    /// Write the memory patches that are due, dropping them unless they are held.
+    fn apply_pokes(&mut self) {
+        let booted = !self.mmu.borrow().boot_rom_mapped();
+        let frame = self.frame;
+        let mut mmu = self.mmu.borrow_mut();
+        self.pokes.retain(|poke| {
+            let due = poke.frame.map_or(booted, |at| frame >= at);
+            if due {
+                mmu.poke8(poke.addr, poke.val);
             }
+            !due || self.poke_hold
+        });
+    }
 
-            // Handle keyboard input.
-            // TODO: Handle Gameboy Joypad input.
-            window
-                .get_keys_pressed(KeyRepeat::No)
-                .iter()
-                .for_each(|key| match key {
-                    Key::Escape => emulate = false,
-                    Key::Space => println!("hemlo <3"),
-                    _ => (),
-                });
+    /// Drop all input queued with set_input.
+    pub fn clear_input(&mut self) {
+        self.input.clear();
+    }
+
+    /// Number of frames emulated since power on.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Emulated time since power on.
+    pub fn counters(&self) -> Counters {
+        Counters {
+            cycles: self.mmu.borrow().cycles(),
+            frames: self.frame,
+            rendered: self.rendered,
+        }
+    }
+
+    /// The CPU registers.
+    pub fn cpu_state(&self) -> CpuState {
+        self.cpu.state()
+    }
+
+    /// Collect host frame timing into the given FrameTimes, e.g. one writing a CSV file.
+    pub fn set_frame_times(&mut self, frame_times: FrameTimes) {
+        self.frame_times = frame_times;
+    }
+
+    /// Send audio samples to the given sink, instead of the default sound card.
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.mmu
+            .borrow_mut()
+            .set_audio_sample_rate(sink.sample_rate());
+        self.audio = Some(sink);
+    }
+
+    /// Keep this much audio queued for the default sound card. More rides out hiccups in emulation, less cuts latency.
+    pub fn set_audio_latency(&mut self, latency: Duration) {
+        self.audio_latency = latency;
+    }
+
+    /// Ask the default sound card for a buffer of this many stereo frames, or None for its default.
+    pub fn set_audio_buffer(&mut self, frames: Option<u32>) {
+        self.audio_buffer = frames;
+    }
+
+    /// Stop sending audio samples anywhere, returning the sink they went to.
+    pub fn take_audio_sink(&mut self) -> Option<Box<dyn AudioSink>> {
+        self.mmu.borrow_mut().set_audio_sample_rate(0);
+        self.audio.take()
+    }
+
+    /// Set the master volume, 0.0 - 1.0.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.mmu.borrow_mut().apu_mixer_mut().volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Set the gain of each sound channel (pulse 1, pulse 2, wave, noise), 0.0 - 1.0.
+    pub fn set_channel_gains(&mut self, gains: [f32; 4]) {
+        self.mmu.borrow_mut().apu_mixer_mut().gains = gains.map(|gain| gain.clamp(0.0, 1.0));
+    }
+
+    /// Mute or unmute the sound, keeping the volume.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.mmu.borrow_mut().apu_mixer_mut().muted = muted;
+    }
+
+    /// Current time on the cartridge's Real Time Clock, None if it doesn't have one.
+    pub fn rtc_time(&self) -> Option<RtcTime> {
+        self.mmu.borrow().rtc_state()
+    }
+
+    /// Set the cartridge's Real Time Clock, if it has one.
+    pub fn set_rtc_time(&mut self, time: RtcTime) {
+        if let Some(rtc) = self.mmu.borrow_mut().rtc_mut() {
+            rtc.set_time(time);
+        }
+    }
+
+    /// Set how many seconds pass on the cartridge's Real Time Clock per emulated second, 0 stops it.
+    pub fn set_rtc_speed(&mut self, speed: u32) {
+        if let Some(rtc) = self.mmu.borrow_mut().rtc_mut() {
+            rtc.set_speed(speed);
+        }
+    }
+
+    /// Set how many frames turbo buttons stay on, and then off, while held.
+    pub fn set_turbo_rate(&mut self, frames: u32) {
+        self.turbo_rate = frames;
+    }
+
+    /// Set the window scale (1-4).
+    pub fn set_scale(&mut self, scale: usize) {
+        self.scale = scale.clamp(SCALES[0], SCALES[SCALES.len() - 1]);
+    }
+
+    /// Bind host inputs by name, in place of the frontend's defaults, e.g. a key to the scale hotkey, see
+    /// InputSource::rebind. A key bindings file binding the same buttons or hotkeys takes precedence.
+    pub fn set_bindings(&mut self, bindings: Vec<(String, Binding)>) {
+        self.bindings = bindings;
+    }
+
+    /// Select the PPU rendering pipeline, scanline or the experimental pixel FIFO, see PpuAccuracy.
+    pub fn set_ppu_accuracy(&mut self, accuracy: PpuAccuracy) {
+        self.mmu.borrow_mut().ppu_set_accuracy(accuracy);
+    }
+
+    /// Show more of the background on each side of the screen, 256x144 instead of 160x144, see Ppu::set_widescreen.
+    /// This only suits games whose backgrounds scroll, sprites and the window can end at the old edges,
+    /// or show up beside the screen where games hide them.
+    pub fn set_widescreen(&mut self, enabled: bool) {
+        self.mmu.borrow_mut().ppu_set_widescreen(enabled);
+    }
+
+    /// Render every other scanline per frame, alternating, to halve the rendering work on slow hosts,
+    /// see Ppu::set_interlaced. It needs the scanline renderer.
+    pub fn set_interlaced(&mut self, enabled: bool) {
+        self.mmu.borrow_mut().ppu_set_interlaced(enabled);
+    }
+
+    /// Show a white screen while the game has the LCD off, like a real Gameboy, or hold the last frame,
+    /// for players who'd rather not see the screen flash between areas.
+    pub fn set_lcd_off_policy(&mut self, policy: LcdOffPolicy) {
+        self.mmu.borrow_mut().ppu_set_lcd_off_policy(policy);
+    }
+
+    /// Select how overlapping sprites are ordered, by X coordinate like the DMG, or by OAM index like the CGB.
+    pub fn set_sprite_priority(&mut self, priority: SpritePriority) {
+        self.mmu.borrow_mut().ppu_set_sprite_priority(priority);
+    }
+
+    /// Enable or disable 1 frame run-ahead.
+    /// Each displayed frame is emulated one frame ahead with the current input, then rolled back,
+    /// so input shows up on screen a frame earlier. This costs two emulated frames per displayed frame.
+    pub fn set_run_ahead(&mut self, enabled: bool) {
+        self.run_ahead = enabled;
+    }
+
+    /// Pause or mute emulation while the window is unfocused or minimized, or keep it running.
+    pub fn set_background_policy(&mut self, policy: BackgroundPolicy) {
+        self.background = policy;
+    }
+
+    /// Pace emulation to the display, or to the Gameboy's own frame rate, see Pacing.
+    /// refresh_rate is the refresh rate of the display the window is on, in Hz.
+    pub fn set_pacing(&mut self, pacing: Pacing, refresh_rate: f64) {
+        self.pacing = pacing;
+        self.refresh_rate = refresh_rate;
+    }
+
+    /// Set how the window presents frames, see Presentation.
+    pub fn set_presentation(&mut self, presentation: Presentation) {
+        self.presentation = presentation;
+    }
+
+    /// Set how fast the fast-forward hotkey runs, and what the sound does meanwhile.
+    pub fn set_fast_forward(&mut self, speed: FastForwardSpeed, audio: FastForwardAudio) {
+        self.fast_forward = speed;
+        self.fast_forward_audio = audio;
+        if self.fast_forwarding {
+            self.mix_fast_forward(speed.factor().unwrap_or(1));
+        }
+    }
+
+    /// Start or stop fast-forwarding, like the fast-forward hotkey.
+    pub fn set_fast_forwarding(&mut self, on: bool) {
+        self.fast_forwarding = on;
+        self.mix_fast_forward(self.fast_forward.factor().unwrap_or(1));
+    }
+
+    pub fn fast_forwarding(&self) -> bool {
+        self.fast_forwarding
+    }
+
+    /// Have the mixer mute, or drop chunks of, the sound while fast-forwarding at speed, see FastForwardAudio.
+    fn mix_fast_forward(&mut self, speed: u32) {
+        self.mmu.borrow_mut().apu_mixer_mut().fast_forward = self
+            .fast_forwarding
+            .then_some((speed, self.fast_forward_audio));
+    }
+
+    /// OSD line while fast-forwarding, e.g. "Fast-forward 4x, muted", uncapped with the speed it's running at.
+    fn fast_forward_line(&self, speed: u32) -> String {
+        let speed = match self.fast_forward {
+            FastForwardSpeed::Uncapped => format!("uncapped ({}x)", speed),
+            _ => format!("{}x", speed),
+        };
+        let audio = match self.fast_forward_audio {
+            FastForwardAudio::Mute => "muted",
+            FastForwardAudio::Pitch => "pitch kept",
+            FastForwardAudio::Raw => "sped up",
+        };
+        format!("Fast-forward {}, {}", speed, audio)
+    }
+
+    /// Run the emulated clock at a multiple of the real Gameboy's speed, for the CPU only or the whole system.
+    pub fn set_clock_multiplier(&mut self, multiplier: f64, scope: ClockScope) {
+        self.clock_multiplier = multiplier;
+        self.clock_scope = scope;
+
+        let scale = match scope {
+            ClockScope::Cpu => (multiplier * mmu::CPU_CLOCK_SCALE_NORMAL as f64).round() as u32,
+            ClockScope::System => mmu::CPU_CLOCK_SCALE_NORMAL,
+        };
+        self.mmu.borrow_mut().set_cpu_clock_scale(scale);
+    }
+
+    /// Select what reads from unmapped or inaccessible memory return.
+    pub fn set_open_bus_policy(&mut self, policy: OpenBusPolicy) {
+        self.mmu.borrow_mut().set_open_bus_policy(policy);
+    }
+
+    /// Select what the CPU sees of the buses an OAM DMA transfer is using.
+    pub fn set_dma_bus_policy(&mut self, policy: DmaBusPolicy) {
+        self.mmu.borrow_mut().set_dma_bus_policy(policy);
+    }
+
+    /// Apply every accuracy setting at once, e.g. from an AccuracyPreset.
+    pub fn set_accuracy(&mut self, accuracy: Accuracy) {
+        self.mmu.borrow_mut().set_accuracy(accuracy);
+    }
+
+    /// The accuracy settings in effect.
+    pub fn accuracy(&self) -> Accuracy {
+        self.mmu.borrow().accuracy()
+    }
+
+    /// Select what happens on a fault. Anything but ignoring them is strict mode,
+    /// where run_frame stops right after the instruction that faulted.
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+        self.mmu
+            .borrow_mut()
+            .set_strict(policy != FaultPolicy::Ignore);
+    }
+
+    /// Warn when SP moves into ROM or the IO registers, or the stack is pushed over IE, usually a crashed game.
+    /// In strict mode these are faults too.
+    pub fn set_stack_check(&mut self, enabled: bool) {
+        self.cpu.set_stack_check(enabled);
+    }
+
+    /// Set what a rumble cartridge's motor does on the host, see Rumble.
+    pub fn set_rumble(&mut self, rumble: Rumble) {
+        self.rumble = rumble;
+    }
+
+    /// How hard the rumble motor ran over the last frame, the share of the frame it was on, 0 to 1.
+    /// Games run it in bursts for weaker rumble. None if the cartridge has no motor.
+    pub fn rumble(&self) -> Option<f32> {
+        self.motor
+    }
+
+    /// The fault that stopped the last frame, in strict mode.
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    /// Stop run_frame right after the instruction that writes the watched tile data or map cell, see Watchpoint.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.mmu.borrow_mut().add_watchpoint(watchpoint);
+    }
+
+    /// The watchpoint hit that stopped the last frame, if any.
+    pub fn watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit
+    }
+
+    /// Look for black screens that won't come back, e.g. after a crash, and pause on them with a diagnosis.
+    /// On by default.
+    pub fn set_stall_detection(&mut self, enabled: bool) {
+        self.stall_watch = enabled.then(StallWatch::default);
+    }
+
+    /// The stall that started in the last frame, if any, see Stall.
+    pub fn stall(&self) -> Option<StallReport> {
+        self.stall
+    }
+
+    /// Update the stall watch with how the frame ended.
+    fn watch_stall(&mut self) {
+        self.stall = None;
+        let Some(watch) = &mut self.stall_watch else {
+            return;
+        };
+        let mut mmu = self.mmu.borrow_mut();
+        let lcdc = mmu.inspect(0xFF40);
+        let ie = mmu.inspect(0xFFFF);
+        let halted = self.cpu.halted();
+        let vram_written = mmu.take_vram_written();
+        let Some(stall) = watch.frame(lcdc & 0x80 != 0, vram_written, halted && ie & 0x1F == 0)
+        else {
+            return;
+        };
+        self.stall = Some(StallReport {
+            stall,
+            pc: mmu.banked(self.cpu.pc()),
+            halted,
+            ime: self.cpu.state().ime,
+            ie,
+            if_: mmu.inspect(0xFF0F),
+            lcdc,
+            vram_idle: watch.vram_idle(),
+        });
+    }
+
+    /// Keep the last len instructions executed, 0 keeps none, see TraceRing.
+    /// They are printed to stderr on the first illegal opcode, or fault in strict mode.
+    pub fn set_trace_len(&mut self, len: usize) {
+        self.cpu.set_trace_len(len);
+    }
+
+    /// The last instructions executed, oldest first.
+    pub fn recent_instructions(&self) -> Vec<Executed> {
+        self.cpu.trace().iter().copied().collect()
+    }
+
+    /// The last instructions executed, one per line, for a crash report, e.g.
+    ///
+    /// Last 64 instructions before Illegal op $D3 at $0150, oldest first:
+    ///   PC:014E op:00 AF:01B0 BC:0013 DE:00D8 HL:014D SP:FFFE
+    ///   ...
+    pub fn trace_dump(&self, reason: &str) -> String {
+        let trace = self.cpu.trace();
+        if trace.is_empty() {
+            return format!("No instructions recorded before {}", reason);
+        }
+        let mut dump = format!(
+            "Last {} instructions before {}, oldest first:",
+            trace.iter().count(),
+            reason
+        );
+        for executed in trace.iter() {
+            dump.push_str(&format!("\n  {}", executed));
+        }
+        dump
+    }
 
-            // Maintain correct CPU speed.
-            ticks -= waitticks;
-            sleep(Duration::from_millis(16));
+    /// Start tracking which cartridge ROM addresses are executed.
+    pub fn enable_coverage(&mut self) {
+        let rom_size = self.mmu.borrow().rom_size();
+        self.coverage = Some(Coverage::new(rom_size));
+    }
+
+    /// Execution coverage collected so far, if enabled.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Start hashing the machine state at the end of every frame, to record or verify a run.
+    pub fn enable_hash_audit(&mut self, audit: HashAudit) {
+        self.hash_audit = Some(audit);
+    }
+
+    /// Determinism audit collected so far, if enabled.
+    pub fn hash_audit(&self) -> Option<&HashAudit> {
+        self.hash_audit.as_ref()
+    }
+
+    /// Start streaming interrupt requests and dispatches, and IO register writes, to a timeline.
+    pub fn set_timeline(&mut self, timeline: Timeline) {
+        self.mmu.borrow_mut().set_timeline(timeline);
+    }
+
+    /// Fill WRAM and HRAM from a seed instead of randomly, so runs are reproducible.
+    /// RAM is filled at power on, so this has to be called before running the first frame.
+    pub fn set_ram_seed(&mut self, seed: u64) {
+        self.mmu.borrow_mut().seed_ram(seed);
+    }
+
+    /// Hash of the whole machine state.
+    pub fn state_hash(&self) -> u64 {
+        audit::hash(&self.save_state())
+    }
+
+    /// The last complete frame, 160x144 pixels, row by row.
+    pub fn viewport(&self) -> Vec<u32> {
+        self.mmu.borrow().ppu_get_viewport().to_vec()
+    }
+
+    /// The last complete frame in a pixel format, e.g. RGBA8888 for a canvas, in the PPU's grays like viewport.
+    pub fn viewport_encoded(&self, format: PixelFormat) -> Vec<u8> {
+        let mut pixels = Vec::new();
+        format.encode(self.mmu.borrow().ppu_get_viewport(), &mut pixels);
+        pixels
+    }
+
+    /// The last complete widescreen frame, 256x144 pixels, row by row, if widescreen is on.
+    pub fn widescreen(&self) -> Option<Vec<u32>> {
+        self.mmu.borrow().ppu_widescreen().map(<[u32]>::to_vec)
+    }
+
+    /// Emulate frame by frame, as an iterator: every call to next runs one frame, see Frames.
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames::new(self)
+    }
+
+    /// Plug a device into the link port, a printer, a link cable, or a custom one.
+    /// Nothing is plugged in by default.
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.mmu.borrow_mut().set_serial_device(device);
+    }
+
+    /// Enable or disable printing serial output.
+    pub fn set_serial_output(&mut self, enabled: bool) {
+        self.mmu.borrow_mut().set_serial_output(enabled);
+    }
+
+    /// Print serial output (and debug port messages) to the given writer, instead of stdout,
+    /// e.g. to tell apart the output of several machines running in one process.
+    pub fn set_serial_writer(&mut self, writer: Box<dyn std::io::Write>) {
+        self.mmu.borrow_mut().set_serial_writer(writer);
+    }
+
+    /// Use external assets, a custom boot ROM, palette, or key bindings, reloaded while running.
+    /// The boot ROM is loaded right away, the rest when running starts.
+    pub fn set_assets(&mut self, mut assets: Assets) {
+        let loaded = assets.load();
+        if let Err(e) = self.mmu.borrow_mut().set_boot_rom(loaded.boot_rom) {
+            warn!("Ignoring the boot ROM, {}", e);
+        }
+        self.assets = assets;
+    }
+
+    /// Show the screen in a palette, see Palette::preset for the built-in ones.
+    /// A palette file in the external assets replaces it when running starts.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Accept commands from external tools on a control socket while running.
+    pub fn set_control_server(&mut self, server: ControlServer) {
+        self.control = Some(server);
+    }
+
+    /// Read a byte from memory, as the CPU sees it.
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.mmu.borrow().peek8(addr)
+    }
+
+    /// Every IO register, $FF00-$FF7F, as the CPU would read it right now.
+    pub fn io_snapshot(&self) -> IoSnapshot {
+        let mmu = self.mmu.borrow();
+        IoSnapshot {
+            regs: std::array::from_fn(|i| mmu.peek8(0xFF00 + i as u16)),
+        }
+    }
+
+    /// The IO registers the game has used that ferrum doesn't fully emulate, in address order.
+    pub fn unsupported_io(&self) -> Vec<(u16, IoSupport)> {
+        self.mmu.borrow().unsupported_io()
+    }
+
+    /// Lines summing up the IO registers the game used that ferrum doesn't fully emulate,
+    /// e.g. "This game used: FF4D (KEY1), FF56 (RP) — unimplemented", none if it didn't use any.
+    pub fn unsupported_io_summary(&self) -> Vec<String> {
+        let used = self.unsupported_io();
+        let unimplemented = used
+            .iter()
+            .filter_map(|(addr, support)| match support {
+                IoSupport::Unimplemented(name) => Some(format!("{:04X} ({})", addr, name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let partial = used
+            .iter()
+            .filter_map(|(addr, support)| match support {
+                IoSupport::Partial(missing) => Some(format!("{:04X} (without {})", addr, missing)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut lines = Vec::new();
+        if !unimplemented.is_empty() {
+            lines.push(format!(
+                "This game used: {} — unimplemented",
+                unimplemented.join(", ")
+            ));
+        }
+        if !partial.is_empty() {
+            lines.push(format!(
+                "This game used: {} — partially implemented",
+                partial.join(", ")
+            ));
+        }
+        lines
+    }
+
+    /// Read a byte from memory like peek, except VRAM and OAM are readable even while the PPU has them locked.
+    pub fn inspect(&self, addr: u16) -> u8 {
+        self.mmu.borrow().inspect(addr)
+    }
+
+    /// The banks the CPU sees, the ones the cartridge has mapped, unless they're overridden.
+    pub fn banks(&self) -> Banks {
+        self.mmu.borrow().banks()
+    }
+
+    /// Banks forced from the debugger, see BankOverride.
+    pub fn bank_override(&self) -> BankOverride {
+        self.mmu.borrow().bank_override()
+    }
+
+    /// Force banks whatever the mapper's registers say, until set back to the default, see BankOverride.
+    /// Not saved in save states.
+    pub fn set_bank_override(&mut self, bank_override: BankOverride) {
+        self.mmu.borrow_mut().set_bank_override(bank_override);
+    }
+
+    /// The address space as the CPU sees it right now, which area maps where, with the banks switched in.
+    pub fn memory_map(&self) -> MemoryMap {
+        let mmu = self.mmu.borrow();
+        MemoryMap {
+            title: mmu.rom_title(),
+            cartridge: mmu
+                .cartridge_type()
+                .map_or("Unknown".to_string(), |cart_type| {
+                    format!("{:?}", cart_type)
+                }),
+            rom_size: mmu.rom_size(),
+            entries: mmu.memory_map(),
+        }
+    }
+
+    /// An address qualified with the bank mapped there, e.g. 03:4F20, for addresses in the cartridge's banked areas.
+    pub fn banked(&self, addr: u16) -> BankedAddr {
+        self.mmu.borrow().banked(addr)
+    }
+
+    /// Write a byte to memory, as if the CPU did.
+    pub fn poke(&mut self, addr: u16, val: u8) {
+        self.mmu.borrow_mut().poke8(addr, val);
+    }
+
+    /// Write the last complete frame to a PNG.
+    pub fn screenshot(&self, path: &std::path::Path) -> std::io::Result<()> {
+        debug::frame_image(self.mmu.borrow().ppu_get_viewport()).write_png(path)
+    }
+
+    /// Enable or disable the debug printf port, a logging channel for homebrew at $FF7E-$FF7F.
+    pub fn set_debug_port(&mut self, enabled: bool) {
+        self.mmu.borrow_mut().set_debug_port(enabled);
+    }
+
+    /// Every tile currently in VRAM, as an indexed image.
+    pub fn tile_sheet(&self) -> IndexedImage {
+        self.mmu.borrow().ppu_tile_sheet()
+    }
+
+    /// Every OAM entry, decoded.
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        self.mmu.borrow().ppu_oam_entries()
+    }
+
+    /// The OAM sprites of the current frame, composed as they are placed, as an indexed image.
+    pub fn sprite_sheet(&self) -> IndexedImage {
+        self.mmu.borrow().ppu_sprite_sheet()
+    }
+
+    /// The whole 256x256 background or window map, with the part on screen outlined, as an indexed image.
+    pub fn map_image(&self, layer: Layer) -> IndexedImage {
+        self.mmu.borrow().ppu_map_image(layer)
+    }
+
+    /// Snapshot the whole machine into a save state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        w.section("counters", |w| {
+            w.u64(self.frame);
+            w.u64(self.rendered);
+        });
+        w.section("cpu", |w| self.cpu.save_state(w));
+        let mmu = self.mmu.borrow();
+        mmu.save_state(&mut w);
+        w.section("thumbnail", |w| {
+            Thumbnail::new(mmu.ppu_get_viewport()).save_state(w)
+        });
+        w.section("accuracy", |w| mmu.accuracy().save_state(w));
+        w.into_bytes()
+    }
+
+    /// Restore the machine from a save state taken with save_state, by this or another version of ferrum,
+    /// see state::migrate. If the state can't be loaded, the machine is left as it was.
+    pub fn load_state(&mut self, data: &[u8]) -> state::Result<()> {
+        let backup = self.save_state();
+        let result = self.restore_state(data);
+        if result.is_err() {
+            self.restore_state(&backup)
+                .expect("Failed to restore the machine after a bad save state");
+        }
+        result
+    }
+
+    fn restore_state(&mut self, data: &[u8]) -> state::Result<()> {
+        let data = state::migrate(data)?;
+        let mut r = StateReader::new(&data);
+        r.section("counters", |r| {
+            self.frame = r.u64()?;
+            self.rendered = r.u64()?;
+            Ok(())
+        })?;
+        r.section("cpu", |r| self.cpu.load_state(r))?;
+        self.mmu.borrow_mut().load_state(&mut r)?;
+        r.skip_section("thumbnail")?;
+        // States from before the accuracy was saved keep the settings in effect.
+        let mut accuracy = self.accuracy();
+        if r.extra_section("accuracy", |r| accuracy.load_state(r))? {
+            self.set_accuracy(accuracy);
+        }
+        if r.remaining() != 0 {
+            return Err(state::StateError::Invalid("length"));
+        }
+        Ok(())
+    }
+
+    /// Run the boot ROM to the end, instruction by instruction, stopping as it hands control to the cartridge at $0100.
+    /// Frames the PPU finishes on the way are counted as rendered, but not as emulated frames.
+    pub fn finish_boot(&mut self) {
+        while self.mmu.borrow().boot_rom_mapped() {
+            self.cpu.cycle();
+            if self.mmu.borrow_mut().ppu_updated() {
+                self.rendered += 1;
+            }
+        }
+    }
+
+    /// Execute one instruction, and dispatch any interrupt after it, for tools comparing ferrum to other emulators
+    /// instruction by instruction. If it halts the CPU, this runs on until an interrupt wakes it up, so the CPU is
+    /// always about to execute the next instruction.
+    /// Frames the PPU finishes on the way are counted as rendered, but not as emulated frames, and their audio is dropped.
+    pub fn step(&mut self) {
+        loop {
+            self.cpu.cycle();
+            if self.mmu.borrow_mut().ppu_updated() {
+                self.rendered += 1;
+                self.mmu.borrow_mut().apu_take_samples();
+            }
+            if !self.cpu.halted() {
+                break;
+            }
+        }
+    }
+
+    /// Make LY ($FF44) read val whatever the PPU is doing, or None to read the PPU's LY again.
+    /// Reference traces, e.g. Gameboy Doctor's, are logged with LY stubbed to $90, so games waiting for V-Blank
+    /// don't spin for a different number of instructions on every emulator.
+    pub fn set_ly_stub(&mut self, val: Option<u8>) {
+        self.mmu.borrow_mut().set_ly_stub(val);
+    }
+
+    /// Start at the cartridge's entry point, with the CPU and IO registers as the boot ROM would leave them,
+    /// without running it. This has to be called at power on, before running the first frame.
+    pub fn skip_boot(&mut self) {
+        let model = self.mmu.borrow().model();
+        let checksum = self.peek(0x014D);
+        self.cpu.set_state(model.post_boot_registers(checksum));
+        self.mmu.borrow_mut().skip_boot();
+    }
+
+    /// Run the CPU until the PPU finishes a frame (enters V-Blank).
+    /// While the LCD is off, this stops after a frame's worth of T-cycles instead.
+    /// Returns true if the PPU produced a new frame.
+    pub fn run_frame(&mut self) -> bool {
+        // An overclocked CPU runs more T-cycles per frame.
+        let budget = match self.clock_scope {
+            ClockScope::Cpu => (FRAME_TICKS as f64 * self.clock_multiplier) as u32,
+            ClockScope::System => FRAME_TICKS,
+        };
+
+        if let Some(&buttons) = self.input.get(&self.frame) {
+            self.set_buttons(buttons);
+        }
+        if !self.pokes.is_empty() {
+            self.apply_pokes();
+        }
+        self.frame += 1;
+        self.mmu.borrow_mut().timeline_frame(self.frame);
+
+        // Faults and watchpoint hits from outside emulation, e.g. pokes, aren't the game's.
+        let strict = self.fault_policy != FaultPolicy::Ignore;
+        self.fault = None;
+        self.watch_hit = None;
+        self.cpu.take_fault();
+        self.mmu.borrow().take_fault();
+        self.mmu.borrow_mut().take_watch_hit();
+
+        let mut ticks = 0;
+        let mut produced = false;
+        while ticks < budget {
+            self.cpu.dump_registers();
+            if let Some(coverage) = &mut self.coverage {
+                if !self.cpu.halted() {
+                    if let Some(offset) = self.mmu.borrow().rom_offset(self.cpu.pc()) {
+                        coverage.mark(offset);
+                    }
+                }
+            }
+            let pc = self.cpu.pc();
+            ticks += self.cpu.cycle();
+            if let Some(interrupt) = self.cpu.take_dispatched() {
+                self.mmu.borrow_mut().trace_dispatch(interrupt);
+            }
+            let hit = self.mmu.borrow_mut().take_watch_hit();
+            if let Some((watchpoint, addr, val)) = hit {
+                self.watch_hit = Some(WatchHit {
+                    watchpoint,
+                    addr,
+                    val,
+                    pc: self.banked(pc),
+                });
+            }
+            if self.mmu.borrow_mut().ppu_updated() {
+                self.rendered += 1;
+                produced = true;
+            }
+            let illegal = self.cpu.take_fault();
+            if strict {
+                self.fault = illegal.or_else(|| self.mmu.borrow().take_fault());
+            }
+            let dump = !self.trace_dumped && !self.cpu.trace().is_empty();
+            if let (Some(fault), true) = (self.fault.or(illegal), dump) {
+                eprintln!("{}", self.trace_dump(&fault.to_string()));
+                self.trace_dumped = true;
+            }
+            if produced || self.fault.is_some() || self.watch_hit.is_some() {
+                break;
+            }
+        }
+        let samples = self.mmu.borrow_mut().apu_take_samples();
+        if let Some(audio) = &mut self.audio {
+            audio.push_samples(&samples);
+        }
+        let motor = self.mmu.borrow_mut().take_rumble();
+        self.motor = motor.map(|on| (on as f32 / ticks.max(1) as f32).min(1.0));
+        self.watch_stall();
+
+        if self.hash_audit.is_some() {
+            let hash = self.state_hash();
+            if let Some(audit) = &mut self.hash_audit {
+                audit.check(self.frame, hash);
+            }
+        }
+        produced
+    }
+
+    /// Change the master volume by step, or toggle mute, and show the volume on the OSD.
+    fn change_volume(&mut self, step: f32, toggle_mute: bool) {
+        let mut mmu = self.mmu.borrow_mut();
+        let mixer = mmu.apu_mixer_mut();
+        mixer.volume = (mixer.volume + step).clamp(0.0, 1.0);
+        if toggle_mute {
+            mixer.muted = !mixer.muted;
+        } else if step != 0.0 {
+            mixer.muted = false;
+        }
+
+        let mut text = if mixer.muted {
+            "Muted".to_string()
+        } else {
+            format!("Volume {}%", (mixer.volume * 100.0).round())
+        };
+        // Channel levels are only shown when some aren't at full.
+        if mixer.gains.iter().any(|&gain| gain < 1.0) {
+            let gains: Vec<String> = mixer
+                .gains
+                .iter()
+                .map(|gain| format!("{}", (gain * 100.0).round()))
+                .collect();
+            text = format!("{} ({})", text, gains.join("/"));
+        }
+        drop(mmu);
+        self.osd.show(text);
+    }
+
+    /// Save the machine to the current save state slot, and report how it went on the OSD.
+    fn save_state_slot(&mut self) {
+        let Some(game_dir) = &self.game_dir else {
+            self.osd.show("No data directory");
+            return;
+        };
+        let path = game_dir.state_path(self.state_slot);
+        match std::fs::write(&path, self.save_state()) {
+            Ok(()) => self.osd.show(format!("State {} saved", self.state_slot)),
+            Err(e) => {
+                warn!("Failed to write {}: {}", path.display(), e);
+                self.osd
+                    .show(format!("State {} not saved", self.state_slot));
+            }
+        }
+    }
+
+    /// The thumbnail of the save state in a slot, None if the slot is empty, or its state has no thumbnail.
+    pub fn slot_thumbnail(&self, slot: u8) -> Option<Thumbnail> {
+        let path = self.game_dir.as_ref()?.state_path(slot);
+        Thumbnail::read(&std::fs::read(path).ok()?)
+    }
+
+    /// Show the selected save state slot on the OSD, with the thumbnail of its state.
+    fn show_state_slot(&mut self) {
+        match self.slot_thumbnail(self.state_slot) {
+            Some(mut thumbnail) => {
+                self.palette.apply(&mut thumbnail.pixels);
+                self.osd.show_picture(thumbnail.pixels, THUMBNAIL_WIDTH);
+                self.osd.show(format!("State slot {}", self.state_slot));
+            }
+            None => self
+                .osd
+                .show(format!("State slot {} (empty)", self.state_slot)),
+        }
+    }
+
+    /// Load the machine from a save state slot, and report how it went on the OSD.
+    fn load_state_slot(&mut self, slot: u8) {
+        let Some(game_dir) = &self.game_dir else {
+            self.osd.show("No data directory");
+            return;
+        };
+        let path = game_dir.state_path(slot);
+        let result = match std::fs::read(&path) {
+            Ok(data) => self.load_state(&data).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(()) => self.osd.show(format!("State {} loaded", slot)),
+            Err(e) => {
+                warn!("Failed to load {}: {}", path.display(), e);
+                self.osd.show(format!("State {} not loaded", slot));
+            }
+        }
+    }
+
+    /// Reload the watched ROM if its file changed, then load the bookmarked save state, if any.
+    fn check_rom_watch(&mut self) {
+        let Some(watch) = &mut self.watch else {
+            return;
+        };
+        let Some(rom) = watch.changed() else {
+            return;
+        };
+        let (mapper, bookmark) = (watch.mapper, watch.bookmark);
+        info!("ROM changed, reloading");
+        self.reload(rom, mapper);
+        self.osd.show("ROM reloaded");
+        if let Some(slot) = bookmark {
+            self.load_state_slot(slot);
+        }
+    }
+
+    /// Fail unless the bank of a bank qualified address is the one mapped.
+    fn check_bank(&self, addr: BankedAddr) -> Result<(), String> {
+        let mapped = self.banked(addr.addr);
+        match (addr.bank, mapped.bank) {
+            (Some(bank), Some(current)) if bank != current => Err(format!(
+                "bank {:02X} isn't mapped at {:04X}, bank {:02X} is",
+                bank, addr.addr, current
+            )),
+            (Some(_), None) => Err(format!("{:04X} isn't banked", addr.addr)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run a command from the control socket, returning its reply.
+    fn control_command(&mut self, command: Command, paused: &mut bool) -> Result<String, String> {
+        match command {
+            Command::Peek { addr, len } => {
+                self.check_bank(addr)?;
+                Ok((0..len)
+                    .map(|i| format!("{:02X}", self.peek(addr.addr.wrapping_add(i as u16))))
+                    .collect::<Vec<String>>()
+                    .join(" "))
+            }
+            Command::Poke { addr, val } => {
+                self.check_bank(addr)?;
+                self.poke(addr.addr, val);
+                Ok("ok".to_string())
+            }
+            Command::Banks => {
+                let bank_override = self.bank_override();
+                Ok(match bank_override == BankOverride::default() {
+                    true => self.banks().to_string(),
+                    false => format!("{} override {}", self.banks(), bank_override),
+                })
+            }
+            Command::OverrideBank(kind, bank) => {
+                let mut bank_override = self.bank_override();
+                match kind {
+                    BankKind::Rom => bank_override.rom = bank,
+                    BankKind::Ram => bank_override.ram = bank,
+                    BankKind::RamEnabled => bank_override.ram_enabled = bank.map(|val| val != 0),
+                }
+                self.set_bank_override(bank_override);
+                self.osd.show(format!("Banks {}", bank_override));
+                Ok("ok".to_string())
+            }
+            Command::ClearBankOverride => {
+                self.set_bank_override(BankOverride::default());
+                self.osd.show("Banks back to the mapper");
+                Ok("ok".to_string())
+            }
+            Command::Pause | Command::Resume => {
+                *paused = command == Command::Pause;
+                self.osd.show(if *paused { "Paused" } else { "Resumed" });
+                Ok("ok".to_string())
+            }
+            Command::Screenshot(path) => self
+                .screenshot(&path)
+                .map(|()| "ok".to_string())
+                .map_err(|e| e.to_string()),
+            Command::Counters => Ok(self.counters().to_string()),
+            Command::LoadState(slot) => {
+                let Some(game_dir) = &self.game_dir else {
+                    return Err("no data directory".to_string());
+                };
+                if slot >= STATE_SLOTS {
+                    return Err(format!("slots are 0-{}", STATE_SLOTS - 1));
+                }
+                let data = std::fs::read(game_dir.state_path(slot)).map_err(|e| e.to_string())?;
+                self.load_state(&data).map_err(|e| e.to_string())?;
+                self.osd.show(format!("State {} loaded", slot));
+                Ok("ok".to_string())
+            }
+        }
+    }
+
+    /// Export the background and window maps to the screenshots directory, and report how it went on the OSD.
+    fn export_maps(&mut self) {
+        let Some(game_dir) = &self.game_dir else {
+            self.osd.show("No data directory");
+            return;
+        };
+        for (layer, name) in [(Layer::Background, "bg"), (Layer::Window, "window")] {
+            let path = game_dir.screenshots_dir().join(format!(
+                "{}-{}-{}.png",
+                game_dir.key(),
+                name,
+                self.frame
+            ));
+            if let Err(e) = self.map_image(layer).write_png(&path) {
+                warn!("Failed to write {}: {}", path.display(), e);
+                self.osd.show("Maps not exported");
+                return;
+            }
+        }
+        self.osd.show("Maps exported");
+    }
+
+    /// Reload the external assets, the ones that fail to load stay as they were, and report key binding conflicts.
+    fn reload_assets(&mut self, input: &mut dyn InputSource) {
+        let loaded = self.assets.load();
+        if let Some(boot_rom) = loaded.boot_rom {
+            if let Err(e) = self.mmu.borrow_mut().set_boot_rom(Some(boot_rom)) {
+                warn!("Ignoring the boot ROM, {}", e);
+            }
+        }
+        if let Some(palette) = loaded.palette {
+            self.palette = palette;
+        }
+        if let Some(keys) = loaded.keys {
+            input.rebind(&keys);
+        }
+        let conflicts = input.conflicts();
+        for conflict in &conflicts {
+            warn!("Key binding conflict: {}", conflict);
+        }
+        match conflicts.len() {
+            0 => (),
+            1 => self.osd.show("1 key binding conflict, see the log"),
+            n => self
+                .osd
+                .show(format!("{} key binding conflicts, see the log", n)),
+        }
+    }
+
+    /// The entry selected in the OAM viewer, if it's shown.
+    /// Moving the pointer over a sprite selects it, the one with the lowest OAM index where sprites overlap.
+    fn select_sprite(&mut self, pointer: Option<(usize, usize)>) -> Option<OamEntry> {
+        let mut index = self.oam_viewer?;
+        let entries = self.oam_entries();
+        if let Some((x, y)) = pointer.filter(|_| pointer != self.pointer) {
+            if let Some(entry) = entries.iter().find(|entry| entry.covers(x, y)) {
+                index = entry.index;
+            }
+        }
+        self.pointer = pointer;
+        self.oam_viewer = Some(index);
+        entries.get(index).copied()
+    }
+
+    /// Show the Real Time Clock on the OSD, after switching to the next speed if asked to.
+    fn show_rtc(&mut self, next_speed: bool) {
+        let mut mmu = self.mmu.borrow_mut();
+        let Some(rtc) = mmu.rtc_mut() else {
+            drop(mmu);
+            self.osd.show("No RTC");
+            return;
+        };
+        if next_speed {
+            let speed = RTC_SPEEDS
+                .iter()
+                .position(|&speed| speed == rtc.speed())
+                .map_or(RTC_SPEEDS[0], |i| RTC_SPEEDS[(i + 1) % RTC_SPEEDS.len()]);
+            rtc.set_speed(speed);
+        }
+        let text = match rtc.speed() {
+            0 => format!("RTC {} paused", rtc.time()),
+            1 => format!("RTC {}", rtc.time()),
+            speed => format!("RTC {} x{}", rtc.time(), speed),
+        };
+        drop(mmu);
+        self.osd.show(text);
+    }
+
+    /// Switch video to the next window scale, and remember it in the game's settings.
+    fn next_scale(&mut self, video: &mut dyn VideoSink) {
+        let scale = SCALES
+            .iter()
+            .position(|&scale| scale == self.scale)
+            .map_or(SCALES[0], |i| SCALES[(i + 1) % SCALES.len()]);
+        if !video.set_scale(scale) {
+            return;
+        }
+        self.scale = scale;
+        self.osd.show(format!("Scale {}x", scale));
+
+        if let Some(game_dir) = &self.game_dir {
+            if let Err(e) = game_dir.set_config_value("scale", scale as i64) {
+                warn!(
+                    "Failed to save the scale to {}: {}",
+                    game_dir.config_path().display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Switch to the next built-in palette, and remember it for the game.
+    fn next_palette(&mut self) {
+        let next = PRESETS
+            .iter()
+            .position(|(_, palette)| *palette == self.palette)
+            .map_or(0, |i| (i + 1) % PRESETS.len());
+        let (name, palette) = &PRESETS[next];
+        self.palette = palette.clone();
+        self.osd.show(format!("Palette {}", name));
+
+        if let Some(game_dir) = &self.game_dir {
+            if let Err(e) = game_dir.set_config_value("palette", *name) {
+                warn!(
+                    "Failed to save the palette to {}: {}",
+                    game_dir.config_path().display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Turn interlaced rendering on or off, and remember it for the game.
+    fn toggle_interlaced(&mut self) {
+        let mut mmu = self.mmu.borrow_mut();
+        if mmu.ppu_accuracy() != PpuAccuracy::Scanline {
+            self.osd.show("Interlaced needs the scanline renderer");
+            return;
+        }
+        let enabled = !mmu.ppu_interlaced();
+        mmu.ppu_set_interlaced(enabled);
+        drop(mmu);
+        self.osd.show(if enabled {
+            "Interlaced on"
+        } else {
+            "Interlaced off"
+        });
+
+        if let Some(game_dir) = &self.game_dir {
+            if let Err(e) = game_dir.set_config_value("interlaced", enabled) {
+                warn!(
+                    "Failed to save interlacing to {}: {}",
+                    game_dir.config_path().display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Save the pages of battery backed RAM the game changed, once it stops writing, see SaveJournal.
+    fn journal_save(&mut self) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let mut mmu = self.mmu.borrow_mut();
+        let written = mmu.take_ram_written();
+        if !written && !journal.pending() {
+            return;
+        }
+        let Some(save) = mmu.battery_save() else {
+            return;
+        };
+        match journal.frame(written, &save) {
+            Ok(0) => (),
+            Ok(pages) => info!("Saved {} pages of cartridge RAM", pages),
+            Err(e) => warn!("Failed to save cartridge RAM: {}", e),
+        }
+    }
+
+    /// Write the battery backed RAM, and the clock, to the data directory, so the game's progress survives a restart.
+    fn write_battery_save(&self) {
+        let (Some(game_dir), Some(save)) = (&self.game_dir, self.battery_save()) else {
+            return;
+        };
+        match game_dir.write_save(&save) {
+            Ok(()) => info!("Saved cartridge RAM to {}", game_dir.save_path().display()),
+            Err(e) => warn!("Failed to write {}: {}", game_dir.save_path().display(), e),
+        }
+    }
+
+    /// Write the playtime to the data directory.
+    fn write_playtime(&mut self) {
+        let Some(playtime) = &mut self.playtime else {
+            return;
+        };
+        if let Err(e) = playtime.save() {
+            warn!("Failed to write playtime: {}", e);
+        }
+    }
+
+    /// Copy the frame to show into buffer, if the PPU produced one, or the LCD is off and the screen blank.
+    fn show_frame(&self, produced: bool, buffer: &mut [u32]) {
+        let mmu = self.mmu.borrow();
+        if produced || !mmu.ppu_lcd_on() {
+            buffer.copy_from_slice(mmu.ppu_get_viewport());
+        }
+    }
+
+    /// Run a frame, with run-ahead if enabled, and copy the frame to show into buffer.
+    fn step_frame(&mut self, buffer: &mut [u32]) {
+        if !self.run_ahead {
+            let produced = self.run_frame();
+            self.show_frame(produced, buffer);
+            return;
+        }
+
+        // Run the real frame, then peek one frame ahead with the same input.
+        // The peeked frame is the one shown, and the machine is rolled back to the real frame,
+        // so a change of input is seen on screen one frame sooner.
+        self.run_frame();
+        if self.fault.is_some() || self.watch_hit.is_some() {
+            return;
+        }
+        let state = self.save_state();
+        let trace = self.cpu.trace().clone();
+        // The peeked frame is rolled back, so the serial device mustn't see it.
+        self.mmu.borrow_mut().set_serial_output(false);
+        let device = self
+            .mmu
+            .borrow_mut()
+            .set_serial_device(Box::new(NullDevice));
+        let timeline = self.mmu.borrow_mut().take_timeline();
+        let audio = self.audio.take();
+        let produced = self.run_frame();
+        self.show_frame(produced, buffer);
+        if let Some(timeline) = timeline {
+            self.mmu.borrow_mut().set_timeline(timeline);
+        }
+        self.audio = audio;
+        self.mmu.borrow_mut().set_serial_output(true);
+        self.mmu.borrow_mut().set_serial_device(device);
+        self.load_state(&state)
+            .expect("Failed to roll back a run-ahead frame");
+        self.cpu.set_trace(trace);
+        // A fault or watchpoint hit in the peeked frame is seen again when the frame is run for real.
+        self.fault = None;
+        self.watch_hit = None;
+    }
+
+    /// Whether the run is being recorded, to a timeline or a hash file.
+    fn is_recording(&self) -> bool {
+        self.mmu.borrow().has_timeline()
+            || self.hash_audit.as_ref().is_some_and(|a| a.is_recording())
+    }
+
+    /// Run Gameboy emulation in a minifb window, with keyboard input.
+    pub fn run(&mut self) {
+        let mut keymap = frontend::minifb::default_keymap();
+        keymap.set_turbo_rate(self.turbo_rate);
+        let hotkeys = frontend::minifb::default_hotkeys();
+        let title = format!("ferrum - {}", self.mmu.borrow().rom_title());
+        if self.presentation == Presentation::TripleBuffer {
+            if !cfg!(target_os = "macos") {
+                let (mut video, mut input) = frontend::minifb::open_threaded(
+                    &title,
+                    self.scale,
+                    self.refresh_rate,
+                    keymap,
+                    hotkeys,
+                );
+                self.run_with(&mut video, &mut input);
+                return;
+            }
+            warn!("Triple buffering needs the window on its own thread, which macOS doesn't allow, using the limiter");
+        }
+        let (mut video, mut input) =
+            frontend::minifb::open(&title, self.scale, self.refresh_rate, keymap, hotkeys);
+        self.run_with(&mut video, &mut input);
+    }
+
+    /// Run Gameboy emulation on a Linux framebuffer device, e.g. /dev/fb0, with input from every evdev device,
+    /// for an SBC without a desktop. Needs the fbdev feature.
+    pub fn run_fbdev(&mut self, device: &std::path::Path) -> std::io::Result<()> {
+        #[cfg(all(feature = "fbdev", target_os = "linux"))]
+        {
+            let mut keymap = frontend::fbdev::default_keymap();
+            keymap.set_turbo_rate(self.turbo_rate);
+            let (mut video, mut input) = frontend::fbdev::open(
+                device,
+                self.refresh_rate,
+                keymap,
+                frontend::fbdev::default_hotkeys(),
+            )?;
+            self.run_with(&mut video, &mut input);
+            Ok(())
+        }
+
+        #[cfg(not(all(feature = "fbdev", target_os = "linux")))]
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!(
+                "built without the fbdev feature (Linux only), can't open {}",
+                device.display()
+            ),
+        ))
+    }
+
+    /// Run Gameboy emulation, showing frames on video and reading the Joypad from input, until input quits.
+    pub fn run_with(&mut self, video: &mut dyn VideoSink, input: &mut dyn InputSource) {
+        warn!("Emulation loop is a work in progress, no threading or event handling.");
+
+        // Initialize Audio
+        self.init_audio();
+
+        // buffer holds the last frame from the PPU, screen is what gets shown (the frame plus the OSD),
+        // encoded is screen in the sink's pixel format, if it takes another.
+        let mut buffer = [0u32; SCREEN_PIXELS];
+        let mut screen = [0u32; SCREEN_PIXELS];
+        let mut encoded = Vec::new();
+        frontend::present(video, &buffer, SCREEN_WIDTH, &mut encoded);
+
+        // Emulation loop
+        // When the whole system is sped up (or slowed down), each displayed frame is worth
+        // clock multiplier emulated frames. Fractions carry over to the next displayed frame.
+        let mut frames_per_update = match self.clock_scope {
+            ClockScope::Cpu => 1.0,
+            ClockScope::System => self.clock_multiplier,
+        };
+        // A sink that paces emulation presents at its refresh rate, one that doesn't keeps up with emulation.
+        let refresh_rate = video.refresh_rate().unwrap_or(FRAME_RATE);
+        let resample_ratio = match self.pacing {
+            Pacing::Host => FRAME_RATE / refresh_rate,
+            Pacing::Exact => {
+                frames_per_update *= FRAME_RATE / refresh_rate;
+                1.0
+            }
+        };
+        self.resample_audio(resample_ratio);
+        let mut frame_credit = 0.0;
+        let mut fast_forward_speed = 1;
+        let mut emulate = true;
+        let mut paused = false;
+
+        // Whether the window is in the background, and the mute setting from before it went there.
+        let mut background = false;
+        let mut muted = false;
+
+        // Frames shown and emulated since the last status update.
+        let mut status = Status {
+            title: self.mmu.borrow().rom_title(),
+            recording: self.is_recording(),
+            playtime: self.playtime(),
+            ..Default::default()
+        };
+        let mut status_time = Instant::now();
+        let mut status_frames = (0u32, self.frame);
+        input.rebind(&self.bindings);
+        self.reload_assets(input);
+        while emulate {
+            let frame_start = Instant::now();
+            if self.background != BackgroundPolicy::Run && input.focused() == background {
+                background = !background;
+                match self.background {
+                    BackgroundPolicy::Mute => {
+                        let mut mmu = self.mmu.borrow_mut();
+                        let mixer = mmu.apu_mixer_mut();
+                        if background {
+                            muted = std::mem::replace(&mut mixer.muted, true);
+                        } else {
+                            mixer.muted = muted;
+                        }
+                    }
+                    BackgroundPolicy::Pause => {
+                        status.paused = paused || background;
+                        video.status(&status);
+                        status_time = Instant::now();
+                        status_frames = (0, self.frame);
+                    }
+                    BackgroundPolicy::Run => (),
+                }
+            }
+            let idle = background && self.background == BackgroundPolicy::Pause;
+            let (playing, first_frame) = (!paused && !idle, self.frame);
+
+            // Sample the Joypad at the start of each frame, so the frame sees the freshest input.
+            if playing {
+                // Keep the sound card's buffer at its target by emulating a little faster or slower, see audio::rate_nudge.
+                // Paced to the host, frames go at its refresh rate, so the audio is resampled a little instead.
+                let nudge = self
+                    .audio
+                    .as_ref()
+                    .and_then(|audio| audio.buffer_stats())
+                    .map_or(1.0, |stats| audio::rate_nudge(&stats));
+
+                // Fast-forwarding, each displayed frame is worth speed times as many emulated frames,
+                // uncapped, as many as fit in a refresh, at the speed that came to last time.
+                // Muted and raw sound is resampled down by the speed, to keep the sound card's buffer at its target.
+                let speed = match (self.fast_forwarding, self.fast_forward.factor()) {
+                    (false, _) => 1,
+                    (true, Some(factor)) => factor,
+                    (true, None) => fast_forward_speed,
+                };
+                let deadline = (self.fast_forwarding && self.fast_forward.factor().is_none())
+                    .then(|| frame_start + Duration::from_secs_f64(1.0 / refresh_rate));
+                let audio_speed = match self.fast_forward_audio {
+                    FastForwardAudio::Pitch => 1.0,
+                    _ => speed as f64,
+                };
+                self.mix_fast_forward(speed);
+                let credit = match deadline {
+                    Some(_) => frames_per_update,
+                    None => frames_per_update * speed as f64,
+                };
+                match self.pacing {
+                    Pacing::Host => {
+                        self.resample_audio(resample_ratio * nudge / audio_speed);
+                        frame_credit += credit;
+                    }
+                    Pacing::Exact => {
+                        self.resample_audio(resample_ratio / audio_speed);
+                        frame_credit += credit * nudge;
+                    }
+                }
+                while frame_credit >= 1.0 || deadline.is_some_and(|d| Instant::now() < d) {
+                    let buttons = input.poll();
+                    self.set_buttons(buttons);
+                    self.step_frame(&mut buffer);
+                    frame_credit = (frame_credit - 1.0).max(0.0);
+                    if let Some(report) = self.stall {
+                        warn!("Stalled: {}, at frame {}", report, self.frame);
+                        eprintln!("{}", self.trace_dump(&report.stall.to_string()));
+                        paused = true;
+                        status.paused = true;
+                        video.status(&status);
+                        status_time = Instant::now();
+                        status_frames = (0, self.frame);
+                        frame_credit = 0.0;
+                        break;
+                    }
+                    if let Some(hit) = self.watch_hit {
+                        warn!(
+                            "Watchpoint: {}, ${:04X}=${:02X}, at frame {}",
+                            hit, hit.addr, hit.val, self.frame
+                        );
+                        paused = true;
+                        status.paused = true;
+                        video.status(&status);
+                        status_time = Instant::now();
+                        status_frames = (0, self.frame);
+                        frame_credit = 0.0;
+                        break;
+                    }
+                    if let Some(fault) = self.fault {
+                        warn!("Strict mode: {}, at frame {}", fault, self.frame);
+                        match self.fault_policy {
+                            FaultPolicy::Pause => {
+                                paused = true;
+                                status.paused = true;
+                                video.status(&status);
+                                status_time = Instant::now();
+                                status_frames = (0, self.frame);
+                            }
+                            FaultPolicy::Exit => emulate = false,
+                            FaultPolicy::Ignore => (),
+                        }
+                        frame_credit = 0.0;
+                        break;
+                    }
+                }
+                if deadline.is_some() {
+                    let frames = (self.frame - first_frame) as f64 / frames_per_update;
+                    fast_forward_speed = (frames.round() as u32).max(1);
+                }
+            }
+
+            // Save battery backed RAM once the game is done writing it, even while paused.
+            self.journal_save();
+            let emulate_time = frame_start.elapsed();
+
+            // The motor stops while emulation does.
+            let strength = match (self.motor, playing) {
+                (Some(strength), true) => Some(strength),
+                (Some(_), false) => Some(0.0),
+                (None, _) => None,
+            };
+            let shaking = match (self.rumble, strength) {
+                (Rumble::Shake, Some(strength)) => strength,
+                _ => 0.0,
+            };
+
+            let mut overlay = Vec::new();
+            if self.show_counters {
+                let counters = self.counters();
+                overlay.extend([
+                    format!("Time {}", counters.clock()),
+                    format!("Frames {}/{}", counters.rendered, counters.frames),
+                    format!("Cycles {}", counters.cycles),
+                ]);
+            }
+            if let Some(race) = &self.race {
+                overlay.extend(race.lines(self.counters()));
+            }
+            if self.fast_forwarding {
+                let speed = self.fast_forward.factor().unwrap_or(fast_forward_speed);
+                overlay.push(self.fast_forward_line(speed));
+            }
+            if self.show_frame_times {
+                if let Some(audio) = &self.audio {
+                    if let Some(stats) = audio.buffer_stats() {
+                        overlay.extend(stats.lines(audio.sample_rate()));
+                    }
+                }
+            }
+            // The fault, or watchpoint hit, that paused emulation stays up until it's resumed.
+            if let (true, Some(fault)) = (paused, self.fault) {
+                overlay.push(fault.to_string());
+            }
+            if let (true, Some(hit)) = (paused, self.watch_hit) {
+                overlay.push(hit.to_string());
+            }
+            if let (true, Some(report)) = (paused, self.stall) {
+                overlay.extend(report.lines());
+            }
+            let selected = self.select_sprite(input.pointer());
+            if let Some(entry) = &selected {
+                overlay.extend(entry.describe());
+            }
+            self.osd.set_overlay(overlay);
+
+            // Draw the OSD on top of the last frame.
+            // The video sink paces emulation, e.g. the minifb window keeps it at ~60 frames per second.
+            screen.copy_from_slice(&buffer);
+            self.palette.apply(&mut screen);
+            if self.show_frame_times {
+                self.frame_times.draw(&mut screen);
+            }
+            if self.show_io {
+                let io = self.io_snapshot();
+                io.draw(self.last_io.as_ref(), &mut screen);
+                self.last_io = Some(io);
+            }
+            if let Some(entry) = selected {
+                let (x, y) = entry.screen_pos();
+                osd::outline(
+                    &mut screen,
+                    x - 1,
+                    y - 1,
+                    10,
+                    entry.height as isize + 2,
+                    SPRITE_OUTLINE_COLOR,
+                );
+            }
+            self.osd.draw(&mut screen);
+            if shaking > 0.0 {
+                let offset = (shaking * SHAKE_PIXELS).ceil() as isize;
+                let offset = if self.frame.is_multiple_of(2) {
+                    offset
+                } else {
+                    -offset
+                };
+                shake(&mut screen, SCREEN_WIDTH, offset);
+            }
+            let present_start = Instant::now();
+            // In widescreen, the sides come straight from the PPU, around the screen with its OSD.
+            match self.widescreen() {
+                Some(mut wide) => {
+                    self.palette.apply(&mut wide);
+                    for (row, screen_row) in wide
+                        .chunks_exact_mut(WIDESCREEN_WIDTH)
+                        .zip(screen.chunks_exact(SCREEN_WIDTH))
+                    {
+                        row[WIDESCREEN_BORDER..WIDESCREEN_BORDER + SCREEN_WIDTH]
+                            .copy_from_slice(screen_row);
+                    }
+                    frontend::present(video, &wide, WIDESCREEN_WIDTH, &mut encoded);
+                }
+                None => frontend::present(video, &screen, SCREEN_WIDTH, &mut encoded),
+            }
+            let present_time = present_start.elapsed();
+            status_frames.0 += 1;
+            if idle {
+                std::thread::sleep(BACKGROUND_POLL);
+            }
+
+            // Update the status once per second, speed is relative to a real Gameboy's frame rate.
+            let elapsed = status_time.elapsed();
+            if elapsed >= STATUS_INTERVAL {
+                let seconds = elapsed.as_secs_f64();
+                status.fps = status_frames.0 as f64 / seconds;
+                status.speed = (self.frame - status_frames.1) as f64 / seconds / FRAME_RATE * 100.0;
+                status.paused = paused || idle;
+                status.recording = self.is_recording();
+                status.playtime = self.playtime();
+                video.status(&status);
+                status_time = Instant::now();
+                status_frames = (0, self.frame);
+
+                // Watch the external assets for changes as often.
+                if self.assets.changed() {
+                    self.reload_assets(input);
+                    self.osd.show("Assets reloaded");
+                }
+                self.check_rom_watch();
+            }
+
+            // Handle hotkeys.
+            for hotkey in input.hotkeys() {
+                match hotkey {
+                    Hotkey::Quit => emulate = false,
+                    Hotkey::Pause => {
+                        paused = !paused;
+                        self.osd.show(if paused { "Paused" } else { "Resumed" });
+                        status.paused = paused || idle;
+                        video.status(&status);
+                        status_time = Instant::now();
+                        status_frames = (0, self.frame);
+                    }
+                    Hotkey::SaveState => self.save_state_slot(),
+                    Hotkey::ExportMaps => self.export_maps(),
+                    Hotkey::VolumeUp => self.change_volume(VOLUME_STEP, false),
+                    Hotkey::VolumeDown => self.change_volume(-VOLUME_STEP, false),
+                    Hotkey::Mute => self.change_volume(0.0, true),
+                    Hotkey::ShowRtc => self.show_rtc(false),
+                    Hotkey::RtcSpeed => self.show_rtc(true),
+                    Hotkey::Scale => self.next_scale(video),
+                    Hotkey::Counters => self.show_counters = !self.show_counters,
+                    Hotkey::FrameTimes => self.show_frame_times = !self.show_frame_times,
+                    Hotkey::IoViewer => {
+                        self.show_io = !self.show_io;
+                        self.last_io = None;
+                    }
+                    Hotkey::OamViewer => {
+                        self.oam_viewer = match self.oam_viewer {
+                            Some(_) => None,
+                            None => Some(0),
+                        };
+                    }
+                    Hotkey::ReloadAssets => {
+                        self.reload_assets(input);
+                        self.osd.show("Assets reloaded");
+                    }
+                    Hotkey::NextPalette => self.next_palette(),
+                    Hotkey::Interlace => self.toggle_interlaced(),
+                    Hotkey::RaceAnchor => {
+                        let state = self.save_state();
+                        match self.set_race_anchor(state) {
+                            Ok(()) => self.osd.show("Race anchor set"),
+                            Err(e) => warn!("Failed to set the race anchor: {}", e),
+                        }
+                    }
+                    Hotkey::FastForward => {
+                        self.set_fast_forwarding(!self.fast_forwarding);
+                        if !self.fast_forwarding {
+                            self.osd.show("Normal speed");
+                        }
+                    }
+                    Hotkey::RaceReset => match &self.race {
+                        Some(_) => {
+                            if let Err(e) = self.race_reset() {
+                                warn!("Failed to reset to the race anchor: {}", e);
+                                self.osd.show("Race anchor not loaded");
+                            }
+                        }
+                        None => self.osd.show("No race anchor set"),
+                    },
+                    Hotkey::PrevSprite | Hotkey::NextSprite => {
+                        if let Some(index) = &mut self.oam_viewer {
+                            *index = if hotkey == Hotkey::PrevSprite {
+                                (*index + OAM_ENTRIES - 1) % OAM_ENTRIES
+                            } else {
+                                (*index + 1) % OAM_ENTRIES
+                            };
+                        }
+                    }
+                    Hotkey::LoadState => self.load_state_slot(self.state_slot),
+                    Hotkey::PrevStateSlot | Hotkey::NextStateSlot => {
+                        self.state_slot = if hotkey == Hotkey::PrevStateSlot {
+                            (self.state_slot + STATE_SLOTS - 1) % STATE_SLOTS
+                        } else {
+                            (self.state_slot + 1) % STATE_SLOTS
+                        };
+                        self.show_state_slot();
+                    }
+                }
+            }
+
+            // Handle commands from the control socket.
+            if let Some(mut control) = self.control.take() {
+                let was_paused = paused;
+                control.poll(|command| self.control_command(command, &mut paused));
+                self.control = Some(control);
+                if paused != was_paused {
+                    status.paused = paused || idle;
+                    video.status(&status);
+                    status_time = Instant::now();
+                    status_frames = (0, self.frame);
+                }
+            }
+
+            self.frame_times.record(FrameTime {
+                emulate: emulate_time,
+                present: present_time,
+                total: frame_start.elapsed(),
+            });
+
+            // Time paused, or idle in the background, isn't played.
+            if let (true, Some(playtime)) = (playing, &mut self.playtime) {
+                let emulated = (self.frame - first_frame) as f64 / FRAME_RATE;
+                if let Err(e) =
+                    playtime.add(frame_start.elapsed(), Duration::from_secs_f64(emulated))
+                {
+                    warn!("Failed to write playtime: {}", e);
+                }
+            }
+        }
+        self.write_battery_save();
+        self.write_playtime();
+        self.frame_times.flush();
+        if self.frame_times.is_recording() {
+            println!("\nFrame times: {}", self.frame_times);
+        } else {
+            info!("Frame times: {}", self.frame_times);
+        }
+        if let Some(stats) = self.audio.as_ref().and_then(|audio| audio.buffer_stats()) {
+            info!(
+                "Audio: {} underruns, {} overruns",
+                stats.underruns, stats.overruns
+            );
+        }
+        for line in self.unsupported_io_summary() {
+            println!("\n{}", line);
         }
-        // TODO: Handle emulation exit, such as saving RAM to file...
         println!("\nkthxbai <3");
     }
 }
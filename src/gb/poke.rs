@@ -0,0 +1,45 @@
+use std::str::FromStr;
+
+/// A byte written to memory while running, a quick RAM patch or trainer given on the command line.
+///
+/// C123=05         Write $05 to $C123 once the boot ROM is done
+/// 0xC123=0x05@600 Write it at the start of frame 600 (counted from power on) instead
+///
+/// The write goes through the bus like the CPU's, so writes to ROM addresses reach the cartridge's mapper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Poke {
+    pub addr: u16,
+    pub val: u8,
+
+    /// Frame to write at, or None for the first frame after the boot ROM is done.
+    pub frame: Option<u64>,
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    let digits = s.trim_start_matches("0x").trim_start_matches('$');
+    u32::from_str_radix(digits, 16).ok()
+}
+
+impl FromStr for Poke {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (patch, frame) = match s.split_once('@') {
+            Some((patch, frame)) => match frame.parse() {
+                Ok(frame) => (patch, Some(frame)),
+                Err(_) => return Err(format!("`{}` isn't a frame", frame)),
+            },
+            None => (s, None),
+        };
+        let Some((addr, val)) = patch.split_once('=') else {
+            return Err(format!("`{}` isn't ADDR=VAL", patch));
+        };
+        let addr = parse_hex(addr)
+            .and_then(|addr| u16::try_from(addr).ok())
+            .ok_or(format!("`{}` isn't an address", addr))?;
+        let val = parse_hex(val)
+            .and_then(|val| u8::try_from(val).ok())
+            .ok_or(format!("`{}` isn't a byte", val))?;
+        Ok(Self { addr, val, frame })
+    }
+}
@@ -0,0 +1,57 @@
+use super::Counters;
+
+/// Race mode
+/// Speedrun practice: an anchor save state, and a reset back to it on a single hotkey, as many times as it takes.
+/// Every attempt starts from exactly the anchor, with the attempt counter and timer shown on the OSD.
+/// The timer runs on emulated time since the reset, so it stops while paused, like the game's own timers.
+pub struct Race {
+    anchor: Vec<u8>,
+
+    /// Attempts since the anchor was set, counting the one in progress.
+    attempts: u32,
+
+    /// The machine's counters at the start of the attempt.
+    start: Counters,
+}
+
+impl Race {
+    /// Race from an anchor state, the machine having just loaded it with these counters.
+    pub(super) fn new(anchor: Vec<u8>, start: Counters) -> Self {
+        Self {
+            anchor,
+            attempts: 1,
+            start,
+        }
+    }
+
+    pub(super) fn anchor(&self) -> &[u8] {
+        &self.anchor
+    }
+
+    /// Start the next attempt, the machine having just loaded the anchor again.
+    pub(super) fn next_attempt(&mut self, start: Counters) {
+        self.attempts += 1;
+        self.start = start;
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Emulated time into the attempt, at the machine's counters now.
+    pub fn elapsed(&self, now: Counters) -> Counters {
+        Counters {
+            cycles: now.cycles.saturating_sub(self.start.cycles),
+            frames: now.frames.saturating_sub(self.start.frames),
+            rendered: now.rendered.saturating_sub(self.start.rendered),
+        }
+    }
+
+    /// OSD lines, e.g. "Attempt 12" and "0:01:07.250".
+    pub fn lines(&self, now: Counters) -> Vec<String> {
+        vec![
+            format!("Attempt {}", self.attempts),
+            self.elapsed(now).clock(),
+        ]
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cartridge::banks::BankedAddr;
+use std::fmt;
+
+/// Frames the LCD can stay off before it's taken as a crash, 5 seconds.
+/// Games turn it off to load graphics, for a few frames at a time.
+const LCD_OFF_FRAMES: u32 = 300;
+
+/// How a game went dark, or froze, for good, typically after a crash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stall {
+    /// The LCD has been off for this many frames.
+    LcdOff(u32),
+
+    /// The CPU is halted with no interrupt enabled in IE, nothing can wake it up.
+    Halted,
+}
+
+/// "LCD off for 5 seconds", or "HALT with no interrupt enabled".
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stall::LcdOff(frames) => write!(f, "LCD off for {} seconds", frames / 60),
+            Stall::Halted => write!(f, "HALT with no interrupt enabled"),
+        }
+    }
+}
+
+/// A stall, and what the machine was doing, for the OSD and the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StallReport {
+    pub stall: Stall,
+    pub pc: BankedAddr,
+    pub halted: bool,
+    pub ime: bool,
+
+    /// The interrupt enable and flag registers.
+    pub ie: u8,
+    pub if_: u8,
+    pub lcdc: u8,
+
+    /// Frames since the last write to VRAM.
+    pub vram_idle: u32,
+}
+
+impl StallReport {
+    /// Short lines for the OSD, with the hotkeys to go on from there.
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            self.stall.to_string(),
+            format!("PC {} IE {:02X} IF {:02X}", self.pc, self.ie, self.if_),
+            "P resumes, I shows IO".to_string(),
+        ]
+    }
+}
+
+/// e.g. "LCD off for 5 seconds, at 03:4F20 (halted, IME 0, IE $00, IF $E1, LCDC $11), no VRAM writes for 300 frames"
+impl fmt::Display for StallReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, at {} ({}, IME {}, IE ${:02X}, IF ${:02X}, LCDC ${:02X}), no VRAM writes for {} frames",
+            self.stall,
+            self.pc,
+            if self.halted { "halted" } else { "running" },
+            self.ime as u8,
+            self.ie,
+            self.if_,
+            self.lcdc,
+            self.vram_idle
+        )
+    }
+}
+
+/// Stall watch
+/// Looks at the machine after every frame for a black screen that won't come back: the LCD off for seconds,
+/// or the CPU halted with nothing to wake it. Each stall is reported once, when it starts, and again only
+/// after the machine recovered from it.
+#[derive(Default)]
+pub struct StallWatch {
+    /// Frames the LCD has been off, and since the last VRAM write.
+    lcd_off: u32,
+    vram_idle: u32,
+    reported: bool,
+}
+
+impl StallWatch {
+    /// Update with how the machine ended a frame, returns the stall if it just started.
+    pub fn frame(
+        &mut self,
+        lcd_on: bool,
+        vram_written: bool,
+        halted_for_good: bool,
+    ) -> Option<Stall> {
+        self.lcd_off = if lcd_on { 0 } else { self.lcd_off + 1 };
+        self.vram_idle = if vram_written { 0 } else { self.vram_idle + 1 };
+        let stall = match (halted_for_good, self.lcd_off >= LCD_OFF_FRAMES) {
+            (true, _) => Some(Stall::Halted),
+            (false, true) => Some(Stall::LcdOff(self.lcd_off)),
+            (false, false) => None,
+        };
+        match (stall, self.reported) {
+            (Some(stall), false) => {
+                self.reported = true;
+                Some(stall)
+            }
+            (None, _) => {
+                self.reported = false;
+                None
+            }
+            (Some(_), true) => None,
+        }
+    }
+
+    /// Frames since the last write to VRAM.
+    pub fn vram_idle(&self) -> u32 {
+        self.vram_idle
+    }
+}
@@ -0,0 +1,71 @@
+use crate::cartridge::Mapper;
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// ROM watch
+/// Reloads the ROM whenever its file changes, for homebrew: assemble, and the new build is running,
+/// without restarting the emulator. A reload is a hard reset with the new ROM, battery backed RAM survives it,
+/// and a bookmarked save state slot can be loaded right after, to get straight back to the part being worked on.
+pub struct RomWatch {
+    path: PathBuf,
+
+    /// Modification time of the file when it was last loaded.
+    modified: Option<SystemTime>,
+
+    /// Mapper to use whatever the header says, if forced.
+    pub(super) mapper: Option<Mapper>,
+
+    /// Save state slot to load after each reload.
+    pub(super) bookmark: Option<u8>,
+}
+
+impl RomWatch {
+    /// Watch the ROM file the machine was loaded from, changes from now on reload it.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = Self::modified(&path);
+        Self {
+            path,
+            modified,
+            mapper: None,
+            bookmark: None,
+        }
+    }
+
+    /// Load new builds with the given mapper, like the first one.
+    pub fn set_mapper(&mut self, mapper: Mapper) {
+        self.mapper = Some(mapper);
+    }
+
+    /// Load a save state slot after every reload.
+    pub fn set_bookmark(&mut self, slot: u8) {
+        self.bookmark = Some(slot);
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// The new ROM image, if the file changed since it was last loaded.
+    /// An empty file is retried on the next call, an assembler may be halfway through writing it.
+    pub(super) fn changed(&mut self) -> Option<Vec<u8>> {
+        let modified = Self::modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        match fs::read(&self.path) {
+            Ok(rom) if !rom.is_empty() => {
+                self.modified = modified;
+                Some(rom)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                self.modified = modified;
+                warn!("Failed to read {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Cell of a test missing from a run.
+const MISSING: &str = "-";
+
+/// Golden image history
+/// The results of golden image runs over time, as a Markdown table, a row per test and a column per run,
+/// so progress on a test ROM pack (and regressions) show at a glance:
+///
+/// | Test | 2026-10-01 | 2026-10-16 |
+/// | --- | --- | --- |
+/// | m3_bgp_change | FAIL | PASS |
+///
+/// Cells are PASS, FAIL, SKIP, or - when the test wasn't in the suite yet.
+pub struct History {
+    runs: Vec<String>,
+    tests: Vec<(String, Vec<String>)>,
+}
+
+/// How the latest run compares to the one before it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Tests passing now, that ran before and didn't pass.
+    pub fixed: Vec<String>,
+
+    /// Tests that passed before, and don't now.
+    pub regressed: Vec<String>,
+}
+
+impl History {
+    /// Read a history file, an empty history if there's none yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut rows = text
+            .lines()
+            .filter(|line| line.starts_with('|'))
+            .map(|line| {
+                line.trim_matches('|')
+                    .split('|')
+                    .map(|cell| cell.trim().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| cells.first().is_some_and(|cell| cell != "---"));
+        let runs = rows
+            .next()
+            .map(|header| header[1..].to_vec())
+            .unwrap_or_default();
+        let tests = rows
+            .map(|mut cells| {
+                let name = cells.remove(0);
+                cells.resize(runs.len(), MISSING.to_string());
+                (name, cells)
+            })
+            .collect();
+        Ok(Self { runs, tests })
+    }
+
+    /// Add a run, labeled e.g. with the date or a commit, with the result of each test by name.
+    pub fn add_run(&mut self, label: &str, results: &[(String, &str)]) {
+        self.runs.push(label.to_string());
+        for (_, cells) in &mut self.tests {
+            cells.push(MISSING.to_string());
+        }
+        for (name, result) in results {
+            let index = match self.tests.iter().position(|(test, _)| test == name) {
+                Some(index) => index,
+                None => {
+                    self.tests
+                        .push((name.clone(), vec![MISSING.to_string(); self.runs.len()]));
+                    self.tests.len() - 1
+                }
+            };
+            *self.tests[index].1.last_mut().unwrap() = result.to_string();
+        }
+    }
+
+    /// The tests that started, or stopped, passing in the last run.
+    pub fn progress(&self) -> Progress {
+        let mut progress = Progress::default();
+        for (name, cells) in &self.tests {
+            let [.., before, now] = cells.as_slice() else {
+                continue;
+            };
+            match (before == "PASS", now == "PASS") {
+                (false, true) if before != MISSING => progress.fixed.push(name.clone()),
+                (true, false) => progress.regressed.push(name.clone()),
+                _ => (),
+            }
+        }
+        progress
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::from("# Golden image history\n\n| Test |");
+        for run in &self.runs {
+            text.push_str(&format!(" {} |", run));
+        }
+        text.push_str(&format!("\n|{}\n", " --- |".repeat(self.runs.len() + 1)));
+        for (name, cells) in &self.tests {
+            text.push_str(&format!("| {} | {} |\n", name, cells.join(" | ")));
+        }
+        fs::write(path, text)
+    }
+}
+
+/// Today's date in UTC, e.g. 2026-10-16, the label of a run that isn't given one.
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() / 86400) as i64;
+    // Days since 1970-01-01 to a civil date, http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,220 @@
+use crate::gb::GameBoy;
+use crate::ppu::debug::IndexedImage;
+use crate::ppu::{PpuAccuracy, SCREEN_HEIGHT, SCREEN_PIXELS, SCREEN_WIDTH};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+mod history;
+
+pub use history::{today, History, Progress};
+
+/// Palette of diff images, the 4 shades, then mismatched pixels.
+const DIFF_PALETTE: [u32; 5] = [0x00FFFFFF, 0x00AAAAAA, 0x00555555, 0x00000000, 0x00FF0000];
+const MISMATCH: u8 = 4;
+
+/// Blargg's test ROMs write this to $A001-$A003 once the result at $A000, and the text at $A004, are valid.
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+/// Result code at $A000 while the test is still running.
+const BLARGG_RUNNING: u8 = 0x80;
+
+/// Golden image tests
+/// Test ROMs like dmg-acid2 draw a picture and stop, which is compared to a screenshot from real hardware.
+/// A suite is a TOML file listing the tests, paths are relative to the suite file:
+///
+/// [[test]]
+/// name = "dmg-acid2"
+/// rom = "acid2/dmg-acid2.gb"
+/// reference = "acid2/dmg-acid2-dmg.png"
+/// frames = 600
+///
+/// Tests run headless, on the scanline renderer unless ppu-accuracy is given, e.g. "fifo" for the experimental
+/// pixel FIFO, see PpuAccuracy.
+/// Images are compared by shade, so references can use any 4 grey (or green) levels.
+///
+/// A test without a reference is one of Blargg's that report their result in cartridge RAM, e.g. dmg_sound.
+/// It passes once the ROM reports passing, frames is how long it gets to finish.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    #[serde(rename = "test", default)]
+    pub tests: Vec<GoldenTest>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GoldenTest {
+    pub name: String,
+    pub rom: PathBuf,
+    pub reference: Option<PathBuf>,
+
+    /// Frames to run before taking the screenshot, or at most, for the result in memory.
+    pub frames: u64,
+    pub ppu_accuracy: Option<PpuAccuracy>,
+}
+
+/// Result of a golden image test.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+
+    /// The screenshot differs from the reference in this many pixels.
+    Fail(usize),
+
+    /// The test ROM reported failing, or didn't finish, with its result code and the last line it printed.
+    Reported(u8, String),
+
+    /// The test couldn't run, e.g. the ROM isn't there.
+    Skipped(String),
+}
+
+impl Suite {
+    /// Read a suite file, resolving the paths of its tests against the suite's directory.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut suite: Suite = toml::from_str(&text).map_err(|e| e.to_string())?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for test in &mut suite.tests {
+            test.rom = dir.join(&test.rom);
+            test.reference = test.reference.as_ref().map(|reference| dir.join(reference));
+        }
+        Ok(suite)
+    }
+}
+
+impl GoldenTest {
+    /// Run the test, writing a diff image (mismatched pixels in red) to diff_dir when it fails.
+    pub fn run(&self, diff_dir: Option<&Path>) -> Outcome {
+        let rom = match fs::read(&self.rom) {
+            Ok(rom) => rom,
+            Err(e) => return Outcome::Skipped(format!("{}: {}", self.rom.display(), e)),
+        };
+        let mut ferrum = GameBoy::from_rom(rom, None);
+        ferrum.set_serial_output(false);
+        ferrum.set_ppu_accuracy(self.ppu_accuracy.unwrap_or(PpuAccuracy::Scanline));
+        let Some(reference) = &self.reference else {
+            return self.run_reported(&mut ferrum);
+        };
+        let reference = match read_reference(reference) {
+            Ok(reference) => reference,
+            Err(e) => return Outcome::Skipped(format!("{}: {}", reference.display(), e)),
+        };
+        for _ in 0..self.frames {
+            ferrum.run_frame();
+        }
+        let screenshot = shades(&ferrum.viewport());
+
+        let mismatched = screenshot
+            .iter()
+            .zip(&reference)
+            .filter(|(a, b)| a != b)
+            .count();
+        if mismatched == 0 {
+            return Outcome::Pass;
+        }
+
+        if let Some(dir) = diff_dir {
+            let path = dir.join(format!("{}.png", self.name));
+            if let Err(e) = diff_image(&screenshot, &reference).write_png(&path) {
+                log::warn!("Failed to write {}: {}", path.display(), e);
+            }
+        }
+        Outcome::Fail(mismatched)
+    }
+
+    /// Run a test ROM until it reports its result in cartridge RAM, the way Blargg's do.
+    fn run_reported(&self, ferrum: &mut GameBoy) -> Outcome {
+        let mut result = BLARGG_RUNNING;
+        for _ in 0..self.frames {
+            ferrum.run_frame();
+            let signature = [0xA001, 0xA002, 0xA003].map(|addr| ferrum.peek(addr));
+            if signature == BLARGG_SIGNATURE {
+                result = ferrum.peek(0xA000);
+                if result != BLARGG_RUNNING {
+                    break;
+                }
+            }
+        }
+        if result == 0x00 {
+            return Outcome::Pass;
+        }
+
+        let text: Vec<u8> = (0xA004..0xC000)
+            .map(|addr| ferrum.peek(addr))
+            .take_while(|&c| c != 0x00)
+            .collect();
+        let text = String::from_utf8_lossy(&text);
+        let last = text.lines().rev().find(|line| !line.trim().is_empty());
+        let last = match (result, last) {
+            (BLARGG_RUNNING, _) => format!("didn't finish in {} frames", self.frames),
+            (_, Some(line)) => line.trim().to_string(),
+            (_, None) => String::new(),
+        };
+        Outcome::Reported(result, last)
+    }
+}
+
+/// Shade (0 = white - 3 = black) of an 0x00RRGGBB color, from its brightness.
+fn shade(color: u32) -> u8 {
+    let (r, g, b) = ((color >> 16) & 0xFF, (color >> 8) & 0xFF, color & 0xFF);
+    let luma = (r * 299 + g * 587 + b * 114) / 1000;
+    3 - ((luma * 4) / 256) as u8
+}
+
+/// Shades of a frame from the PPU.
+pub fn shades(frame: &[u32]) -> Vec<u8> {
+    frame.iter().map(|&color| shade(color)).collect()
+}
+
+/// Read a 160x144 reference screenshot as shades.
+pub fn read_reference(path: &Path) -> io::Result<Vec<u8>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| invalid(e.to_string()))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut data)
+        .map_err(|e| invalid(e.to_string()))?;
+    if info.width as usize != SCREEN_WIDTH || info.height as usize != SCREEN_HEIGHT {
+        return Err(invalid(format!(
+            "{}x{} isn't the size of the screen",
+            info.width, info.height
+        )));
+    }
+
+    let samples = info.color_type.samples();
+    Ok(data[..info.buffer_size()]
+        .chunks_exact(samples)
+        .map(|pixel| {
+            let color = match pixel {
+                [y] | [y, _] => *y as u32 * 0x010101,
+                [r, g, b, ..] => (*r as u32) << 16 | (*g as u32) << 8 | *b as u32,
+                [] => 0,
+            };
+            shade(color)
+        })
+        .collect())
+}
+
+/// The screenshot with the pixels that differ from the reference in red.
+fn diff_image(screenshot: &[u8], reference: &[u8]) -> IndexedImage {
+    IndexedImage {
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+        pixels: (0..SCREEN_PIXELS)
+            .map(|i| {
+                if screenshot[i] == reference[i] {
+                    screenshot[i]
+                } else {
+                    MISMATCH
+                }
+            })
+            .collect(),
+        palette: DIFF_PALETTE.to_vec(),
+        transparent: None,
+    }
+}
@@ -0,0 +1,149 @@
+use crate::frontend::Hotkey;
+use bitflags::bitflags;
+use std::fmt;
+
+bitflags!(
+    /// Modifier keys held down with a hotkey's key, either side of the keyboard.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Modifiers: u8 {
+        const CTRL  = 0b_0001;
+        const SHIFT = 0b_0010;
+        const ALT   = 0b_0100;
+    }
+);
+
+/// A key, pressed with exactly these modifiers held, e.g. Ctrl+F5.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord<K> {
+    pub modifiers: Modifiers,
+    pub key: K,
+}
+
+impl<K> Chord<K> {
+    /// The key on its own, without modifiers.
+    pub fn key(key: K) -> Self {
+        Self {
+            modifiers: Modifiers::empty(),
+            key,
+        }
+    }
+
+    /// Parse a chord, e.g. "F5", "Ctrl+F5", or "Ctrl+Shift+S", with the front-end's key names.
+    pub fn parse(name: &str, parse_key: impl Fn(&str) -> Option<K>) -> Result<Self, String> {
+        let mut parts: Vec<&str> = name.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        let mut modifiers = Modifiers::empty();
+        for part in parts {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" => Modifiers::ALT,
+                _ => return Err(format!("unknown modifier `{}` in `{}`", part, name)),
+            };
+        }
+        let key = parse_key(key).ok_or_else(|| format!("unknown key `{}`", key))?;
+        Ok(Self { modifiers, key })
+    }
+}
+
+/// e.g. "Ctrl+Shift+F5", with the key as it's Debug formatted.
+impl<K: fmt::Debug> fmt::Display for Chord<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [
+            (Modifiers::CTRL, "Ctrl"),
+            (Modifiers::SHIFT, "Shift"),
+            (Modifiers::ALT, "Alt"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+/// Maps chords of host keys to hotkeys, like InputMap does keys to Joypad buttons.
+/// A hotkey can have more than one chord, e.g. = and the keypad's + both turn the volume up.
+/// A chord only triggers with exactly its modifiers held, so Ctrl+F5 doesn't also trigger F5.
+pub struct HotkeyMap<K> {
+    bindings: Vec<(Chord<K>, Hotkey)>,
+}
+
+impl<K: Copy + PartialEq + fmt::Debug> HotkeyMap<K> {
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Bind a chord to a hotkey, on top of the chords already bound to it.
+    pub fn bind(&mut self, chord: Chord<K>, hotkey: Hotkey) {
+        self.bindings.push((chord, hotkey));
+    }
+
+    /// Bind a chord to a hotkey, in place of the chords bound to it so far.
+    pub fn rebind(&mut self, chord: Chord<K>, hotkey: Hotkey) {
+        self.bindings.retain(|(_, h)| *h != hotkey);
+        self.bind(chord, hotkey);
+    }
+
+    /// The chords bound to a hotkey.
+    pub fn chords(&self, hotkey: Hotkey) -> Vec<Chord<K>> {
+        self.bindings
+            .iter()
+            .filter(|(_, h)| *h == hotkey)
+            .map(|(chord, _)| *chord)
+            .collect()
+    }
+
+    /// The hotkeys triggered by keys just pressed, with modifiers held.
+    /// Where a chord is bound to more than one hotkey, the one bound last wins, see conflicts.
+    pub fn resolve(&self, pressed: &[K], modifiers: Modifiers) -> Vec<Hotkey> {
+        pressed
+            .iter()
+            .filter_map(|key| {
+                self.bindings
+                    .iter()
+                    .rev()
+                    .find(|(chord, _)| chord.key == *key && chord.modifiers == modifiers)
+                    .map(|(_, hotkey)| *hotkey)
+            })
+            .collect()
+    }
+
+    /// Assignments that get in each other's way: a chord bound to more than one hotkey,
+    /// or a hotkey on a key that's also bound to a Joypad button in joypad_keys, which presses it too.
+    pub fn conflicts(&self, joypad_keys: &[K]) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, (chord, hotkey)) in self.bindings.iter().enumerate() {
+            let shared: Vec<&str> = self.bindings[..i]
+                .iter()
+                .filter(|(other, h)| other == chord && h != hotkey)
+                .map(|(_, h)| h.name())
+                .collect();
+            if !shared.is_empty() {
+                conflicts.push(format!(
+                    "{} is bound to {} and {}, only {} works",
+                    chord,
+                    shared.join(", "),
+                    hotkey.name(),
+                    hotkey.name()
+                ));
+            }
+            if joypad_keys.contains(&chord.key) {
+                conflicts.push(format!(
+                    "{} is bound to {}, and its key to a Joypad button, which it presses too",
+                    chord,
+                    hotkey.name()
+                ));
+            }
+        }
+        conflicts
+    }
+}
+
+impl<K: Copy + PartialEq + fmt::Debug> Default for HotkeyMap<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
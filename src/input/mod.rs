@@ -0,0 +1,141 @@
+use crate::frontend::Hotkey;
+use crate::joypad::Buttons;
+
+mod hotkeys;
+
+pub use hotkeys::{Chord, HotkeyMap, Modifiers};
+
+/// Default number of frames a turbo button stays on (and then off) while held.
+pub const DEFAULT_TURBO_RATE: u32 = 2;
+
+/// What a host input (keyboard key, controller button, etc.) is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// Hold the Gameboy button(s) while the host input is held.
+    Button(Buttons),
+
+    /// Autofire, toggle the Gameboy button(s) on and off every turbo_rate frames while the host input is held.
+    Turbo(Buttons),
+
+    /// Trigger a hotkey when the host input is pressed, with modifiers if it's a chord, see HotkeyMap.
+    Hotkey(Hotkey),
+}
+
+/// Maps host inputs to Gameboy buttons.
+/// The mapping is generic over the host input type, so the same turbo logic works for
+/// keyboards and controllers alike, each front-end just feeds in whatever inputs are held.
+pub struct InputMap<K> {
+    bindings: Vec<(K, Binding)>,
+
+    /// Frames per turbo toggle.
+    turbo_rate: u32,
+
+    /// Frames since a turbo input started being held.
+    turbo_frame: u32,
+}
+
+impl<K: PartialEq> InputMap<K> {
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+            turbo_rate: DEFAULT_TURBO_RATE,
+            turbo_frame: 0,
+        }
+    }
+
+    /// Bind a host input to a Gameboy button (or turbo button).
+    pub fn bind(&mut self, input: K, binding: Binding) {
+        self.bindings.push((input, binding));
+    }
+
+    /// Bind a host input to a Gameboy button (or turbo button), in place of the inputs bound to it so far.
+    pub fn rebind(&mut self, input: K, binding: Binding) {
+        self.bindings.retain(|(_, b)| *b != binding);
+        self.bind(input, binding);
+    }
+
+    /// The host inputs bound to Gameboy buttons (or turbo buttons).
+    pub fn inputs(&self) -> Vec<&K> {
+        self.bindings.iter().map(|(input, _)| input).collect()
+    }
+
+    /// Set how many frames a turbo button stays on, and then off, while held.
+    pub fn set_turbo_rate(&mut self, frames: u32) {
+        self.turbo_rate = frames.max(1);
+    }
+
+    /// Resolve the currently held host inputs into the Gameboy buttons for the next frame.
+    /// This should be called once per frame, as it also advances the turbo timing.
+    pub fn update(&mut self, held: &[K]) -> Buttons {
+        let mut buttons = Buttons::empty();
+        let mut turbo = Buttons::empty();
+        for (input, binding) in &self.bindings {
+            if !held.contains(input) {
+                continue;
+            }
+            match binding {
+                Binding::Button(b) => buttons |= *b,
+                Binding::Turbo(b) => turbo |= *b,
+                Binding::Hotkey(_) => (),
+            }
+        }
+
+        // Turbo starts in the "on" phase as soon as it is pressed.
+        if turbo.is_empty() {
+            self.turbo_frame = 0;
+        } else {
+            if (self.turbo_frame / self.turbo_rate) & 0x01 == 0 {
+                buttons |= turbo;
+            }
+            self.turbo_frame = self.turbo_frame.wrapping_add(1);
+        }
+
+        buttons
+    }
+}
+
+/// Parse a key bindings file, which binds Gameboy buttons (and turbo buttons), and hotkeys, to host inputs by name:
+///
+/// up = "Up"
+/// a = "X"
+/// start = "Enter"
+/// turbo-a = "S"
+/// save-state = "Ctrl+S"
+///
+/// Buttons: up, down, left, right, a, b, select, start, turbo-a, turbo-b.
+/// Hotkeys: by name, e.g. pause, save-state, or volume-up, see Hotkey::name. They can be chords, e.g. "Ctrl+Shift+S".
+/// Input names are up to the front-end, e.g. key names for a keyboard.
+pub fn parse_bindings(text: &str) -> Result<Vec<(String, Binding)>, String> {
+    let table = text.parse::<toml::Table>().map_err(|e| e.to_string())?;
+    table
+        .into_iter()
+        .map(|(button, input)| {
+            let binding = match button.as_str() {
+                "up" => Binding::Button(Buttons::UP),
+                "down" => Binding::Button(Buttons::DOWN),
+                "left" => Binding::Button(Buttons::LEFT),
+                "right" => Binding::Button(Buttons::RIGHT),
+                "a" => Binding::Button(Buttons::A),
+                "b" => Binding::Button(Buttons::B),
+                "select" => Binding::Button(Buttons::SELECT),
+                "start" => Binding::Button(Buttons::START),
+                "turbo-a" => Binding::Turbo(Buttons::A),
+                "turbo-b" => Binding::Turbo(Buttons::B),
+                name => match Hotkey::from_name(name) {
+                    Some(hotkey) => Binding::Hotkey(hotkey),
+                    None => return Err(format!("unknown button or hotkey `{}`", button)),
+                },
+            };
+            match input {
+                toml::Value::String(input) => Ok((input, binding)),
+                _ => Err(format!("`{}` should be bound to an input name", button)),
+            }
+        })
+        .collect()
+}
+
+impl<K: PartialEq> Default for InputMap<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,107 @@
+use bitflags::bitflags;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::cpu::interrupts::{Flags, InterruptFlags};
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
+bitflags!(
+    /// The Gameboy has 8 buttons, a D-Pad (Right, Left, Up, Down) and 4 action buttons (A, B, Select, Start).
+    /// The low nibble holds the D-Pad and the high nibble the action buttons, in the same bit order
+    /// the P1/JOYP register reports them.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Buttons: u8 {
+        const RIGHT  = 0b_0000_0001;
+        const LEFT   = 0b_0000_0010;
+        const UP     = 0b_0000_0100;
+        const DOWN   = 0b_0000_1000;
+        const A      = 0b_0001_0000;
+        const B      = 0b_0010_0000;
+        const SELECT = 0b_0100_0000;
+        const START  = 0b_1000_0000;
+    }
+);
+
+/// FF00 - P1/JOYP - Joypad (R/W)
+/// The eight Gameboy buttons are arranged as a 2x4 matrix. The game selects either the action buttons or
+/// the D-Pad by writing to bits 5 and 4, then reads the state of the selected buttons from bits 3-0.
+/// Note that, rather unconventionally for the Gameboy, a button being pressed is seen as the corresponding bit being 0, not 1.
+///
+/// Bit 7-6 Unused (Always returns 1)
+/// Bit 5   Select action buttons    (0=Select)
+/// Bit 4   Select direction buttons (0=Select)
+/// Bit 3   Down  or Start           (0=Pressed) (Read Only)
+/// Bit 2   Up    or Select          (0=Pressed) (Read Only)
+/// Bit 1   Left  or B               (0=Pressed) (Read Only)
+/// Bit 0   Right or A               (0=Pressed) (Read Only)
+///
+/// https://gbdev.io/pandocs/Joypad_Input.html
+pub struct Joypad {
+    if_: Rc<RefCell<InterruptFlags>>,
+
+    /// Bits 5-4, which button group(s) are selected.
+    select: u8,
+
+    /// Buttons currently held down.
+    pressed: Buttons,
+}
+
+impl Joypad {
+    pub fn new(if_: Rc<RefCell<InterruptFlags>>) -> Self {
+        Self {
+            if_,
+            select: 0x30,
+            pressed: Buttons::empty(),
+        }
+    }
+
+    /// Read the P1/JOYP register.
+    pub fn get(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    /// Write the P1/JOYP register, only the select bits are writable.
+    pub fn set(&mut self, v: u8) {
+        let before = self.lines();
+        self.select = v & 0x30;
+        self.request_interrupt(before);
+    }
+
+    /// Update which buttons are held down.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        let before = self.lines();
+        self.pressed = buttons;
+        self.request_interrupt(before);
+    }
+
+    /// State of the 4 input lines (bits 3-0) for the selected button group(s), 0 = pressed.
+    fn lines(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & 0x10 == 0x00 {
+            lines &= !self.pressed.bits() & 0x0F;
+        }
+        if self.select & 0x20 == 0x00 {
+            lines &= !(self.pressed.bits() >> 4) & 0x0F;
+        }
+        lines
+    }
+
+    /// The Joypad interrupt is requested when any of the input lines goes from high to low.
+    fn request_interrupt(&mut self, before: u8) {
+        if before & !self.lines() != 0x00 {
+            self.if_.borrow_mut().set(Flags::Joypad);
+        }
+    }
+}
+
+impl Savestate for Joypad {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.select);
+        w.u8(self.pressed.bits());
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.select = r.u8()? & 0x30;
+        self.pressed = Buttons::from_bits_retain(r.u8()?);
+        Ok(())
+    }
+}
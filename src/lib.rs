@@ -0,0 +1,60 @@
+//! `ferrum` is a GameBoy (DMG-01) emulator and research project using Rust.
+//!
+//! The emulator is a library as well, it runs headless, frame by frame:
+//!
+//! ```
+//! use ferrum::gb::GameBoy;
+//! use ferrum::testrom;
+//!
+//! let mut gb = GameBoy::from_rom(testrom::demo(), None);
+//! gb.set_serial_output(false);
+//! gb.skip_boot();
+//! let serial: Vec<u8> = gb.frames().take(10).flat_map(|frame| frame.serial).collect();
+//! assert_eq!(serial, b"Hello from ferrum!\n");
+//! ```
+//!
+//! The examples directory has more: rendering a frame to a PNG, running test ROMs that report over the serial port,
+//! and a custom video sink. Without a ROM, they run the demo cartridge, see testrom::demo.
+//!
+//! rom_info decodes a ROM's cartridge header without starting an emulator, e.g. to list games.
+
+pub mod accuracy;
+mod apu;
+pub mod assets;
+pub mod audio;
+pub mod audit;
+pub mod avdump;
+mod boot;
+pub mod bus;
+pub mod cartridge;
+pub mod control;
+pub mod coverage;
+mod cpu;
+pub mod data;
+mod debugport;
+pub mod diff;
+pub mod doctor;
+pub mod fault;
+pub mod frametime;
+pub mod frontend;
+pub mod gb;
+pub mod golden;
+pub mod input;
+pub mod joypad;
+mod mmu;
+pub mod model;
+pub mod osd;
+pub mod palette;
+pub mod ppu;
+pub mod reftrace;
+pub mod serial;
+pub mod shots;
+pub mod state;
+pub mod testrom;
+pub mod timeline;
+mod timer;
+
+pub use cartridge::info::{rom_info, RomInfo};
+
+#[macro_use]
+extern crate lazy_static;
@@ -1,16 +1,43 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
+use ferrum::accuracy::AccuracyPreset;
+use ferrum::assets::Assets;
+use ferrum::audit::HashAudit;
+use ferrum::avdump::AvDump;
+use ferrum::bus::{DmaBusPolicy, OpenBusPolicy};
+use ferrum::cartridge::header::{fix_checksums, Header};
+use ferrum::cartridge::rtc::RtcTime;
+use ferrum::cartridge::Mapper;
+use ferrum::control::ControlServer;
+use ferrum::data::{format_playtime, DataDir, PlaytimeClock, STATE_SLOTS};
+use ferrum::diff::PpuDiff;
+use ferrum::doctor::{self, Status};
+use ferrum::fault::FaultPolicy;
+use ferrum::frametime::FrameTimes;
+use ferrum::frontend::{
+    headless::{ConsoleInput, PacedVideo},
+    terminal::TerminalVideo,
+    Hotkey,
+};
+use ferrum::gb::{
+    self, BackgroundPolicy, ClockScope, FastForwardAudio, FastForwardSpeed, Pacing, Poke,
+    Presentation, RomWatch, Rumble, DEFAULT_REFRESH_RATE, DEFAULT_TRACE_LEN,
+};
+use ferrum::golden::{self, History, Outcome, Suite};
+use ferrum::input::{Binding, DEFAULT_TURBO_RATE};
+use ferrum::model::Model;
+use ferrum::palette::{Palette, PRESETS};
+use ferrum::ppu::debug::{self, Layer};
+use ferrum::ppu::watchpoint::Watchpoint;
+use ferrum::ppu::{LcdOffPolicy, PpuAccuracy, SpritePriority};
+use ferrum::reftrace::{self, Outcome as TraceOutcome};
+use ferrum::rom_info;
+use ferrum::serial::{Printer, TcpLink};
+use ferrum::shots::{self, Shot};
+use ferrum::state::diff::StateDiff;
+use ferrum::timeline::{Timeline, DEFAULT_REGISTERS};
 use log::{info, warn};
-
-mod boot;
-mod cartridge;
-mod cpu;
-mod gb;
-mod mmu;
-mod ppu;
-mod timer;
-
-#[macro_use]
-extern crate lazy_static;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 fn main() {
     env_logger::init();
@@ -28,11 +55,1755 @@ fn main() {
                 .help("Sets the ROM file to load.")
                 .required(true),
         )
+        .arg(
+            Arg::new("model")
+                .long("model")
+                .value_name("MODEL")
+                .help("Sets the Gameboy model to emulate, early DMG, DMG, or Pocket. [default: dmg]")
+                .value_parser(["dmg0", "dmg", "mgb"]),
+        )
+        .arg(
+            Arg::new("accuracy")
+                .long("accuracy")
+                .value_name("PRESET")
+                .help("Sets every accuracy setting at once: fast (scanline PPU, nothing locked), balanced (scanline PPU, hardware memory access rules), or accurate (experimental pixel FIFO, no window or sprites yet, hardware memory access rules). The other accuracy options override it. [default: balanced]")
+                .value_parser(["fast", "balanced", "accurate"]),
+        )
+        .arg(
+            Arg::new("ppu-accuracy")
+                .long("ppu-accuracy")
+                .value_name("MODE")
+                .help("Sets the PPU rendering pipeline: scanline, or fifo, the experimental pixel FIFO, which takes registers written mid-scanline, but only draws the background, without the window, sprites, or fine horizontal scroll. [default: from --accuracy]")
+                .value_parser(["scanline", "fifo"]),
+        )
+        .arg(
+            Arg::new("sprite-priority")
+                .long("sprite-priority")
+                .value_name("MODE")
+                .help("Sets how overlapping sprites are ordered, by X coordinate (dmg) or by OAM index (cgb). [default: dmg]")
+                .value_parser(["dmg", "cgb"]),
+        )
+        .arg(
+            Arg::new("lcd-off")
+                .long("lcd-off")
+                .value_name("POLICY")
+                .help("Sets what the screen shows while a game has the LCD off, a white screen like a real Gameboy (blank), or the last frame (hold). [default: blank]")
+                .value_parser(["blank", "hold"]),
+        )
+        .arg(
+            Arg::new("turbo-rate")
+                .long("turbo-rate")
+                .value_name("FRAMES")
+                .help("Sets how many frames turbo buttons stay on, and then off, while held. [default: 2]")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("run-ahead")
+                .long("run-ahead")
+                .help("Emulates one frame ahead to cut a frame of input latency, at twice the CPU cost.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("widescreen")
+                .long("widescreen")
+                .help("Experimental: shows more of the background on each side of the screen, 16:9 instead of 10:9, with the scanline renderer. Suits games whose backgrounds scroll, sprites and the window may be cut off.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("interlaced")
+                .long("interlaced")
+                .help("Renders every other scanline per frame, alternating, with the scanline renderer. Halves the rendering work on slow hosts, moving things show combing.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("background")
+                .long("background")
+                .value_name("POLICY")
+                .help("Sets what happens while the window is unfocused or minimized, keep running, mute the sound, or pause.")
+                .value_parser(["run", "mute", "pause"])
+                .default_value("run"),
+        )
+        .arg(
+            Arg::new("playtime-clock")
+                .long("playtime-clock")
+                .value_name("CLOCK")
+                .help("Sets what counts towards the game's playtime, real time played, or emulated time, which counts fast-forwarding at its speed. Time paused never counts. [default: wall]")
+                .value_parser(["wall", "emulated"]),
+        )
+        .arg(
+            Arg::new("pacing")
+                .long("pacing")
+                .value_name("MODE")
+                .help("Sets how emulation is paced to the display: host shows a frame per refresh, resampling the sound to keep up, exact runs at the Gameboy's 59.73 Hz, showing a frame twice now and then.")
+                .value_parser(["host", "exact"])
+                .default_value("host"),
+        )
+        .arg(
+            Arg::new("refresh-rate")
+                .long("refresh-rate")
+                .value_name("HZ")
+                .help("Sets the refresh rate of the display, for pacing. [default: 60]")
+                .value_parser(parse_refresh_rate),
+        )
+        .arg(
+            Arg::new("presentation")
+                .long("presentation")
+                .value_name("MODE")
+                .help("Sets how the window presents frames: limiter updates it from emulation, held to the refresh rate, triple-buffer updates it from its own thread, for less judder (not on macOS). [default: limiter]")
+                .value_parser(["limiter", "triple-buffer"]),
+        )
+        .arg(
+            Arg::new("rumble")
+                .long("rumble")
+                .value_name("MODE")
+                .help("Sets what a rumble cartridge's motor does: shake shakes the screen, off ignores it. [default: shake]")
+                .value_parser(["shake", "off"]),
+        )
+        .arg(
+            Arg::new("fast-forward")
+                .long("fast-forward")
+                .value_name("SPEED")
+                .help("Sets how fast the fast-forward hotkey (Tab) runs, uncapped runs as fast as it can. [default: 4x]")
+                .value_parser(["2x", "4x", "8x", "uncapped"]),
+        )
+        .arg(
+            Arg::new("fast-forward-audio")
+                .long("fast-forward-audio")
+                .value_name("MODE")
+                .help("Sets what the sound does while fast-forwarding: mute, pitch plays snippets at their own pitch, raw plays it all faster and higher. [default: mute]")
+                .value_parser(["mute", "pitch", "raw"]),
+        )
+        .arg(
+            Arg::new("volume")
+                .long("volume")
+                .value_name("PERCENT")
+                .help("Sets the master volume. [default: 100]")
+                .value_parser(clap::value_parser!(u8).range(0..=100)),
+        )
+        .arg(
+            Arg::new("audio-latency")
+                .long("audio-latency")
+                .value_name("MS")
+                .help("Sets how much audio is kept queued for the sound card. Lower cuts latency, higher rides out slow frames. [default: 50]")
+                .value_parser(clap::value_parser!(u32).range(10..=500)),
+        )
+        .arg(
+            Arg::new("audio-buffer")
+                .long("audio-buffer")
+                .value_name("FRAMES")
+                .help("Asks the sound card for a buffer of this many samples per channel, instead of its default.")
+                .value_parser(clap::value_parser!(u32).range(16..=16384)),
+        )
+        .arg(
+            Arg::new("force-mbc")
+                .long("force-mbc")
+                .value_name("MAPPER")
+                .help("Uses the given Memory Bank Controller, whatever the cartridge header says, for ROMs that declare the wrong one.")
+                .value_parser(["rom", "mbc1", "mbc3", "mbc5", "mmm01"]),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Reloads the ROM whenever the file changes, with a hard reset, for homebrew development. Battery backed RAM is kept.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("watch-state")
+                .long("watch-state")
+                .value_name("SLOT")
+                .help("Loads a save state slot after every reload, to get straight back to the part being worked on.")
+                .value_parser(clap::value_parser!(u8).range(0..STATE_SLOTS as i64))
+                .requires("watch"),
+        )
+        .arg(
+            Arg::new("race")
+                .long("race")
+                .value_name("SLOT")
+                .help("Speedrun practice: starts from a save state slot, and the race-reset hotkey (R) goes back to it, with an attempt counter and timer on screen. Shift+R makes the machine as it is the start instead.")
+                .value_parser(clap::value_parser!(u8).range(0..STATE_SLOTS as i64)),
+        )
+        .arg(
+            Arg::new("rtc")
+                .long("rtc")
+                .value_name("D:HH:MM:SS")
+                .help("Sets the Real Time Clock of MBC3 cartridges at power on, e.g. 12:08:30:00 for day 12, 8:30 AM.")
+                .value_parser(clap::value_parser!(RtcTime)),
+        )
+        .arg(
+            Arg::new("rtc-speed")
+                .long("rtc-speed")
+                .value_name("X")
+                .help("Runs the Real Time Clock of MBC3 cartridges X times faster, 0 stops it. [default: 1]")
+                .value_parser(clap::value_parser!(u32).range(0..=86400)),
+        )
+        .arg(
+            Arg::new("clock-multiplier")
+                .long("clock-multiplier")
+                .value_name("X")
+                .help("Runs the emulated clock at X times the speed of a real Gameboy (0.1 - 16).")
+                .value_parser(parse_clock_multiplier)
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("clock-scope")
+                .long("clock-scope")
+                .value_name("SCOPE")
+                .help("Sets what the clock multiplier applies to, the CPU only or the whole system.")
+                .value_parser(["cpu", "system"])
+                .default_value("cpu"),
+        )
+        .arg(
+            Arg::new("open-bus")
+                .long("open-bus")
+                .value_name("POLICY")
+                .help("Sets what reads from unmapped memory return, the DMG's value for the region, 0xFF, or the last bus value. [default: from --accuracy]")
+                .value_parser(["accurate", "ff", "last"]),
+        )
+        .arg(
+            Arg::new("dma-bus")
+                .long("dma-bus")
+                .value_name("POLICY")
+                .help("Sets whether OAM DMA locks the CPU out of everything but HRAM, like on hardware, or leaves memory free, for games that only work that way. [default: from --accuracy]")
+                .value_parser(["restricted", "free"]),
+        )
+        .arg(
+            Arg::new("coverage")
+                .long("coverage")
+                .value_name("FILE")
+                .help("Writes the executed ROM addresses to FILE on exit, as JSON if it ends in .json, otherwise as a binary map.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("record-hashes")
+                .long("record-hashes")
+                .value_name("FILE")
+                .help("Records a hash of the whole machine state at every frame to FILE on exit.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("verify-hashes")
+                .long("verify-hashes")
+                .value_name("FILE")
+                .help("Verifies the machine state at every frame against the hashes recorded in FILE, and reports the first divergent frame.")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("record-hashes"),
+        )
+        .arg(
+            Arg::new("timeline")
+                .long("timeline")
+                .value_name("FILE")
+                .help("Streams interrupt requests and dispatches, IO register writes, bank switches, cartridge RAM writes, LCD on/off, and serial transfers, with cycle timestamps to FILE, as JSON if it ends in .json, JSON lines if it ends in .jsonl, otherwise as CSV.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("timeline-registers")
+                .long("timeline-registers")
+                .value_name("ADDRS")
+                .help("Sets the IO registers whose writes go on the timeline, as comma separated hex addresses. [default: FF04,FF05,FF06,FF07,FF0F,FF40,FF41,FF45,FFFF]")
+                .value_parser(parse_registers)
+                .requires("timeline"),
+        )
+        .arg(
+            Arg::new("audio-only")
+                .long("audio-only")
+                .help("Plays the game's sound without a window, reading joypad presses from the console (n/p for the next/previous track).")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fbdev")
+                .long("fbdev")
+                .value_name("DEVICE")
+                .help("Shows the game on a Linux framebuffer instead of a window, e.g. on a Raspberry Pi without a desktop, with input from keyboards and controllers through evdev. Needs the fbdev feature. [default: /dev/fb0]")
+                .value_parser(clap::value_parser!(PathBuf))
+                .num_args(0..=1)
+                .default_missing_value("/dev/fb0")
+                .conflicts_with("audio-only"),
+        )
+        .arg(
+            Arg::new("terminal")
+                .long("terminal")
+                .value_name("FRAMES")
+                .help("Draws the game in the terminal, with 24-bit colors and half blocks (160x72 characters), every so many frames, reading joypad presses from the console like --audio-only. Piped into a log, frames are printed one after another. [default: 4, or 60 into a log]")
+                .value_parser(clap::value_parser!(u32))
+                .num_args(0..=1)
+                .default_missing_value("0")
+                .conflicts_with_all(["audio-only", "fbdev"]),
+        )
+        .arg(
+            Arg::new("control")
+                .long("control")
+                .value_name("ADDR")
+                .help("Accepts peek, poke, pause, resume, screenshot, and loadstate commands over TCP, e.g. 127.0.0.1:7777."),
+        )
+        .arg(
+            Arg::new("frame-times")
+                .long("frame-times")
+                .value_name("FILE")
+                .help("Writes the host time spent emulating and presenting every frame to FILE as CSV, to diagnose stutter. F3 shows it as a graph, with how full the audio buffer is.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("printer")
+                .long("printer")
+                .value_name("DIR")
+                .help("Plugs a Game Boy Printer into the link port, writing printed pages to DIR as PNGs.")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["link", "link-listen"]),
+        )
+        .arg(
+            Arg::new("link")
+                .long("link")
+                .value_name("ADDR")
+                .help("Connects the link port to another ferrum listening at ADDR, e.g. 127.0.0.1:7778.")
+                .conflicts_with("link-listen"),
+        )
+        .arg(
+            Arg::new("link-listen")
+                .long("link-listen")
+                .value_name("ADDR")
+                .help("Waits for another ferrum to connect its link port on ADDR, e.g. 127.0.0.1:7778."),
+        )
+        .arg(
+            Arg::new("debug-port")
+                .long("debug-port")
+                .help("Prints messages written to the debug printf port ($FF7E-$FF7F), for homebrew development.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Pauses on illegal opcodes, prohibited memory accesses, and unknown IO register accesses, for homebrew development. With --audio-only, exits with an error instead.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check-stack")
+                .long("check-stack")
+                .help("Warns when SP moves into ROM or the IO registers, or the stack is pushed over IE at $FFFF, which usually means the game crashed. With --strict, these are faults.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-stall-pause")
+                .long("no-stall-pause")
+                .help("Keeps running when the game stalls on a black screen, the LCD off for seconds or the CPU halted for good, instead of pausing with a diagnosis.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("trace-ring")
+                .long("trace-ring")
+                .value_name("N")
+                .help("Keeps the last N executed instructions, printed on the first illegal opcode, strict mode fault, or crash. 0 keeps none. [default: 64]")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("poke")
+                .long("poke")
+                .value_name("ADDR=VAL[@FRAME]")
+                .help("Writes a byte to memory once the boot ROM is done, or at the start of FRAME, e.g. C123=05@600. Can be repeated.")
+                .value_parser(clap::value_parser!(Poke))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("watch-vram")
+                .long("watch-vram")
+                .value_name("WATCHPOINT")
+                .help("Pauses right after the CPU writes a tile's data or a map cell, logging the instruction that did: tile:N (0-383 from $8000), tile8800:N (the map byte with LCDC bit 4 clear), or map0:X,Y / map1:X,Y. Can be repeated.")
+                .value_parser(clap::value_parser!(Watchpoint))
+                .action(clap::ArgAction::Append)
+                .conflicts_with("audio-only"),
+        )
+        .arg(
+            Arg::new("poke-hold")
+                .long("poke-hold")
+                .help("Writes the --poke bytes again at the start of every frame, so the game can't change them.")
+                .action(clap::ArgAction::SetTrue)
+                .requires("poke"),
+        )
+        .arg(
+            Arg::new("boot-rom")
+                .long("boot-rom")
+                .value_name("FILE")
+                .help("Runs a custom boot ROM (256 bytes) instead of the built-in one. Reloaded with F1, or when the file changes.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("skip-boot")
+                .long("skip-boot")
+                .help("Starts the cartridge right away, with the registers the boot ROM would leave, instead of showing the logo.")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("boot-rom"),
+        )
+        .arg(
+            Arg::new("palette")
+                .long("palette")
+                .value_name("FILE")
+                .help("Shows the screen in the palette file's colors, four hex RGB colors, lightest first. Reloaded with F1, or when the file changes.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("palette-preset")
+                .long("palette-preset")
+                .value_name("NAME")
+                .help("Shows the screen in a built-in palette, including high contrast and color vision friendly ones. C cycles through them.")
+                .value_parser(PRESETS.map(|(name, _)| name))
+                .conflicts_with("palette"),
+        )
+        .arg(
+            Arg::new("keys")
+                .long("keys")
+                .value_name("FILE")
+                .help("Binds keys from a TOML file, e.g. a = \"X\", turbo-b = \"A\", save-state = \"Ctrl+S\". Conflicting bindings are logged. Reloaded with F1, or when the file changes.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("data-dir")
+                .long("data-dir")
+                .value_name("DIR")
+                .help("Sets the directory for saves, states, screenshots, and per-game settings.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("import-save")
+                .long("import-save")
+                .value_name("FILE")
+                .help("Imports a .sav or .srm from another emulator or a flash cart, replacing the game's save in the data directory. It's fitted to the cartridge's RAM size, and an RTC footer sets the cartridge's clock.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .subcommand(
+            Command::new("export-save")
+                .about("Exports the game's save from the data directory to a raw .sav, the size of the cartridge's RAM with the RTC footer of a cartridge with a clock, for other emulators and flash carts.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file of the game.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .value_name("OUT")
+                        .help("Sets the .sav file to write.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("data-dir")
+                        .long("data-dir")
+                        .value_name("DIR")
+                        .help("Sets the directory the game's save is in.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("rip")
+                .about("Rips the tiles, sprites, and background and window maps of a ROM to PNGs.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file to rip.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("DIR")
+                        .help("Sets the directory the PNGs are written to.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .default_value("rip"),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .long("frames")
+                        .value_name("FRAMES")
+                        .help("Runs the ROM for FRAMES frames, then rips VRAM, OAM, and the background and window maps.")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("600"),
+                )
+                .arg(
+                    Arg::new("from-rom")
+                        .long("from-rom")
+                        .help("Decodes every ROM bank as tile data instead of running the ROM.")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("diff-ppu")
+                .about("Runs the scanline and experimental FIFO renderers in lockstep, and highlights the pixels that differ.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file to load.")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("golden")
+                .about("Runs golden image tests, comparing screenshots of test ROMs to reference images, and test ROMs reporting their result in memory.")
+                .arg(
+                    Arg::new("suite")
+                        .value_name("SUITE")
+                        .help("Sets the suite file listing the tests.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .default_value("roms/test/golden.toml"),
+                )
+                .arg(
+                    Arg::new("diff-dir")
+                        .long("diff-dir")
+                        .value_name("DIR")
+                        .help("Writes an image of the differing pixels of each failed test to DIR.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("report")
+                        .long("report")
+                        .value_name("FILE")
+                        .help("Writes the results to FILE, as a Markdown table.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .value_name("FILE")
+                        .help("Adds the results to a pass/fail matrix in FILE, a column per run, and shows the tests fixed and regressed since the last one.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("label")
+                        .long("label")
+                        .value_name("LABEL")
+                        .help("Labels the run in the history, e.g. with a commit. [default: today's date]"),
+                ),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Shows the decoded cartridge header of a ROM, and checks its logo and checksums.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file to inspect.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .value_name("OUT")
+                        .help("Writes a copy of the ROM with corrected header and global checksums to OUT.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Prints the header as JSON instead, for other tools.")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("memory-map")
+                .about("Shows which area of the address space maps where once a ROM has run, with the banks switched in and whether cartridge RAM is enabled.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file to load.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .long("frames")
+                        .value_name("FRAMES")
+                        .help("Runs the ROM for FRAMES frames before mapping, 0 maps it at power on, boot ROM and all.")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("600"),
+                )
+                .arg(
+                    Arg::new("svg")
+                        .long("svg")
+                        .value_name("FILE")
+                        .help("Draws the map to FILE as an SVG image, as well as printing it.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("state-diff")
+                .about("Compares two save states of a ROM, showing the registers and memory that differ.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file the states were saved from.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("a")
+                        .value_name("STATE_A")
+                        .help("Sets the first save state, e.g. from a good run.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("b")
+                        .value_name("STATE_B")
+                        .help("Sets the second save state, e.g. from a bad run.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("trace-diff")
+                .about("Steps a ROM instruction by instruction alongside a reference register trace, e.g. a Gameboy Doctor log, and stops at the first line that differs.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file the trace was logged from.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("trace")
+                        .value_name("TRACE")
+                        .help("Sets the reference trace, one line per instruction, starting at $0100 after the boot ROM.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("model")
+                        .long("model")
+                        .value_name("MODEL")
+                        .help("Sets the model the trace was logged on, for the registers the boot ROM leaves. [default: dmg]")
+                        .value_parser(["dmg0", "dmg", "mgb"]),
+                )
+                .arg(
+                    Arg::new("no-stub-ly")
+                        .long("no-stub-ly")
+                        .help("Reads LY from the PPU, for traces logged without LY stubbed to $90, as Gameboy Doctor expects.")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("av-dump")
+                .about("Runs a ROM headless, and dumps its video and audio to a Matroska file, in sync, for encoding.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file to run.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .value_name("OUT")
+                        .help("Sets the Matroska file to write, e.g. dump.mkv.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .long("frames")
+                        .value_name("FRAMES")
+                        .help("Sets how many frames to dump.")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("3600"),
+                )
+                .arg(
+                    Arg::new("sample-rate")
+                        .long("sample-rate")
+                        .value_name("HZ")
+                        .help("Sets the sample rate of the audio.")
+                        .value_parser(clap::value_parser!(u32).range(8000..=192000))
+                        .default_value("48000"),
+                ),
+        )
+        .subcommand(
+            Command::new("screenshots")
+                .about("Runs a ROM headless, and takes screenshots of the given frames, the same ones every run.")
+                .arg(
+                    Arg::new("rom")
+                        .value_name("ROM")
+                        .help("Sets the ROM file to run.")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("shot")
+                        .long("shot")
+                        .value_name("FRAME:FILE")
+                        .help("Writes frame FRAME, counted from power on, to the PNG FILE, e.g. 300:title.png. Can be given more than once.")
+                        .value_parser(Shot::parse)
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("script")
+                        .long("script")
+                        .value_name("FILE")
+                        .help("Takes the shots listed in FILE, a \"FRAME FILE\" pair per line, files relative to the script's directory.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Checks that ferrum can run here: a window, audio, the data directory and its configs, a custom boot ROM, and the emulated CPU.")
+                .arg(
+                    Arg::new("data-dir")
+                        .long("data-dir")
+                        .value_name("DIR")
+                        .help("Sets the data directory to check, as given when playing.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("boot-rom")
+                        .long("boot-rom")
+                        .value_name("FILE")
+                        .help("Checks a custom boot ROM, as given when playing.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("library")
+                .about("Lists the games in the data directory, with the time played.")
+                .arg(
+                    Arg::new("data-dir")
+                        .long("data-dir")
+                        .value_name("DIR")
+                        .help("Sets the data directory to list, as given when playing.")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
         .arg_required_else_help(true)
         .get_matches();
 
-    let rom_path = matches.get_one::<String>("rom").unwrap();
-    let mut ferrum = gb::GameBoy::power_on(rom_path.to_string());
-    warn!("Graphics, input, and sound are not implemented yet. Ferrum will run, but you won't see anything outside of the console.");
-    ferrum.run();
+    match matches.subcommand() {
+        Some(("rip", matches)) => {
+            if !rip(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("diff-ppu", matches)) => {
+            if !diff_ppu(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("info", matches)) => {
+            if !info(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("memory-map", matches)) => {
+            if !memory_map(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("state-diff", matches)) => {
+            if !state_diff(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("export-save", matches)) => {
+            if !export_save(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("trace-diff", matches)) => {
+            if !trace_diff(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("av-dump", matches)) => {
+            if !av_dump(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("screenshots", matches)) => {
+            if !screenshots(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("doctor", matches)) => {
+            if !doctor(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("library", matches)) => {
+            if !library(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(("golden", matches)) => {
+            if !golden(matches) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => (),
+    }
+
+    let rom_path = Path::new(matches.get_one::<String>("rom").unwrap());
+    let rom = std::fs::read(rom_path).unwrap();
+
+    // Each game gets its own data directory, for saves, states, and per-game settings.
+    let data_dir = DataDir::new(matches.get_one::<PathBuf>("data-dir").cloned());
+    let game_dir = data_dir.game(&rom);
+    if let Err(e) = game_dir.create() {
+        warn!("Failed to create {}: {}", data_dir.root().display(), e);
+    }
+    if let Err(e) = game_dir.migrate_save(rom_path) {
+        warn!("Failed to migrate the save next to the ROM: {}", e);
+    }
+    let config = game_dir.load_config();
+    let ram = game_dir.load_save().unwrap_or_else(|e| {
+        warn!("Failed to read {}: {}", game_dir.save_path().display(), e);
+        None
+    });
+    let imported = matches.get_one::<PathBuf>("import-save").map(|path| {
+        std::fs::read(path).unwrap_or_else(|e| {
+            warn!("Failed to read {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let import = imported.is_some();
+    let ram = imported.or(ram);
+
+    // Command line options override the per-game settings.
+    let model = match matches.get_one::<String>("model").map(String::as_str) {
+        Some("dmg0") => Model::Dmg0,
+        Some("mgb") => Model::Mgb,
+        Some(_) => Model::Dmg,
+        None => config.model.unwrap_or_default(),
+    };
+    let widescreen = matches.get_flag("widescreen") || config.widescreen.unwrap_or(false);
+    let interlaced = matches.get_flag("interlaced") || config.interlaced.unwrap_or(false);
+    let preset = match matches.get_one::<String>("accuracy").map(String::as_str) {
+        Some("fast") => AccuracyPreset::Fast,
+        Some("balanced") => AccuracyPreset::Balanced,
+        Some(_) => AccuracyPreset::Accurate,
+        None => config.accuracy.unwrap_or_default(),
+    };
+    let mut accuracy = preset.accuracy();
+    accuracy.ppu = match matches
+        .get_one::<String>("ppu-accuracy")
+        .map(String::as_str)
+    {
+        Some("scanline") => PpuAccuracy::Scanline,
+        Some(_) => PpuAccuracy::Fifo,
+        None => config.ppu_accuracy.unwrap_or(accuracy.ppu),
+    };
+    match matches.get_one::<String>("open-bus").map(String::as_str) {
+        Some("ff") => accuracy.open_bus = OpenBusPolicy::Ff,
+        Some("last") => accuracy.open_bus = OpenBusPolicy::LastValue,
+        Some(_) => accuracy.open_bus = OpenBusPolicy::Accurate,
+        None => (),
+    }
+    match matches.get_one::<String>("dma-bus").map(String::as_str) {
+        Some("free") => accuracy.dma_bus = DmaBusPolicy::Free,
+        Some(_) => accuracy.dma_bus = DmaBusPolicy::Restricted,
+        None => (),
+    }
+    let turbo_rate = matches
+        .get_one::<u32>("turbo-rate")
+        .copied()
+        .or(config.turbo_rate)
+        .unwrap_or(DEFAULT_TURBO_RATE);
+    let run_ahead = matches.get_flag("run-ahead") || config.run_ahead.unwrap_or(false);
+    if widescreen {
+        warn!("Widescreen is experimental, sprites and the window may be cut off at the edges of the real screen");
+        if accuracy.ppu != PpuAccuracy::Scanline {
+            warn!("Widescreen needs the scanline renderer, using it instead of the pixel FIFO");
+            accuracy.ppu = PpuAccuracy::Scanline;
+        }
+    }
+    if interlaced && accuracy.ppu != PpuAccuracy::Scanline {
+        warn!("Interlacing needs the scanline renderer, using it instead of the pixel FIFO");
+        accuracy.ppu = PpuAccuracy::Scanline;
+    }
+    if accuracy.ppu == PpuAccuracy::Fifo {
+        warn!("The pixel FIFO is experimental, it only draws the background, without the window, sprites, or fine horizontal scroll");
+    }
+
+    let mapper = match matches.get_one::<String>("force-mbc").map(String::as_str) {
+        Some("rom") => Some(Mapper::RomOnly),
+        Some("mbc1") => Some(Mapper::Mbc1),
+        Some("mbc3") => Some(Mapper::Mbc3),
+        Some("mbc5") => Some(Mapper::Mbc5),
+        Some("mmm01") => Some(Mapper::Mmm01),
+        _ => None,
+    };
+    let mut ferrum = match mapper {
+        Some(mapper) => gb::GameBoy::from_rom_as(rom, ram, mapper),
+        None => gb::GameBoy::from_rom(rom, ram),
+    };
+    // The imported save, fitted to the cartridge, is the game's save from now on.
+    if import {
+        match ferrum.battery_save() {
+            Some(save) => match game_dir.write_save(&save) {
+                Ok(()) => info!("Imported the save to {}", game_dir.save_path().display()),
+                Err(e) => warn!("Failed to write {}: {}", game_dir.save_path().display(), e),
+            },
+            None => warn!("The cartridge has no battery backed RAM, the save wasn't imported."),
+        }
+    }
+    if matches.get_flag("watch") {
+        let mut watch = RomWatch::new(rom_path);
+        if let Some(mapper) = mapper {
+            watch.set_mapper(mapper);
+        }
+        if let Some(&slot) = matches.get_one::<u8>("watch-state") {
+            watch.set_bookmark(slot);
+        }
+        ferrum.set_rom_watch(watch);
+    }
+    ferrum.set_model(model);
+    if matches.get_flag("skip-boot") {
+        ferrum.skip_boot();
+    }
+    ferrum.set_playtime_clock(
+        match matches
+            .get_one::<String>("playtime-clock")
+            .map(String::as_str)
+        {
+            Some("emulated") => PlaytimeClock::Emulated,
+            Some(_) => PlaytimeClock::Wall,
+            None => config.playtime_clock.unwrap_or_default(),
+        },
+    );
+    let race = matches
+        .get_one::<u8>("race")
+        .map(|&slot| game_dir.state_path(slot));
+    ferrum.set_game_dir(game_dir);
+    if let Some(path) = race {
+        let result = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|state| ferrum.set_race_anchor(state).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("Failed to start the race from {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    ferrum.set_accuracy(accuracy);
+    ferrum.set_widescreen(widescreen);
+    ferrum.set_interlaced(interlaced);
+    if matches
+        .get_one::<String>("sprite-priority")
+        .map(String::as_str)
+        == Some("cgb")
+    {
+        ferrum.set_sprite_priority(SpritePriority::Cgb);
+    }
+    ferrum.set_lcd_off_policy(
+        match matches.get_one::<String>("lcd-off").map(String::as_str) {
+            Some("hold") => LcdOffPolicy::Hold,
+            Some(_) => LcdOffPolicy::Blank,
+            None => config.lcd_off.unwrap_or_default(),
+        },
+    );
+    ferrum.set_turbo_rate(turbo_rate);
+    ferrum.set_run_ahead(run_ahead);
+    ferrum.set_background_policy(
+        match matches.get_one::<String>("background").unwrap().as_str() {
+            "mute" => BackgroundPolicy::Mute,
+            "pause" => BackgroundPolicy::Pause,
+            _ => BackgroundPolicy::Run,
+        },
+    );
+    let refresh_rate = matches
+        .get_one::<f64>("refresh-rate")
+        .copied()
+        .unwrap_or(DEFAULT_REFRESH_RATE);
+    ferrum.set_pacing(
+        match matches.get_one::<String>("pacing").unwrap().as_str() {
+            "exact" => Pacing::Exact,
+            _ => Pacing::Host,
+        },
+        refresh_rate,
+    );
+    ferrum.set_presentation(
+        match matches
+            .get_one::<String>("presentation")
+            .map(String::as_str)
+        {
+            Some("triple-buffer") => Presentation::TripleBuffer,
+            Some(_) => Presentation::Limiter,
+            None => config.presentation.unwrap_or_default(),
+        },
+    );
+    ferrum.set_rumble(
+        match matches.get_one::<String>("rumble").map(String::as_str) {
+            Some("off") => Rumble::Off,
+            Some(_) => Rumble::Shake,
+            None => config.rumble.unwrap_or_default(),
+        },
+    );
+    ferrum.set_fast_forward(
+        match matches
+            .get_one::<String>("fast-forward")
+            .map(String::as_str)
+        {
+            Some("2x") => FastForwardSpeed::X2,
+            Some("8x") => FastForwardSpeed::X8,
+            Some("uncapped") => FastForwardSpeed::Uncapped,
+            Some(_) => FastForwardSpeed::X4,
+            None => config.fast_forward.unwrap_or_default(),
+        },
+        match matches
+            .get_one::<String>("fast-forward-audio")
+            .map(String::as_str)
+        {
+            Some("pitch") => FastForwardAudio::Pitch,
+            Some("raw") => FastForwardAudio::Raw,
+            Some(_) => FastForwardAudio::Mute,
+            None => config.fast_forward_audio.unwrap_or_default(),
+        },
+    );
+    let volume = matches
+        .get_one::<u8>("volume")
+        .copied()
+        .or(config.volume)
+        .unwrap_or(100);
+    ferrum.set_volume(volume.min(100) as f32 / 100.0);
+    if let Some(latency) = matches
+        .get_one::<u32>("audio-latency")
+        .copied()
+        .or(config.audio_latency)
+    {
+        ferrum.set_audio_latency(Duration::from_millis(latency.clamp(10, 500) as u64));
+    }
+    ferrum.set_audio_buffer(
+        matches
+            .get_one::<u32>("audio-buffer")
+            .copied()
+            .or(config.audio_buffer),
+    );
+    if let Some(scale) = config.scale {
+        ferrum.set_scale(scale);
+    }
+    if let Some(name) = &config.scale_key {
+        ferrum.set_bindings(vec![(name.clone(), Binding::Hotkey(Hotkey::Scale))]);
+    }
+    if let Some(channel_volume) = config.channel_volume {
+        ferrum.set_channel_gains(channel_volume.map(|volume| volume as f32 / 100.0));
+    }
+    if let Some(&time) = matches.get_one::<RtcTime>("rtc") {
+        ferrum.set_rtc_time(time);
+    }
+    if let Some(&speed) = matches.get_one::<u32>("rtc-speed") {
+        ferrum.set_rtc_speed(speed);
+    }
+    let clock_scope = match matches.get_one::<String>("clock-scope").unwrap().as_str() {
+        "system" => ClockScope::System,
+        _ => ClockScope::Cpu,
+    };
+    ferrum.set_clock_multiplier(
+        *matches.get_one::<f64>("clock-multiplier").unwrap(),
+        clock_scope,
+    );
+
+    if let Some(name) = matches
+        .get_one::<String>("palette-preset")
+        .or(config.palette.as_ref())
+    {
+        match Palette::preset(name) {
+            Some(palette) => ferrum.set_palette(palette),
+            None => warn!("Ignoring unknown palette `{}`", name),
+        }
+    }
+
+    let mut assets = Assets::new();
+    if let Some(path) = matches.get_one::<PathBuf>("boot-rom") {
+        assets.set_boot_rom(path.clone());
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("palette") {
+        assets.set_palette(path.clone());
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("keys") {
+        assets.set_keys(path.clone());
+    }
+    ferrum.set_assets(assets);
+
+    ferrum.set_debug_port(matches.get_flag("debug-port"));
+    ferrum.set_trace_len(
+        matches
+            .get_one::<usize>("trace-ring")
+            .copied()
+            .unwrap_or(DEFAULT_TRACE_LEN),
+    );
+    for &poke in matches.get_many::<Poke>("poke").into_iter().flatten() {
+        ferrum.add_poke(poke);
+    }
+    ferrum.set_poke_hold(matches.get_flag("poke-hold"));
+    for &watchpoint in matches
+        .get_many::<Watchpoint>("watch-vram")
+        .into_iter()
+        .flatten()
+    {
+        ferrum.add_watchpoint(watchpoint);
+    }
+    if matches.get_flag("strict") {
+        ferrum.set_fault_policy(if matches.get_flag("audio-only") {
+            FaultPolicy::Exit
+        } else {
+            FaultPolicy::Pause
+        });
+    }
+    if matches.get_flag("check-stack") {
+        ferrum.set_stack_check(true);
+    }
+    if matches.get_flag("no-stall-pause") {
+        ferrum.set_stall_detection(false);
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("frame-times") {
+        let mut frame_times = FrameTimes::new();
+        match frame_times.set_csv(path) {
+            Ok(()) => ferrum.set_frame_times(frame_times),
+            Err(e) => warn!("Failed to create {}: {}", path.display(), e),
+        }
+    }
+    if let Some(dir) = matches.get_one::<PathBuf>("printer") {
+        match std::fs::create_dir_all(dir) {
+            Ok(()) => ferrum.set_serial_device(Box::new(Printer::new(dir))),
+            Err(e) => warn!("Failed to create {}: {}", dir.display(), e),
+        }
+    }
+    if let Some(addr) = matches.get_one::<String>("link") {
+        match TcpLink::connect(addr.as_str()) {
+            Ok(link) => ferrum.set_serial_device(Box::new(link)),
+            Err(e) => warn!("Failed to connect the link cable to {}: {}", addr, e),
+        }
+    }
+    if let Some(addr) = matches.get_one::<String>("link-listen") {
+        match TcpLink::listen(addr.as_str()) {
+            Ok(link) => ferrum.set_serial_device(Box::new(link)),
+            Err(e) => warn!("Failed to listen for a link cable on {}: {}", addr, e),
+        }
+    }
+    if let Some(addr) = matches.get_one::<String>("control") {
+        match ControlServer::bind(addr.as_str()) {
+            Ok(server) => ferrum.set_control_server(server),
+            Err(e) => warn!("Failed to listen for control commands on {}: {}", addr, e),
+        }
+    }
+
+    let coverage_path = matches.get_one::<PathBuf>("coverage");
+    if coverage_path.is_some() {
+        ferrum.enable_coverage();
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("timeline") {
+        let registers = matches
+            .get_one::<Vec<u16>>("timeline-registers")
+            .cloned()
+            .unwrap_or(DEFAULT_REGISTERS.to_vec());
+        match Timeline::create(path, registers) {
+            Ok(timeline) => ferrum.set_timeline(timeline),
+            Err(e) => warn!("Failed to create {}: {}", path.display(), e),
+        }
+    }
+
+    // Runs being audited start from the same RAM contents, so they can be compared.
+    let record_hashes = matches.get_one::<PathBuf>("record-hashes");
+    if record_hashes.is_some() {
+        ferrum.set_ram_seed(0);
+        ferrum.enable_hash_audit(HashAudit::record());
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("verify-hashes") {
+        match HashAudit::read(path) {
+            Ok(expected) => {
+                ferrum.set_ram_seed(0);
+                ferrum.enable_hash_audit(HashAudit::verify(expected));
+            }
+            Err(e) => {
+                warn!("Failed to read hashes from {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // A crash comes with the instructions that led to it.
+    let run = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        if matches.get_flag("audio-only") {
+            println!("Audio only, type n/p for the next/previous track, a button name to press it, or q to quit.");
+            ferrum.run_with(&mut PacedVideo::new(), &mut ConsoleInput::new());
+        } else if let Some(&interval) = matches.get_one::<u32>("terminal") {
+            let mut video = TerminalVideo::new((interval > 0).then_some(interval));
+            ferrum.run_with(&mut video, &mut ConsoleInput::new());
+        } else if let Some(device) = matches.get_one::<PathBuf>("fbdev") {
+            if let Err(e) = ferrum.run_fbdev(device) {
+                warn!("Failed to open the framebuffer {}: {}", device.display(), e);
+                std::process::exit(1);
+            }
+        } else {
+            warn!("Graphics are a work in progress.");
+            ferrum.run();
+        }
+    }));
+    if let Err(panic) = run {
+        eprintln!("{}", ferrum.trace_dump("the crash"));
+        std::panic::resume_unwind(panic);
+    }
+
+    if let (Some(path), Some(coverage)) = (coverage_path, ferrum.coverage()) {
+        if let Err(e) = coverage.write(path) {
+            warn!("Failed to write coverage to {}: {}", path.display(), e);
+        }
+    }
+
+    if let Some(audit) = ferrum.hash_audit() {
+        if let Some(path) = record_hashes {
+            if let Err(e) = audit.write(path) {
+                warn!("Failed to write hashes to {}: {}", path.display(), e);
+            }
+        } else if let Some(frame) = audit.divergence() {
+            println!(
+                "Determinism audit failed, the run diverged at frame {}.",
+                frame
+            );
+            std::process::exit(1);
+        } else {
+            println!(
+                "Determinism audit passed, {} recorded frames weren't reached.",
+                audit.unverified()
+            );
+        }
+    }
+
+    if let (true, Some(fault)) = (matches.get_flag("audio-only"), ferrum.fault()) {
+        println!("Strict mode: {}, at frame {}.", fault, ferrum.frame());
+        std::process::exit(1);
+    }
+}
+
+/// Print the cartridge header of a ROM, and optionally write a copy with its checksums repaired,
+/// e.g. for homebrew the boot ROM rejects. Returns false if the ROM can't be read or the copy can't be written.
+fn info(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let mut rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let Some(header) = Header::new(&rom) else {
+        println!(
+            "{} is only {} bytes, too small to hold a cartridge header.",
+            path.display(),
+            rom.len()
+        );
+        return false;
+    };
+    if matches.get_flag("json") {
+        let info = rom_info(&rom).unwrap();
+        println!("{}", serde_json::to_string_pretty(&info).unwrap());
+    } else {
+        println!("{}", header);
+    }
+
+    let Some(out) = matches.get_one::<PathBuf>("fix") else {
+        return true;
+    };
+    // The original is left alone, the repaired ROM is always a copy.
+    if out.canonicalize().ok() == path.canonicalize().ok() {
+        println!(
+            "Refusing to overwrite {}, pick another file for the copy.",
+            path.display()
+        );
+        return false;
+    }
+    let (stored, computed) = header.header_checksum();
+    if stored == computed {
+        println!("Header checksum is already correct, fixing the global checksum only.");
+    }
+    if !header.logo_valid() {
+        println!("The Nintendo logo is wrong too, the boot ROM will still reject the copy.");
+    }
+    fix_checksums(&mut rom);
+    match std::fs::write(out, &rom) {
+        Ok(()) => {
+            println!("Wrote {}", out.display());
+            true
+        }
+        Err(e) => {
+            println!("Failed to write {}: {}", out.display(), e);
+            false
+        }
+    }
+}
+
+/// Run a ROM for a while, then print its memory map, and draw it to an SVG if asked.
+/// Returns false if the ROM can't be read, or the SVG can't be written.
+fn memory_map(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let mut ferrum = gb::GameBoy::from_rom(rom, None);
+    // Serial output would end up in the middle of the map.
+    ferrum.set_serial_output(false);
+    for _ in 0..*matches.get_one::<u64>("frames").unwrap() {
+        ferrum.run_frame();
+    }
+    let map = ferrum.memory_map();
+    print!("{}", map);
+
+    let Some(out) = matches.get_one::<PathBuf>("svg") else {
+        return true;
+    };
+    match std::fs::write(out, map.svg()) {
+        Ok(()) => {
+            println!("Wrote {}", out.display());
+            true
+        }
+        Err(e) => {
+            println!("Failed to write {}: {}", out.display(), e);
+            false
+        }
+    }
+}
+
+/// Run a ROM headless, dumping its video and audio to a Matroska file.
+/// Returns false if the ROM can't be read, or the dump can't be written.
+fn av_dump(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let out = matches.get_one::<PathBuf>("out").unwrap();
+    let frames = *matches.get_one::<u64>("frames").unwrap();
+    let sample_rate = *matches.get_one::<u32>("sample-rate").unwrap();
+
+    let mut ferrum = gb::GameBoy::from_rom(rom, None);
+    ferrum.set_serial_output(false);
+    let result =
+        AvDump::create(out, sample_rate).and_then(|mut dump| dump.record(&mut ferrum, frames));
+    match result {
+        Ok(()) => {
+            println!("Dumped {} frames to {}", frames, out.display());
+            true
+        }
+        Err(e) => {
+            println!("Failed to write {}: {}", out.display(), e);
+            false
+        }
+    }
+}
+
+/// Run a ROM headless, and take the screenshots given with --shot and in the --script.
+/// Returns false if the ROM or the script can't be read, or a screenshot can't be written.
+fn screenshots(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let mut list: Vec<Shot> = matches
+        .get_many::<Shot>("shot")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    if let Some(script) = matches.get_one::<PathBuf>("script") {
+        match shots::load_script(script) {
+            Ok(script) => list.extend(script),
+            Err(e) => {
+                println!("Failed to read {}: {}", script.display(), e);
+                return false;
+            }
+        }
+    }
+    if list.is_empty() {
+        println!("No screenshots to take, give them with --shot or --script");
+        return false;
+    }
+
+    let mut ferrum = gb::GameBoy::from_rom(rom, None);
+    ferrum.set_serial_output(false);
+    match shots::capture(&mut ferrum, &list) {
+        Ok(()) => {
+            println!("Took {} screenshots", list.len());
+            true
+        }
+        Err(e) => {
+            println!("Failed to write {}", e);
+            false
+        }
+    }
+}
+
+/// Load two save states of a ROM, and print what differs between them.
+/// Returns false if the ROM or either state can't be loaded.
+fn state_diff(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+
+    let mut machines = Vec::new();
+    for id in ["a", "b"] {
+        let path = matches.get_one::<PathBuf>(id).unwrap();
+        let mut ferrum = gb::GameBoy::from_rom(rom.clone(), None);
+        ferrum.set_serial_output(false);
+        let result = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| ferrum.load_state(&data).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            println!("Failed to load {}: {}", path.display(), e);
+            return false;
+        }
+        machines.push(ferrum);
+    }
+
+    print!("{}", StateDiff::new(&machines[0], &machines[1]));
+    true
+}
+
+/// Write the game's save to a raw .sav, fitted to the cartridge's RAM size by loading it into the cartridge,
+/// with the RTC footer of a cartridge with a clock.
+/// Returns false if there's no save to export, or it can't be written.
+fn export_save(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let game_dir = DataDir::new(matches.get_one::<PathBuf>("data-dir").cloned()).game(&rom);
+    let ram = match game_dir.load_save() {
+        Ok(Some(ram)) => ram,
+        Ok(None) => {
+            println!(
+                "{} has no save in {}",
+                path.display(),
+                game_dir.save_path().display()
+            );
+            return false;
+        }
+        Err(e) => {
+            println!("Failed to read {}: {}", game_dir.save_path().display(), e);
+            return false;
+        }
+    };
+    let Some(ram) = gb::GameBoy::from_rom(rom, Some(ram)).battery_save() else {
+        println!("The cartridge has no battery backed RAM to export.");
+        return false;
+    };
+
+    let out = matches.get_one::<PathBuf>("out").unwrap();
+    match std::fs::write(out, &ram) {
+        Ok(()) => {
+            println!("Wrote {} bytes to {}", ram.len(), out.display());
+            true
+        }
+        Err(e) => {
+            println!("Failed to write {}: {}", out.display(), e);
+            false
+        }
+    }
+}
+
+/// Run a ROM alongside a reference trace, from the cartridge's entry point, and print where they part ways.
+/// Returns false if they do, or the ROM or the trace can't be read.
+fn trace_diff(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let path = matches.get_one::<PathBuf>("trace").unwrap();
+    let trace = match std::fs::File::open(path) {
+        Ok(trace) => std::io::BufReader::new(trace),
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+
+    let mut ferrum = gb::GameBoy::from_rom(rom, None);
+    ferrum.set_serial_output(false);
+    ferrum.set_model(
+        match matches.get_one::<String>("model").map(String::as_str) {
+            Some("dmg0") => Model::Dmg0,
+            Some("mgb") => Model::Mgb,
+            _ => Model::Dmg,
+        },
+    );
+    ferrum.skip_boot();
+    if !matches.get_flag("no-stub-ly") {
+        ferrum.set_ly_stub(Some(0x90));
+    }
+
+    match reftrace::compare(&mut ferrum, trace) {
+        Ok(TraceOutcome::Matched(lines)) => {
+            println!("All {} instructions matched the reference", lines);
+            true
+        }
+        Ok(TraceOutcome::Diverged(divergence)) => {
+            print!("{}", divergence);
+            false
+        }
+        Ok(TraceOutcome::Malformed { line, error }) => {
+            println!("Line {} of {}: {}", line, path.display(), error);
+            false
+        }
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+/// Run the PPU diff window, then print the frames where the renderers differed. Returns whether the ROM was read.
+fn diff_ppu(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<String>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path, e);
+            return false;
+        }
+    };
+    for (frame, differing) in PpuDiff::new(rom).run() {
+        println!("Frame {}: {} pixels differ", frame, differing);
+    }
+    true
+}
+
+/// Rip the graphics of a ROM to indexed PNG sheets.
+/// Either the tiles in VRAM, the sprites in OAM, and the background and window maps after running the ROM for a while,
+/// or every ROM bank decoded as tiles. Returns whether the ROM was read and the sheets written.
+fn rip(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<String>("rom").unwrap();
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Failed to read {}: {}", path, e);
+            return false;
+        }
+    };
+    let out = matches.get_one::<PathBuf>("out").unwrap();
+    if let Err(e) = std::fs::create_dir_all(out) {
+        warn!("Failed to create {}: {}", out.display(), e);
+        return false;
+    }
+
+    let images = if matches.get_flag("from-rom") {
+        rom.chunks(0x4000)
+            .enumerate()
+            .map(|(bank, data)| (format!("bank{:03}.png", bank), debug::tile_sheet(data)))
+            .collect()
+    } else {
+        let mut ferrum = gb::GameBoy::from_rom(rom, None);
+        for _ in 0..*matches.get_one::<u64>("frames").unwrap() {
+            ferrum.run_frame();
+        }
+        vec![
+            ("tiles.png".to_string(), ferrum.tile_sheet()),
+            ("sprites.png".to_string(), ferrum.sprite_sheet()),
+            ("bg.png".to_string(), ferrum.map_image(Layer::Background)),
+            ("window.png".to_string(), ferrum.map_image(Layer::Window)),
+        ]
+    };
+
+    let mut written = true;
+    for (name, image) in images {
+        let path = out.join(name);
+        match image.write_png(&path) {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(e) => {
+                warn!("Failed to write {}: {}", path.display(), e);
+                written = false;
+            }
+        }
+    }
+    written
+}
+
+/// Run a golden image suite, printing each test's outcome. Returns whether every test that ran passed.
+fn golden(matches: &ArgMatches) -> bool {
+    let path = matches.get_one::<PathBuf>("suite").unwrap();
+    let suite = match Suite::load(path) {
+        Ok(suite) => suite,
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let diff_dir = matches.get_one::<PathBuf>("diff-dir");
+    if let Some(dir) = diff_dir {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            warn!("Failed to create {}: {}", dir.display(), e);
+        }
+    }
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    let mut report = String::from("| Test | Result |\n| --- | --- |\n");
+    let mut results = Vec::new();
+    for test in &suite.tests {
+        let (result, detail) = match test.run(diff_dir.map(PathBuf::as_path)) {
+            Outcome::Pass => {
+                passed += 1;
+                ("PASS", String::new())
+            }
+            Outcome::Fail(pixels) => {
+                failed += 1;
+                ("FAIL", format!(" ({} pixels differ)", pixels))
+            }
+            Outcome::Reported(code, text) => {
+                failed += 1;
+                ("FAIL", format!(" (result {}, {})", code, text))
+            }
+            Outcome::Skipped(reason) => {
+                skipped += 1;
+                ("SKIP", format!(" ({})", reason))
+            }
+        };
+        println!("{} {}{}", result, test.name, detail);
+        report.push_str(&format!("| {} | {}{} |\n", test.name, result, detail));
+        results.push((test.name.clone(), result));
+    }
+    let summary = format!("{} passed, {} failed, {} skipped", passed, failed, skipped);
+    println!("{}", summary);
+
+    if let Some(path) = matches.get_one::<PathBuf>("report") {
+        report.push_str(&format!("\n{}\n", summary));
+        if let Err(e) = std::fs::write(path, report) {
+            warn!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("history") {
+        let label = matches.get_one::<String>("label").cloned();
+        if let Err(e) = add_history(path, &label.unwrap_or_else(golden::today), &results) {
+            warn!("Failed to update {}: {}", path.display(), e);
+        }
+    }
+    failed == 0
+}
+
+/// Add a golden image run to the history, and print what changed since the last run.
+fn add_history(path: &Path, label: &str, results: &[(String, &str)]) -> std::io::Result<()> {
+    let mut history = History::load(path)?;
+    history.add_run(label, results);
+    history.save(path)?;
+    let progress = history.progress();
+    println!(
+        "Since the last run: {} fixed{}, {} regressed{}",
+        progress.fixed.len(),
+        listed(&progress.fixed),
+        progress.regressed.len(),
+        listed(&progress.regressed)
+    );
+    Ok(())
+}
+
+/// " (a, b)", or nothing for no names.
+fn listed(names: &[String]) -> String {
+    match names.is_empty() {
+        true => String::new(),
+        false => format!(" ({})", names.join(", ")),
+    }
+}
+
+/// Print a readiness report, false if ferrum can't run as it normally does.
+fn doctor(matches: &ArgMatches) -> bool {
+    let data_dir = DataDir::new(matches.get_one::<PathBuf>("data-dir").cloned());
+    let findings = doctor::examine(
+        &data_dir,
+        matches.get_one::<PathBuf>("boot-rom").map(PathBuf::as_path),
+    );
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    let count = |status| findings.iter().filter(|f| f.status == status).count();
+    match (count(Status::Fail), count(Status::Warn)) {
+        (0, 0) => println!("Ready to play"),
+        (0, 1) => println!("Ready to play, with a warning"),
+        (0, warnings) => println!("Ready to play, with {} warnings", warnings),
+        (1, _) => println!("1 problem to fix"),
+        (failures, _) => println!("{} problems to fix", failures),
+    }
+    count(Status::Fail) == 0
+}
+
+/// List the games in the data directory, and the time played of each.
+/// Returns false if the data directory can't be read.
+fn library(matches: &ArgMatches) -> bool {
+    let data_dir = DataDir::new(matches.get_one::<PathBuf>("data-dir").cloned());
+    let games = match data_dir.games() {
+        Ok(games) => games,
+        Err(e) => {
+            println!("Failed to read {}: {}", data_dir.root().display(), e);
+            return false;
+        }
+    };
+    for game in &games {
+        let playtime = game
+            .playtime()
+            .map(format_playtime)
+            .unwrap_or_else(|e| format!("unknown ({})", e));
+        println!("{:<24} {}", game.key(), playtime);
+    }
+    println!("{} games in {}", games.len(), data_dir.root().display());
+    true
+}
+
+/// Parse the --clock-multiplier option, a speed relative to a real Gameboy.
+fn parse_clock_multiplier(s: &str) -> Result<f64, String> {
+    let multiplier: f64 = s.parse().map_err(|_| format!("`{}` isn't a number", s))?;
+    if (0.1..=16.0).contains(&multiplier) {
+        Ok(multiplier)
+    } else {
+        Err("must be between 0.1 and 16".to_string())
+    }
+}
+
+/// Parse the --refresh-rate option, in Hz.
+fn parse_refresh_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|_| format!("`{}` isn't a number", s))?;
+    if (20.0..=500.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err("must be between 20 and 500".to_string())
+    }
+}
+
+/// Parse the --timeline-registers option, comma separated IO register addresses in hex, e.g. FF41,FF45.
+fn parse_registers(s: &str) -> Result<Vec<u16>, String> {
+    s.split(',')
+        .map(|addr| {
+            let addr = addr.trim().trim_start_matches("0x").trim_start_matches('$');
+            match u16::from_str_radix(addr, 16) {
+                Ok(addr @ (0xFF00..=0xFF7F | 0xFFFF)) => Ok(addr),
+                _ => Err(format!("`{}` isn't an IO register address", addr)),
+            }
+        })
+        .collect()
 }
@@ -0,0 +1,97 @@
+use crate::state::{self, StateReader, StateWriter};
+use std::ops::Range;
+
+/// Bytes an OAM DMA transfer copies, all of OAM.
+const LENGTH: u16 = 0xA0;
+
+/// T-cycles per byte, one M-cycle, and before the first byte.
+const BYTE_TICKS: u32 = 4;
+const STARTUP_TICKS: u32 = 4;
+
+/// OAM DMA transfer
+/// Writing $XX to DMA ($FF46) copies $XX00-$XX9F to OAM, one byte per M-cycle after a one M-cycle startup,
+/// 644 T-cycles in all. The MMU does the copying, this keeps track of where the transfer is.
+pub struct Dma {
+    /// Where the bytes are copied from.
+    source: u16,
+
+    /// The transfer was started by the instruction being executed, it starts counting T-cycles after it.
+    pending: bool,
+
+    /// T-cycles since the transfer started.
+    ticks: u32,
+
+    /// Bytes copied so far, and the last one, which is on the buses the transfer is using.
+    copied: u16,
+    byte: u8,
+}
+
+impl Dma {
+    pub fn new(source: u16) -> Self {
+        Self {
+            source,
+            pending: true,
+            ticks: 0,
+            copied: 0,
+            byte: 0xFF,
+        }
+    }
+
+    pub fn source(&self) -> u16 {
+        self.source
+    }
+
+    /// The last byte copied, what the CPU reads from the buses the transfer is using.
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+
+    pub fn set_byte(&mut self, byte: u8) {
+        self.byte = byte;
+    }
+
+    /// Does the transfer keep the CPU from addr? Only HRAM, the IO registers, and IE stay reachable,
+    /// OAM is the transfer's destination.
+    pub fn blocks(&self, addr: u16) -> bool {
+        !self.pending && addr < 0xFEA0
+    }
+
+    /// Run the transfer for some T-cycles, returning the offsets of the bytes to copy now.
+    pub fn advance(&mut self, ticks: u32) -> Range<u16> {
+        if std::mem::take(&mut self.pending) {
+            return 0..0;
+        }
+        self.ticks += ticks;
+        let start = self.copied;
+        self.copied =
+            (self.ticks.saturating_sub(STARTUP_TICKS) / BYTE_TICKS).min(LENGTH as u32) as u16;
+        start..self.copied
+    }
+
+    /// Are all the bytes copied?
+    pub fn done(&self) -> bool {
+        self.copied == LENGTH
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.source);
+        w.bool(self.pending);
+        w.u32(self.ticks);
+        w.u16(self.copied);
+        w.u8(self.byte);
+    }
+
+    pub fn load_state(r: &mut StateReader) -> state::Result<Self> {
+        let dma = Self {
+            source: r.u16()?,
+            pending: r.bool()?,
+            ticks: r.u32()?,
+            copied: r.u16()?,
+            byte: r.u8()?,
+        };
+        if dma.copied > LENGTH {
+            return Err(state::StateError::Invalid("DMA"));
+        }
+        Ok(dma)
+    }
+}
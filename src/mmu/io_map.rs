@@ -0,0 +1,156 @@
+/// How the CPU reads an IO register ($FF00-$FF7F) on DMG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoRead {
+    /// A DMG register, the bits in the mask aren't wired up and read as 1.
+    Dmg(u8),
+
+    /// A CGB-only register, nothing drives the bus on DMG and it reads $FF.
+    Cgb,
+
+    /// Nothing is there on any model, it reads $FF.
+    Unmapped,
+}
+
+/// DMG registers, and the bits of each that read as 1 whatever was written, $FF for write-only registers.
+/// https://gbdev.io/pandocs/Hardware_Reg_List.html
+const DMG: [(u16, u8); 58] = [
+    (0xFF00, 0xC0), // P1
+    (0xFF01, 0x00), // SB
+    (0xFF02, 0x7E), // SC
+    (0xFF04, 0x00), // DIV
+    (0xFF05, 0x00), // TIMA
+    (0xFF06, 0x00), // TMA
+    (0xFF07, 0xF8), // TAC
+    (0xFF0F, 0xE0), // IF
+    (0xFF10, 0x80), // NR10
+    (0xFF11, 0x3F), // NR11, the length is write-only.
+    (0xFF12, 0x00), // NR12
+    (0xFF13, 0xFF), // NR13, the period is write-only.
+    (0xFF14, 0xBF), // NR14, only the length enable reads back.
+    (0xFF16, 0x3F), // NR21
+    (0xFF17, 0x00), // NR22
+    (0xFF18, 0xFF), // NR23
+    (0xFF19, 0xBF), // NR24
+    (0xFF1A, 0x7F), // NR30
+    (0xFF1B, 0xFF), // NR31
+    (0xFF1C, 0x9F), // NR32
+    (0xFF1D, 0xFF), // NR33
+    (0xFF1E, 0xBF), // NR34
+    (0xFF20, 0xFF), // NR41
+    (0xFF21, 0x00), // NR42
+    (0xFF22, 0x00), // NR43
+    (0xFF23, 0xBF), // NR44
+    (0xFF24, 0x00), // NR50
+    (0xFF25, 0x00), // NR51
+    (0xFF26, 0x70), // NR52
+    (0xFF30, 0x00), // Wave RAM
+    (0xFF31, 0x00),
+    (0xFF32, 0x00),
+    (0xFF33, 0x00),
+    (0xFF34, 0x00),
+    (0xFF35, 0x00),
+    (0xFF36, 0x00),
+    (0xFF37, 0x00),
+    (0xFF38, 0x00),
+    (0xFF39, 0x00),
+    (0xFF3A, 0x00),
+    (0xFF3B, 0x00),
+    (0xFF3C, 0x00),
+    (0xFF3D, 0x00),
+    (0xFF3E, 0x00),
+    (0xFF3F, 0x00),
+    (0xFF40, 0x00), // LCDC
+    (0xFF41, 0x80), // STAT
+    (0xFF42, 0x00), // SCY
+    (0xFF43, 0x00), // SCX
+    (0xFF44, 0x00), // LY
+    (0xFF45, 0x00), // LYC
+    (0xFF46, 0x00), // DMA
+    (0xFF47, 0x00), // BGP
+    (0xFF48, 0x00), // OBP0
+    (0xFF49, 0x00), // OBP1
+    (0xFF4A, 0x00), // WY
+    (0xFF4B, 0x00), // WX
+    (0xFF50, 0xFF), // BOOT, write-only.
+];
+
+/// CGB registers, the undocumented $FF72-$FF75 too. A DMG doesn't have them, they read $FF,
+/// which is how some games tell the models apart.
+const CGB: [(u16, &str); 21] = [
+    (0xFF4C, "KEY0"),
+    (0xFF4D, "KEY1"),
+    (0xFF4F, "VBK"),
+    (0xFF51, "HDMA1"),
+    (0xFF52, "HDMA2"),
+    (0xFF53, "HDMA3"),
+    (0xFF54, "HDMA4"),
+    (0xFF55, "HDMA5"),
+    (0xFF56, "RP"),
+    (0xFF68, "BCPS"),
+    (0xFF69, "BCPD"),
+    (0xFF6A, "OCPS"),
+    (0xFF6B, "OCPD"),
+    (0xFF6C, "OPRI"),
+    (0xFF70, "SVBK"),
+    (0xFF72, "FF72"),
+    (0xFF73, "FF73"),
+    (0xFF74, "FF74"),
+    (0xFF75, "FF75"),
+    (0xFF76, "PCM12"),
+    (0xFF77, "PCM34"),
+];
+
+/// DMG registers ferrum emulates, short of some of their behavior.
+const PARTIAL: [(u16, &str); 1] = [(0xFF41, "the spurious STAT interrupt on writes")];
+
+/// How much of an IO register ferrum emulates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoSupport {
+    Full,
+
+    /// Emulated, short of the behavior given.
+    Partial(&'static str),
+
+    /// A CGB register, with its name. ferrum only emulates the DMG, so it reads $FF and ignores writes, like a DMG,
+    /// but a game using it is likely after CGB hardware.
+    Unimplemented(&'static str),
+}
+
+/// IO register map
+/// What every address of $FF00-$FF7F reads as on DMG, by offset from $FF00.
+const IO_MAP: [IoRead; 0x80] = {
+    let mut map = [IoRead::Unmapped; 0x80];
+    let mut i = 0;
+    while i < DMG.len() {
+        map[DMG[i].0 as usize - 0xFF00] = IoRead::Dmg(DMG[i].1);
+        i += 1;
+    }
+    let mut i = 0;
+    while i < CGB.len() {
+        map[CGB[i].0 as usize - 0xFF00] = IoRead::Cgb;
+        i += 1;
+    }
+    map
+};
+
+/// How the CPU reads an IO register, addr is in $FF00-$FF7F.
+pub fn io_read(addr: u16) -> IoRead {
+    IO_MAP[addr as usize & 0x7F]
+}
+
+/// How much ferrum emulates the IO register at addr, in $FF00-$FF7F, None if there's no register there.
+pub fn io_support(addr: u16) -> Option<IoSupport> {
+    match io_read(addr) {
+        IoRead::Dmg(_) => Some(
+            PARTIAL
+                .iter()
+                .find(|(partial, _)| *partial == addr)
+                .map_or(IoSupport::Full, |(_, missing)| IoSupport::Partial(missing)),
+        ),
+        IoRead::Cgb => CGB
+            .iter()
+            .find(|(cgb, _)| *cgb == addr)
+            .map(|(_, name)| IoSupport::Unimplemented(name)),
+        IoRead::Unmapped => None,
+    }
+}
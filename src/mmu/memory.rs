@@ -1,3 +1,5 @@
+use crate::cartridge::banks::BankedAddr;
+
 pub trait Memory {
     /// Read a byte (u8) from memory.
     fn read8(&self, addr: u16) -> u8;
@@ -6,11 +8,63 @@ pub trait Memory {
     fn write8(&mut self, addr: u16, val: u8);
 
     /// Read a word (u16) from memory.
-    fn read16(&self, addr: u16) -> u16;
+    /// The address of the high byte wraps around at the end of the address space ($FFFF -> $0000).
+    fn read16(&self, addr: u16) -> u16 {
+        u16::from(self.read8(addr)) | (u16::from(self.read8(addr.wrapping_add(1))) << 8)
+    }
 
     /// Write a word (u16) to memory.
-    fn write16(&mut self, addr: u16, val: u16);
+    /// The address of the high byte wraps around at the end of the address space ($FFFF -> $0000).
+    fn write16(&mut self, addr: u16, val: u16) {
+        self.write8(addr, (val & 0xFF) as u8);
+        self.write8(addr.wrapping_add(1), (val >> 8) as u8);
+    }
+
+    /// The address qualified with the bank mapped there, if it's banked, e.g. 03:4F20.
+    fn banked(&self, addr: u16) -> BankedAddr {
+        BankedAddr::new(None, addr)
+    }
 
     /// Cycle the memory.
     fn cycle(&mut self, ticks: u32) -> u32;
 }
+
+/// RAM over the whole address space, and nothing else, for tests of what sits on the bus.
+#[cfg(test)]
+pub struct FlatRam(pub Vec<u8>);
+
+#[cfg(test)]
+impl FlatRam {
+    pub fn new() -> Self {
+        Self(vec![0x00; 0x10000])
+    }
+}
+
+#[cfg(test)]
+impl Memory for FlatRam {
+    fn read8(&self, addr: u16) -> u8 {
+        self.0[addr as usize]
+    }
+
+    fn write8(&mut self, addr: u16, val: u8) {
+        self.0[addr as usize] = val;
+    }
+
+    fn cycle(&mut self, ticks: u32) -> u32 {
+        ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A word at $FFFF has its high byte at $0000.
+    #[test]
+    fn word_wraps() {
+        let mut ram = FlatRam::new();
+        ram.write16(0xFFFF, 0x1234);
+        assert_eq!((ram.0[0xFFFF], ram.0[0x0000]), (0x34, 0x12));
+        assert_eq!(ram.read16(0xFFFF), 0x1234);
+    }
+}
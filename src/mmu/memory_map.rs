@@ -0,0 +1,118 @@
+/// What the CPU reaches in an area of the address space, on DMG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// Cartridge ROM, the fixed bank, with the boot ROM over $0000-$00FF while it's mapped.
+    Rom0,
+
+    /// Cartridge ROM, the bank the mapper switches in.
+    RomX,
+    Vram,
+
+    /// Cartridge RAM, or an MBC3's RTC register.
+    CartRam,
+    Wram0,
+    WramX,
+
+    /// Echo RAM, mirrors of $C000-$CFFF and $D000-$DDFF.
+    Echo0,
+    EchoX,
+    Oam,
+
+    /// Nothing is there, Nintendo says not to use it.
+    Prohibited,
+    Io,
+    Hram,
+
+    /// The Interrupt Enable register.
+    Ie,
+}
+
+impl Region {
+    /// A short name, e.g. "ROM X" or "WRAM 0".
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Rom0 => "ROM 0",
+            Region::RomX => "ROM X",
+            Region::Vram => "VRAM",
+            Region::CartRam => "Cart RAM",
+            Region::Wram0 => "WRAM 0",
+            Region::WramX => "WRAM X",
+            Region::Echo0 | Region::EchoX => "Echo RAM",
+            Region::Oam => "OAM",
+            Region::Prohibited => "Unusable",
+            Region::Io => "IO",
+            Region::Hram => "HRAM",
+            Region::Ie => "IE",
+        }
+    }
+}
+
+/// An area of the address space, from start to end inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Area {
+    pub start: u16,
+    pub end: u16,
+    pub region: Region,
+}
+
+const fn area(start: u16, end: u16, region: Region) -> Area {
+    Area { start, end, region }
+}
+
+/// Memory map
+/// Every area of the address space, in address order. The MMU dispatches reads and writes by it,
+/// so this is the memory map as emulated, not only as documented.
+/// https://gbdev.io/pandocs/Memory_Map.html
+pub const MEMORY_MAP: [Area; 13] = [
+    area(0x0000, 0x3FFF, Region::Rom0),
+    area(0x4000, 0x7FFF, Region::RomX),
+    area(0x8000, 0x9FFF, Region::Vram),
+    area(0xA000, 0xBFFF, Region::CartRam),
+    area(0xC000, 0xCFFF, Region::Wram0),
+    area(0xD000, 0xDFFF, Region::WramX),
+    area(0xE000, 0xEFFF, Region::Echo0),
+    area(0xF000, 0xFDFF, Region::EchoX),
+    area(0xFE00, 0xFE9F, Region::Oam),
+    area(0xFEA0, 0xFEFF, Region::Prohibited),
+    area(0xFF00, 0xFF7F, Region::Io),
+    area(0xFF80, 0xFFFE, Region::Hram),
+    area(0xFFFF, 0xFFFF, Region::Ie),
+];
+
+/// The area each 256 byte page starts in, by index in MEMORY_MAP. Only $FE and $FF hold more than one.
+const PAGES: [u8; 256] = {
+    let mut pages = [0; 256];
+    let mut page = 0;
+    let mut i = 0;
+    while page < 256 {
+        while (page << 8) as u16 > MEMORY_MAP[i].end {
+            i += 1;
+        }
+        pages[page] = i as u8;
+        page += 1;
+    }
+    pages
+};
+
+/// What the CPU reaches at addr.
+pub fn region(addr: u16) -> Region {
+    let mut i = PAGES[addr as usize >> 8] as usize;
+    while addr > MEMORY_MAP[i].end {
+        i += 1;
+    }
+    MEMORY_MAP[i].region
+}
+
+/// An area of the address space as the machine has it mapped right now, e.g. which ROM bank is switched in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapEntry {
+    pub start: u16,
+    pub end: u16,
+    pub region: Region,
+
+    /// A short name, the region's, or "Boot ROM" where it's mapped over cartridge ROM.
+    pub name: &'static str,
+
+    /// What's there, e.g. "RAM bank 01 of 04, disabled, reads $FF".
+    pub detail: String,
+}
@@ -1,17 +1,44 @@
-use crate::boot::BOOTROM;
+use crate::accuracy::Accuracy;
+use crate::apu::{Apu, Mixer};
+use crate::boot::POST_BOOT_IO;
+use crate::bus::{DmaBusPolicy, OpenBus, OpenBusPolicy};
 use crate::cartridge;
-use crate::cartridge::Cartridge;
-use crate::ppu::Ppu;
+use crate::cartridge::banks::{BankOverride, BankedAddr, Banks};
+use crate::cartridge::header::CartridgeType;
+use crate::cartridge::rtc::{Rtc, RtcTime};
+use crate::cartridge::{Cartridge, Mapper};
+use crate::debugport::{self, DebugPort};
+use crate::fault::Fault;
+use crate::joypad::{Buttons, Joypad};
+use crate::model::Model;
+use crate::ppu::debug::{IndexedImage, Layer, OamEntry};
+use crate::ppu::watchpoint::Watchpoint;
+use crate::ppu::{LcdOffPolicy, Ppu, PpuAccuracy, SpritePriority, SCREEN_PIXELS};
+use crate::serial::{Serial, SerialDevice};
+use crate::state::{self, Savestate, StateReader, StateWriter};
+use crate::timeline::{Event, Timeline};
 use crate::timer::Timer;
+use io_map::IoRead;
+pub use io_map::IoSupport;
+use memory_map::MEMORY_MAP;
+pub use memory_map::{MapEntry, Region};
 
+use self::dma::Dma;
 use self::memory::Memory;
 use super::cpu::interrupts::InterruptFlags;
 use log::{info, warn};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use std::cell::{Cell, RefCell};
 use std::io;
 use std::io::prelude::*;
-use std::{cell::RefCell, rc::Rc};
+use std::rc::Rc;
+mod dma;
+mod io_map;
 pub mod memory;
+mod memory_map;
+
+/// CPU clock scale of a Gameboy running at its normal speed, the scale is in thousandths.
+pub const CPU_CLOCK_SCALE_NORMAL: u32 = 1000;
 
 /// MMU is the Memory Management Unit. While the GameBoy did not have an actual
 /// MMU, it makes sense for our emulator. The GameBoy uses Memory Mapping to talk to
@@ -34,6 +61,7 @@ pub mod memory;
 /// FF80    FFFE    High RAM (HRAM)
 /// FFFF    FFFF    Interrupt Enable register (IE)
 ///
+/// Reads and writes are dispatched by MEMORY_MAP, see Mmu::memory_map for what's mapped where right now.
 /// https://gbdev.io/pandocs/Memory_Map.html
 pub struct Mmu {
     /// ROM Bank 00 - From cartridge, usually a fixed bank.
@@ -48,9 +76,18 @@ pub struct Mmu {
     /// Gameboy Timer
     timer: Timer,
 
+    /// Gameboy Joypad
+    joypad: Joypad,
+
+    /// Serial port, and the device plugged into it.
+    serial: Serial,
+
     /// Gameboy PPU
     ppu: Ppu,
 
+    /// Gameboy APU
+    apu: Apu,
+
     /// Video RAM (VRAM) - In CGB mode, switchable bank 0/1.
     //vram: [u8; (0x9FFF - 0x8000) + 1],
 
@@ -66,25 +103,114 @@ pub struct Mmu {
     /// Sprite attribute table (OAM).
     //oam: [u8; (0xFE9F - 0xFE00) + 1],
 
-    /// I/O Registers.
+    /// Writes to IO addresses no device handles, kept for save states, they read back as the register map says.
     io: [u8; (0xFF7F - 0xFF00) + 1],
 
+    /// Boot ROM Disable register (BANK) - ($FF50)
+    /// The boot ROM is overlaid on $0000-$00FF at power on. Writing a non-zero value to $FF50 unmaps it,
+    /// and the only way to map it back in is to power cycle the Gameboy.
+    boot_rom_enabled: bool,
+
     /// Interrupt Flags (IF).
     if_: Rc<RefCell<InterruptFlags>>,
 
+    /// Data bus, for reads that nothing responds to.
+    open_bus: Rc<OpenBus>,
+
+    /// OAM DMA transfer in progress, if any, and what the CPU sees of the buses it uses.
+    dma: Option<Dma>,
+    dma_bus: DmaBusPolicy,
+
     /// High RAM (HRAM).
     hram: [u8; (0xFFFE - 0xFF80) + 1],
 
     ///Interrupt Enable register (IE)
     ie: u8,
+
+    /// Print bytes written to the serial port (SB), and debug port messages.
+    serial_output: bool,
+
+    /// Where serial output is printed, stdout unless the host gives each machine its own.
+    serial_writer: Box<dyn Write>,
+
+    /// Bytes written to SB since they were last taken, while capturing them.
+    serial_capture: Option<Vec<u8>>,
+
+    /// Debug printf port, if enabled.
+    debug_port: Option<DebugPort>,
+
+    /// Record faults, for strict mode, and the first one since they were last taken.
+    /// Reads can fault too, hence the Cell.
+    strict: bool,
+    fault: Cell<Option<Fault>>,
+
+    /// IO registers the game read or wrote, a bit per register ($FF00 is bit 0). The first use of one
+    /// that isn't fully emulated is logged. The boot ROM, and peeks and pokes from the debugger, don't count.
+    io_used: Cell<u128>,
+    peeking: Cell<bool>,
+
+    /// Hardware revision, selects the boot ROM.
+    model: Model,
+
+    /// A boot ROM to run instead of the model's, if any.
+    boot_rom: Option<Vec<u8>>,
+
+    /// Speed of the CPU relative to the timer and PPU, in thousandths (1000 = normal speed).
+    cpu_clock_scale: u32,
+
+    /// CPU T-cycles, in thousandths, not yet passed on to the timer and PPU.
+    cpu_clock_remainder: u32,
+
+    /// T-cycles since power on, as seen by the timer and PPU.
+    cycles: u64,
+
+    /// Interrupt and IO register timeline, if enabled.
+    timeline: Option<Timeline>,
+
+    /// IF as of the last timeline update, to spot newly requested interrupts.
+    timeline_if: u8,
+
+    /// Whether VRAM was written since it was last taken, for the stall watch.
+    vram_written: bool,
+
+    /// Whether cartridge RAM was written since it was last taken, for the save journal.
+    ram_written: bool,
+
+    /// What LY reads, whatever the PPU is doing, if stubbed.
+    ly_stub: Option<u8>,
+
+    /// Banks forced from a debugger, on top of the cartridge's.
+    bank_override: BankOverride,
+
+    /// VRAM watchpoints, and the first write that triggered one since it was last taken, with its value.
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Option<(Watchpoint, u16, u8)>,
 }
 
 impl Mmu {
+    /// Initialize the MMU with a cartridge loaded from a ROM file on disk.
     pub fn new(rom_path: String) -> Self {
-        let cartridge = cartridge::new(rom_path);
+        Self::with_cartridge(cartridge::new(rom_path))
+    }
+
+    /// Initialize the MMU with a cartridge built from an in-memory ROM image, and optional external RAM image.
+    pub fn from_rom(rom: impl Into<Vec<u8>>, ram: Option<Vec<u8>>) -> Self {
+        Self::with_cartridge(cartridge::from_rom(rom, ram))
+    }
+
+    /// Initialize the MMU with a cartridge built from an in-memory ROM image, with the given mapper.
+    pub fn from_rom_as(rom: impl Into<Vec<u8>>, ram: Option<Vec<u8>>, mapper: Mapper) -> Self {
+        Self::with_cartridge(cartridge::from_rom_as(rom, ram, mapper))
+    }
+
+    fn with_cartridge(cartridge: Box<dyn Cartridge>) -> Self {
         let interrupt_flags = Rc::new(RefCell::new(InterruptFlags::new()));
-        let timer = Timer::new(interrupt_flags.clone());
-        let ppu = Ppu::new(interrupt_flags.clone());
+        let mut timer = Timer::new(interrupt_flags.clone());
+        timer.set_div_counter(Model::default().div_phase());
+        let joypad = Joypad::new(interrupt_flags.clone());
+        let serial = Serial::new(interrupt_flags.clone());
+        let open_bus = Rc::new(OpenBus::new());
+        let ppu = Ppu::new(interrupt_flags.clone(), open_bus.clone());
 
         // Randomize WRAM and HRAM, per Pan docs
         // https://gbdev.io/pandocs/Power_Up_Sequence.html#common-remarks
@@ -105,15 +231,43 @@ impl Mmu {
         Self {
             cartridge,
             timer,
+            joypad,
+            serial,
             ppu,
+            apu: Apu::new(),
             //vram: [0x00; (0x9FFF - 0x8000) + 1],
             wram0,
             wramx,
             //oam: [0x00; (0xFE9F - 0xFE00) + 1],
             io: [0x00; (0xFF7F - 0xFF00) + 1],
+            boot_rom_enabled: true,
             if_: interrupt_flags,
+            open_bus,
+            dma: None,
+            dma_bus: DmaBusPolicy::default(),
             hram,
             ie: 0x00,
+            serial_output: true,
+            serial_writer: Box::new(io::stdout()),
+            serial_capture: None,
+            debug_port: None,
+            strict: false,
+            fault: Cell::new(None),
+            io_used: Cell::new(0),
+            peeking: Cell::new(false),
+            model: Model::default(),
+            boot_rom: None,
+            cpu_clock_scale: CPU_CLOCK_SCALE_NORMAL,
+            cpu_clock_remainder: 0,
+            cycles: 0,
+            timeline: None,
+            timeline_if: 0x00,
+            vram_written: false,
+            ram_written: false,
+            ly_stub: None,
+            bank_override: BankOverride::default(),
+            watchpoints: Vec::new(),
+            watch_hit: None,
         }
     }
 
@@ -121,6 +275,438 @@ impl Mmu {
         self.cartridge.title()
     }
 
+    /// Cartridge type from the header, None if it isn't a known type.
+    pub fn cartridge_type(&self) -> Option<CartridgeType> {
+        self.cartridge.mbc()
+    }
+
+    /// The cartridge's Real Time Clock, if it has one.
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.cartridge.rtc_mut()
+    }
+
+    /// Size of the cartridge ROM.
+    pub fn rom_size(&self) -> usize {
+        self.cartridge.rom_len()
+    }
+
+    /// Is the boot ROM still mapped over $0000-$00FF, still running?
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom_enabled
+    }
+
+    /// Leave the IO registers and the divider as the boot ROM would, and unmap it, without running it.
+    /// VRAM stays blank, the logo is never drawn.
+    pub fn skip_boot(&mut self) {
+        for (addr, val) in POST_BOOT_IO {
+            self.write8(addr, val);
+        }
+        self.timer.set_div_counter(self.model.post_boot_div());
+        self.boot_rom_enabled = false;
+    }
+
+    /// Offset in the cartridge ROM of the byte the CPU sees at addr, None if it isn't cartridge ROM.
+    pub fn rom_offset(&self, addr: u16) -> Option<usize> {
+        match (addr, self.bank_override.rom) {
+            (0x0000..=0x00FF, _) if self.boot_rom_enabled => None,
+            // Banks past the end of the ROM wrap around, as if the bank bits weren't wired up.
+            (0x4000..=0x7FFF, Some(bank)) => {
+                Some((bank * 0x4000 + (addr as usize - 0x4000)) % self.cartridge.rom_len())
+            }
+            _ => self.cartridge.rom_offset(addr),
+        }
+    }
+
+    /// What the cartridge's battery keeps alive, its external RAM and the RTC footer, see Cartridge::battery_save.
+    pub fn battery_save(&self) -> Option<Vec<u8>> {
+        self.cartridge.battery_save()
+    }
+
+    /// Replace the contents of the cartridge's external RAM, e.g. with a save file.
+    pub fn load_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_ram(data);
+    }
+
+    /// Current time of the cartridge's Real Time Clock, if it has one.
+    pub fn rtc_state(&self) -> Option<RtcTime> {
+        self.cartridge.rtc_state()
+    }
+
+    /// T-cycles the cartridge's rumble motor ran for since they were last taken, None if it has no motor.
+    pub fn take_rumble(&mut self) -> Option<u32> {
+        self.cartridge.take_rumble()
+    }
+
+    /// The hardware revision.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Swap cartridges with another machine, e.g. to power on with a new build of the ROM.
+    pub fn swap_cartridge(&mut self, other: &mut Mmu) {
+        std::mem::swap(&mut self.cartridge, &mut other.cartridge);
+    }
+
+    /// Select the hardware revision, this must happen at power on, before the boot ROM runs.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.timer.set_div_counter(model.div_phase());
+    }
+
+    /// Run a custom boot ROM (256 bytes) instead of the model's, None goes back to the model's.
+    /// A new boot ROM takes effect right away, if the boot ROM is still mapped.
+    /// One of any other size is refused, and the current boot ROM stays.
+    pub fn set_boot_rom(&mut self, boot_rom: Option<Vec<u8>>) -> Result<(), String> {
+        match boot_rom {
+            Some(boot_rom) if boot_rom.len() != 0x100 => {
+                Err(format!("a boot ROM is 256 bytes, not {}", boot_rom.len()))
+            }
+            boot_rom => {
+                self.boot_rom = boot_rom;
+                Ok(())
+            }
+        }
+    }
+
+    /// Refill WRAM and HRAM from a seeded generator instead of a random one, so runs are reproducible.
+    pub fn seed_ram(&mut self, seed: u64) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        rng.fill(&mut self.wram0[..]);
+        rng.fill(&mut self.wramx[..]);
+        rng.fill(&mut self.hram[..]);
+    }
+
+    /// Enable or disable printing serial output, used to silence frames that will be rolled back.
+    pub fn set_serial_output(&mut self, enabled: bool) {
+        self.serial_output = enabled;
+    }
+
+    /// Print serial output to the given writer, instead of stdout.
+    pub fn set_serial_writer(&mut self, writer: Box<dyn Write>) {
+        self.serial_writer = writer;
+    }
+
+    /// Start or stop keeping the bytes written to SB, to be taken with take_serial_bytes.
+    pub fn set_serial_capture(&mut self, enabled: bool) {
+        self.serial_capture = enabled.then(Vec::new);
+    }
+
+    /// Bytes written to SB since the last call, while capturing them.
+    pub fn take_serial_bytes(&mut self) -> Vec<u8> {
+        self.serial_capture
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Print serial output, a failure to write it doesn't stop emulation.
+    fn print_serial(&mut self, text: &str) {
+        if !self.serial_output {
+            return;
+        }
+        if let Err(e) = self
+            .serial_writer
+            .write_all(text.as_bytes())
+            .and_then(|()| self.serial_writer.flush())
+        {
+            warn!("Failed to print serial output: {}", e);
+        }
+    }
+
+    /// Overclock (or underclock) the CPU relative to the timer and PPU, in thousandths (1000 = normal speed).
+    pub fn set_cpu_clock_scale(&mut self, scale: u32) {
+        self.cpu_clock_scale = scale.max(1);
+        self.cpu_clock_remainder = 0;
+    }
+
+    /// Start streaming interrupts and IO register writes to a timeline.
+    pub fn set_timeline(&mut self, timeline: Timeline) {
+        self.timeline_if = self.if_.borrow().data;
+        self.timeline = Some(timeline);
+    }
+
+    /// Stop the timeline, handing it back.
+    pub fn take_timeline(&mut self) -> Option<Timeline> {
+        self.timeline.take()
+    }
+
+    /// Whether a timeline is being streamed.
+    pub fn has_timeline(&self) -> bool {
+        self.timeline.is_some()
+    }
+
+    /// Tell the timeline which frame the events that follow belong to.
+    pub fn timeline_frame(&mut self, frame: u64) {
+        if let Some(timeline) = &mut self.timeline {
+            timeline.set_frame(frame);
+        }
+    }
+
+    /// T-cycles since power on, as seen by the timer and PPU.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Record an event on the timeline, if there is one.
+    fn trace(&mut self, event: Event) {
+        if let Some(timeline) = &mut self.timeline {
+            timeline.record(self.cycles, event);
+        }
+    }
+
+    /// The banks the CPU sees, the cartridge's, or the overridden ones.
+    pub fn banks(&self) -> Banks {
+        self.bank_override.apply(self.cartridge.current_banks())
+    }
+
+    /// The banks the cartridge's mapper has selected, whatever the override.
+    pub fn mapper_banks(&self) -> Banks {
+        self.cartridge.current_banks()
+    }
+
+    pub fn bank_override(&self) -> BankOverride {
+        self.bank_override
+    }
+
+    /// Force banks whatever the mapper's registers say, see BankOverride.
+    pub fn set_bank_override(&mut self, bank_override: BankOverride) {
+        self.bank_override = bank_override;
+    }
+
+    /// The address space as the CPU sees it right now, area by area of MEMORY_MAP, what reads and writes
+    /// are dispatched by. The boot ROM gets its own entry while it's mapped over $0000-$00FF.
+    pub fn memory_map(&self) -> Vec<MapEntry> {
+        let banks = self.banks();
+        let rom_banks = self.cartridge.rom_len().div_ceil(0x4000);
+        let mut entries = Vec::new();
+        for area in MEMORY_MAP {
+            let mut start = area.start;
+            let detail = match area.region {
+                Region::Rom0 => {
+                    if self.boot_rom_enabled {
+                        let boot_rom = match self.boot_rom {
+                            Some(_) => "custom boot ROM",
+                            None => "built-in boot ROM",
+                        };
+                        entries.push(MapEntry {
+                            start,
+                            end: 0x00FF,
+                            region: area.region,
+                            name: "Boot ROM",
+                            detail: format!("{}, until $FF50 is written", boot_rom),
+                        });
+                        start = 0x0100;
+                    }
+                    format!("ROM bank {:02X} of {:02X}", banks.rom0, rom_banks)
+                }
+                Region::RomX => format!(
+                    "ROM bank {:02X} of {:02X}{}",
+                    banks.rom,
+                    rom_banks,
+                    if self.bank_override.rom.is_some() {
+                        ", forced"
+                    } else {
+                        ""
+                    }
+                ),
+                Region::Vram => "tile data and tile maps".to_string(),
+                Region::CartRam => self.cart_ram_detail(banks),
+                Region::Wram0 | Region::WramX => "work RAM".to_string(),
+                Region::Echo0 => "mirror of $C000-$CFFF".to_string(),
+                Region::EchoX => "mirror of $D000-$DDFF".to_string(),
+                Region::Oam => "40 sprites, 4 bytes each".to_string(),
+                Region::Prohibited => "reads $00, or $FF while OAM is blocked".to_string(),
+                Region::Io => {
+                    let registers = (area.start..=area.end)
+                        .filter(|&addr| matches!(io_map::io_read(addr), IoRead::Dmg(_)))
+                        .count();
+                    format!("{} DMG registers, the rest read $FF", registers)
+                }
+                Region::Hram => format!("{} bytes", area.end - area.start + 1),
+                Region::Ie => format!("${:02X}", self.ie),
+            };
+            entries.push(MapEntry {
+                start,
+                end: area.end,
+                region: area.region,
+                name: area.region.name(),
+                detail,
+            });
+        }
+        entries
+    }
+
+    /// What's at $A000-$BFFF with banks mapped, e.g. "RAM bank 01 of 04, enabled", for the memory map.
+    fn cart_ram_detail(&self, banks: Banks) -> String {
+        let ram_len = self.cartridge.ram().map_or(0, <[u8]>::len);
+        let has_rtc = self.cartridge.rtc().is_some();
+        if ram_len == 0 && !has_rtc {
+            return "no RAM, reads $FF".to_string();
+        }
+        let mut detail = match banks.ram {
+            Some(register @ 0x08..=0x0C) if has_rtc && self.bank_override.ram.is_none() => {
+                format!("RTC register {:02X}", register)
+            }
+            Some(bank) => format!("RAM bank {:02X} of {:02X}", bank, ram_len.div_ceil(0x2000)),
+            None => format!("{} KiB RAM", ram_len / 0x400),
+        };
+        match banks.ram_enabled {
+            Some(true) => detail.push_str(", enabled"),
+            Some(false) => detail.push_str(", disabled, reads $FF"),
+            None => (),
+        }
+        if !self.bank_override.ram_is_mapped() {
+            detail.push_str(", forced");
+        }
+        detail
+    }
+
+    /// Read cartridge ROM ($0000-$7FFF), through the ROM bank override if there is one.
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x4000..=0x7FFF if self.bank_override.rom.is_some() => self
+                .rom_offset(addr)
+                .map_or(0xFF, |offset| self.cartridge.rom()[offset]),
+            _ => self.cartridge.read8(addr),
+        }
+    }
+
+    /// Offset in external RAM of addr ($A000-$BFFF) with the RAM override, None if it's disabled,
+    /// or there's no such bank.
+    fn ram_override_offset(&self, addr: u16) -> Option<usize> {
+        let banks = self.banks();
+        if banks.ram_enabled == Some(false) {
+            return None;
+        }
+        let offset = banks.ram.unwrap_or(0) * 0x2000 + (addr as usize - 0xA000);
+        (offset < self.cartridge.ram().map_or(0, <[u8]>::len)).then_some(offset)
+    }
+
+    /// Read external RAM ($A000-$BFFF), None if nothing drives the bus, through the RAM override if there is one.
+    fn read_ram(&self, addr: u16) -> Option<u8> {
+        if self.bank_override.ram_is_mapped() {
+            return self.cartridge.read_ram(addr);
+        }
+        let offset = self.ram_override_offset(addr)?;
+        self.cartridge.ram().map(|ram| ram[offset])
+    }
+
+    /// Write external RAM ($A000-$BFFF), through the RAM override if there is one.
+    fn write_ram(&mut self, addr: u16, val: u8) {
+        self.ram_written = true;
+        if self.bank_override.ram_is_mapped() {
+            self.cartridge.write8(addr, val);
+            return;
+        }
+        if let (Some(offset), Some(ram)) =
+            (self.ram_override_offset(addr), self.cartridge.ram_mut())
+        {
+            ram[offset] = val;
+        }
+    }
+
+    /// Record an interrupt dispatched by the CPU on the timeline.
+    pub fn trace_dispatch(&mut self, interrupt: u8) {
+        if let Some(timeline) = &mut self.timeline {
+            // The interrupt can be requested and dispatched before the next timeline update.
+            if self.timeline_if & (1 << interrupt) == 0 {
+                timeline.record(self.cycles, Event::Request(interrupt));
+            }
+            timeline.record(self.cycles, Event::Dispatch(interrupt));
+            self.timeline_if = self.if_.borrow().data;
+        }
+    }
+
+    pub fn set_open_bus_policy(&mut self, policy: OpenBusPolicy) {
+        self.open_bus.set_policy(policy);
+    }
+
+    pub fn set_dma_bus_policy(&mut self, policy: DmaBusPolicy) {
+        self.dma_bus = policy;
+    }
+
+    /// The accuracy settings of the PPU and the buses.
+    pub fn accuracy(&self) -> Accuracy {
+        Accuracy {
+            ppu: self.ppu.accuracy(),
+            vram_blocking: self.ppu.vram_blocking(),
+            open_bus: self.open_bus.policy(),
+            dma_bus: self.dma_bus,
+        }
+    }
+
+    pub fn set_accuracy(&mut self, accuracy: Accuracy) {
+        self.ppu.set_accuracy(accuracy.ppu);
+        self.ppu.set_vram_blocking(accuracy.vram_blocking);
+        self.open_bus.set_policy(accuracy.open_bus);
+        self.dma_bus = accuracy.dma_bus;
+    }
+
+    /// Start an OAM DMA transfer, from $XX00.
+    /// DMG has no source past $DF, the transfer reads WRAM for $E0-$FF, like echo RAM.
+    fn start_dma(&mut self, val: u8) {
+        let mut source = (val as u16) << 8;
+        if val > 0xDF {
+            warn!("OAM DMA from ${:04X}, which DMG reads from WRAM", source);
+            self.fault(Fault::DmaSource(source));
+            source -= 0x2000;
+        }
+        self.dma = Some(Dma::new(source));
+    }
+
+    /// A byte for an OAM DMA transfer to copy, it reads memory directly, whatever the CPU or PPU are doing.
+    fn dma_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.read_rom(addr),
+            0x8000..=0x9FFF => self.ppu.inspect(addr),
+            0xA000..=0xBFFF => self.read_ram(addr).unwrap_or(0xFF),
+            0xC000..=0xCFFF => self.wram0[addr as usize & 0x0FFF],
+            _ => self.wramx[addr as usize & 0x0FFF],
+        }
+    }
+
+    /// Copy the bytes an OAM DMA transfer reached.
+    fn cycle_dma(&mut self, ticks: u32) {
+        let Some(mut dma) = self.dma.take() else {
+            return;
+        };
+        for offset in dma.advance(ticks) {
+            let byte = self.dma_read(dma.source() + offset);
+            self.ppu.dma_write(offset as usize, byte);
+            dma.set_byte(byte);
+        }
+        if !dma.done() {
+            self.dma = Some(dma);
+        }
+    }
+
+    /// Plug a device into the link port, returning the one it replaces.
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+        self.serial.set_device(device)
+    }
+
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.joypad.set_buttons(buttons);
+    }
+
+    /// Produce audio samples at the given rate, 0 stops producing samples.
+    pub fn set_audio_sample_rate(&mut self, rate: u32) {
+        self.apu.set_sample_rate(rate);
+    }
+
+    pub fn audio_sample_rate(&self) -> u32 {
+        self.apu.sample_rate()
+    }
+
+    /// Audio samples produced since the last call, interleaved stereo.
+    pub fn apu_take_samples(&mut self) -> Vec<i16> {
+        self.apu.take_samples()
+    }
+
+    pub fn apu_mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.apu.mixer
+    }
+
     pub fn ppu_updated(&mut self) -> bool {
         let result = self.ppu.updated;
         self.ppu.updated = false;
@@ -128,66 +714,254 @@ impl Mmu {
         //true
     }
 
-    pub fn ppu_get_viewport(&mut self) -> &Vec<Vec<u32>> {
-        &self.ppu.viewport_buffer
+    pub fn ppu_set_accuracy(&mut self, accuracy: PpuAccuracy) {
+        self.ppu.set_accuracy(accuracy);
+    }
+
+    pub fn ppu_set_sprite_priority(&mut self, priority: SpritePriority) {
+        self.ppu.set_sprite_priority(priority);
+    }
+
+    pub fn ppu_get_viewport(&self) -> &[u32; SCREEN_PIXELS] {
+        self.ppu.viewport()
+    }
+
+    pub fn ppu_set_widescreen(&mut self, enabled: bool) {
+        self.ppu.set_widescreen(enabled);
+    }
+
+    pub fn ppu_set_interlaced(&mut self, enabled: bool) {
+        self.ppu.set_interlaced(enabled);
+    }
+
+    pub fn ppu_set_lcd_off_policy(&mut self, policy: LcdOffPolicy) {
+        self.ppu.set_lcd_off_policy(policy);
+    }
+
+    pub fn ppu_lcd_on(&self) -> bool {
+        self.ppu.lcd_on()
+    }
+
+    pub fn ppu_interlaced(&self) -> bool {
+        self.ppu.interlaced()
+    }
+
+    pub fn ppu_accuracy(&self) -> PpuAccuracy {
+        self.ppu.accuracy()
+    }
+
+    pub fn ppu_widescreen(&self) -> Option<&[u32]> {
+        self.ppu.widescreen()
+    }
+
+    pub fn ppu_tile_sheet(&self) -> IndexedImage {
+        self.ppu.tile_sheet()
+    }
+
+    pub fn ppu_sprite_sheet(&self) -> IndexedImage {
+        self.ppu.sprite_sheet()
+    }
+
+    pub fn ppu_oam_entries(&self) -> Vec<OamEntry> {
+        self.ppu.oam_entries()
+    }
+
+    /// Read a byte as the CPU sees it, except VRAM and OAM are readable in every PPU mode.
+    pub fn inspect(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.ppu.inspect(addr),
+            _ => self.peek8(addr),
+        }
+    }
+
+    /// Whether VRAM was written since the last call.
+    pub fn take_vram_written(&mut self) -> bool {
+        std::mem::take(&mut self.vram_written)
+    }
+
+    /// Whether cartridge RAM was written since the last call.
+    pub fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.ram_written)
+    }
+
+    /// Make LY read val whatever the PPU is doing, or None to read the PPU's LY again.
+    pub fn set_ly_stub(&mut self, val: Option<u8>) {
+        self.ly_stub = val;
+    }
+
+    /// Watch for writes to VRAM, once the boot ROM is done, see Watchpoint.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// The first write that triggered a watchpoint since the last call, if any: the watchpoint, address, and value.
+    pub fn take_watch_hit(&mut self) -> Option<(Watchpoint, u16, u8)> {
+        self.watch_hit.take()
+    }
+
+    /// Record faults (prohibited memory and unknown IO register accesses), see Fault.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+        self.fault.set(None);
+    }
+
+    /// The first fault since the last call, if any.
+    pub fn take_fault(&self) -> Option<Fault> {
+        self.fault.take()
+    }
+
+    fn fault(&self, fault: Fault) {
+        if self.strict && self.fault.get().is_none() {
+            self.fault.set(Some(fault));
+        }
+    }
+
+    /// Note the game using the IO register at addr, if it's one, logging the first use of one that isn't fully emulated.
+    fn use_io(&self, addr: u16) {
+        if !(0xFF00..0xFF80).contains(&addr) || self.boot_rom_enabled || self.peeking.get() {
+            return;
+        }
+        let bit = 1u128 << (addr & 0x7F);
+        if self.io_used.get() & bit != 0 {
+            return;
+        }
+        self.io_used.set(self.io_used.get() | bit);
+        match io_map::io_support(addr) {
+            Some(IoSupport::Unimplemented(name)) => {
+                warn!(
+                    "The game used ${:04X} ({}), which isn't implemented",
+                    addr, name
+                )
+            }
+            Some(IoSupport::Partial(missing)) => info!(
+                "The game used ${:04X}, which is implemented without {}",
+                addr, missing
+            ),
+            _ => (),
+        }
+    }
+
+    /// The IO registers the game used that aren't fully emulated, in address order.
+    pub fn unsupported_io(&self) -> Vec<(u16, IoSupport)> {
+        (0xFF00..0xFF80)
+            .filter(|addr| self.io_used.get() & (1u128 << (addr & 0x7F)) != 0)
+            .filter_map(|addr| Some((addr, io_map::io_support(addr)?)))
+            .filter(|(_, support)| *support != IoSupport::Full)
+            .collect()
+    }
+
+    /// Read a byte as the CPU would, without it counting as the game using an IO register.
+    pub fn peek8(&self, addr: u16) -> u8 {
+        self.peeking.set(true);
+        let val = self.read8(addr);
+        self.peeking.set(false);
+        val
+    }
+
+    /// Write a byte as the CPU would, without it counting as the game using an IO register.
+    pub fn poke8(&mut self, addr: u16, val: u8) {
+        self.peeking.set(true);
+        self.write8(addr, val);
+        self.peeking.set(false);
+    }
+
+    /// Enable or disable the debug printf port at $FF7E-$FF7F.
+    pub fn set_debug_port(&mut self, enabled: bool) {
+        self.debug_port = enabled.then(DebugPort::new);
+    }
+
+    pub fn ppu_map_image(&self, layer: Layer) -> IndexedImage {
+        self.ppu.map_image(layer)
     }
 }
 
 impl Memory for Mmu {
     /// Read a byte (u8) from memory.
     fn read8(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => {
-                // Should we read from Boot ROM?
-                if addr <= 0xFF {
-                    // Is the Boot ROM enabled?
-                    if self.io[0x50] == 0x00 {
-                        // Yes, read from Boot ROM.
-                        info!("Reading from Boot ROM: {:04X}", addr);
-                        return BOOTROM[addr as usize];
-                    } else {
-                        // No, read from ROM0.
-                        info!("Reading from ROM0: {:04X}", addr);
-                        return self.cartridge.read8(addr);
-                    }
+        self.use_io(addr);
+        // OAM reads $FF while DMA writes to it, and the rest of the buses it uses read the byte being copied.
+        if let Some(dma) = self.dma.as_ref().filter(|dma| dma.blocks(addr)) {
+            self.fault(Fault::DmaConflict(addr));
+            let val = match addr {
+                0xFE00..=0xFE9F => Some(0xFF),
+                _ if self.dma_bus == DmaBusPolicy::Restricted => Some(dma.byte()),
+                _ => None,
+            };
+            if let Some(val) = val {
+                return val;
+            }
+        }
+
+        let val = match memory_map::region(addr) {
+            // The Boot ROM only overlays $0000-$00FF while it is mapped.
+            // Everything from $0100 up, including the cartridge header and the Nintendo logo
+            // the Boot ROM compares against, is always read from the cartridge.
+            Region::Rom0 if addr <= 0x00FF && self.boot_rom_enabled => {
+                info!("Reading from Boot ROM: {:04X}", addr);
+                match &self.boot_rom {
+                    Some(boot_rom) => boot_rom[addr as usize],
+                    None => self.model.boot_rom()[addr as usize],
                 }
-                self.cartridge.read8(addr)
             }
-            0x4000..=0x7FFF => self.cartridge.read8(addr),
-            0x8000..=0x9FFF => self.ppu.read8(addr),
-            0xA000..=0xBFFF => self.cartridge.read8(addr),
-            0xC000..=0xCFFF | 0xE000..=0xEFFF => self.wram0[addr as usize & 0x0FFF],
-            0xD000..=0xDFFF | 0xF000..=0xFDFF => self.wramx[addr as usize & 0x0FFF],
-            0xFE00..=0xFE9F => self.ppu.read8(addr),
-            0xFF00..=0xFF7F => {
-                match addr {
-                    // TODO: Implement the rest of the IO registers.
-                    0xFF0F => {
-                        // Interrupt Flags
-                        self.if_.borrow().data
-                    }
+            Region::Rom0 | Region::RomX => self.read_rom(addr),
+            Region::Vram => self.ppu.read8(addr),
+            // Disabled or missing external RAM leaves the bus open, which reads as 0xFF on DMG.
+            Region::CartRam => self
+                .read_ram(addr)
+                .unwrap_or_else(|| self.open_bus.read(0xFF)),
+            Region::Wram0 | Region::Echo0 => self.wram0[addr as usize & 0x0FFF],
+            Region::WramX | Region::EchoX => self.wramx[addr as usize & 0x0FFF],
+            Region::Oam => self.ppu.read8(addr),
+            // The devices read their registers, the register map fills in the bits that aren't wired up.
+            Region::Io => match io_map::io_read(addr) {
+                IoRead::Dmg(unused) => {
+                    unused
+                        | match addr {
+                            // Joypad
+                            0xFF00 => self.joypad.get(),
 
-                    // Timer Registers
-                    0xFF04..=0xFF07 => self.timer.get(addr),
+                            // Serial transfer data and control
+                            0xFF01 | 0xFF02 => self.serial.get(addr),
 
-                    // PPU Registers
-                    0xFF40..=0xFF4B => self.ppu.read8(addr),
+                            // Interrupt Flags
+                            0xFF0F => self.if_.borrow().data,
+
+                            // Timer Registers
+                            0xFF04..=0xFF07 => self.timer.get(addr),
+
+                            // Sound Registers, and Wave RAM
+                            0xFF10..=0xFF3F => self.apu.read(addr),
 
-                    // Stub LY, for testing.
-                    //0xFF44 => 0x90,
-                    _ => self.io[addr as usize - 0xFF00],
+                            // PPU Registers, LY can be stubbed, for comparing traces.
+                            0xFF40..=0xFF4B => match (addr, self.ly_stub) {
+                                (0xFF44, Some(ly)) => ly,
+                                _ => self.ppu.read8(addr),
+                            },
+
+                            // Boot ROM Disable register is write-only.
+                            _ => 0xFF,
+                        }
                 }
-            }
-            0xFF80..=0xFFFE => self.hram[addr as usize - 0xFF80],
-            0xFFFF => self.ie,
-            _ => {
+                IoRead::Cgb => 0xFF,
+                IoRead::Unmapped => {
+                    self.fault(Fault::UnknownIoRead(addr));
+                    0xFF
+                }
+            },
+            Region::Hram => self.hram[addr as usize - 0xFF80],
+            Region::Ie => self.ie,
+            Region::Prohibited => {
                 warn!("Attempt to read prohibited area of memory, {:#02x}.", addr);
+                self.fault(Fault::ProhibitedRead(addr));
                 // 0xFEA0 - 0xFEFF is prohibited.
-                // DMG will return 0x00.
+                // DMG will return 0x00, or 0xFF while OAM is blocked by the PPU.
                 // https://gbdev.io/pandocs/Memory_Map.html
-                0x00
+                self.open_bus
+                    .read(if self.ppu.oam_blocked() { 0xFF } else { 0x00 })
             }
-        }
+        };
+        self.open_bus.latch(val);
+        val
     }
 
     /// Write a byte (u8) to memory.
@@ -196,56 +970,147 @@ impl Memory for Mmu {
             "MMU Write8 val --> [addr]: {:#02x} --> [{:#02x}]",
             val, addr
         );
-        match addr {
-            0x0000..=0x3FFF => self.cartridge.write8(addr, val),
-            0x4000..=0x7FFF => self.cartridge.write8(addr, val),
-            0x8000..=0x9FFF => self.ppu.write8(addr, val),
-            0xA000..=0xBFFF => self.cartridge.write8(addr, val),
-            0xC000..=0xCFFF | 0xE000..=0xEFFF => self.wram0[addr as usize & 0x0FFF] = val,
-            0xD000..=0xDFFF | 0xF000..=0xFDFF => self.wramx[addr as usize & 0x0FFF] = val,
-            0xFE00..=0xFE9F => self.ppu.write8(addr, val),
-            0xFF00..=0xFF7F => {
+        self.open_bus.latch(val);
+        self.use_io(addr);
+        if let Some(timeline) = &mut self.timeline {
+            if timeline.traces(addr) {
+                timeline.record(self.cycles, Event::Write { addr, value: val });
+            }
+        }
+        // Writes to the buses OAM DMA uses are lost.
+        if self.dma.as_ref().is_some_and(|dma| dma.blocks(addr)) {
+            self.fault(Fault::DmaConflict(addr));
+            if (0xFE00..=0xFE9F).contains(&addr) || self.dma_bus == DmaBusPolicy::Restricted {
+                return;
+            }
+        }
+        match memory_map::region(addr) {
+            // Writes to ROM go to the mapper, the timeline shows the bank switches they make.
+            Region::Rom0 | Region::RomX if self.timeline.is_some() => {
+                let before = self.mapper_banks();
+                self.cartridge.write8(addr, val);
+                let banks = self.mapper_banks();
+                if banks.rom != before.rom {
+                    self.trace(Event::RomBank(banks.rom as u16));
+                }
+                if let (Some(bank), true) = (banks.ram, banks.ram != before.ram) {
+                    self.trace(Event::RamBank(bank as u8));
+                }
+            }
+            Region::Rom0 | Region::RomX => self.cartridge.write8(addr, val),
+            Region::Vram => {
+                // The boot ROM clearing VRAM and drawing the logo isn't the game's code.
+                if !self.watchpoints.is_empty()
+                    && !self.boot_rom_enabled
+                    && self.watch_hit.is_none()
+                {
+                    self.watch_hit = self
+                        .watchpoints
+                        .iter()
+                        .find(|watchpoint| watchpoint.range().contains(&addr))
+                        .map(|&watchpoint| (watchpoint, addr, val));
+                }
+                self.vram_written = true;
+                self.ppu.write8(addr, val);
+            }
+            Region::CartRam => {
+                if self.timeline.is_some() && self.read_ram(addr).is_some() {
+                    self.trace(Event::RamWrite { addr, value: val });
+                }
+                self.write_ram(addr, val);
+            }
+            Region::Wram0 | Region::Echo0 => self.wram0[addr as usize & 0x0FFF] = val,
+            Region::WramX | Region::EchoX => self.wramx[addr as usize & 0x0FFF] = val,
+            Region::Oam => self.ppu.write8(addr, val),
+            Region::Io => {
                 match addr {
                     //TODO: Implement the rest of the IO registers.
+                    // Joypad
+                    0xFF00 => self.joypad.set(val),
+
                     0xFF0F => {
                         // Interrupt Flags
-                        self.if_.borrow_mut().data = val;
+                        self.if_.borrow_mut().data = val & 0x1F;
                     }
-                    // Intercept Serial writes, and output to stdout.
+                    // Intercept Serial writes, and print them.
                     0xFF01 => {
-                        // Output serial data, and flush stdout.
-                        print!("{}", val as char);
-                        io::stdout().flush().unwrap();
-                        self.io[addr as usize - 0xFF00] = val;
+                        self.print_serial((val as char).encode_utf8(&mut [0; 4]));
+                        if let Some(bytes) = &mut self.serial_capture {
+                            bytes.push(val);
+                        }
+                        self.serial.set(addr, val);
+                    }
+                    0xFF02 => {
+                        self.serial.set(addr, val);
+                        if val & 0x80 != 0 {
+                            self.trace(Event::SerialSend(self.serial.get(0xFF01)));
+                        }
                     }
 
                     // Timer Registers
                     0xFF04..=0xFF07 => {
+                        // Resetting DIV while bit 4 is set is a falling edge, which steps the frame sequencer.
+                        if addr == 0xFF04 && self.timer.get(0xFF04) & 0x10 != 0 {
+                            self.apu.div_apu();
+                        }
                         self.timer.set(addr, val);
                     }
 
+                    // Sound Registers, and Wave RAM
+                    0xFF10..=0xFF3F => self.apu.write(addr, val),
+
+                    // OAM DMA, the PPU keeps the register.
+                    0xFF46 => {
+                        self.ppu.write8(addr, val);
+                        self.start_dma(val);
+                    }
+
                     // PPU Registers
-                    0xFF40..=0xFF4B => self.ppu.write8(addr, val),
+                    0xFF40 => {
+                        let was_on = self.ppu.read8(addr) & 0x80 != 0;
+                        let on = val & 0x80 != 0;
+                        self.ppu.write8(addr, val);
+                        if on != was_on {
+                            self.trace(Event::Lcd(on));
+                        }
+                    }
+                    0xFF41..=0xFF4B => self.ppu.write8(addr, val),
+
+                    // Boot ROM Disable register
+                    // The Boot ROM writes to 0xFF50 as its last instruction to unmap itself.
+                    // This is one-way, once the Boot ROM is disabled it can't be re-enabled.
+                    0xFF50 => {
+                        if self.boot_rom_enabled && val != 0x00 {
+                            self.boot_rom_enabled = false;
+                            info!("Boot ROM disabled");
+                        }
+                    }
 
-                    _ => self.io[addr as usize - 0xFF00] = val,
+                    // Debug printf port, messages are printed like serial output.
+                    debugport::DBGARG | debugport::DBGOUT if self.debug_port.is_some() => {
+                        let port = self.debug_port.as_mut().unwrap();
+                        if addr == debugport::DBGARG {
+                            port.write_arg(val);
+                        } else if let Some(message) = port.write_char(val) {
+                            self.print_serial(&format!("[debug] {}\n", message));
+                        }
+                    }
+
+                    _ => {
+                        self.fault(Fault::UnknownIoWrite(addr));
+                        self.io[addr as usize - 0xFF00] = val;
+                    }
                 }
             }
-            0xFF80..=0xFFFE => self.hram[addr as usize - 0xFF80] = val,
-            0xFFFF => self.ie = val,
-            _ => {
+            Region::Hram => self.hram[addr as usize - 0xFF80] = val,
+            Region::Ie => self.ie = val,
+            Region::Prohibited => {
                 warn!("Attempt to write prohibited area of memory, {:#02x}.", addr);
+                self.fault(Fault::ProhibitedWrite(addr));
             }
         }
     }
 
-    /// Read a word (u16) from memory
-    fn read16(&self, addr: u16) -> u16 {
-        u16::from(self.read8(addr)) | (u16::from(self.read8(addr + 1)) << 8)
-        /*let lo = self.read8(addr);
-        let hi = self.read8(addr + 1);
-        u16::from_le_bytes([lo, hi])*/
-    }
-
     /// Write a word (u16) to memory
     fn write16(&mut self, addr: u16, val: u16) {
         info!(
@@ -253,21 +1118,181 @@ impl Memory for Mmu {
             val, addr
         );
         self.write8(addr, (val & 0xFF) as u8);
-        self.write8(addr + 1, (val >> 8) as u8);
+        self.write8(addr.wrapping_add(1), (val >> 8) as u8);
     }
 
-    fn cycle(&mut self, ticks: u32) -> u32 {
-        // TODO: Cycle the other components, APU?
+    /// The boot ROM isn't banked, while it's mapped over the cartridge.
+    fn banked(&self, addr: u16) -> BankedAddr {
+        let bank = match addr {
+            0x0000..=0x00FF if self.boot_rom_enabled => None,
+            0x0000..=0x7FFF => self.rom_offset(addr).map(|offset| offset / 0x4000),
+            0xA000..=0xBFFF => self.banks().ram,
+            _ => None,
+        };
+        BankedAddr::new(bank.map(|bank| bank as u16), addr)
+    }
 
+    fn cycle(&mut self, ticks: u32) -> u32 {
         let cpu_ticks = ticks;
 
+        // With an overclocked (or underclocked) CPU, the rest of the system sees fewer (or more) T-cycles.
+        let scaled = cpu_ticks * CPU_CLOCK_SCALE_NORMAL + self.cpu_clock_remainder;
+        let system_ticks = scaled / self.cpu_clock_scale;
+        self.cpu_clock_remainder = scaled % self.cpu_clock_scale;
+
         // Cycle the timer.
-        self.timer.cycle(cpu_ticks);
+        // The APU's frame sequencer steps on the falling edge of DIV bit 4, at 512 Hz.
+        let div = self.timer.get(0xFF04);
+        self.timer.cycle(system_ticks);
+        if div & 0x10 != 0 && self.timer.get(0xFF04) & 0x10 == 0 {
+            self.apu.div_apu();
+        }
+
+        // Cycle the APU.
+        self.apu.cycle(system_ticks);
+
+        // Cycle the PPU, it runs in lockstep with the CPU.
+        self.ppu.cycle(system_ticks);
+
+        // Cycle OAM DMA, it copies a byte per M-cycle.
+        self.cycle_dma(system_ticks);
+
+        // Cycle the serial port, transfers with the internal clock are timed like the rest of the system.
+        let transferring = self.serial.get(0xFF02) & 0x80 != 0;
+        self.serial.cycle(system_ticks);
+        if transferring && self.serial.get(0xFF02) & 0x80 == 0 {
+            self.trace(Event::SerialReceive(self.serial.get(0xFF01)));
+        }
+
+        // Cycle the cartridge, for mappers with a clock.
+        self.cartridge.cycle(system_ticks);
+
+        self.cycles += system_ticks as u64;
+        if let Some(timeline) = &mut self.timeline {
+            // Interrupts whose IF bit went from 0 to 1 since the last update were requested.
+            let if_ = self.if_.borrow().data;
+            let requested = if_ & !self.timeline_if;
+            for bit in (0..5).filter(|bit| requested & (1 << bit) != 0) {
+                timeline.record(self.cycles, Event::Request(bit));
+            }
+            self.timeline_if = if_;
+        }
+
+        cpu_ticks
+    }
+}
 
-        // Cycle the PPU.
-        let gpu_ticks = self.ppu.cycle(cpu_ticks);
+impl Savestate for Mmu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.section("mmu", |w| self.save_own_state(w));
+        w.section("timer", |w| self.timer.save_state(w));
+        w.section("joypad", |w| self.joypad.save_state(w));
+        w.section("serial", |w| self.serial.save_state(w));
+        w.section("ppu", |w| self.ppu.save_state(w));
+        w.section("apu", |w| self.apu.save_state(w));
+        w.section("cartridge", |w| self.cartridge.save_state(w));
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        r.section("mmu", |r| self.load_own_state(r))?;
+        r.section("timer", |r| self.timer.load_state(r))?;
+        r.section("joypad", |r| self.joypad.load_state(r))?;
+        r.section("serial", |r| self.serial.load_state(r))?;
+        r.section("ppu", |r| self.ppu.load_state(r))?;
+        r.section("apu", |r| self.apu.load_state(r))?;
+        r.section("cartridge", |r| self.cartridge.load_state(r))
+    }
+}
+
+/// The MMU's own state, memory and registers, the hardware it owns is saved in sections of its own.
+impl Mmu {
+    fn save_own_state(&self, w: &mut StateWriter) {
+        w.u8(self.model as u8);
+        w.bytes(&self.wram0);
+        w.bytes(&self.wramx);
+        w.bytes(&self.io);
+        w.bool(self.boot_rom_enabled);
+        w.u8(self.if_.borrow().data);
+        w.bytes(&self.hram);
+        w.u8(self.ie);
+        w.u32(self.cpu_clock_remainder);
+        w.u64(self.cycles);
+        w.u8(self.open_bus.last());
+        w.bool(self.dma.is_some());
+        if let Some(dma) = &self.dma {
+            dma.save_state(w);
+        }
+    }
+
+    fn load_own_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.model = match r.u8()? {
+            0 => Model::Dmg0,
+            1 => Model::Dmg,
+            2 => Model::Mgb,
+            _ => return Err(state::StateError::Invalid("model")),
+        };
+        r.bytes(&mut self.wram0)?;
+        r.bytes(&mut self.wramx)?;
+        r.bytes(&mut self.io)?;
+        self.boot_rom_enabled = r.bool()?;
+        self.if_.borrow_mut().data = r.u8()?;
+        self.timeline_if = self.if_.borrow().data;
+        r.bytes(&mut self.hram)?;
+        self.ie = r.u8()?;
+        self.cpu_clock_remainder = r.u32()? % self.cpu_clock_scale;
+        self.cycles = r.u64()?;
+        self.open_bus.latch(r.u8()?);
+        self.dma = if r.bool()? {
+            Some(Dma::load_state(r)?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 32 KiB ROM only image, each byte the low byte of its address, and a boot ROM of $B0 bytes.
+    fn booting() -> Mmu {
+        let rom: Vec<u8> = (0..0x8000).map(|addr| addr as u8).collect();
+        let mut mmu = Mmu::from_rom(rom, None);
+        mmu.set_boot_rom(Some(vec![0xB0; 0x100])).unwrap();
+        mmu
+    }
+
+    /// The boot ROM overlays $0000-$00FF while it's mapped, the cartridge shows through from $0100 on.
+    #[test]
+    fn boot_rom_overlay() {
+        let mmu = booting();
+        assert_eq!(mmu.read8(0x0000), 0xB0);
+        assert_eq!(mmu.read8(0x00FF), 0xB0);
+        assert_eq!(mmu.read8(0x0100), 0x00);
+        // The logo and header the boot ROM checks are the cartridge's, read while it's mapped.
+        for addr in 0x0104..=0x014F {
+            assert_eq!(mmu.read8(addr), addr as u8, "${:04X}", addr);
+        }
+        assert_eq!(mmu.read8(0x0150), 0x50);
+        assert!(mmu.boot_rom_mapped(), "the boot ROM was unmapped");
+    }
+
+    /// Writing $FF50 unmaps the boot ROM for good.
+    #[test]
+    fn boot_rom_unmapped() {
+        let mut mmu = booting();
+        mmu.write8(0xFF50, 0x01);
+        assert_eq!(mmu.read8(0x00FF), 0xFF);
+        mmu.write8(0xFF50, 0x00);
+        assert_eq!(mmu.read8(0x0000), 0x00, "the boot ROM mapped back in");
+    }
 
-        // Calculate total ticks from each subsystem cycle
-        cpu_ticks + gpu_ticks
+    /// A boot ROM that isn't 256 bytes is refused, and the one already set keeps running.
+    #[test]
+    fn boot_rom_wrong_size() {
+        let mut mmu = booting();
+        assert!(mmu.set_boot_rom(Some(vec![0xC0; 0x900])).is_err());
+        assert_eq!(mmu.read8(0x0000), 0xB0);
     }
 }
@@ -0,0 +1,79 @@
+use crate::boot::{BOOTROM, DMG0_BOOTROM, MGB_BOOTROM};
+use crate::gb::CpuState;
+use serde::Deserialize;
+
+/// Gameboy hardware revision being emulated.
+/// The revisions run the same hardware, but ship different boot ROMs, which leave different values in the CPU
+/// registers and the divider when control is handed to the cartridge. Games (and test ROMs) use these to detect
+/// which model they are running on.
+/// https://gbdev.io/pandocs/Power_Up_Sequence.html#cpu-registers
+///
+/// Register values at $0100 (F depends on the header checksum on DMG and MGB):
+///         A   F   B   C   D   E   H   L   DIV
+/// DMG0    01  00  FF  13  00  C1  84  03  18
+/// DMG     01  B0  00  13  00  D8  01  4D  AB
+/// MGB     FF  B0  00  13  00  D8  01  4D  AB
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    /// Early DMG-01, only sold in Japan.
+    Dmg0 = 0,
+
+    /// DMG-01, the original Gameboy.
+    #[default]
+    Dmg = 1,
+
+    /// MGB, the Gameboy Pocket.
+    Mgb = 2,
+}
+
+impl Model {
+    /// The boot ROM overlaid on $0000-$00FF at power on.
+    pub fn boot_rom(self) -> &'static [u8] {
+        match self {
+            Model::Dmg0 => DMG0_BOOTROM,
+            Model::Dmg => BOOTROM,
+            Model::Mgb => MGB_BOOTROM,
+        }
+    }
+
+    /// Value of the internal 16-bit divider counter at power on, DIV is its upper byte.
+    /// The divider starts counting before the CPU runs the boot ROM, so it has to start at this phase for DIV to read
+    /// the documented value (down to the T-cycle) when the boot ROM hands control to the cartridge.
+    pub fn div_phase(self) -> u16 {
+        match self {
+            Model::Dmg0 => 0xB7B8,
+            Model::Dmg | Model::Mgb => 0x4A0C,
+        }
+    }
+
+    /// Value of the internal divider counter when the boot ROM hands control to the cartridge,
+    /// div_phase plus the T-cycles the boot ROM takes.
+    pub fn post_boot_div(self) -> u16 {
+        match self {
+            Model::Dmg0 => 0x1800,
+            Model::Dmg | Model::Mgb => 0xABD0,
+        }
+    }
+
+    /// The CPU registers at $0100, from the table above, given the header checksum at $014D.
+    /// On DMG and MGB, H and C are set unless the checksum is $00, DMG0 clears every flag.
+    pub fn post_boot_registers(self, checksum: u8) -> CpuState {
+        let flags = if checksum == 0x00 { 0x80 } else { 0xB0 };
+        let (af, bc, de, hl) = match self {
+            Model::Dmg0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+            Model::Dmg => (0x0100 | flags, 0x0013, 0x00D8, 0x014D),
+            Model::Mgb => (0xFF00 | flags, 0x0013, 0x00D8, 0x014D),
+        };
+        CpuState {
+            af,
+            bc,
+            de,
+            hl,
+            sp: 0xFFFE,
+            pc: 0x0100,
+            ime: false,
+            halted: false,
+        }
+    }
+}
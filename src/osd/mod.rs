@@ -0,0 +1,151 @@
+mod font;
+
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use self::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// How many frames a message stays on screen, about a second.
+const MESSAGE_FRAMES: u32 = 60;
+
+/// How many messages can be on screen at once, older messages are dropped first.
+const MAX_MESSAGES: usize = 3;
+
+/// Each character cell has a 1 pixel gap to the right and below the glyph.
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+/// Size of a character cell, lines of text are drawn CELL_SIZE.1 + 1 pixels apart.
+pub const CELL_SIZE: (usize, usize) = (CELL_WIDTH, CELL_HEIGHT);
+
+const TEXT_COLOR: u32 = 0x00FFFFFF;
+const BACKGROUND_COLOR: u32 = 0x00000000;
+
+struct Message {
+    text: String,
+    frames_left: u32,
+}
+
+/// On-Screen Display (OSD)
+/// Draws short lived status messages ("Paused", "State 3 saved", etc.) on top of the Gameboy screen.
+/// Messages are drawn into a copy of the frame by the front-end, never into the PPU's own buffers.
+#[derive(Default)]
+pub struct Osd {
+    messages: Vec<Message>,
+
+    /// Lines that stay in the top left corner until replaced, e.g. live counters.
+    overlay: Vec<String>,
+
+    /// A picture shown in the top right corner as long as a message, e.g. a save state's thumbnail,
+    /// its width, and frames left.
+    picture: Option<(Vec<u32>, usize, u32)>,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show a message for about a second.
+    pub fn show(&mut self, text: impl Into<String>) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        self.messages.push(Message {
+            text: text.into(),
+            frames_left: MESSAGE_FRAMES,
+        });
+    }
+
+    /// Show a picture, width pixels wide, for about a second, replacing the one shown.
+    pub fn show_picture(&mut self, pixels: Vec<u32>, width: usize) {
+        self.picture = Some((pixels, width, MESSAGE_FRAMES));
+    }
+
+    /// Replace the overlay lines, an empty overlay hides it.
+    pub fn set_overlay(&mut self, lines: Vec<String>) {
+        self.overlay = lines;
+    }
+
+    /// Draw the overlay from the top left corner, the active messages stacked up from the bottom left corner,
+    /// and the picture in the top right corner, into a 160x144 frame, and count down their display time by one frame.
+    pub fn draw(&mut self, frame: &mut [u32]) {
+        if let Some((pixels, width, frames_left)) = &mut self.picture {
+            let x = SCREEN_WIDTH - *width - 2;
+            for (y, row) in pixels.chunks_exact(*width).enumerate() {
+                let start = (y + 2) * SCREEN_WIDTH + x;
+                frame[start..start + *width].copy_from_slice(row);
+            }
+            let height = pixels.len() / *width;
+            outline(
+                frame,
+                x as isize - 1,
+                1,
+                *width as isize + 2,
+                height as isize + 2,
+                TEXT_COLOR,
+            );
+            *frames_left -= 1;
+            if *frames_left == 0 {
+                self.picture = None;
+            }
+        }
+
+        let max_chars = (SCREEN_WIDTH - 2) / CELL_WIDTH;
+        for (i, line) in self.overlay.iter().enumerate() {
+            let y = 1 + i * (CELL_HEIGHT + 1);
+            for (n, c) in line.chars().take(max_chars).enumerate() {
+                draw_char(frame, 1 + n * CELL_WIDTH, y, c, TEXT_COLOR);
+            }
+        }
+
+        for (i, message) in self.messages.iter().rev().enumerate() {
+            let y = SCREEN_HEIGHT - (i + 1) * (CELL_HEIGHT + 1);
+            for (n, c) in message.text.chars().take(max_chars).enumerate() {
+                draw_char(frame, 1 + n * CELL_WIDTH, y, c, TEXT_COLOR);
+            }
+        }
+
+        self.messages.retain_mut(|m| {
+            m.frames_left -= 1;
+            m.frames_left > 0
+        });
+    }
+}
+
+/// Outline a box in a 160x144 frame, with its top left corner at x, y, clipped to the frame.
+pub fn outline(frame: &mut [u32], x: isize, y: isize, width: isize, height: isize, color: u32) {
+    let (right, bottom) = (x + width - 1, y + height - 1);
+    let mut plot = |px: isize, py: isize| {
+        if (0..SCREEN_WIDTH as isize).contains(&px) && (0..SCREEN_HEIGHT as isize).contains(&py) {
+            frame[py as usize * SCREEN_WIDTH + px as usize] = color;
+        }
+    };
+    for px in x..=right {
+        plot(px, y);
+        plot(px, bottom);
+    }
+    for py in y..=bottom {
+        plot(x, py);
+        plot(right, py);
+    }
+}
+
+/// Draw a line of text in a 160x144 frame, with its top left corner at x, y, e.g. for debug views.
+/// The text must fit in the frame.
+pub fn text(frame: &mut [u32], x: usize, y: usize, text: &str, color: u32) {
+    for (n, c) in text.chars().enumerate() {
+        draw_char(frame, x + n * CELL_WIDTH, y, c, color);
+    }
+}
+
+/// Draw a single character cell with its top left corner at x, y.
+/// The cell is padded with 1 row of background above the glyph, so stacked lines don't touch.
+fn draw_char(frame: &mut [u32], x: usize, y: usize, c: char, color: u32) {
+    let glyph = glyph(c);
+    for row in 0..=CELL_HEIGHT {
+        for col in 0..CELL_WIDTH {
+            let lit = col < GLYPH_WIDTH && row > 0 && (glyph[col] >> (row - 1)) & 0x01 != 0;
+            frame[(y + row) * SCREEN_WIDTH + x + col] = if lit { color } else { BACKGROUND_COLOR };
+        }
+    }
+}
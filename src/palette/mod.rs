@@ -0,0 +1,111 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The shades the PPU outputs, from lightest to darkest.
+const SHADES: [u32; 4] = [0x00FFFFFF, 0x00AAAAAA, 0x00555555, 0x00000000];
+
+/// Built-in palettes, by name, in the order the palette hotkey cycles through them.
+/// Every preset keeps the shades' brightness order, so the game reads the same in any of them.
+/// The high contrast and color vision presets stretch the two middle shades apart in both brightness and hue,
+/// along the blue-yellow axis red-green deficiencies (protanopia, deuteranopia) leave intact,
+/// or the red-cyan axis tritanopia leaves intact.
+pub const PRESETS: [(&str, Palette); 5] = [
+    ("gray", Palette::GRAY),
+    (
+        "dmg",
+        Palette::new([0x00E0F8D0, 0x0088C070, 0x00346856, 0x00081820]),
+    ),
+    (
+        "high-contrast",
+        Palette::new([0x00FFFFFF, 0x00FFE000, 0x003050FF, 0x00000000]),
+    ),
+    (
+        "red-green",
+        Palette::new([0x00F0F0F0, 0x00E69F00, 0x000072B2, 0x00101010]),
+    ),
+    (
+        "blue-yellow",
+        Palette::new([0x00FFFFFF, 0x005FD3D3, 0x00C8283C, 0x00000000]),
+    ),
+];
+
+/// Display palette
+/// The colors the four shades of the Gameboy screen are shown in, applied to every displayed frame.
+/// The PPU always renders in grays, so screenshots, exports, and hashes don't depend on the palette.
+///
+/// A palette file lists the four colors as hex RGB, lightest first, with # starting a comment:
+///
+/// # Pea soup
+/// E0F8D0
+/// 88C070
+/// 346856
+/// 081820
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: [u32; 4],
+}
+
+impl Palette {
+    /// The PPU's own grays, which leave frames unchanged.
+    pub const GRAY: Palette = Palette { colors: SHADES };
+
+    pub const fn new(colors: [u32; 4]) -> Self {
+        Self { colors }
+    }
+
+    /// A built-in palette, see PRESETS.
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| palette.clone())
+    }
+
+    /// Read a palette file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Recolor a frame rendered by the PPU.
+    pub fn apply(&self, frame: &mut [u32]) {
+        if *self == Self::GRAY {
+            return;
+        }
+        for pixel in frame {
+            if let Some(shade) = SHADES.iter().position(|&shade| shade == *pixel) {
+                *pixel = self.colors[shade];
+            }
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::GRAY
+    }
+}
+
+impl std::str::FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = s
+            .lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                u32::from_str_radix(line, 16)
+                    .ok()
+                    .filter(|_| line.len() == 6)
+                    .ok_or(format!("`{}` isn't a color, expected RRGGBB", line))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        colors
+            .try_into()
+            .map(Self::new)
+            .map_err(|colors: Vec<u32>| format!("expected 4 colors, found {}", colors.len()))
+    }
+}
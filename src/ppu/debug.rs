@@ -0,0 +1,364 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use super::{Color, Ppu, Tile, BG_HEIGHT, BG_WIDTH, OAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Identity palette, color number n is shade n.
+const IDENTITY_PALETTE: u8 = 0b11_10_01_00;
+
+/// Tiles per row in a tile sheet.
+pub const SHEET_TILES_PER_ROW: usize = 16;
+
+/// VRAM holds 384 tiles, at $8000-$97FF.
+const VRAM_TILES: usize = 384;
+
+/// Sprites are composed in OAM coordinate space, which is 256x256 pixels.
+/// The visible screen is at (8, 16)-(167, 159) in this space.
+const OAM_SPACE: usize = 256;
+
+/// Palette index used for transparent pixels in sprite compositions.
+const TRANSPARENT: u8 = 4;
+
+/// Palette index, and color, of the viewport outline drawn on map exports.
+const OUTLINE: u8 = 4;
+const OUTLINE_COLOR: u32 = 0x00FF0000;
+
+/// An image made of palette indices, for exporting Gameboy graphics.
+/// Keeping the palette indices, rather than RGB colors, lets artists recolor the export.
+pub struct IndexedImage {
+    pub width: usize,
+    pub height: usize,
+
+    /// Palette indices, row by row.
+    pub pixels: Vec<u8>,
+
+    /// RGB colors (0x00RRGGBB) of the palette indices.
+    pub palette: Vec<u32>,
+
+    /// Palette index that is fully transparent, if any.
+    pub transparent: Option<u8>,
+}
+
+impl IndexedImage {
+    fn new(width: usize, height: usize, palette: Vec<u32>, fill: u8) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width * height],
+            palette,
+            transparent: None,
+        }
+    }
+
+    /// Draw a tile with its top left corner at (x, y), mapping its color numbers through map.
+    /// Pixels outside the image, and pixels mapped to None, are skipped.
+    fn draw_tile(
+        &mut self,
+        tile: &Tile,
+        x: isize,
+        y: isize,
+        x_flip: bool,
+        y_flip: bool,
+        map: impl Fn(u8) -> Option<u8>,
+    ) {
+        for row in 0..8 {
+            for col in 0..8 {
+                let (px, py) = (x + col as isize, y + row as isize);
+                if px < 0 || py < 0 || px >= self.width as isize || py >= self.height as isize {
+                    continue;
+                }
+                let src_col = if x_flip { 7 - col } else { col };
+                let src_row = if y_flip { 7 - row } else { row };
+                let color = tile.get_pixel(src_col, src_row) as u8;
+                if let Some(index) = map(color) {
+                    self.pixels[py as usize * self.width + px as usize] = index;
+                }
+            }
+        }
+    }
+
+    /// Outline the w x h rectangle with its top left corner at (x, y), wrapping around the image edges.
+    fn outline(&mut self, x: usize, y: usize, w: usize, h: usize, index: u8) {
+        for i in 0..w {
+            self.wrapping_set(x + i, y, index);
+            self.wrapping_set(x + i, y + h - 1, index);
+        }
+        for i in 0..h {
+            self.wrapping_set(x, y + i, index);
+            self.wrapping_set(x + w - 1, y + i, index);
+        }
+    }
+
+    fn wrapping_set(&mut self, x: usize, y: usize, index: u8) {
+        let (x, y) = (x % self.width, y % self.height);
+        self.pixels[y * self.width + x] = index;
+    }
+
+    /// Write the image as an indexed color PNG.
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            self.palette
+                .iter()
+                .flat_map(|&c| [(c >> 16) as u8, (c >> 8) as u8, c as u8])
+                .collect::<Vec<u8>>(),
+        );
+        if let Some(transparent) = self.transparent {
+            let alpha: Vec<u8> = (0..self.palette.len())
+                .map(|i| {
+                    if i == transparent as usize {
+                        0x00
+                    } else {
+                        0xFF
+                    }
+                })
+                .collect();
+            encoder.set_trns(alpha);
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+}
+
+/// A frame (0x00RRGGBB pixels, row by row) as an image, for screenshots.
+/// Frames only use a handful of colors, which become the palette.
+pub fn frame_image(frame: &[u32]) -> IndexedImage {
+    let mut palette: Vec<u32> = Vec::new();
+    let pixels = frame
+        .iter()
+        .map(|&color| match palette.iter().position(|&c| c == color) {
+            Some(index) => index as u8,
+            None if palette.len() < 256 => {
+                palette.push(color);
+                (palette.len() - 1) as u8
+            }
+            None => 0,
+        })
+        .collect();
+    IndexedImage {
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+        pixels,
+        palette,
+        transparent: None,
+    }
+}
+
+/// Decode raw 2bpp tile data (16 bytes per tile) into a sheet, SHEET_TILES_PER_ROW tiles wide.
+/// Pixels are the tiles' color numbers (0-3), shown in the DMG grey shades.
+/// This also works on ROM banks, to find graphics that haven't been loaded into VRAM yet.
+pub fn tile_sheet(data: &[u8]) -> IndexedImage {
+    tile_rows(data, SHEET_TILES_PER_ROW, IDENTITY_PALETTE)
+}
+
+/// Decode raw 2bpp tile data into rows of tiles_per_row tiles, mapping color numbers to shades through a
+/// palette register value (like BGP). Pixels are the shades (0-3), e.g. for a Game Boy Printer page.
+pub fn tile_rows(data: &[u8], tiles_per_row: usize, palette: u8) -> IndexedImage {
+    let tiles = data.len() / 16;
+    let rows = tiles.div_ceil(tiles_per_row).max(1);
+    let mut image = IndexedImage::new(
+        tiles_per_row * 8,
+        rows * 8,
+        Color::palette(IDENTITY_PALETTE).to_vec(),
+        0,
+    );
+    for (i, tile) in data.chunks_exact(16).map(Tile::new).enumerate() {
+        let x = (i % tiles_per_row) * 8;
+        let y = (i / tiles_per_row) * 8;
+        image.draw_tile(&tile, x as isize, y as isize, false, false, |color| {
+            Some((palette >> (color * 2)) & 0x03)
+        });
+    }
+    image
+}
+
+impl Ppu {
+    /// Every tile currently in VRAM ($8000-$97FF), in tile sheet layout.
+    pub fn tile_sheet(&self) -> IndexedImage {
+        tile_sheet(&self.vram.borrow()[..VRAM_TILES * 16])
+    }
+
+    /// All 40 OAM sprites composed as they are placed for the current frame, with their palettes (OBP0/OBP1) applied.
+    /// The image covers the whole OAM coordinate space, so sprites parked off screen are included.
+    /// Overlapping sprites follow the same priority as on screen, see SpritePriority.
+    pub fn sprite_sheet(&self) -> IndexedImage {
+        let oam = self.oam.borrow();
+        let vram = self.vram.borrow();
+        let height = if self.lcdc.sprite_size() { 16 } else { 8 };
+        let palettes = [self.obp0, self.obp1];
+
+        let mut palette = Color::palette(IDENTITY_PALETTE).to_vec();
+        palette.push(0x00FF00FF);
+        let mut image = IndexedImage::new(OAM_SPACE, OAM_SPACE, palette, TRANSPARENT);
+        image.transparent = Some(TRANSPARENT);
+
+        // Draw the lowest priority sprites first, so higher priority ones end up on top.
+        let mut order: Vec<usize> = (0..OAM_SIZE / 4).collect();
+        order.sort_by_key(|&i| self.sprite_priority.key(oam[i * 4 + 1], i));
+        for &i in order.iter().rev() {
+            let sprite = &oam[i * 4..i * 4 + 4];
+            let (y, x, attr) = (sprite[0] as isize, sprite[1] as isize, sprite[3]);
+            let y_flip = attr & 0x40 != 0;
+            let x_flip = attr & 0x20 != 0;
+            let obp = palettes[(attr >> 4) as usize & 0x01];
+
+            // In 8x16 mode, the lowest bit of the tile number is ignored, and flipping swaps the two tiles.
+            let tile_ids = if height == 16 {
+                let top = sprite[2] & 0xFE;
+                if y_flip {
+                    vec![top | 0x01, top]
+                } else {
+                    vec![top, top | 0x01]
+                }
+            } else {
+                vec![sprite[2]]
+            };
+            for (n, &tile_id) in tile_ids.iter().enumerate() {
+                let addr = tile_id as usize * 16;
+                let tile = Tile::new(&vram[addr..addr + 16]);
+                // Color 0 is transparent for sprites.
+                image.draw_tile(&tile, x, y + n as isize * 8, x_flip, y_flip, |c| {
+                    (c != 0).then_some((obp >> (c * 2)) & 0x03)
+                });
+            }
+        }
+        image
+    }
+}
+
+/// An OAM entry, decoded for the OAM viewer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OamEntry {
+    /// Index of the entry in OAM, 0-39.
+    pub index: usize,
+
+    /// Position in OAM coordinates, the top left corner of the screen is (8, 16).
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+
+    /// Bit 7 behind the background, bit 6 Y flip, bit 5 X flip, bit 4 palette (OBP1).
+    pub attr: u8,
+
+    /// 8 or 16 pixels, from LCDC.2.
+    pub height: u8,
+}
+
+impl OamEntry {
+    /// Top left corner on screen, which can be off screen.
+    pub fn screen_pos(&self) -> (isize, isize) {
+        (self.x as isize - 8, self.y as isize - 16)
+    }
+
+    /// Does the sprite cover the given screen pixel?
+    pub fn covers(&self, x: usize, y: usize) -> bool {
+        let (left, top) = self.screen_pos();
+        let (x, y) = (x as isize, y as isize);
+        (left..left + 8).contains(&x) && (top..top + self.height as isize).contains(&y)
+    }
+
+    /// The decoded entry, in lines short enough for the OSD, e.g.
+    /// OAM 12 X 40 Y 56
+    /// Tile 2C 8x8 OBP1
+    /// Flip X Behind BG
+    pub fn describe(&self) -> Vec<String> {
+        let flip = match self.attr & 0x60 {
+            0x00 => "None",
+            0x20 => "X",
+            0x40 => "Y",
+            _ => "XY",
+        };
+        vec![
+            format!("OAM {} X {} Y {}", self.index, self.x, self.y),
+            format!(
+                "Tile {:02X} 8x{} OBP{}",
+                self.tile,
+                self.height,
+                (self.attr >> 4) & 0x01
+            ),
+            format!(
+                "Flip {} {}",
+                flip,
+                if self.attr & 0x80 != 0 {
+                    "Behind BG"
+                } else {
+                    "Above BG"
+                }
+            ),
+        ]
+    }
+}
+
+impl Ppu {
+    /// Decode every OAM entry, whatever the PPU is doing.
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        let oam = self.oam.borrow();
+        let height = if self.lcdc.sprite_size() { 16 } else { 8 };
+        oam.chunks_exact(4)
+            .enumerate()
+            .map(|(index, entry)| OamEntry {
+                index,
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                attr: entry[3],
+                height,
+            })
+            .collect()
+    }
+}
+
+/// The two tile maps the PPU can show, the background and the window.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layer {
+    Background,
+    Window,
+}
+
+impl Ppu {
+    /// Render a whole 256x256 tile map, the way the PPU currently sees it.
+    /// The map ($9800 or $9C00), tile data addressing mode, and BGP palette come from LCDC and BGP, and pixels are
+    /// looked up with the same code the renderer uses. The part of the map on screen is outlined in red,
+    /// wrapping around for the background.
+    pub fn map_image(&self, layer: Layer) -> IndexedImage {
+        let high_map = match layer {
+            Layer::Background => self.lcdc.bg_tile_map_select(),
+            Layer::Window => self.lcdc.window_tile_map_select(),
+        };
+
+        let mut palette = Color::palette(IDENTITY_PALETTE).to_vec();
+        palette.push(OUTLINE_COLOR);
+        let mut image = IndexedImage::new(BG_WIDTH, BG_HEIGHT, palette, 0);
+        for y in 0..BG_HEIGHT {
+            for x in 0..BG_WIDTH {
+                let color = self.tile_pixel(high_map, x as u8, y as u8);
+                image.pixels[y * BG_WIDTH + x] = (self.bgp >> (color * 2)) & 0x03;
+            }
+        }
+
+        match layer {
+            Layer::Background => image.outline(
+                self.scx as usize,
+                self.scy as usize,
+                SCREEN_WIDTH,
+                SCREEN_HEIGHT,
+                OUTLINE,
+            ),
+            Layer::Window => {
+                // The window is drawn from (WX - 7, WY) to the bottom right of the screen, showing the map's top left.
+                let w = SCREEN_WIDTH as isize - (self.wx as isize - 7);
+                let h = SCREEN_HEIGHT as isize - self.wy as isize;
+                if w > 0 && h > 0 {
+                    let w = (w as usize).min(SCREEN_WIDTH);
+                    image.outline(0, 0, w, h as usize, OUTLINE);
+                }
+            }
+        }
+        image
+    }
+}
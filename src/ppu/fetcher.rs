@@ -1,8 +1,10 @@
 use std::{cell::RefCell, rc::Rc};
 
 use super::{fifo::Fifo, OAM_SIZE, VRAM_SIZE};
+use crate::state::{self, Savestate, StateReader, StateWriter};
 
 /// Pixel Fetcher States.
+#[derive(Clone, Copy)]
 enum FetcherState {
     ReadTileId,
     ReadTileData0,
@@ -153,3 +155,36 @@ impl Fetcher {
         }
     }
 }
+
+impl Savestate for Fetcher {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.fifo.save_state(w);
+        w.u8(self.ticks);
+        w.u8(self.state as u8);
+        w.u16(self.map_addr);
+        w.u16(self.data_addr);
+        w.u8(self.tile_line);
+        w.u8(self.tile_index);
+        w.u8(self.tile_id);
+        w.bytes(&self.tile_data);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.fifo.load_state(r)?;
+        self.ticks = r.u8()?;
+        self.state = match r.u8()? {
+            0 => FetcherState::ReadTileId,
+            1 => FetcherState::ReadTileData0,
+            2 => FetcherState::ReadTileData1,
+            3 => FetcherState::PushToFifo,
+            _ => return Err(state::StateError::Invalid("fetcher state")),
+        };
+        self.map_addr = r.u16()?;
+        self.data_addr = r.u16()?;
+        self.tile_line = r.u8()?;
+        self.tile_index = r.u8()?;
+        self.tile_id = r.u8()?;
+        r.bytes(&mut self.tile_data)?;
+        Ok(())
+    }
+}
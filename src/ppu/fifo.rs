@@ -1,3 +1,5 @@
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
 /// FIFO (First In First Out) queue for storing pixel data.
 /// This will be used for shifting pixel data out to the LCD.
 /// This data structure is a fixed size.
@@ -62,3 +64,23 @@ impl Fifo {
         self.size = 0;
     }
 }
+
+impl Savestate for Fifo {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.data);
+        w.u8(self.tail as u8);
+        w.u8(self.head as u8);
+        w.u8(self.size as u8);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        r.bytes(&mut self.data)?;
+        self.tail = r.u8()? as usize;
+        self.head = r.u8()? as usize;
+        self.size = r.u8()? as usize;
+        if self.tail >= 16 || self.head >= 16 || self.size > 16 {
+            return Err(state::StateError::Invalid("pixel FIFO"));
+        }
+        Ok(())
+    }
+}
@@ -1,16 +1,24 @@
 use std::{cell::RefCell, rc::Rc};
 
 use log::warn;
+use serde::Deserialize;
 
 use crate::{
+    bus::OpenBus,
     cpu::interrupts::{Flags, InterruptFlags},
     mmu::memory::Memory,
+    state::{self, Savestate, StateReader, StateWriter},
 };
 
 use self::fetcher::Fetcher;
+use self::timing::DRAWING_TICKS;
 
+pub mod debug;
 mod fetcher;
 mod fifo;
+mod scanline;
+mod timing;
+pub mod watchpoint;
 
 // TODO: Look at doing Pixel FIFO - Rendering one line at a time is fine in most cases for now.
 // Only a few games actually require pixel FIFO.
@@ -22,6 +30,12 @@ pub const SCREEN_WIDTH: usize = 160;
 pub const SCREEN_HEIGHT: usize = 144;
 pub const SCREEN_PIXELS: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
 
+/// Widescreen frames add this many background columns on each side of the viewport,
+/// making them 256x144, 16:9, and as wide as the background map.
+pub const WIDESCREEN_BORDER: usize = 48;
+pub const WIDESCREEN_WIDTH: usize = SCREEN_WIDTH + 2 * WIDESCREEN_BORDER;
+pub const WIDESCREEN_PIXELS: usize = WIDESCREEN_WIDTH * SCREEN_HEIGHT;
+
 /// The Gameboy has three layers for rendering. Background, Window, and Sprites.
 pub const BG_WIDTH: usize = 256;
 pub const BG_HEIGHT: usize = 256;
@@ -34,11 +48,8 @@ pub const WIN_PIXELS: usize = WIN_WIDTH * WIN_HEIGHT;
 pub const WIN_TILES: usize = 32 * 32;
 pub const WIN_MAP: usize = 32 * 32;
 
-/// The PPU had varying cycles depending on the mode it was in.
-const ACCESS_OAM_CYCLES: u32 = 21;
-const ACCESS_VRAM_CYCLES: u32 = 43;
-const HBLANK_CYCLES: u32 = 50;
-const VBLANK_CYCLES: u32 = 114;
+/// Length of the OAM Scan mode (Mode 2), 2 dots per OAM entry.
+const OAM_SCAN_TICKS: u32 = 80;
 
 /// PPU also handles VRAM and OAM memory.
 pub const VRAM_START: u16 = 0x8000;
@@ -48,7 +59,7 @@ pub const OAM_SIZE: usize = 0xA0;
 pub const OAM_START: u16 = 0xFE00;
 pub const OAM_END: u16 = 0xFE9F;
 
-/// The PPU always returned 0xFF for undefined reads.
+/// The PPU always returned 0xFF for undefined reads, this is what the DMG returns with an accurate open bus.
 const UNDEFINED_READ: u8 = 0xFF;
 
 /// Gameboy DMG-01 grey scale colors.
@@ -93,6 +104,11 @@ impl Color {
             Color::Black => BLACK,
         }
     }
+
+    /// Decode a palette register (BGP, OBP0, OBP1) into the u32 colors for color numbers 0-3.
+    fn palette(palette: u8) -> [u32; 4] {
+        [0, 1, 2, 3].map(|i| Color::from_u8((palette >> (i * 2)) & 0x03).to_u32())
+    }
 }
 
 /// Tiles are 8x8 pixels.
@@ -133,98 +149,66 @@ impl Tile {
     }
 }
 
-/// Each sprite can be 8x8 or 8x16 pixels (1x1 tile or 1x2 tiles) depending on the sprite size flag (LCDC.2).
-/// NOTE: This is universal for all sprites in the loaded ROM.
-#[derive(Default, Clone, Copy)]
-enum SpriteSize {
-    /// 8x8 pixels (1x1 tile)
+/// The PPU can render using one of two pipelines, both sharing the same register state.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PpuAccuracy {
+    /// Render a whole scanline at once at the start of the Drawing mode.
+    /// Draws every layer, the background, window, and sprites, and is what games should run on.
     #[default]
-    Small,
+    Scanline,
 
-    /// 8x16 pixels (1x2 tiles)
-    Large,
+    /// Experimental: push pixels through the pixel FIFO one dot at a time, so registers written mid-scanline
+    /// take effect where they would on hardware. It only draws the background so far, from the $9800 map and
+    /// $8000 tile data, without fine horizontal scroll, the window, or sprites.
+    Fifo,
 }
 
-/// The sprite layer is made up of 40 sprites that are stored in OAM.
-/// Each sprite can be 8x8 or 8x16 pixels (1x1 tile or 1x2 tiles) depending on the sprite size flag (LCDC.2).
-/// Sprites are rendered on top of the background and window layers.
-/// Sprites can be rendered behind the background and window layers by setting the priority flag (OAM.7).
-/// Sprites can be flipped horizontally and vertically.
-/// Sprites can be colored using one of the four palettes.
-/// Sprites can be hidden by setting the hidden flag (OAM.1).
-/// Sprites can be moved off screen by setting the x position to 0 or 160, or the y position to 0 or 144.
-#[derive(Clone)]
-struct Sprite {
-    /// The x position of the sprite.
-    x: u8,
+/// How overlapping sprites are ordered, when more than one has an opaque pixel at the same spot.
+/// Games lean on this for layering, e.g. which part of a multi-sprite character is drawn in front.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpritePriority {
+    /// The sprite with the smaller X coordinate wins, the earlier OAM entry breaks ties.
+    #[default]
+    Dmg,
 
-    /// The y position of the sprite.
-    y: u8,
-
-    /// The tile number of the sprite.
-    tile_id: u8,
-
-    /// The attributes of the sprite (Sprite Flags)
-    /// Bit 7   OBJ-to-BG Priority (0=OBJ Above BG, 1=OBJ Behind BG color 1-3)
-    /// Bit 6   Y flip          (0=Normal, 1=Vertically mirrored)
-    /// Bit 5   X flip          (0=Normal, 1=Horizontally mirrored)
-    /// Bit 4   Palette number  **DMG Only* (0=OBP0, 1=OBP1)
-    /// Bit 0-3 CGB Only
-    attr: u8,
-    priority: bool,
-    y_flip: bool,
-    x_flip: bool,
-    palette: bool,
-
-    /// The sprite size (determined by the LCDC.2 flag).
-    size: SpriteSize,
-
-    /// The tile data of the sprite.
-    tile: Vec<Tile>,
+    /// The earlier OAM entry wins, wherever the sprites are. This is what the CGB does in CGB mode.
+    Cgb,
 }
 
-impl Sprite {
-    /// Create a new Sprite from a slice of 4 bytes.
-    /// Also using the sprite size flag (LCDC.2) to determine the sprite size.
-    fn new(data: &[u8], size: SpriteSize) -> Self {
-        let mut tile = Vec::new();
-        match size {
-            SpriteSize::Small => {
-                tile.push(Tile::new(&[0; 16]));
-            }
-            SpriteSize::Large => {
-                tile.push(Tile::new(&[0; 16]));
-                tile.push(Tile::new(&[0; 16]));
-            }
-        }
-        let priority = data[3] & 0x80 == 0x80;
-        let y_flip = data[3] & 0x40 == 0x40;
-        let x_flip = data[3] & 0x20 == 0x20;
-        let palette = data[3] & 0x10 == 0x10;
-        Self {
-            x: data[0],
-            y: data[1],
-            tile_id: data[2],
-            attr: data[3],
-            priority,
-            y_flip,
-            x_flip,
-            palette,
-            size,
-            tile,
+impl SpritePriority {
+    /// Sort key of a sprite, from its X coordinate and OAM index, lower keys win.
+    fn key(self, x: u8, index: usize) -> (u8, usize) {
+        match self {
+            SpritePriority::Dmg => (x, index),
+            SpritePriority::Cgb => (0, index),
         }
     }
 }
 
+/// What the screen shows while a game has the LCD off, e.g. for a moment while it loads a new area.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LcdOffPolicy {
+    /// A blank, white screen, like a real Gameboy.
+    #[default]
+    Blank,
+
+    /// The last frame drawn, so games turning the LCD off between screens don't flash white.
+    Hold,
+}
+
 /// During a scanline, the PPU enters multiple different modes.
 /// There are 4 modes, each with a specific function.
+/// The discriminants match the mode number reported in STAT bits 1-0.
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum PpuMode {
     /// Mode 0 - H-Blank
     /// This mode takes up the remainder of the scanline after the Drawing Mode finishes.
     /// This is more or less “padding” the duration of the scanline to a total of 456 T-Cycles.
     /// The PPU effectively pauses during this mode.
-    HBlank,
+    HBlank = 0,
 
     /// Mode 1 - V-Blank
     /// This mode is similar to H-Blank, in that it the PPU does not render to the LCD during its duration.
@@ -236,7 +220,7 @@ enum PpuMode {
     /// In reality there are 154 scanlines, the 10 last of which being “pseudo-scanlines” during which
     /// no pixels are drawn as the PPU is in the V-Blank state during their duration.
     /// A V-Blank scanline takes the same amount of time as any other scanline - 456 T-Cycles.
-    VBlank,
+    VBlank = 1,
 
     /// Mode 2 - OAM Scan
     /// This mode is entered at the start of every scanline (except for V-Blank) before pixels are actually drawn to the screen.
@@ -248,13 +232,13 @@ enum PpuMode {
     ///     * LY + 16 must be greater than or equal to Sprite Y-Position
     ///     * LY + 16 must be less than Sprite Y-Position + Sprite Height (8 in Normal Mode, 16 in Tall-Sprite-Mode)
     ///     * The amount of sprites already stored in the OAM Buffer must be less than 10
-    OamScan,
+    OamScan = 2,
 
     /// Mode 3 - Drawing
     /// The Drawing Mode is where the PPU transfers pixels to the LCD.
     /// The duration of this mode changes depending on multiple variables,
     /// such as background scrolling, the amount of sprites on the scanline, whether or not the window should be rendered, etc.
-    Drawing,
+    Drawing = 3,
 }
 
 /// LCD Control Register (LCDC - $FF40)
@@ -307,7 +291,7 @@ impl Lcdc {
     /// This bit controls which Background Map is used to determine the tile numbers of the tiles displayed in the Window layer.
     /// If it is set to 1, the background map located at $9C00-$9FFF is used, otherwise it uses the one at $9800-$9BFF.
     fn window_tile_map_select(&self) -> bool {
-        self.data * (1 << 6) != 0
+        self.data & (1 << 6) != 0
     }
 
     /// LCDC.5 - Window Display Enable
@@ -322,7 +306,7 @@ impl Lcdc {
     /// This bit determines which addressing mode to use for fetching Tile Data.
     /// If it is set to 1, the 8000 method is used. Otherwise, the 8800 method is used.
     fn tile_data_select(&self) -> bool {
-        self.data * (1 << 4) != 0
+        self.data & (1 << 4) != 0
     }
 
     /// LCDC.3 - BG Tile Map Select
@@ -392,6 +376,16 @@ impl Stat {
         self.data = data;
     }
 
+    /// A CPU write only reaches the interrupt enable bits, the coincidence flag and mode are read-only.
+    fn write(&mut self, data: u8) {
+        self.data = (self.data & 0x07) | (data & 0x78);
+    }
+
+    /// Bit 7 isn't wired to anything, it always reads as 1.
+    fn read(&self) -> u8 {
+        self.data | 0x80
+    }
+
     /// Update the STAT register based on the current state of the PPU.
     fn update(&mut self, ppu_mode: PpuMode, ppu_ly: u8, ppu_lyc: u8) {
         let mut data = self.data;
@@ -484,28 +478,6 @@ pub struct Ppu {
     /// Is the disable enabled? Use this to track LCD on/off state.
     ldc_on: bool,
 
-    /// The background layer is made up of 32x32 tiles (256x256 pixels).
-    /// The Gameboy can only display 20x18 tiles (160x144 pixels) at a time (this is the viewport).
-    /// The offsets of the viewport are determined by the scroll registers (SCX, SCY).
-    bg_tiles: Vec<Tile>,
-
-    /// The window layer is made up of 32x32 tiles (256x256 pixels).
-    /// The Gameboy can only display 20x18 tiles (160x144 pixels) at a time (this is the viewport).
-    /// The offsets of the viewport are determined by the window position registers (WX, WY).
-    /// The window layer is rendered on top of the background layer, think of it like an overlay.
-    window_tiles: Vec<Tile>,
-
-    /// The sprite layer is made up of 40 sprites that are stored in OAM.
-    /// Each sprite can be 8x8 or 8x16 pixels (1x1 or 1x2 Tiles) depending on the sprite size flag (LCDC.2).
-    sprites: Vec<Sprite>,
-
-    /// Background Maps
-    /// These keep track of the order tiles should be rendered in for the background and window layers.
-    /// The VRAM sections $9800-$9BFF and $9C00-$9FFF each contain one of these background maps.
-    /// The background map is made up of 32x32 bytes, representing tile numbers, organized row by row.
-    background_map: Vec<u8>,
-    window_map: Vec<u8>,
-
     /// The current PPU Mode
     mode: PpuMode,
 
@@ -547,6 +519,10 @@ pub struct Ppu {
     /// Object Palette 1 Register - OBP1 - ($FF49)
     obp1: u8,
 
+    /// OAM DMA Source Register - DMA - ($FF46)
+    /// Reads back the last value written.
+    dma: u8,
+
     /// Pixel FIFO Fetcher
     fetcher: Fetcher,
 
@@ -562,6 +538,33 @@ pub struct Ppu {
     /// Is set to true when a window fetch is in progress.
     window_fetch: bool,
 
+    /// Internal window line counter, only incremented on scanlines where the window was rendered.
+    window_line: u8,
+
+    /// LY matched WY at some point this frame, so the window can be drawn on the following lines.
+    window_triggered: bool,
+
+    /// Length of the Drawing mode on the current line, worked out when it starts.
+    drawing_ticks: u32,
+
+    /// Which rendering pipeline to use during the Drawing mode.
+    accuracy: PpuAccuracy,
+
+    /// Whether the CPU is locked out of VRAM and OAM while the PPU uses them, like on hardware.
+    vram_blocking: bool,
+
+    /// How overlapping sprites are ordered.
+    sprite_priority: SpritePriority,
+
+    /// Render only every other scanline each frame, see set_interlaced. A setting, so not saved in save states.
+    interlaced: bool,
+
+    /// Whether this frame renders the odd lines when interlaced, flipped at every V-Blank.
+    odd_field: bool,
+
+    /// What the front buffer holds while the LCD is off.
+    lcd_off_policy: LcdOffPolicy,
+
     /// The PPU handles VRAM and OAM memory.
     /// VRAM is used to store the background and window tiles.
     /// OAM is used to store the sprite data.
@@ -571,15 +574,26 @@ pub struct Ppu {
     /// Reference to interrupts
     if_: Rc<RefCell<InterruptFlags>>,
 
-    /// Rendering buffer of the viewport.
-    /// u32 vector of size 160x144. Each u32 represents the color of a pixel.
-    /// buffer is a 2D vector, [y][x]
-    pub viewport_buffer: Vec<Vec<u32>>,
+    /// Open bus, for reads of inaccessible VRAM and OAM, and undefined registers.
+    open_bus: Rc<OpenBus>,
+
+    /// Rendering buffers of the viewport.
+    /// Flat u32 arrays of size 160x144, row by row. Each u32 represents the color of a pixel.
+    /// The PPU draws into the back buffer, which is swapped with the front buffer at V-Blank,
+    /// so the front buffer always holds the last complete frame.
+    back_buffer: Box<[u32; SCREEN_PIXELS]>,
+    front_buffer: Box<[u32; SCREEN_PIXELS]>,
+
+    /// Widescreen buffers, 256x144, swapped along with the viewport's. Empty unless widescreen is on.
+    /// The viewport stays exactly as a real Gameboy shows it, widescreen frames are extra.
+    /// They're drawn from VRAM like the viewport, so save states don't keep them, see load_state.
+    wide_back_buffer: Vec<u32>,
+    wide_front_buffer: Vec<u32>,
     pub updated: bool,
 }
 
 impl Ppu {
-    pub fn new(if_: Rc<RefCell<InterruptFlags>>) -> Self {
+    pub fn new(if_: Rc<RefCell<InterruptFlags>>, open_bus: Rc<OpenBus>) -> Self {
         let mut vram = Rc::new(RefCell::new([0; VRAM_SIZE]));
         let mut oam = Rc::new(RefCell::new([0; OAM_SIZE]));
         let fetcher = Fetcher::new(vram.clone(), oam.clone());
@@ -588,12 +602,6 @@ impl Ppu {
             window_enabled: false,
             sprite_enabled: false,
             ldc_on: false,
-            bg_tiles: vec![Tile::new(&[0; 16]); BG_TILES],
-            window_tiles: vec![Tile::new(&[0; 16]); WIN_TILES],
-            //sprites: vec![Sprite::new(&[0; 4], SpriteSize::Small); 40],
-            sprites: vec![],
-            background_map: vec![0; BG_MAP],
-            window_map: vec![0; WIN_MAP],
             mode: PpuMode::OamScan,
             lcdc: Lcdc::new(),
             stat: Stat::new(),
@@ -606,126 +614,132 @@ impl Ppu {
             bgp: 0x00,
             obp0: 0x00,
             obp1: 0x00,
+            dma: 0x00,
             fetcher,
             ticks: 0,
             x: 0,
             to_drop: 0,
             window_fetch: false,
+            window_line: 0,
+            window_triggered: false,
+            drawing_ticks: DRAWING_TICKS,
+            accuracy: PpuAccuracy::default(),
+            vram_blocking: true,
+            sprite_priority: SpritePriority::default(),
+            interlaced: false,
+            odd_field: false,
+            lcd_off_policy: LcdOffPolicy::default(),
             vram,
             oam,
             if_,
-            //viewport_buffer: vec![BLACK; SCREEN_PIXELS],
-            viewport_buffer: vec![vec![BLACK; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            open_bus,
+            back_buffer: Box::new([BLACK; SCREEN_PIXELS]),
+            front_buffer: Box::new([BLACK; SCREEN_PIXELS]),
+            wide_back_buffer: Vec::new(),
+            wide_front_buffer: Vec::new(),
             updated: false,
         }
     }
 
-    /// Initialize sprites vector once we know the sprite size.
-    fn init_sprites(&mut self, size: SpriteSize) {
-        self.sprites = vec![Sprite::new(&[0; 4], size); 40];
+    /// Select the rendering pipeline used during the Drawing mode.
+    pub fn set_accuracy(&mut self, accuracy: PpuAccuracy) {
+        self.accuracy = accuracy;
     }
-}
 
-impl Memory for Ppu {
-    fn read8(&self, addr: u16) -> u8 {
-        match addr {
-            0x8000..=0x9FFF => {
-                // VRAM Operations only allowed in H-Blank, V-Blank and OAM Scan modes.
-                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
-                if self.mode != PpuMode::Drawing {
-                    self.vram.borrow()[(addr - 0x8000) as usize]
-                } else {
-                    UNDEFINED_READ
-                }
-            }
-            0xFE00..=0xFE9F => {
-                // OAM Operations only allowed in H-Blank and V-Blank modes.
-                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
-                if self.mode == PpuMode::HBlank || self.mode == PpuMode::VBlank {
-                    self.oam.borrow()[(addr - 0xFE00) as usize]
-                } else {
-                    UNDEFINED_READ
-                }
-            }
-            0xFF40 => self.lcdc.data,
-            0xFF41 => self.stat.data,
-            0xFF42 => self.scy,
-            0xFF43 => self.scx,
-            0xFF44 => self.ly,
-            0xFF47 => self.bgp,
-            0xFF48 => self.obp0,
-            0xFF49 => self.obp1,
-            0xFF4A => self.wy,
-            0xFF4B => self.wx,
-            _ => UNDEFINED_READ,
-        }
+    pub fn accuracy(&self) -> PpuAccuracy {
+        self.accuracy
     }
 
-    fn write8(&mut self, addr: u16, val: u8) {
+    /// Lock the CPU out of VRAM during Drawing, and out of OAM during OAM Scan and Drawing, like on hardware,
+    /// or let it through in any mode, for games with sloppy timing that get away with it elsewhere.
+    pub fn set_vram_blocking(&mut self, blocking: bool) {
+        self.vram_blocking = blocking;
+    }
+
+    pub fn vram_blocking(&self) -> bool {
+        self.vram_blocking
+    }
+
+    /// Can the CPU access VRAM right now?
+    fn vram_open(&self) -> bool {
+        !self.vram_blocking || self.mode != PpuMode::Drawing
+    }
+
+    /// Can the CPU access OAM right now?
+    fn oam_open(&self) -> bool {
+        !self.vram_blocking || self.mode == PpuMode::HBlank || self.mode == PpuMode::VBlank
+    }
+
+    /// Select how overlapping sprites are ordered.
+    pub fn set_sprite_priority(&mut self, priority: SpritePriority) {
+        self.sprite_priority = priority;
+    }
+
+    /// Render the even lines one frame and the odd lines the next, keeping the other half of the lines from the
+    /// frame before. This halves the rendering work for slow hosts, at the cost of combing on anything moving.
+    /// Only the scanline renderer skips lines, the FIFO pipeline's timing depends on pushing every pixel.
+    pub fn set_interlaced(&mut self, enabled: bool) {
+        self.interlaced = enabled;
+    }
+
+    pub fn interlaced(&self) -> bool {
+        self.interlaced
+    }
+
+    /// Blank the screen while the LCD is off, or keep showing the last frame.
+    pub fn set_lcd_off_policy(&mut self, policy: LcdOffPolicy) {
+        self.lcd_off_policy = policy;
+    }
+
+    /// Is the LCD on? While it's off the PPU finishes no frames.
+    pub fn lcd_on(&self) -> bool {
+        self.ldc_on
+    }
+
+    /// The last complete frame, 160x144 pixels, row by row.
+    /// While the LCD is off, a white screen instead, unless the LCD off policy holds the last frame.
+    pub fn viewport(&self) -> &[u32; SCREEN_PIXELS] {
+        &self.front_buffer
+    }
+
+    /// Render the background beyond the sides of the viewport too, into widescreen frames.
+    /// Only the scanline renderer draws the extra columns, the FIFO pipeline leaves them black.
+    pub fn set_widescreen(&mut self, enabled: bool) {
+        let size = if enabled { WIDESCREEN_PIXELS } else { 0 };
+        self.wide_back_buffer = vec![BLACK; size];
+        self.wide_front_buffer = vec![BLACK; size];
+    }
+
+    /// The last complete widescreen frame, 256x144 pixels, row by row, if widescreen is on.
+    pub fn widescreen(&self) -> Option<&[u32]> {
+        (!self.wide_front_buffer.is_empty()).then_some(&self.wide_front_buffer[..])
+    }
+
+    /// Read VRAM or OAM whatever mode the PPU is in, for debugging tools.
+    pub fn inspect(&self, addr: u16) -> u8 {
         match addr {
-            0x8000..=0x9FFF => {
-                // VRAM Operations only allowed in H-Blank, V-Blank and OAM Scan modes.
-                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
-                if self.mode != PpuMode::Drawing {
-                    self.vram.borrow_mut()[(addr - 0x8000) as usize] = val;
-                }
-            }
-            0xFE00..=0xFE9F => {
-                // OAM Operations only allowed in H-Blank and V-Blank modes.
-                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
-                if self.mode == PpuMode::HBlank || self.mode == PpuMode::VBlank {
-                    self.oam.borrow_mut()[(addr - 0xFE00) as usize] = val;
-                }
-            }
-            0xFF40 => {
-                self.lcdc.set(val);
-            }
-            0xFF41 => {
-                self.stat.set(val & 0xF8);
-            }
-            0xFF42 => {
-                self.scy = val;
-            }
-            0xFF43 => {
-                self.scx = val;
-            }
-            0xFF44 => {
-                //self.ly = 0;
-                warn!("Ignoring write to LY register, as this is read-only.");
-            }
-            0xFF47 => {
-                self.bgp = val;
-            }
-            0xFF48 => {
-                self.obp0 = val;
-            }
-            0xFF49 => {
-                self.obp1 = val;
-            }
-            0xFF4A => {
-                self.wy = val;
-            }
-            0xFF4B => {
-                self.wx = val;
-            }
-            _ => warn!("Ignoring write to PPU register {:04X}", addr),
+            0x8000..=0x9FFF => self.vram.borrow()[(addr - 0x8000) as usize],
+            0xFE00..=0xFE9F => self.oam.borrow()[(addr - 0xFE00) as usize],
+            _ => self.read8(addr),
         }
     }
 
-    fn read16(&self, addr: u16) -> u16 {
-        u16::from(self.read8(addr)) | (u16::from(self.read8(addr + 1)) << 8)
+    /// Write a byte of OAM for an OAM DMA transfer, which the PPU's mode doesn't block.
+    pub fn dma_write(&mut self, offset: usize, val: u8) {
+        self.oam.borrow_mut()[offset] = val;
     }
 
-    fn write16(&mut self, addr: u16, val: u16) {
-        self.write8(addr, (val & 0xFF) as u8);
-        self.write8(addr + 1, (val >> 8) as u8);
+    /// Is OAM blocked from the CPU? It is during OAM Scan and Drawing.
+    pub fn oam_blocked(&self) -> bool {
+        self.mode == PpuMode::OamScan || self.mode == PpuMode::Drawing
     }
 
-    fn cycle(&mut self, _: u32) -> u32 {
+    /// Advance the PPU by a single dot (one T-cycle).
+    fn dot(&mut self) {
         // Check if LCD is enabled
         if !self.ldc_on {
             if !self.lcdc.lcd_display_enable() {
-                return 0;
+                return;
             } else {
                 self.ldc_on = true;
                 self.mode = PpuMode::OamScan;
@@ -735,10 +749,21 @@ impl Memory for Ppu {
             }
         } else if !self.lcdc.lcd_display_enable() {
             // Turn LDC off and reset PPU
+            // STAT reads mode 0 while the LCD is off, and VRAM and OAM are open to the CPU.
             self.ldc_on = false;
             self.ly = 0;
+            self.ticks = 0;
             self.x = 0;
-            return 0;
+            self.window_line = 0;
+            self.window_triggered = false;
+            self.mode = PpuMode::HBlank;
+            self.stat.update(self.mode, self.ly, self.lyc);
+            // The screen goes blank, the frame the PPU was drawing is never finished.
+            if self.lcd_off_policy == LcdOffPolicy::Blank {
+                self.front_buffer.fill(WHITE);
+                self.wide_front_buffer.fill(WHITE);
+            }
+            return;
         }
 
         // Since the screen it on, keep counting ticks.
@@ -757,7 +782,12 @@ impl Memory for Ppu {
 
                     if self.ly == 144 {
                         self.mode = PpuMode::VBlank;
+                        std::mem::swap(&mut self.back_buffer, &mut self.front_buffer);
+                        std::mem::swap(&mut self.wide_back_buffer, &mut self.wide_front_buffer);
                         self.updated = true;
+                        self.odd_field = !self.odd_field;
+                        self.window_line = 0;
+                        self.window_triggered = false;
 
                         // Check if we need to request a STAT interrupt
                         if self.stat.mode_0_stat_interrupt_enable() {
@@ -799,13 +829,13 @@ impl Memory for Ppu {
             PpuMode::OamScan => {
                 // In this state, the PPU would scan the OAM (Objects Attribute Memory)
                 // from 0xfe00 to 0xfe9f to mix sprite pixels in the current line later.
-                // This always takes 40 clock ticks.
+                // This always takes 80 dots.
 
                 //
                 // TODO: OAM search will happen here (when implemented).
                 //
 
-                if self.ticks == 40 {
+                if self.ticks == OAM_SCAN_TICKS {
                     // Move to Pixel Transfer state. Initialize the fetcher to start
                     // reading background tiles from VRAM. We don't do scrolling yet
                     // and the boot ROM does nothing fancy with map addresses, so we
@@ -823,14 +853,56 @@ impl Memory for Ppu {
                     // LY modulo 8.
                     let y = self.scy.wrapping_add(self.ly);
                     self.x = 0;
-                    let tile_line = y % 8;
-                    let tile_map_row_adder = 0x9800 + (((y / 8) as u16) * 32);
-                    self.fetcher.start(tile_map_row_adder, tile_line);
+
+                    // The window starts on the first line where LY matched WY this frame, moving WY afterwards
+                    // doesn't move (or hide) it.
+                    if self.ly == self.wy {
+                        self.window_triggered = true;
+                    }
+                    self.drawing_ticks = self.drawing_length();
+                    if self.accuracy == PpuAccuracy::Fifo {
+                        let tile_line = y % 8;
+                        let tile_map_row_adder = 0x9800 + (((y / 8) as u16) * 32);
+                        self.fetcher.start(tile_map_row_adder, tile_line);
+                    }
 
                     self.mode = PpuMode::Drawing;
                 }
             }
+            PpuMode::Drawing if self.accuracy == PpuAccuracy::Scanline => {
+                // Render the whole line as soon as we enter the Drawing mode,
+                // then wait out the rest of the mode before switching to HBlank.
+                if self.x == 0 {
+                    if self.interlaced && (self.ly % 2 == 1) != self.odd_field {
+                        self.skip_scanline();
+                    } else {
+                        self.render_scanline();
+                    }
+                    self.x = SCREEN_WIDTH as u8;
+                }
+
+                if self.ticks == OAM_SCAN_TICKS + self.drawing_ticks {
+                    self.mode = PpuMode::HBlank;
+
+                    if self.stat.mode_0_stat_interrupt_enable() {
+                        self.if_.borrow_mut().set(Flags::LCDStat);
+                    }
+                }
+            }
             PpuMode::Drawing => {
+                // The pipeline doesn't fetch sprites yet, once the line is out, wait out the dots they
+                // (and the window) would have taken.
+                if self.x == 160 {
+                    if self.ticks >= OAM_SCAN_TICKS + self.drawing_ticks {
+                        self.mode = PpuMode::HBlank;
+
+                        if self.stat.mode_0_stat_interrupt_enable() {
+                            self.if_.borrow_mut().set(Flags::LCDStat);
+                        }
+                    }
+                    return;
+                }
+
                 // Fetch pixel data from our pixel FIFO
                 self.fetcher.tick();
 
@@ -838,25 +910,17 @@ impl Memory for Ppu {
                 // NOTE: This will be used to mix in sprite data when we implement these.
                 // It also guarantees the FIFO will always have data to Pop() later.
                 if self.fetcher.fifo.size() < 8 {
-                    return 0;
+                    return;
                 }
 
                 // Put a pixel from the FIFO in the render buffer
                 let raw_pixel_color = self.fetcher.fifo.pop();
                 let palette_color = (self.bgp >> (raw_pixel_color * 2)) & 0x03;
                 let pixel_color = Color::from_u8(palette_color);
-                self.viewport_buffer[self.ly as usize][self.x as usize] = pixel_color.to_u32();
+                self.back_buffer[self.ly as usize * SCREEN_WIDTH + self.x as usize] =
+                    pixel_color.to_u32();
 
-                // Check when scan line is finished
                 self.x += 1;
-                if self.x == 160 {
-                    // Switch mode to HBlank
-                    self.mode = PpuMode::HBlank;
-
-                    if self.stat.mode_0_stat_interrupt_enable() {
-                        self.if_.borrow_mut().set(Flags::LCDStat);
-                    }
-                }
             }
         }
 
@@ -865,15 +929,271 @@ impl Memory for Ppu {
         let ppu_ly = self.ly;
         let ppu_lyc = self.lyc;
         self.stat.update(ppu_mode, ppu_ly, ppu_lyc);
+    }
+}
 
-        //todo!("PPU is a WIP, plz try again soon <3");
+impl Memory for Ppu {
+    fn read8(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF => {
+                // VRAM Operations only allowed in H-Blank, V-Blank and OAM Scan modes.
+                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
+                if self.vram_open() {
+                    self.vram.borrow()[(addr - 0x8000) as usize]
+                } else {
+                    self.open_bus.read(UNDEFINED_READ)
+                }
+            }
+            0xFE00..=0xFE9F => {
+                // OAM Operations only allowed in H-Blank and V-Blank modes.
+                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
+                if self.oam_open() {
+                    self.oam.borrow()[(addr - 0xFE00) as usize]
+                } else {
+                    self.open_bus.read(UNDEFINED_READ)
+                }
+            }
+            0xFF40 => self.lcdc.data,
+            0xFF41 => self.stat.read(),
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF46 => self.dma,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            _ => self.open_bus.read(UNDEFINED_READ),
+        }
+    }
 
-        //self.ticks
-        match self.mode {
-            PpuMode::HBlank => HBLANK_CYCLES,
-            PpuMode::VBlank => VBLANK_CYCLES,
-            PpuMode::OamScan => ACCESS_OAM_CYCLES,
-            PpuMode::Drawing => ACCESS_VRAM_CYCLES,
+    fn write8(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x8000..=0x9FFF => {
+                // VRAM Operations only allowed in H-Blank, V-Blank and OAM Scan modes.
+                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
+                if self.vram_open() {
+                    self.vram.borrow_mut()[(addr - 0x8000) as usize] = val;
+                }
+            }
+            0xFE00..=0xFE9F => {
+                // OAM Operations only allowed in H-Blank and V-Blank modes.
+                // https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
+                if self.oam_open() {
+                    self.oam.borrow_mut()[(addr - 0xFE00) as usize] = val;
+                }
+            }
+            0xFF40 => {
+                self.lcdc.set(val);
+            }
+            0xFF41 => {
+                self.stat.write(val);
+            }
+            0xFF42 => {
+                self.scy = val;
+            }
+            0xFF43 => {
+                self.scx = val;
+            }
+            0xFF44 => {
+                //self.ly = 0;
+                warn!("Ignoring write to LY register, as this is read-only.");
+            }
+            0xFF45 => {
+                self.lyc = val;
+            }
+            0xFF46 => {
+                // The MMU does the transfer, the register only reads back.
+                self.dma = val;
+            }
+            0xFF47 => {
+                self.bgp = val;
+            }
+            0xFF48 => {
+                self.obp0 = val;
+            }
+            0xFF49 => {
+                self.obp1 = val;
+            }
+            0xFF4A => {
+                self.wy = val;
+            }
+            0xFF4B => {
+                self.wx = val;
+            }
+            _ => warn!("Ignoring write to PPU register {:04X}", addr),
         }
     }
+
+    fn cycle(&mut self, ticks: u32) -> u32 {
+        // The PPU runs one dot per T-cycle, so catch up with the CPU one dot at a time.
+        for _ in 0..ticks {
+            self.dot();
+        }
+        0
+    }
+}
+
+impl Savestate for Ppu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.ldc_on);
+        w.u8(self.mode as u8);
+        w.u8(self.lcdc.data);
+        w.u8(self.stat.data);
+        w.u8(self.ly);
+        w.u8(self.lyc);
+        w.u8(self.scx);
+        w.u8(self.scy);
+        w.u8(self.wx);
+        w.u8(self.wy);
+        w.u8(self.bgp);
+        w.u8(self.obp0);
+        w.u8(self.obp1);
+        w.u8(self.dma);
+        self.fetcher.save_state(w);
+        w.u32(self.ticks);
+        w.u8(self.x);
+        w.u8(self.to_drop);
+        w.bool(self.window_fetch);
+        w.u8(self.window_line);
+        w.bool(self.window_triggered);
+        w.bytes(&*self.vram.borrow());
+        w.bytes(&*self.oam.borrow());
+        w.pixels(&*self.back_buffer);
+        w.pixels(&*self.front_buffer);
+        w.bool(self.updated);
+        w.bool(self.odd_field);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.ldc_on = r.bool()?;
+        self.mode = match r.u8()? {
+            0 => PpuMode::HBlank,
+            1 => PpuMode::VBlank,
+            2 => PpuMode::OamScan,
+            3 => PpuMode::Drawing,
+            _ => return Err(state::StateError::Invalid("PPU mode")),
+        };
+        self.lcdc.set(r.u8()?);
+        self.stat.set(r.u8()?);
+        self.ly = r.u8()?;
+        self.lyc = r.u8()?;
+        self.scx = r.u8()?;
+        self.scy = r.u8()?;
+        self.wx = r.u8()?;
+        self.wy = r.u8()?;
+        self.bgp = r.u8()?;
+        self.obp0 = r.u8()?;
+        self.obp1 = r.u8()?;
+        self.dma = r.u8()?;
+        self.fetcher.load_state(r)?;
+        self.ticks = r.u32()?;
+        self.x = r.u8()?;
+        self.to_drop = r.u8()?;
+        self.window_fetch = r.bool()?;
+        self.window_line = r.u8()?;
+        self.window_triggered = r.bool()?;
+        r.bytes(&mut *self.vram.borrow_mut())?;
+        r.bytes(&mut *self.oam.borrow_mut())?;
+        r.pixels(&mut *self.back_buffer)?;
+        r.pixels(&mut *self.front_buffer)?;
+        self.updated = r.bool()?;
+        self.odd_field = r.bool()?;
+
+        // Not saved, it's worked out again from the registers and OAM.
+        self.drawing_ticks = self.drawing_length();
+        // Not saved either, the next frame draws the sides again, until then they're black around the viewport.
+        if !self.wide_front_buffer.is_empty() {
+            for buffer in [&mut self.wide_back_buffer, &mut self.wide_front_buffer] {
+                buffer.fill(BLACK);
+                for (y, row) in self.front_buffer.chunks(SCREEN_WIDTH).enumerate() {
+                    let start = y * WIDESCREEN_WIDTH + WIDESCREEN_BORDER;
+                    buffer[start..start + SCREEN_WIDTH].copy_from_slice(row);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PpuAccuracy;
+    use crate::gb::GameBoy;
+    use crate::testrom::TestRom;
+
+    /// Draw a frame of a still scene with a pipeline: past the boot ROM, the LCD is turned off, setup writes VRAM
+    /// and the registers, then LCDC turns the LCD back on.
+    fn draw(accuracy: PpuAccuracy, setup: &impl Fn(&mut GameBoy)) -> Vec<u32> {
+        let mut rom = TestRom::new("TEST");
+        rom.end();
+        let mut gb = GameBoy::from_rom(rom.build(), None);
+        gb.set_serial_output(false);
+        gb.set_ppu_accuracy(accuracy);
+        gb.skip_boot();
+        gb.poke(0xFF40, 0x00);
+        // For the PPU to notice the LCD is off, and free VRAM.
+        gb.step();
+        setup(&mut gb);
+        gb.poke(0xFF40, 0x91);
+        for _ in 0..3 {
+            gb.run_frame();
+        }
+        gb.viewport()
+    }
+
+    /// Both pipelines draw the scene the same, pixel for pixel.
+    fn same_frame(setup: impl Fn(&mut GameBoy)) {
+        let scanline = draw(PpuAccuracy::Scanline, &setup);
+        let fifo = draw(PpuAccuracy::Fifo, &setup);
+        if let Some(i) = (0..scanline.len()).find(|&i| scanline[i] != fifo[i]) {
+            panic!(
+                "pixel {},{} is {:06X} drawn by line, {:06X} through the FIFO",
+                i % super::SCREEN_WIDTH,
+                i / super::SCREEN_WIDTH,
+                scanline[i],
+                fifo[i]
+            );
+        }
+        assert!(
+            scanline.iter().any(|&pixel| pixel != scanline[0]),
+            "the scene is blank"
+        );
+    }
+
+    /// Tiles at $8000 with every color in every row, all different, and a map at $9800 of every tile.
+    /// The FIFO pipeline doesn't fetch from the other tile data or map, nor scroll horizontally, or draw the window
+    /// and sprites, so the scenes keep to what both draw.
+    fn tiles(gb: &mut GameBoy) {
+        for addr in 0x8000..0x9000u16 {
+            gb.poke(addr, (addr.wrapping_mul(37) >> 3) as u8 ^ (addr >> 4) as u8);
+        }
+        for addr in 0x9800..0x9C00u16 {
+            gb.poke(addr, addr.wrapping_mul(7) as u8);
+        }
+        gb.poke(0xFF47, 0xE4);
+    }
+
+    #[test]
+    fn background() {
+        same_frame(tiles);
+    }
+
+    #[test]
+    fn scrolled_background() {
+        same_frame(|gb| {
+            tiles(gb);
+            gb.poke(0xFF42, 0x2D);
+        });
+    }
+
+    #[test]
+    fn palette() {
+        same_frame(|gb| {
+            tiles(gb);
+            gb.poke(0xFF47, 0x1B);
+        });
+    }
 }
@@ -0,0 +1,164 @@
+use super::{Color, Ppu, SCREEN_WIDTH, WIDESCREEN_BORDER, WIDESCREEN_WIDTH};
+
+impl Ppu {
+    /// Render the current scanline (LY) in one go, straight from VRAM and OAM.
+    /// This is the fast path, it doesn't emulate the pixel FIFO timing, but it does honor
+    /// the same register state (LCDC, SCX/SCY, WX/WY, and palettes) as the FIFO pipeline.
+    /// In widescreen, the line goes on past both sides of the viewport, with the background wrapping around
+    /// the map as if the viewport were wider, and the window running to the right end.
+    pub(super) fn render_scanline(&mut self) {
+        let ly = self.ly;
+        let border = if self.wide_back_buffer.is_empty() {
+            0
+        } else {
+            WIDESCREEN_BORDER
+        };
+        let width = SCREEN_WIDTH + 2 * border;
+        let mut bg_line = [0u8; WIDESCREEN_WIDTH];
+        let bg_line = &mut bg_line[..width];
+
+        // Background and Window
+        if self.lcdc.bg_window_enable() {
+            let window_visible = self.window_visible();
+            let window_start = self.wx as i16 - 7;
+
+            for (i, pixel) in bg_line.iter_mut().enumerate() {
+                // x is relative to the viewport, negative left of it.
+                let x = i as i16 - border as i16;
+                *pixel = if window_visible && x >= window_start {
+                    let win_x = (x - window_start) as u8;
+                    self.tile_pixel(self.lcdc.window_tile_map_select(), win_x, self.window_line)
+                } else {
+                    let bg_x = self.scx.wrapping_add(x as u8);
+                    let bg_y = self.scy.wrapping_add(ly);
+                    self.tile_pixel(self.lcdc.bg_tile_map_select(), bg_x, bg_y)
+                };
+            }
+
+            // The window keeps its own line counter, which only advances on lines it was drawn on.
+            if window_visible {
+                self.window_line += 1;
+            }
+        }
+
+        let bg_palette = Color::palette(self.bgp);
+        let mut line = [0u32; WIDESCREEN_WIDTH];
+        let line = &mut line[..width];
+        for (pixel, &bg) in line.iter_mut().zip(bg_line.iter()) {
+            *pixel = bg_palette[bg as usize];
+        }
+
+        // Sprites
+        if self.lcdc.sprite_enable() {
+            self.render_sprites(bg_line, line, border);
+        }
+
+        let start = ly as usize * SCREEN_WIDTH;
+        self.back_buffer[start..start + SCREEN_WIDTH]
+            .copy_from_slice(&line[border..border + SCREEN_WIDTH]);
+        if border > 0 {
+            let start = ly as usize * width;
+            self.wide_back_buffer[start..start + width].copy_from_slice(line);
+        }
+    }
+
+    /// Keep the current scanline (LY) from the last frame, for interlaced rendering.
+    /// The window's line counter still advances as if the line had been drawn, so the window
+    /// doesn't shift on the lines that are.
+    pub(super) fn skip_scanline(&mut self) {
+        if self.window_visible() {
+            self.window_line += 1;
+        }
+
+        let start = self.ly as usize * SCREEN_WIDTH;
+        self.back_buffer[start..start + SCREEN_WIDTH]
+            .copy_from_slice(&self.front_buffer[start..start + SCREEN_WIDTH]);
+        if !self.wide_back_buffer.is_empty() {
+            let start = self.ly as usize * WIDESCREEN_WIDTH;
+            self.wide_back_buffer[start..start + WIDESCREEN_WIDTH]
+                .copy_from_slice(&self.wide_front_buffer[start..start + WIDESCREEN_WIDTH]);
+        }
+    }
+
+    /// Look up the color number (0-3) of a pixel in the 256x256 background map.
+    /// This is shared by the background and window layers, which only differ in map and coordinates.
+    pub(super) fn tile_pixel(&self, high_map: bool, x: u8, y: u8) -> u8 {
+        let vram = self.vram.borrow();
+        let map_addr: usize = if high_map { 0x1C00 } else { 0x1800 };
+        let tile_id = vram[map_addr + (y as usize / 8) * 32 + (x as usize / 8)];
+
+        // 8000 method uses tile_id as an unsigned offset from $8000.
+        // 8800 method uses tile_id as a signed offset from $9000.
+        let tile_addr = if self.lcdc.tile_data_select() {
+            tile_id as usize * 16
+        } else {
+            (0x1000 + (tile_id as i8 as isize) * 16) as usize
+        };
+
+        let row = tile_addr + (y as usize % 8) * 2;
+        let bit = 7 - (x % 8);
+        let lo = (vram[row] >> bit) & 0x01;
+        let hi = (vram[row + 1] >> bit) & 0x01;
+        lo | (hi << 1)
+    }
+
+    /// Mix the sprites on the current scanline into line, which starts border pixels left of the viewport.
+    /// bg_line holds the raw background color numbers, for OBJ-to-BG priority.
+    fn render_sprites(&self, bg_line: &[u8], line: &mut [u32], border: usize) {
+        let ly = self.ly as i16;
+        let height: i16 = if self.lcdc.sprite_size() { 16 } else { 8 };
+        let mut sprites = self.scan_oam();
+        let vram = self.vram.borrow();
+        let palettes = [Color::palette(self.obp0), Color::palette(self.obp1)];
+
+        // On the DMG, the sprite with the lowest X wins, and earlier OAM entries win ties.
+        // In CGB mode, only the OAM order counts.
+        let priority = self.sprite_priority;
+        sprites.sort_by_key(|&(i, s)| priority.key(s[1], i));
+
+        let border = border as i16;
+        for x in -border..SCREEN_WIDTH as i16 + border {
+            let i = (x + border) as usize;
+            // The first opaque sprite pixel, in priority order, is the one that gets mixed.
+            // It hides lower priority sprites even when it's itself hidden behind the background.
+            for &(_, sprite) in &sprites {
+                let sprite_x = sprite[1] as i16 - 8;
+                if x < sprite_x || x >= sprite_x + 8 {
+                    continue;
+                }
+
+                let attr = sprite[3];
+                let mut tile_row = ly - (sprite[0] as i16 - 16);
+                if attr & 0x40 != 0 {
+                    tile_row = height - 1 - tile_row;
+                }
+                let mut col = x - sprite_x;
+                if attr & 0x20 == 0 {
+                    col = 7 - col;
+                }
+
+                // In 8x16 mode, the lowest bit of the tile number is ignored.
+                let tile_id = if height == 16 {
+                    sprite[2] & 0xFE
+                } else {
+                    sprite[2]
+                };
+                let row = tile_id as usize * 16 + tile_row as usize * 2;
+                let lo = (vram[row] >> col) & 0x01;
+                let hi = (vram[row + 1] >> col) & 0x01;
+                let color_id = lo | (hi << 1);
+                if color_id == 0 {
+                    // Color 0 is transparent for sprites.
+                    continue;
+                }
+
+                // OBJ-to-BG Priority, sprite is hidden behind BG colors 1-3.
+                if attr & 0x80 == 0 || bg_line[i] == 0 {
+                    let palette = &palettes[(attr >> 4) as usize & 0x01];
+                    line[i] = palette[color_id as usize];
+                }
+                break;
+            }
+        }
+    }
+}
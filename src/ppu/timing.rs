@@ -0,0 +1,89 @@
+use super::Ppu;
+
+/// Maximum number of sprites the PPU can display on a single scanline.
+pub(super) const MAX_SPRITES_PER_LINE: usize = 10;
+
+/// Length of the Drawing mode (Mode 3) with no fine scroll, no window and no sprites, the shortest it gets.
+pub(super) const DRAWING_TICKS: u32 = 172;
+
+/// Dots the background fetcher loses restarting on the window.
+const WINDOW_PENALTY: u32 = 6;
+
+/// Dots each sprite fetch takes, on top of waiting for the background fetcher.
+const SPRITE_FETCH: u32 = 6;
+
+/// Dots a sprite at X=0 holds up the fetcher, wherever the background is.
+const SPRITE_X0_PENALTY: u32 = 11;
+
+impl Ppu {
+    /// OAM Scan - The first 10 sprites (in OAM order) that overlap this line, with their OAM index.
+    /// Sprites off screen horizontally still count towards the limit, sprites past it are neither drawn nor fetched.
+    pub(super) fn scan_oam(&self) -> Vec<(usize, [u8; 4])> {
+        let ly = self.ly as i16;
+        let height: i16 = if self.lcdc.sprite_size() { 16 } else { 8 };
+        self.oam
+            .borrow()
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, s)| {
+                let y = s[0] as i16 - 16;
+                ly >= y && ly < y + height
+            })
+            .take(MAX_SPRITES_PER_LINE)
+            .map(|(i, s)| (i, [s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    /// Whether the window shows on the current line, anywhere.
+    pub(super) fn window_visible(&self) -> bool {
+        self.lcdc.bg_window_enable()
+            && self.lcdc.window_display_enable()
+            && self.window_triggered
+            && self.wx <= 166
+    }
+
+    /// Length of the Drawing mode (Mode 3) on the current line, in dots.
+    /// On top of the shortest 172, the fetcher drops SCX % 8 pixels first, restarts once on the window,
+    /// and stops for every sprite it meets, for longer when the background tile under it isn't fetched yet.
+    /// HBlank is that much shorter, which moves the mode 0 STAT interrupt raster effects key off.
+    /// https://gbdev.io/pandocs/Rendering.html#mode-3-length
+    pub(super) fn drawing_length(&self) -> u32 {
+        let mut length = DRAWING_TICKS + (self.scx % 8) as u32;
+        let window = self.window_visible();
+        if window {
+            length += WINDOW_PENALTY;
+        }
+        if !self.lcdc.sprite_enable() {
+            return length;
+        }
+
+        // Sprites are fetched left to right, the first one on a background (or window) tile waits for
+        // the rest of that tile's fetch, later ones on the same tile don't.
+        let mut sprites = self.scan_oam();
+        sprites.sort_by_key(|&(i, s)| (s[1], i));
+        let mut fetched = [false; 64];
+        for (_, sprite) in sprites {
+            let x = sprite[1] as u32;
+            if x == 0 {
+                length += SPRITE_X0_PENALTY;
+                continue;
+            }
+            if x >= 168 {
+                continue;
+            }
+            let (tile, pixel) = if window && x > self.wx as u32 {
+                let col = x - self.wx as u32 - 1;
+                (32 + col / 8, col % 8)
+            } else {
+                let col = x + (self.scx % 8) as u32;
+                (col / 8, col % 8)
+            };
+            if !fetched[tile as usize] {
+                fetched[tile as usize] = true;
+                length += 5u32.saturating_sub(pixel);
+            }
+            length += SPRITE_FETCH;
+        }
+        length
+    }
+}
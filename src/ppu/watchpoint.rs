@@ -0,0 +1,118 @@
+use crate::cartridge::banks::BankedAddr;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// VRAM holds 384 tiles, at $8000-$97FF.
+const TILES: u16 = 384;
+
+/// A VRAM watchpoint, stopping emulation when the CPU writes a tile's data or a background map cell,
+/// to find the code drawing a glitching tile. Given on the command line as:
+///
+/// tile:N          Tile N of VRAM, 0-383 from $8000, in decimal or $hex, e.g. tile:$2A.
+///                 This is the tile number sprites, and maps with LCDC bit 4 set, use.
+/// tile8800:N      Tile N as maps with LCDC bit 4 clear number it, -128 to 127 around $9000,
+///                 given as the byte in the map, e.g. tile8800:$FF is the tile at $8FF0.
+/// map0:X,Y        The cell at column X, row Y (0-31) of the map at $9800.
+/// map1:X,Y        The same, for the map at $9C00.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// Writes to a tile's 16 bytes of data, by VRAM tile number (0-383).
+    Tile(u16),
+
+    /// Writes to a cell of a background map, 0 for $9800, 1 for $9C00.
+    MapCell { map: u8, x: u8, y: u8 },
+}
+
+impl Watchpoint {
+    /// The tile a map cell holding index points to, with LCDC bit 4 clear (signed) or set.
+    pub fn map_tile(index: u8, signed: bool) -> Self {
+        match signed {
+            true => Watchpoint::Tile((256 + index as i8 as i16) as u16),
+            false => Watchpoint::Tile(index as u16),
+        }
+    }
+
+    /// The VRAM addresses written to trigger it.
+    pub fn range(&self) -> RangeInclusive<u16> {
+        match *self {
+            Watchpoint::Tile(tile) => {
+                let start = 0x8000 + tile * 16;
+                start..=start + 15
+            }
+            Watchpoint::MapCell { map, x, y } => {
+                let addr = 0x9800 + map as u16 * 0x400 + y as u16 * 32 + x as u16;
+                addr..=addr
+            }
+        }
+    }
+}
+
+/// e.g. "Tile 42" or "Map0 3,4", short enough for a line of the OSD with a hit.
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watchpoint::Tile(tile) => write!(f, "Tile {}", tile),
+            Watchpoint::MapCell { map, x, y } => write!(f, "Map{} {},{}", map, x, y),
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Option<u16> {
+    match s.strip_prefix('$').or_else(|| s.strip_prefix("0x")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, arg)) = s.split_once(':') else {
+            return Err(format!("`{}` isn't tile:N, tile8800:N, or mapM:X,Y", s));
+        };
+        match kind {
+            "tile" => match parse_number(arg) {
+                Some(tile) if tile < TILES => Ok(Watchpoint::Tile(tile)),
+                _ => Err(format!("`{}` isn't a tile (0-383)", arg)),
+            },
+            "tile8800" => match parse_number(arg) {
+                Some(index @ 0x00..=0xFF) => Ok(Watchpoint::map_tile(index as u8, true)),
+                _ => Err(format!("`{}` isn't a map byte (0-255)", arg)),
+            },
+            "map0" | "map1" => {
+                let cell = arg
+                    .split_once(',')
+                    .and_then(|(x, y)| Some((x.parse::<u8>().ok()?, y.parse::<u8>().ok()?)))
+                    .filter(|&(x, y)| x < 32 && y < 32);
+                let Some((x, y)) = cell else {
+                    return Err(format!("`{}` isn't a map cell X,Y (0-31)", arg));
+                };
+                let map = (kind == "map1") as u8;
+                Ok(Watchpoint::MapCell { map, x, y })
+            }
+            _ => Err(format!("`{}` isn't tile, tile8800, map0, or map1", kind)),
+        }
+    }
+}
+
+/// A write that triggered a watchpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+
+    /// The address written, and the value.
+    pub addr: u16,
+    pub val: u8,
+
+    /// The instruction that wrote it.
+    pub pc: BankedAddr,
+}
+
+/// e.g. "Tile 42 write at 03:4F20", short enough for a line of the OSD.
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} write at {}", self.watchpoint, self.pc)
+    }
+}
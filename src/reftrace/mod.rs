@@ -0,0 +1,269 @@
+use crate::gb::GameBoy;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// Reference lines shown before a divergence.
+const CONTEXT: usize = 8;
+
+/// Reference trace line
+/// The CPU registers before an instruction, and the 4 bytes at PC, as Gameboy Doctor logs them:
+///
+/// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+///
+/// Fields are read by name, so the Gameboy-logs layout, with spaces after the colons, the bank in PC,
+/// and the bytes at PC in parentheses, parses too:
+///
+/// A: 01 F: B0 B: 00 C: 13 D: 00 E: D8 H: 01 L: 4D SP: FFFE PC: 00:0100 (00 C3 13 02)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraceLine {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+
+    /// The bytes at PC, None if the log doesn't have them, then they aren't compared.
+    pub pcmem: Option<[u8; 4]>,
+}
+
+impl TraceLine {
+    /// The line ferrum would log, before executing its next instruction.
+    pub fn capture(gb: &GameBoy) -> Self {
+        let state = gb.cpu_state();
+        let [a, f] = state.af.to_be_bytes();
+        let [b, c] = state.bc.to_be_bytes();
+        let [d, e] = state.de.to_be_bytes();
+        let [h, l] = state.hl.to_be_bytes();
+        let pcmem = [0, 1, 2, 3].map(|i| gb.peek(state.pc.wrapping_add(i)));
+        Self {
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            sp: state.sp,
+            pc: state.pc,
+            pcmem: Some(pcmem),
+        }
+    }
+
+    /// The fields that differ from other, by name.
+    pub fn diff(&self, other: &TraceLine) -> Vec<&'static str> {
+        let mut fields: Vec<_> = [
+            ("A", self.a, other.a),
+            ("F", self.f, other.f),
+            ("B", self.b, other.b),
+            ("C", self.c, other.c),
+            ("D", self.d, other.d),
+            ("E", self.e, other.e),
+            ("H", self.h, other.h),
+            ("L", self.l, other.l),
+        ]
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(name, _, _)| name)
+        .collect();
+        if self.sp != other.sp {
+            fields.push("SP");
+        }
+        if self.pc != other.pc {
+            fields.push("PC");
+        }
+        if let (Some(a), Some(b)) = (self.pcmem, other.pcmem) {
+            if a != b {
+                fields.push("PCMEM");
+            }
+        }
+        fields
+    }
+}
+
+/// In Gameboy Doctor's layout.
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X}",
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc
+        )?;
+        if let Some([m0, m1, m2, m3]) = self.pcmem {
+            write!(f, " PCMEM:{:02X},{:02X},{:02X},{:02X}", m0, m1, m2, m3)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TraceLine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Gameboy-logs puts the bytes at PC in parentheses, and a space after every colon.
+        let (regs, mem) = match s.split_once('(') {
+            Some((regs, mem)) => (regs, Some(mem.trim_end().trim_end_matches(')'))),
+            None => (s, None),
+        };
+        let regs = regs.replace(": ", ":");
+
+        let mut line = TraceLine::default();
+        let mut found = 0;
+        let mut pcmem = mem.map(str::to_string);
+        for field in regs.split_whitespace() {
+            let Some((name, val)) = field.split_once(':') else {
+                return Err(format!("`{}` isn't NAME:VALUE", field));
+            };
+            if name == "PCMEM" {
+                pcmem = Some(val.to_string());
+                continue;
+            }
+            // PC may have the bank before it, e.g. 00:0100.
+            let val = val.rsplit(':').next().unwrap_or(val);
+            let parse =
+                |val| u16::from_str_radix(val, 16).map_err(|_| format!("`{}` isn't hex", field));
+            match name {
+                "A" => line.a = parse(val)? as u8,
+                "F" => line.f = parse(val)? as u8,
+                "B" => line.b = parse(val)? as u8,
+                "C" => line.c = parse(val)? as u8,
+                "D" => line.d = parse(val)? as u8,
+                "E" => line.e = parse(val)? as u8,
+                "H" => line.h = parse(val)? as u8,
+                "L" => line.l = parse(val)? as u8,
+                "SP" => line.sp = parse(val)?,
+                "PC" => line.pc = parse(val)?,
+                _ => return Err(format!("unknown field {}", name)),
+            }
+            found += 1;
+        }
+        if found != 10 {
+            return Err("expected A, F, B, C, D, E, H, L, SP, and PC".to_string());
+        }
+        if let Some(mem) = pcmem {
+            let bytes: Vec<_> = mem
+                .split([',', ' '])
+                .filter(|byte| !byte.is_empty())
+                .map(|byte| u8::from_str_radix(byte, 16))
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("`{}` isn't 4 hex bytes", mem))?;
+            let bytes = bytes
+                .try_into()
+                .map_err(|_| format!("`{}` isn't 4 hex bytes", mem))?;
+            line.pcmem = Some(bytes);
+        }
+        Ok(line)
+    }
+}
+
+/// Where ferrum first parted ways with the reference.
+pub struct Divergence {
+    /// Line of the reference, from 1, and the instructions that matched before it.
+    pub line: usize,
+    pub matched: usize,
+
+    pub expected: TraceLine,
+    pub actual: TraceLine,
+
+    /// The fields that differ, by name.
+    pub fields: Vec<&'static str>,
+
+    /// The reference lines before it, oldest first.
+    pub context: Vec<TraceLine>,
+
+    /// ferrum's own trace of the instructions before it, with ROM banks.
+    pub trace: String,
+}
+
+/// e.g.
+///
+/// Diverged at line 1234 of the reference, after 1233 matching instructions, A F differ
+/// Reference before it:
+///   A:01 F:B0 ...
+/// Expected: A:02 F:00 ...
+/// ferrum:   A:01 F:80 ...
+/// Last 64 instructions before ..., oldest first:
+///   PC:00:0150 op:3C ...
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Diverged at line {} of the reference, after {} matching instructions, {} differ",
+            self.line,
+            self.matched,
+            self.fields.join(" ")
+        )?;
+        if !self.context.is_empty() {
+            writeln!(f, "Reference before it:")?;
+            for line in &self.context {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+        writeln!(f, "Expected: {}", self.expected)?;
+        writeln!(f, "ferrum:   {}", self.actual)?;
+        writeln!(f, "{}", self.trace)
+    }
+}
+
+/// How a run compared to the reference.
+pub enum Outcome {
+    /// Every line of the reference matched, this many.
+    Matched(usize),
+
+    Diverged(Box<Divergence>),
+
+    /// A line of the reference couldn't be read, every instruction before it matched.
+    Malformed {
+        line: usize,
+        error: String,
+    },
+}
+
+/// Step the machine instruction by instruction alongside a reference trace, e.g. from another emulator,
+/// comparing the registers before every instruction, and stop at the first line that differs.
+/// Blank lines are skipped. The machine should be where the trace starts, usually at $0100 with the boot ROM skipped.
+pub fn compare(gb: &mut GameBoy, reference: impl BufRead) -> io::Result<Outcome> {
+    let mut context = VecDeque::with_capacity(CONTEXT);
+    let mut matched = 0;
+    for (i, text) in reference.lines().enumerate() {
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let line = i + 1;
+        let expected: TraceLine = match text.trim().parse() {
+            Ok(expected) => expected,
+            Err(error) => return Ok(Outcome::Malformed { line, error }),
+        };
+        let mut actual = TraceLine::capture(gb);
+        if expected.pcmem.is_none() {
+            actual.pcmem = None;
+        }
+        let fields = actual.diff(&expected);
+        if !fields.is_empty() {
+            return Ok(Outcome::Diverged(Box::new(Divergence {
+                line,
+                matched,
+                expected,
+                actual,
+                fields,
+                context: context.into(),
+                trace: gb.trace_dump(&format!("line {}", line)),
+            })));
+        }
+        if context.len() == CONTEXT {
+            context.pop_front();
+        }
+        context.push_back(expected);
+        matched += 1;
+        gb.step();
+    }
+    Ok(Outcome::Matched(matched))
+}
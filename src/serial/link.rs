@@ -0,0 +1,174 @@
+use super::{Clock, SerialDevice};
+use log::{info, warn};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Message carrying a byte clocked out by the side driving the clock.
+const CLOCKED: u8 = 0x01;
+
+/// Message carrying the byte shifted back in answer.
+const ANSWER: u8 = 0x02;
+
+/// How long the side driving the clock waits for the answer, before taking the line as disconnected.
+const ANSWER_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Link cable over TCP, to another ferrum (or anything speaking the same protocol).
+/// Every exchange is two messages of two bytes, a kind and a byte:
+///     01 XX   The side driving the clock sent XX.
+///     02 YY   The other side answers with YY, the contents of its SB.
+/// The side with the internal clock waits for the answer, briefly, so the two emulators stay roughly in step.
+/// The other side only answers while it waits for an external clock transfer, if it doesn't in time,
+/// the byte is lost and the driving side reads $FF, like with nothing plugged in.
+pub struct TcpLink {
+    /// Listening for the other side to connect, if it hasn't yet.
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+
+    /// Bytes received that don't make a whole message yet.
+    incoming: Vec<u8>,
+
+    /// A byte clocked in by the other side, waiting for a transfer on this side.
+    pending: Option<u8>,
+}
+
+impl TcpLink {
+    /// Connect to another emulator listening at the given address.
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            listener: None,
+            stream: Some(stream),
+            incoming: Vec::new(),
+            pending: None,
+        })
+    }
+
+    /// Wait for another emulator to connect on the given address.
+    /// This doesn't block, the cable is unplugged until the other side connects.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+            stream: None,
+            incoming: Vec::new(),
+            pending: None,
+        })
+    }
+
+    /// Accept the other side, if it has connected since the last call.
+    fn accept(&mut self) {
+        let Some(listener) = &self.listener else {
+            return;
+        };
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                if stream.set_nodelay(true).is_ok() && stream.set_nonblocking(true).is_ok() {
+                    info!("Link cable connected to {}", peer);
+                    self.stream = Some(stream);
+                    self.listener = None;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+            Err(e) => warn!("Failed to accept a link cable connection: {}", e),
+        }
+    }
+
+    /// Read the next message, if a whole one has arrived.
+    fn read_message(&mut self) -> Option<(u8, u8)> {
+        loop {
+            if self.incoming.len() >= 2 {
+                let message = (self.incoming[0], self.incoming[1]);
+                self.incoming.drain(..2);
+                return Some(message);
+            }
+            let mut buf = [0u8; 64];
+            match self.stream.as_mut()?.read(&mut buf) {
+                Ok(0) => {
+                    self.disconnect(ErrorKind::UnexpectedEof.into());
+                    return None;
+                }
+                Ok(n) => self.incoming.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    return None
+                }
+                Err(e) => {
+                    self.disconnect(e);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, kind: u8, byte: u8) {
+        let Some(stream) = self.stream.as_mut() else {
+            return;
+        };
+        if let Err(e) = stream.write_all(&[kind, byte]) {
+            self.disconnect(e);
+        }
+    }
+
+    fn disconnect(&mut self, e: io::Error) {
+        warn!("Link cable disconnected: {}", e);
+        self.stream = None;
+        self.incoming.clear();
+        self.pending = None;
+    }
+
+    /// Drive the clock: send a byte and wait for the answer.
+    fn clock_out(&mut self, byte: u8) -> u8 {
+        self.accept();
+        self.send(CLOCKED, byte);
+        let Some(stream) = self.stream.as_mut() else {
+            return 0xFF;
+        };
+        if stream.set_nonblocking(false).is_err()
+            || stream.set_read_timeout(Some(ANSWER_TIMEOUT)).is_err()
+        {
+            return 0xFF;
+        }
+
+        let mut answer = 0xFF;
+        while let Some((kind, byte)) = self.read_message() {
+            if kind == ANSWER {
+                answer = byte;
+                break;
+            }
+            // Both sides drove the clock at once, on real hardware neither would see the other's byte.
+            self.send(ANSWER, 0xFF);
+        }
+        if let Some(stream) = self.stream.as_mut() {
+            let _ = stream.set_nonblocking(true);
+        }
+        answer
+    }
+}
+
+impl SerialDevice for TcpLink {
+    fn exchange_byte(&mut self, byte: u8, clock: Clock) -> u8 {
+        match clock {
+            Clock::Internal => self.clock_out(byte),
+            Clock::External => {
+                self.send(ANSWER, byte);
+                self.pending.take().unwrap_or(0xFF)
+            }
+        }
+    }
+
+    fn external_clock(&mut self) -> bool {
+        self.accept();
+        if self.pending.is_none() {
+            while let Some((kind, byte)) = self.read_message() {
+                if kind == CLOCKED {
+                    self.pending = Some(byte);
+                    break;
+                }
+            }
+        }
+        self.pending.is_some()
+    }
+}
@@ -0,0 +1,148 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::cpu::interrupts::{Flags, InterruptFlags};
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
+mod link;
+mod printer;
+
+pub use link::TcpLink;
+pub use printer::Printer;
+
+/// T-cycles to shift a bit with the internal clock, which runs at 8192 Hz.
+const BIT_TICKS: u32 = 512;
+
+/// Which side of the link cable drives the clock of a transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clock {
+    /// The Gameboy, at 8192 Hz.
+    Internal,
+
+    /// The device on the other end.
+    External,
+}
+
+/// Something plugged into the link port, another Gameboy, a printer, a test harness, etc.
+/// Bits are shifted out MSB first, and the device shifts a bit back in for every bit it receives.
+/// Byte-oriented devices only implement exchange_byte, bit-oriented ones only exchange_bit.
+pub trait SerialDevice {
+    /// Exchange a bit on a clock pulse, returning the bit shifted back in.
+    /// Nothing is plugged in by default, an open line reads as 1.
+    fn exchange_bit(&mut self, _bit: bool, _clock: Clock) -> bool {
+        true
+    }
+
+    /// Exchange a whole byte, returning the byte shifted back in.
+    /// The controller calls this once a transfer is done, the default shifts the byte through exchange_bit.
+    fn exchange_byte(&mut self, byte: u8, clock: Clock) -> u8 {
+        (0..8).rev().fold(0, |received, i| {
+            received << 1 | self.exchange_bit(byte >> i & 1 != 0, clock) as u8
+        })
+    }
+
+    /// Does the device clock a byte in? Polled every instruction while the Gameboy waits on the external clock,
+    /// a device that never drives the clock leaves such a transfer pending forever, like a real Gameboy.
+    fn external_clock(&mut self) -> bool {
+        false
+    }
+}
+
+/// Nothing plugged in, every bit shifted in is 1.
+pub struct NullDevice;
+
+impl SerialDevice for NullDevice {}
+
+/// Serial port
+/// https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+///
+/// FF01 - SB - Serial transfer data, the byte to send, replaced by the byte received when the transfer is done.
+/// FF02 - SC - Serial transfer control
+///     Bit 7 - Transfer enable (1=Transfer in progress, or requested)
+///     Bit 0 - Clock select    (0=External clock, 1=Internal clock)
+///
+/// With the internal clock, a transfer takes 8 bits at 8192 Hz, then the byte is exchanged with the device
+/// and the serial interrupt is requested. With the external clock, the transfer waits for the device to clock it.
+/// SB isn't shifted a bit at a time, it only changes once the transfer is done.
+pub struct Serial {
+    if_: Rc<RefCell<InterruptFlags>>,
+    device: Box<dyn SerialDevice>,
+
+    sb: u8,
+    sc: u8,
+
+    /// T-cycles until an internally clocked transfer is done.
+    ticks: u32,
+}
+
+impl Serial {
+    pub fn new(if_: Rc<RefCell<InterruptFlags>>) -> Self {
+        Self {
+            if_,
+            device: Box::new(NullDevice),
+            sb: 0x00,
+            sc: 0x00,
+            ticks: 0,
+        }
+    }
+
+    /// Plug a device into the link port, returning the one it replaces.
+    pub fn set_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+        std::mem::replace(&mut self.device, device)
+    }
+
+    /// Read SB or SC, SC's unused bits read as 1.
+    pub fn get(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF01 => self.sb,
+            _ => self.sc | 0x7E,
+        }
+    }
+
+    /// Write SB or SC, writing SC with bit 7 set starts a transfer.
+    pub fn set(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xFF01 => self.sb = val,
+            _ => {
+                self.sc = val & 0x81;
+                self.ticks = BIT_TICKS * 8;
+            }
+        }
+    }
+
+    /// Advance the transfer by the given T-cycles.
+    pub fn cycle(&mut self, ticks: u32) {
+        if self.sc & 0x80 == 0 {
+            return;
+        }
+        let clock = if self.sc & 0x01 != 0 {
+            self.ticks = self.ticks.saturating_sub(ticks);
+            if self.ticks > 0 {
+                return;
+            }
+            Clock::Internal
+        } else {
+            if !self.device.external_clock() {
+                return;
+            }
+            Clock::External
+        };
+        self.sb = self.device.exchange_byte(self.sb, clock);
+        self.sc &= 0x7F;
+        self.if_.borrow_mut().set(Flags::Serial);
+    }
+}
+
+impl Savestate for Serial {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.sb);
+        w.u8(self.sc);
+        w.u32(self.ticks);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.sb = r.u8()?;
+        self.sc = r.u8()? & 0x81;
+        self.ticks = r.u32()?.min(BIT_TICKS * 8);
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use super::{Clock, SerialDevice};
+use crate::ppu::debug;
+use log::{info, warn};
+use std::path::PathBuf;
+
+/// The printer's answer to the first of the two bytes ending a packet, its device ID.
+const DEVICE_ID: u8 = 0x81;
+
+/// Paper is 160 pixels wide, 20 tiles.
+const TILES_PER_ROW: usize = 20;
+
+/// Most image data the printer buffers, 9 data packets of 2 tile rows each.
+const MAX_DATA: usize = 9 * 640;
+
+/// Status bits, reported at the end of every packet.
+const STATUS_CHECKSUM_ERROR: u8 = 0x01;
+const STATUS_PRINTING: u8 = 0x02;
+const STATUS_DATA_FULL: u8 = 0x04;
+const STATUS_UNPROCESSED: u8 = 0x08;
+
+/// Status packets the printer reports itself busy for, after a print command.
+const PRINTING_POLLS: u8 = 2;
+
+/// Where in a packet the next byte goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLo,
+    LengthHi,
+    Data,
+    ChecksumLo,
+    ChecksumHi,
+    DeviceId,
+    Status,
+}
+
+/// Game Boy Printer
+/// https://gbdev.io/pandocs/Gameboy_Printer.html
+///
+/// The game sends packets, always with the internal clock:
+///     88 33 | command | compression | length (LE) | data | checksum (LE) | 00 00
+/// and the printer answers the two trailing bytes with its device ID ($81), then its status.
+/// The checksum is the sum of every byte from the command to the end of the data.
+///
+/// Commands: $01 initialize, $02 print, $04 image data, $0F status.
+/// Image data is 2bpp tiles, 20 per row, and can be RLE compressed. Printing writes the buffered image,
+/// mapped through the palette of the print command, as a PNG to the output directory, print-000.png, print-001.png, ...
+pub struct Printer {
+    dir: PathBuf,
+
+    stage: Stage,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    packet: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+
+    /// Decompressed image data, waiting to be printed.
+    image: Vec<u8>,
+    status: u8,
+
+    /// Status packets left before the print is done.
+    printing: u8,
+}
+
+impl Printer {
+    /// A printer writing its pages to the given directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            stage: Stage::Magic1,
+            command: 0x00,
+            compressed: false,
+            length: 0,
+            packet: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            image: Vec::new(),
+            status: 0x00,
+            printing: 0,
+        }
+    }
+
+    /// Take a byte of the packet being received, returning the printer's answer.
+    fn receive(&mut self, byte: u8) -> u8 {
+        let (next, answer) = match self.stage {
+            Stage::Magic1 => (
+                if byte == 0x88 {
+                    Stage::Magic2
+                } else {
+                    Stage::Magic1
+                },
+                0x00,
+            ),
+            Stage::Magic2 => (
+                if byte == 0x33 {
+                    Stage::Command
+                } else {
+                    Stage::Magic1
+                },
+                0x00,
+            ),
+            Stage::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                (Stage::Compression, 0x00)
+            }
+            Stage::Compression => {
+                self.compressed = byte & 0x01 != 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                (Stage::LengthLo, 0x00)
+            }
+            Stage::LengthLo => {
+                self.length = byte as u16;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                (Stage::LengthHi, 0x00)
+            }
+            Stage::LengthHi => {
+                self.length |= (byte as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.packet.clear();
+                if self.length == 0 {
+                    (Stage::ChecksumLo, 0x00)
+                } else {
+                    (Stage::Data, 0x00)
+                }
+            }
+            Stage::Data => {
+                self.packet.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.packet.len() == self.length as usize {
+                    (Stage::ChecksumLo, 0x00)
+                } else {
+                    (Stage::Data, 0x00)
+                }
+            }
+            Stage::ChecksumLo => {
+                self.received_checksum = byte as u16;
+                (Stage::ChecksumHi, 0x00)
+            }
+            Stage::ChecksumHi => {
+                self.received_checksum |= (byte as u16) << 8;
+                (Stage::DeviceId, 0x00)
+            }
+            Stage::DeviceId => (Stage::Status, DEVICE_ID),
+            Stage::Status => {
+                self.run_command();
+                (Stage::Magic1, self.status)
+            }
+        };
+        self.stage = next;
+        answer
+    }
+
+    /// Run the command of the packet just received, updating the status reported for it.
+    fn run_command(&mut self) {
+        if self.checksum != self.received_checksum {
+            warn!(
+                "Printer packet checksum is {:04X}, expected {:04X}",
+                self.received_checksum, self.checksum
+            );
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            // Initialize
+            0x01 => {
+                self.image.clear();
+                self.printing = 0;
+                self.status = 0x00;
+            }
+
+            // Print, the data is sheets, margins, palette, and exposure.
+            0x02 => {
+                let palette = match self.packet.get(2) {
+                    Some(&0x00) | None => 0xE4,
+                    Some(&palette) => palette,
+                };
+                self.print(palette);
+                self.image.clear();
+                self.printing = PRINTING_POLLS;
+            }
+
+            // Image data, an empty packet ends it.
+            0x04 => {
+                let data = std::mem::take(&mut self.packet);
+                if self.compressed {
+                    self.decompress(&data);
+                } else {
+                    self.image.extend_from_slice(&data);
+                }
+                self.image.truncate(MAX_DATA);
+            }
+
+            // Status
+            0x0F => self.printing = self.printing.saturating_sub(1),
+
+            command => warn!("Unknown printer command {:02X}", command),
+        }
+
+        self.status &= STATUS_CHECKSUM_ERROR;
+        if self.printing > 0 {
+            self.status |= STATUS_PRINTING;
+        }
+        if !self.image.is_empty() {
+            self.status |= STATUS_UNPROCESSED;
+        }
+        if self.image.len() >= MAX_DATA {
+            self.status |= STATUS_DATA_FULL;
+        }
+    }
+
+    /// Run-length decoding: a control byte with bit 7 set repeats the next byte (control & 0x7F) + 2 times,
+    /// otherwise the next (control + 1) bytes are copied as is.
+    fn decompress(&mut self, data: &[u8]) {
+        let mut bytes = data.iter().copied();
+        while let Some(control) = bytes.next() {
+            if control & 0x80 != 0 {
+                let Some(byte) = bytes.next() else {
+                    break;
+                };
+                let count = (control & 0x7F) as usize + 2;
+                self.image.extend(std::iter::repeat_n(byte, count));
+            } else {
+                self.image.extend(bytes.by_ref().take(control as usize + 1));
+            }
+        }
+    }
+
+    /// Write the buffered image to the next free print-NNN.png.
+    fn print(&self, palette: u8) {
+        if self.image.is_empty() {
+            return;
+        }
+        let image = debug::tile_rows(&self.image, TILES_PER_ROW, palette);
+        let path = (0..)
+            .map(|n| self.dir.join(format!("print-{:03}.png", n)))
+            .find(|path| !path.exists())
+            .unwrap();
+        match image.write_png(&path) {
+            Ok(()) => info!("Printed {}", path.display()),
+            Err(e) => warn!("Failed to write {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl SerialDevice for Printer {
+    /// The printer never drives the clock, so bytes only ever come from the Gameboy's internal clock.
+    fn exchange_byte(&mut self, byte: u8, _clock: Clock) -> u8 {
+        self.receive(byte)
+    }
+}
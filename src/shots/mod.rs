@@ -0,0 +1,88 @@
+use crate::gb::GameBoy;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A screenshot to take: the frame after this many frames from power on, and the PNG to write it to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shot {
+    pub frame: u64,
+    pub path: PathBuf,
+}
+
+impl Shot {
+    /// Parse a shot given on the command line, e.g. "300:title.png".
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let (frame, path) = arg
+            .split_once(':')
+            .ok_or_else(|| format!("`{}` isn't FRAME:FILE", arg))?;
+        Self::new(frame, path)
+    }
+
+    fn new(frame: &str, path: &str) -> Result<Self, String> {
+        let frame = match frame.trim().parse() {
+            Ok(0) | Err(_) => return Err(format!("`{}` isn't a frame number from 1", frame)),
+            Ok(frame) => frame,
+        };
+        match path.trim() {
+            "" => Err("the file is missing".to_string()),
+            path => Ok(Self {
+                frame,
+                path: PathBuf::from(path),
+            }),
+        }
+    }
+}
+
+/// Screenshot scripts
+/// The frames to capture from a headless run, for repeatable screenshots, e.g. of a game's intro for documentation,
+/// or to compare pixel for pixel across versions. A script holds a shot per line, the frame and the file:
+///
+/// # Title screen, then the menu
+/// 300 title.png
+/// 1200 menu.png
+///
+/// Blank lines, and lines starting with #, are skipped. Files are relative to the script's directory.
+/// Emulation is deterministic, nothing but the ROM decides what's on screen, so a script always takes the same shots.
+pub fn load_script(path: &Path) -> io::Result<Vec<Shot>> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut shots = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let shot = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| "expected FRAME FILE".to_string())
+            .and_then(|(frame, file)| Shot::new(frame, file))
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, e),
+                )
+            })?;
+        shots.push(Shot {
+            path: dir.join(shot.path),
+            ..shot
+        });
+    }
+    Ok(shots)
+}
+
+/// Run a Gameboy from power on, writing each shot as its frame comes up, in frame order.
+/// Several shots of the same frame each get a copy. Stops at the first PNG that can't be written.
+pub fn capture(gb: &mut GameBoy, shots: &[Shot]) -> io::Result<()> {
+    let mut shots: Vec<&Shot> = shots.iter().collect();
+    shots.sort_by_key(|shot| shot.frame);
+    let mut frame = 0;
+    for shot in shots {
+        while frame < shot.frame {
+            gb.run_frame();
+            frame += 1;
+        }
+        gb.screenshot(&shot.path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", shot.path.display(), e)))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use crate::gb::{Counters, GameBoy};
+use crate::timeline::register_name;
+use std::fmt;
+
+/// Memory compared byte by byte, by name and address range.
+/// Cartridge RAM is only compared in the bank mapped at the time, and reads as $FF while it is disabled.
+const REGIONS: [(&str, u16, u16); 5] = [
+    ("VRAM", 0x8000, 0x9FFF),
+    ("SRAM", 0xA000, 0xBFFF),
+    ("WRAM", 0xC000, 0xDFFF),
+    ("OAM", 0xFE00, 0xFE9F),
+    ("HRAM", 0xFF80, 0xFFFE),
+];
+
+/// Most differing ranges listed per region, the rest are only counted.
+const MAX_RANGES: usize = 8;
+
+/// A memory region, and the bytes in it that differ.
+struct RegionDiff {
+    name: &'static str,
+    bytes: usize,
+
+    /// Runs of differing bytes, first and last address.
+    ranges: Vec<(u16, u16)>,
+}
+
+/// State diff
+/// What differs between two machines, typically a good and a bad run loaded from save states:
+/// the counters, the CPU registers, every IO register, and a summary of the memory that changed.
+/// Both machines are read through GameBoy::inspect, so VRAM and OAM are compared whatever the PPU is doing.
+pub struct StateDiff {
+    counters: (Counters, Counters),
+
+    /// CPU registers and flags that differ, by name.
+    cpu: Vec<(&'static str, u16, u16)>,
+
+    /// IO registers ($FF00-$FF7F, and IE) that differ.
+    io: Vec<(u16, u8, u8)>,
+    regions: Vec<RegionDiff>,
+}
+
+impl StateDiff {
+    pub fn new(a: &GameBoy, b: &GameBoy) -> Self {
+        let (ca, cb) = (a.cpu_state(), b.cpu_state());
+        let cpu = [
+            ("AF", ca.af, cb.af),
+            ("BC", ca.bc, cb.bc),
+            ("DE", ca.de, cb.de),
+            ("HL", ca.hl, cb.hl),
+            ("SP", ca.sp, cb.sp),
+            ("PC", ca.pc, cb.pc),
+            ("IME", ca.ime as u16, cb.ime as u16),
+            ("HALT", ca.halted as u16, cb.halted as u16),
+        ]
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .collect();
+
+        let io = (0xFF00..=0xFF7F)
+            .chain([0xFFFF])
+            .map(|addr| (addr, a.inspect(addr), b.inspect(addr)))
+            .filter(|(_, a, b)| a != b)
+            .collect();
+
+        let regions = REGIONS
+            .iter()
+            .map(|&(name, start, end)| {
+                let mut region = RegionDiff {
+                    name,
+                    bytes: 0,
+                    ranges: Vec::new(),
+                };
+                for addr in start..=end {
+                    if a.inspect(addr) == b.inspect(addr) {
+                        continue;
+                    }
+                    region.bytes += 1;
+                    match region.ranges.last_mut() {
+                        Some((_, last)) if *last + 1 == addr => *last = addr,
+                        _ => region.ranges.push((addr, addr)),
+                    }
+                }
+                region
+            })
+            .filter(|region| region.bytes > 0)
+            .collect();
+
+        Self {
+            counters: (a.counters(), b.counters()),
+            cpu,
+            io,
+            regions,
+        }
+    }
+
+    /// Do the machines match? The counters aren't compared, two runs can reach the same state at different times.
+    pub fn is_empty(&self) -> bool {
+        self.cpu.is_empty() && self.io.is_empty() && self.regions.is_empty()
+    }
+}
+
+/// e.g.
+/// Counters
+///   frames     1200 -> 1260
+/// CPU
+///   PC         0150 -> 0153
+/// IO registers
+///   FF40 LCDC    91 -> 81
+/// Memory
+///   WRAM       12 bytes in 3 ranges: C010-C013 C080 C0F0-C0F6
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (a, b) = self.counters;
+        writeln!(f, "Counters")?;
+        for (name, a, b) in [
+            ("cycles", a.cycles, b.cycles),
+            ("frames", a.frames, b.frames),
+            ("rendered", a.rendered, b.rendered),
+        ] {
+            writeln!(f, "  {:<10} {} -> {}", name, a, b)?;
+        }
+
+        if !self.cpu.is_empty() {
+            writeln!(f, "CPU")?;
+            for (name, a, b) in &self.cpu {
+                writeln!(f, "  {:<10} {:04X} -> {:04X}", name, a, b)?;
+            }
+        }
+
+        if !self.io.is_empty() {
+            writeln!(f, "IO registers")?;
+            for (addr, a, b) in &self.io {
+                let name = register_name(*addr).unwrap_or("");
+                writeln!(f, "  {:04X} {:<5}  {:02X} -> {:02X}", addr, name, a, b)?;
+            }
+        }
+
+        if !self.regions.is_empty() {
+            writeln!(f, "Memory")?;
+            for region in &self.regions {
+                write!(
+                    f,
+                    "  {:<10} {} bytes in {} ranges:",
+                    region.name,
+                    region.bytes,
+                    region.ranges.len()
+                )?;
+                for &(first, last) in region.ranges.iter().take(MAX_RANGES) {
+                    if first == last {
+                        write!(f, " {:04X}", first)?;
+                    } else {
+                        write!(f, " {:04X}-{:04X}", first, last)?;
+                    }
+                }
+                if region.ranges.len() > MAX_RANGES {
+                    write!(f, " ...")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if self.is_empty() {
+            writeln!(f, "The machine states are identical.")?;
+        }
+        Ok(())
+    }
+}
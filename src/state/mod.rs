@@ -0,0 +1,253 @@
+use std::fmt;
+
+pub mod diff;
+pub mod thumbnail;
+pub mod version;
+
+pub use self::thumbnail::{Thumbnail, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH};
+pub use self::version::{migrate, SECTIONS};
+
+/// Save States
+/// A save state is a snapshot of the whole machine, CPU, memory, and every piece of hardware state.
+/// Each component writes its fields to a flat little-endian byte buffer, and reads them back in the
+/// same order when the state is loaded.
+///
+/// The buffer starts with a header, and each subsystem's fields are in a section of their own,
+/// with the subsystem's layout version, see the version module.
+pub trait Savestate {
+    /// Write the component's state.
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// Restore the component's state, in the order it was written by save_state.
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()>;
+}
+
+/// Errors that can occur while loading a save state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The save state ended before every component was restored.
+    Truncated,
+
+    /// The save state holds a value that doesn't fit the component being restored.
+    Invalid(&'static str),
+
+    /// The save state was made by another version of ferrum, whose layout of these subsystems
+    /// (e.g. "ppu v3, this ferrum has v2") can't be migrated.
+    Incompatible(Vec<String>),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "save state has an invalid {}", what),
+            StateError::Incompatible(sections) => write!(
+                f,
+                "save state is from an incompatible version of ferrum: {}",
+                sections.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+pub type Result<T> = std::result::Result<T, StateError>;
+
+/// Serializes component state into a save state buffer.
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    /// A save state with its header written.
+    pub fn new() -> Self {
+        let mut w = Self { data: Vec::new() };
+        w.bytes(&version::MAGIC);
+        w.u16(version::FORMAT_VERSION);
+        w
+    }
+
+    /// Write a subsystem's section, with the layout version from SECTIONS, and its length,
+    /// so it can be migrated (or skipped over) without knowing what's in it.
+    pub fn section(&mut self, name: &str, f: impl FnOnce(&mut Self)) {
+        let version = version::current(name).expect("Save state section missing from SECTIONS");
+        self.u8(name.len() as u8);
+        self.bytes(name.as_bytes());
+        self.u16(version);
+        let start = self.data.len();
+        self.u32(0);
+        f(self);
+        let len = (self.data.len() - start - 4) as u32;
+        self.data[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    pub fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    /// Write a fixed size block of bytes, the reader must know its length.
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.data.extend_from_slice(v);
+    }
+
+    /// Write a fixed size block of pixels, such as a frame buffer.
+    pub fn pixels(&mut self, v: &[u32]) {
+        for pixel in v {
+            self.u32(*pixel);
+        }
+    }
+
+    /// The finished save state.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Deserializes component state from a save state buffer.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+
+    /// Whether sections have a header, they don't in states from before save states were versioned.
+    framed: bool,
+}
+
+impl<'a> StateReader<'a> {
+    /// Read a save state brought up to date by migrate, starting after its header.
+    pub fn new(data: &'a [u8]) -> Self {
+        match version::has_header(data) {
+            true => Self {
+                data,
+                pos: version::HEADER_LEN,
+                framed: true,
+            },
+            false => Self {
+                data,
+                pos: 0,
+                framed: false,
+            },
+        }
+    }
+
+    /// Skip over an extra section, if it's next.
+    pub fn skip_section(&mut self, name: &str) -> Result<()> {
+        if !self.framed || self.remaining() == 0 {
+            return Ok(());
+        }
+        let header = version::read_section(self.data, self.pos)?;
+        if header.name == name.as_bytes() {
+            self.pos = header.end;
+        }
+        Ok(())
+    }
+
+    /// Read an extra section, if it's next, returns whether it was.
+    pub fn extra_section(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<bool> {
+        if !self.framed || self.remaining() == 0 {
+            return Ok(false);
+        }
+        if version::read_section(self.data, self.pos)?.name != name.as_bytes() {
+            return Ok(false);
+        }
+        self.section(name, f)?;
+        Ok(true)
+    }
+
+    /// Read a subsystem's section written by StateWriter::section, all of it.
+    pub fn section(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if !self.framed {
+            return f(self);
+        }
+        let header = version::read_section(self.data, self.pos)?;
+        if header.name != name.as_bytes() {
+            return Err(StateError::Invalid("section order"));
+        }
+        let mut section = Self {
+            data: &self.data[..header.end],
+            pos: header.start,
+            framed: true,
+        };
+        f(&mut section)?;
+        if section.remaining() != 0 {
+            return Err(StateError::Invalid("section length"));
+        }
+        self.pos = header.end;
+        Ok(())
+    }
+
+    /// Take the next n bytes of the save state.
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).ok_or(StateError::Truncated)?;
+        let chunk = self.data.get(self.pos..end).ok_or(StateError::Truncated)?;
+        self.pos = end;
+        Ok(chunk)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("boolean")),
+        }
+    }
+
+    /// Fill v with the next v.len() bytes.
+    pub fn bytes(&mut self, v: &mut [u8]) -> Result<()> {
+        v.copy_from_slice(self.take(v.len())?);
+        Ok(())
+    }
+
+    /// Fill v with the next v.len() pixels.
+    pub fn pixels(&mut self, v: &mut [u32]) -> Result<()> {
+        for pixel in v.iter_mut() {
+            *pixel = self.u32()?;
+        }
+        Ok(())
+    }
+
+    /// Number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+}
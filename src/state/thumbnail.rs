@@ -0,0 +1,47 @@
+use super::{version, Result, Savestate, StateReader, StateWriter};
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Size of a thumbnail, half the screen's.
+pub const THUMBNAIL_WIDTH: usize = SCREEN_WIDTH / 2;
+pub const THUMBNAIL_HEIGHT: usize = SCREEN_HEIGHT / 2;
+
+/// Thumbnail
+/// The frame on screen when a save state was made, at half size, so front-ends can show what's in a slot
+/// without loading it. Pixels are 0x00RRGGBB, row by row, in the PPU's shades, before any palette.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub pixels: Vec<u32>,
+}
+
+impl Thumbnail {
+    /// Scale a 160x144 frame down, keeping every other pixel of every other line,
+    /// rather than blending them, so the pixels stay in the PPU's shades and palettes still apply.
+    pub fn new(frame: &[u32]) -> Self {
+        let pixels = (0..THUMBNAIL_HEIGHT)
+            .flat_map(|y| (0..THUMBNAIL_WIDTH).map(move |x| frame[y * 2 * SCREEN_WIDTH + x * 2]))
+            .collect();
+        Self { pixels }
+    }
+
+    /// The thumbnail in a save state, None if it has none, e.g. it's from before thumbnails were saved.
+    pub fn read(state: &[u8]) -> Option<Self> {
+        let section = version::find_section(state, "thumbnail")
+            .filter(|section| Some(section.version) == version::current("thumbnail"))?;
+        let mut thumbnail = Self {
+            pixels: vec![0; THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT],
+        };
+        let mut r = StateReader::new(&state[section.start..section.end]);
+        thumbnail.load_state(&mut r).ok()?;
+        Some(thumbnail)
+    }
+}
+
+impl Savestate for Thumbnail {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.pixels(&self.pixels);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        r.pixels(&mut self.pixels)
+    }
+}
@@ -0,0 +1,193 @@
+use super::{Result, StateError};
+use std::borrow::Cow;
+
+/// Every save state starts with these bytes, then the format version.
+pub const MAGIC: [u8; 4] = *b"FRST";
+
+/// Version of the header and the section framing, as opposed to what's in the sections.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Length of the magic and the format version.
+pub(super) const HEADER_LEN: usize = 6;
+
+/// Save state sections, in the order they're written, and the layout version of each.
+/// Bump a section's version whenever what its subsystem saves changes, and add a migration from the previous
+/// layout to MIGRATIONS if the new fields can be given sensible values, so older states still load.
+pub const SECTIONS: [(&str, u16); 9] = [
+    ("counters", 1),
+    ("cpu", 1),
+    ("mmu", 1),
+    ("timer", 1),
+    ("joypad", 1),
+    ("serial", 1),
+    ("ppu", 2),
+    ("apu", 1),
+    ("cartridge", 1),
+];
+
+/// Sections that aren't part of the machine, and that a state may not have, e.g. from before they were added.
+/// They're written after the machine, and loading a state skips them.
+pub const EXTRAS: [(&str, u16); 2] = [("thumbnail", 1), ("accuracy", 1)];
+
+/// A step bringing a section from one layout version to the next.
+pub struct Migration {
+    pub section: &'static str,
+    pub from: u16,
+    pub migrate: fn(&[u8]) -> Result<Vec<u8>>,
+}
+
+/// Migrations from older layouts, e.g. a flag added at the end of the PPU's fields in v2, off at power on:
+///     Migration { section: "ppu", from: 1, migrate: |data| Ok([data, &[0]].concat()) }
+const MIGRATIONS: &[Migration] = &[
+    // v2 saves which field an interlaced frame renders, the even lines at power on.
+    Migration {
+        section: "ppu",
+        from: 1,
+        migrate: |data| Ok([data, &[0]].concat()),
+    },
+];
+
+/// The layout version of a section, None if there's no such section.
+pub(super) fn current(name: &str) -> Option<u16> {
+    SECTIONS
+        .iter()
+        .chain(&EXTRAS)
+        .find(|(section, _)| *section == name)
+        .map(|&(_, version)| version)
+}
+
+/// Whether a save state starts with a header, states from before save states were versioned don't.
+pub(super) fn has_header(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// A section's header, and where its data starts and ends in the save state.
+pub(super) struct SectionHeader<'a> {
+    pub name: &'a [u8],
+    pub version: u16,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The header of a section, None if the state doesn't have it, or isn't versioned.
+pub(super) fn find_section<'a>(data: &'a [u8], name: &str) -> Option<SectionHeader<'a>> {
+    if !has_header(data) {
+        return None;
+    }
+    let mut pos = HEADER_LEN;
+    while pos < data.len() {
+        let section = read_section(data, pos).ok()?;
+        if section.name == name.as_bytes() {
+            return Some(section);
+        }
+        pos = section.end;
+    }
+    None
+}
+
+/// Read the header of the section at pos.
+pub(super) fn read_section(data: &[u8], pos: usize) -> Result<SectionHeader<'_>> {
+    let take = |pos: usize, n: usize| data.get(pos..pos + n).ok_or(StateError::Truncated);
+    let name_len = take(pos, 1)?[0] as usize;
+    let name = take(pos + 1, name_len)?;
+    let version = take(pos + 1 + name_len, 2)?;
+    let len = take(pos + 3 + name_len, 4)?;
+    let start = pos + 7 + name_len;
+    let end = start + u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    if end > data.len() {
+        return Err(StateError::Truncated);
+    }
+    Ok(SectionHeader {
+        name,
+        version: u16::from_le_bytes([version[0], version[1]]),
+        start,
+        end,
+    })
+}
+
+/// Bring a save state made by any version of ferrum up to this one's layout, for StateReader.
+/// Sections in an older layout are migrated, anything that can't be (a newer layout, an older one without
+/// a migration, an unknown or missing section) is listed in a StateError::Incompatible.
+/// Extra sections aren't migrated, and are dropped from a state that was.
+/// States from before save states were versioned are taken as the first layout of every section.
+pub fn migrate(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if !has_header(data) {
+        let newer: Vec<String> = SECTIONS
+            .iter()
+            .filter(|&&(_, version)| version != 1)
+            .map(|(name, version)| format!("{} unversioned, this ferrum has v{}", name, version))
+            .collect();
+        return match newer.is_empty() {
+            true => Ok(Cow::Borrowed(data)),
+            false => Err(StateError::Incompatible(newer)),
+        };
+    }
+    let format = data.get(4..HEADER_LEN).ok_or(StateError::Truncated)?;
+    let format = u16::from_le_bytes([format[0], format[1]]);
+    if format != FORMAT_VERSION {
+        return Err(StateError::Incompatible(vec![format!(
+            "format v{}, this ferrum has v{}",
+            format, FORMAT_VERSION
+        )]));
+    }
+
+    let mut found = Vec::new();
+    let mut pos = HEADER_LEN;
+    while pos < data.len() {
+        let section = read_section(data, pos)?;
+        pos = section.end;
+        found.push(section);
+    }
+
+    let mut problems = Vec::new();
+    let mut migrated = false;
+    let mut sections = Vec::new();
+    for &(name, version) in &SECTIONS {
+        let Some(section) = found.iter().find(|section| section.name == name.as_bytes()) else {
+            problems.push(format!("{} missing", name));
+            continue;
+        };
+        let mut body = Cow::Borrowed(&data[section.start..section.end]);
+        let mut from = section.version;
+        while let Some(migration) = MIGRATIONS
+            .iter()
+            .find(|m| m.section == name && m.from == from && from < version)
+        {
+            body = Cow::Owned((migration.migrate)(&body)?);
+            from += 1;
+            migrated = true;
+        }
+        if from != version {
+            problems.push(format!(
+                "{} v{}, this ferrum has v{}",
+                name, section.version, version
+            ));
+            continue;
+        }
+        sections.push((name, version, body));
+    }
+    for section in &found {
+        let name = String::from_utf8_lossy(section.name);
+        if current(&name).is_some() {
+            continue;
+        }
+        problems.push(format!("unknown section {}", name));
+    }
+    if !problems.is_empty() {
+        return Err(StateError::Incompatible(problems));
+    }
+    if !migrated {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    // Put the state back together, with the migrated sections.
+    let mut out = data[..HEADER_LEN].to_vec();
+    for (name, version, body) in sections {
+        out.push(name.len() as u8);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+    }
+    Ok(Cow::Owned(out))
+}
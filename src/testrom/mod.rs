@@ -0,0 +1,291 @@
+pub use crate::cartridge::header::fix_checksums;
+use crate::cartridge::header::logo;
+
+/// Size of a ROM only cartridge.
+const ROM_SIZE: usize = 0x8000;
+
+/// Code starts right after the cartridge header.
+const CODE_START: u16 = 0x0150;
+
+/// Test ROMs
+/// A tiny assembler for building minimal ROM only cartridges, so CPU and MMU behaviors can be exercised without
+/// distributing copyrighted ROMs. The header (logo, checksums) is filled in so the ROM passes the boot ROM's checks,
+/// and the entry point jumps to the code, which is assembled from $0150.
+///
+/// Results are usually reported over the serial port, like blargg's test ROMs, or left in memory for the test to read.
+///
+/// ```
+/// # use ferrum::gb::GameBoy;
+/// # use ferrum::testrom::TestRom;
+/// let mut rom = TestRom::new("LD A");
+/// rom.ld_a(0x42);
+/// rom.ld_mem_a(0xC000);
+/// rom.print("ok");
+/// rom.end();
+/// let mut gb = GameBoy::from_rom(rom.build(), None);
+/// # gb.set_serial_output(false);
+/// gb.skip_boot();
+/// gb.run_frame();
+/// assert_eq!(gb.peek(0xC000), 0x42);
+/// ```
+pub struct TestRom {
+    rom: Vec<u8>,
+
+    /// Address the next instruction is assembled at.
+    pc: u16,
+}
+
+impl TestRom {
+    pub fn new(title: &str) -> Self {
+        let mut rom = vec![0x00; ROM_SIZE];
+
+        // Entry point: NOP, JP $0150
+        rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, CODE_START as u8, (CODE_START >> 8) as u8]);
+        rom[0x104..0x134].copy_from_slice(logo());
+        for (i, c) in title.bytes().take(15).enumerate() {
+            rom[0x134 + i] = c.to_ascii_uppercase();
+        }
+
+        Self {
+            rom,
+            pc: CODE_START,
+        }
+    }
+
+    /// Address the next instruction is assembled at, to use as a jump target.
+    pub fn here(&self) -> u16 {
+        self.pc
+    }
+
+    /// Continue assembling at addr, e.g. to place an interrupt handler at its vector.
+    pub fn org(&mut self, addr: u16) {
+        self.pc = addr;
+    }
+
+    /// Emit raw bytes, for instructions without a helper.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        let start = self.pc as usize;
+        self.rom[start..start + bytes.len()].copy_from_slice(bytes);
+        self.pc += bytes.len() as u16;
+    }
+
+    fn op16(&mut self, op: u8, nn: u16) {
+        self.bytes(&[op, nn as u8, (nn >> 8) as u8]);
+    }
+
+    pub fn nop(&mut self) {
+        self.bytes(&[0x00]);
+    }
+
+    pub fn di(&mut self) {
+        self.bytes(&[0xF3]);
+    }
+
+    pub fn ei(&mut self) {
+        self.bytes(&[0xFB]);
+    }
+
+    pub fn halt(&mut self) {
+        self.bytes(&[0x76]);
+    }
+
+    /// LD A, n
+    pub fn ld_a(&mut self, n: u8) {
+        self.bytes(&[0x3E, n]);
+    }
+
+    /// LD HL, nn
+    pub fn ld_hl(&mut self, nn: u16) {
+        self.op16(0x21, nn);
+    }
+
+    /// LD SP, nn
+    pub fn ld_sp(&mut self, nn: u16) {
+        self.op16(0x31, nn);
+    }
+
+    /// LD (nn), A
+    pub fn ld_mem_a(&mut self, nn: u16) {
+        self.op16(0xEA, nn);
+    }
+
+    /// LD A, (nn)
+    pub fn ld_a_mem(&mut self, nn: u16) {
+        self.op16(0xFA, nn);
+    }
+
+    /// LDH ($FF00 + n), A
+    pub fn ldh_write(&mut self, n: u8) {
+        self.bytes(&[0xE0, n]);
+    }
+
+    /// LDH A, ($FF00 + n)
+    pub fn ldh_read(&mut self, n: u8) {
+        self.bytes(&[0xF0, n]);
+    }
+
+    pub fn inc_a(&mut self) {
+        self.bytes(&[0x3C]);
+    }
+
+    pub fn dec_a(&mut self) {
+        self.bytes(&[0x3D]);
+    }
+
+    /// CP n
+    pub fn cp(&mut self, n: u8) {
+        self.bytes(&[0xFE, n]);
+    }
+
+    /// JP nn
+    pub fn jp(&mut self, nn: u16) {
+        self.op16(0xC3, nn);
+    }
+
+    /// JR to target, which has to be within -128..127 bytes of the next instruction.
+    pub fn jr(&mut self, target: u16) {
+        self.jr_op(0x18, target);
+    }
+
+    /// JR NZ to target, which has to be within -128..127 bytes of the next instruction.
+    pub fn jr_nz(&mut self, target: u16) {
+        self.jr_op(0x20, target);
+    }
+
+    /// JR Z to target, which has to be within -128..127 bytes of the next instruction.
+    pub fn jr_z(&mut self, target: u16) {
+        self.jr_op(0x28, target);
+    }
+
+    fn jr_op(&mut self, op: u8, target: u16) {
+        let offset = target as i32 - (self.pc as i32 + 2);
+        assert!(
+            (-128..=127).contains(&offset),
+            "JR target {:04X} is out of range",
+            target
+        );
+        self.bytes(&[op, offset as i8 as u8]);
+    }
+
+    /// CALL nn
+    pub fn call(&mut self, nn: u16) {
+        self.op16(0xCD, nn);
+    }
+
+    pub fn ret(&mut self) {
+        self.bytes(&[0xC9]);
+    }
+
+    pub fn reti(&mut self) {
+        self.bytes(&[0xD9]);
+    }
+
+    /// Send a byte over the serial port, with the internal clock.
+    /// ferrum prints serial output, like the test ROMs that report their results this way expect.
+    pub fn serial_out(&mut self, byte: u8) {
+        self.ld_a(byte);
+        self.ldh_write(0x01);
+        self.ld_a(0x81);
+        self.ldh_write(0x02);
+    }
+
+    /// Send a string over the serial port.
+    pub fn print(&mut self, text: &str) {
+        for byte in text.bytes() {
+            self.serial_out(byte);
+        }
+    }
+
+    /// Loop forever, the end of the test.
+    pub fn end(&mut self) {
+        let here = self.here();
+        self.jr(here);
+    }
+
+    /// The finished ROM image, with the header checksum and global checksum filled in.
+    pub fn build(&self) -> Vec<u8> {
+        let mut rom = self.rom.clone();
+        fix_checksums(&mut rom);
+        rom
+    }
+}
+
+/// Turn arbitrary bytes into a ROM image the boot ROM accepts, for fuzzing.
+/// The first bytes are the cartridge type, and pick a ROM size and RAM size, the rest is the ROM image,
+/// with the Nintendo logo and checksums written over its header.
+pub fn fuzz_rom(data: &[u8]) -> Vec<u8> {
+    const RAM_SIZES: [u8; 5] = [0x00, 0x02, 0x03, 0x04, 0x05];
+
+    let (params, image) = data.split_at(data.len().min(3));
+    let param = |i: usize| params.get(i).copied().unwrap_or(0) as usize;
+
+    let mut rom = image.to_vec();
+    if rom.len() < 0x150 {
+        rom.resize(0x150, 0x00);
+    }
+    rom[0x104..0x134].copy_from_slice(logo());
+    // Any type, the ones without a mapper load as ROM only.
+    rom[0x147] = param(0) as u8;
+    rom[0x148] = (param(1) % 9) as u8;
+    rom[0x149] = RAM_SIZES[param(2) % RAM_SIZES.len()];
+    fix_checksums(&mut rom);
+    rom
+}
+
+/// Demo cartridge
+/// A tiny homebrew ROM for examples and documentation, written for ferrum and free to use for anything.
+/// It prints "Hello from ferrum!" over the serial port, fills the background with 8 pixel wide stripes,
+/// then scrolls them left by a pixel every frame, forever.
+pub fn demo() -> Vec<u8> {
+    let mut rom = TestRom::new("FERRUM DEMO");
+    rom.di();
+    rom.print("Hello from ferrum!\n");
+
+    // Wait for V-Blank, and turn the LCD off to fill VRAM.
+    let vblank = rom.here();
+    rom.ldh_read(0x44);
+    rom.cp(144);
+    rom.jr_nz(vblank);
+    rom.ld_a(0x00);
+    rom.ldh_write(0x40);
+
+    // Tile 1 is solid black, tile 0 is left blank.
+    rom.ld_hl(0x8010);
+    rom.ld_a(0xFF);
+    for _ in 0..16 {
+        rom.bytes(&[0x22]); // LD (HL+), A
+    }
+
+    // Tiles 0 and 1 alternate across the whole map, 4 times 256 entries.
+    rom.ld_hl(0x9800);
+    rom.bytes(&[0x0E, 0x04]); // LD C, 4
+    let rows = rom.here();
+    rom.bytes(&[0x06, 0x00]); // LD B, 0
+    let entries = rom.here();
+    rom.bytes(&[0x7D, 0xE6, 0x01]); // LD A, L; AND 1
+    rom.bytes(&[0x22, 0x05]); // LD (HL+), A; DEC B
+    rom.jr_nz(entries);
+    rom.bytes(&[0x0D]); // DEC C
+    rom.jr_nz(rows);
+
+    // LCD on, tile data at $8000, background on.
+    rom.ld_a(0xE4);
+    rom.ldh_write(0x47);
+    rom.ld_a(0x91);
+    rom.ldh_write(0x40);
+
+    // Scroll a pixel at the start of every V-Blank.
+    let frame = rom.here();
+    rom.ldh_read(0x44);
+    rom.cp(144);
+    rom.jr_nz(frame);
+    rom.ldh_read(0x43);
+    rom.inc_a();
+    rom.ldh_write(0x43);
+    let vblank = rom.here();
+    rom.ldh_read(0x44);
+    rom.cp(144);
+    rom.jr_z(vblank);
+    rom.jr(frame);
+    rom.build()
+}
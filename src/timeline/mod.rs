@@ -0,0 +1,213 @@
+use log::warn;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Interrupt names, by IF/IE bit.
+const INTERRUPTS: [&str; 5] = ["VBlank", "STAT", "Timer", "Serial", "Joypad"];
+
+/// IO registers traced when no selection is given, the ones involved in interrupt timing.
+pub const DEFAULT_REGISTERS: [u16; 9] = [
+    0xFF04, 0xFF05, 0xFF06, 0xFF07, 0xFF0F, 0xFF40, 0xFF41, 0xFF45, 0xFFFF,
+];
+
+/// Name of an IO register, if it has one here.
+pub fn register_name(addr: u16) -> Option<&'static str> {
+    let name = match addr {
+        0xFF00 => "P1",
+        0xFF01 => "SB",
+        0xFF02 => "SC",
+        0xFF04 => "DIV",
+        0xFF05 => "TIMA",
+        0xFF06 => "TMA",
+        0xFF07 => "TAC",
+        0xFF0F => "IF",
+        0xFF40 => "LCDC",
+        0xFF41 => "STAT",
+        0xFF42 => "SCY",
+        0xFF43 => "SCX",
+        0xFF44 => "LY",
+        0xFF45 => "LYC",
+        0xFF46 => "DMA",
+        0xFF47 => "BGP",
+        0xFF48 => "OBP0",
+        0xFF49 => "OBP1",
+        0xFF4A => "WY",
+        0xFF4B => "WX",
+        0xFF50 => "BOOT",
+        0xFFFF => "IE",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Something that happened on the timeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// An interrupt was requested, its IF bit went from 0 to 1.
+    Request(u8),
+
+    /// The CPU dispatched an interrupt, jumping to its vector.
+    Dispatch(u8),
+
+    /// An IO register was written.
+    Write {
+        addr: u16,
+        value: u8,
+    },
+
+    /// The cartridge switched the ROM bank at $4000-$7FFF, or the RAM bank at $A000-$BFFF.
+    RomBank(u16),
+    RamBank(u8),
+
+    /// The LCD was turned on (true) or off.
+    Lcd(bool),
+
+    /// A serial transfer started, sending a byte, or finished, having received one.
+    SerialSend(u8),
+    SerialReceive(u8),
+
+    /// Cartridge RAM was written, what battery saves are made of.
+    RamWrite {
+        addr: u16,
+        value: u8,
+    },
+}
+
+/// Output format of the timeline.
+enum Format {
+    Csv,
+    Json,
+    JsonLines,
+}
+
+/// Interrupt and IO register timeline
+/// Interrupt requests and dispatches, and writes to selected IO registers, are streamed to a file as they happen,
+/// timestamped with the T-cycle and frame since power on (see gb::Counters). The file can be loaded into a
+/// timeline viewer to debug STAT and timer interactions over thousands of frames.
+/// Cartridge bank switches and RAM writes, the LCD turning on and off, and serial transfers are streamed too.
+///
+/// CSV:  cycle,frame,event,name,value
+///       70224,1,request,VBlank,
+///       70232,1,dispatch,VBlank,
+///       70300,2,write,STAT,0x40
+///       70310,2,bank,ROM,0x03
+///       70320,2,lcd,off,
+///       70330,2,serial,send,0x41
+///       70340,2,ram-write,A010,0x07
+/// JSON: an array of {"cycle": 70300, "frame": 2, "event": "write", "name": "STAT", "value": 64} objects.
+/// JSON lines: the same objects, one per line, so the file can be processed while it's written.
+pub struct Timeline {
+    out: BufWriter<File>,
+    format: Format,
+
+    /// IO registers whose writes are traced.
+    registers: Vec<u16>,
+
+    /// Events written so far.
+    events: u64,
+
+    /// Frame being emulated, stamped on events.
+    frame: u64,
+}
+
+impl Timeline {
+    /// Start a timeline in path, as JSON if the file name ends in .json, JSON lines if it ends in .jsonl,
+    /// otherwise as CSV.
+    pub fn create(path: &Path, registers: Vec<u16>) -> io::Result<Self> {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Format::Json,
+            Some("jsonl") => Format::JsonLines,
+            _ => Format::Csv,
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Csv => writeln!(out, "cycle,frame,event,name,value")?,
+            Format::Json => write!(out, "[")?,
+            Format::JsonLines => (),
+        }
+        Ok(Self {
+            out,
+            format,
+            registers,
+            events: 0,
+            frame: 0,
+        })
+    }
+
+    /// Is the IO register at addr traced?
+    pub fn traces(&self, addr: u16) -> bool {
+        self.registers.contains(&addr)
+    }
+
+    /// Set the frame stamped on the events that follow.
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    /// Add an event at the given T-cycle.
+    pub fn record(&mut self, cycle: u64, event: Event) {
+        let (kind, name, value) = match event {
+            Event::Request(bit) => ("request", INTERRUPTS[bit as usize].to_string(), None),
+            Event::Dispatch(bit) => ("dispatch", INTERRUPTS[bit as usize].to_string(), None),
+            Event::Write { addr, value } => (
+                "write",
+                register_name(addr).map_or_else(|| format!("{:04X}", addr), String::from),
+                Some(value as u16),
+            ),
+            Event::RomBank(bank) => ("bank", "ROM".to_string(), Some(bank)),
+            Event::RamBank(bank) => ("bank", "RAM".to_string(), Some(bank as u16)),
+            Event::Lcd(on) => ("lcd", if on { "on" } else { "off" }.to_string(), None),
+            Event::SerialSend(byte) => ("serial", "send".to_string(), Some(byte as u16)),
+            Event::SerialReceive(byte) => ("serial", "receive".to_string(), Some(byte as u16)),
+            Event::RamWrite { addr, value } => {
+                ("ram-write", format!("{:04X}", addr), Some(value as u16))
+            }
+        };
+
+        let result = match self.format {
+            Format::Csv => writeln!(
+                self.out,
+                "{},{},{},{},{}",
+                cycle,
+                self.frame,
+                kind,
+                name,
+                value.map(|v| format!("0x{:02X}", v)).unwrap_or_default()
+            ),
+            Format::Json => {
+                let sep = if self.events == 0 { "" } else { "," };
+                let value = value.map(|v| v.to_string()).unwrap_or("null".to_string());
+                write!(
+                    self.out,
+                    "{}\n  {{\"cycle\": {}, \"frame\": {}, \"event\": \"{}\", \"name\": \"{}\", \"value\": {}}}",
+                    sep, cycle, self.frame, kind, name, value
+                )
+            }
+            Format::JsonLines => {
+                let value = value.map(|v| v.to_string()).unwrap_or("null".to_string());
+                writeln!(
+                    self.out,
+                    "{{\"cycle\": {}, \"frame\": {}, \"event\": \"{}\", \"name\": \"{}\", \"value\": {}}}",
+                    cycle, self.frame, kind, name, value
+                )
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to write the timeline: {}", e);
+        }
+        self.events += 1;
+    }
+}
+
+impl Drop for Timeline {
+    /// Close the JSON array, and flush whatever is still buffered.
+    fn drop(&mut self) {
+        if let Format::Json = self.format {
+            let _ = write!(self.out, "\n]\n");
+        }
+        if let Err(e) = self.out.flush() {
+            warn!("Failed to write the timeline: {}", e);
+        }
+    }
+}
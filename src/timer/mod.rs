@@ -1,106 +1,219 @@
-pub mod clock;
-
 use std::{cell::RefCell, rc::Rc};
 
 use crate::cpu::interrupts::{Flags, InterruptFlags};
+use crate::state::{self, Savestate, StateReader, StateWriter};
+
+/// T-cycles between TIMA overflowing, and it being reloaded from TMA.
+const RELOAD_DELAY: u8 = 4;
+
+/// Timer and Divider
+/// https://gbdev.io/pandocs/Timer_and_Divider_Registers.html
+///
+/// DIV, and the clock of TIMA, both come from a single 16-bit counter, incremented every T-cycle.
+/// DIV ($FF04) is the upper byte of the counter, so it increments at 16384 Hz. Writing any value to it resets the
+/// whole counter to 0.
+///
+/// TIMA ($FF05) increments on the falling edge of a bit of the counter, selected by TAC ($FF07), ANDed with the
+/// timer enable bit:
+///     Bit  2   - Timer Enable
+///     Bits 1-0 - Input Clock Select
+///                00: bit 9 (4096 Hz), 01: bit 3 (262144 Hz), 10: bit 5 (65536 Hz), 11: bit 7 (16384 Hz)
+///
+/// Since it's an edge detector, anything that makes its input fall increments TIMA, not just the counter:
+///     * Writing DIV while the selected bit is 1.
+///     * Writing TAC, while the input is 1, to disable the timer, or to select a bit that is 0.
+///
+/// When TIMA overflows, it reads 00 for 4 T-cycles, then it's reloaded from TMA ($FF06), and the timer interrupt
+/// is requested. Writing TIMA during those 4 T-cycles cancels the reload, and the interrupt.
+/// https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html
+pub struct Timer {
+    if_: Rc<RefCell<InterruptFlags>>,
+
+    /// Internal divider counter, DIV is its upper byte.
+    counter: u16,
 
-use self::clock::Clock;
-
-#[derive(Default)]
-struct Register {
-    // This register is incremented at rate of 16384Hz (~16779Hz on SGB). Writing any value to this register resets it
-    // to 00h.
-    // Note: The divider is affected by CGB double speed mode, and will increment at 32768Hz in double speed.
-    div: u8,
-    // This timer is incremented by a clock frequency specified by the TAC register ($FF07). When the value overflows
-    // (gets bigger than FFh) then it will be reset to the value specified in TMA (FF06), and an interrupt will be
-    // requested, as described below.
     tima: u8,
-    // When the TIMA overflows, this data will be loaded.
     tma: u8,
-    //  Bit  2   - Timer Enable
-    //  Bits 1-0 - Input Clock Select
-    //             00: CPU Clock / 1024 (DMG, CGB:   4096 Hz, SGB:   ~4194 Hz)
-    //             01: CPU Clock / 16   (DMG, CGB: 262144 Hz, SGB: ~268400 Hz)
-    //             10: CPU Clock / 64   (DMG, CGB:  65536 Hz, SGB:  ~67110 Hz)
-    //             11: CPU Clock / 256  (DMG, CGB:  16384 Hz, SGB:  ~16780 Hz)
     tac: u8,
-}
 
-// Each time when the timer overflows (ie. when TIMA gets bigger than FFh), then an interrupt is requested by
-// setting Bit 2 in the IF Register (FF0F). When that interrupt is enabled, then the CPU will execute it by calling
-// the timer interrupt vector at 0050h.
-pub struct Timer {
-    if_: Rc<RefCell<InterruptFlags>>,
-    reg: Register,
-    div_clock: Clock,
-    tma_clock: Clock,
+    /// T-cycles until an overflowed TIMA is reloaded from TMA, 0 if it didn't overflow.
+    reload: u8,
 }
 
 impl Timer {
     pub fn new(if_: Rc<RefCell<InterruptFlags>>) -> Self {
         Timer {
             if_,
-            reg: Register::default(),
-            div_clock: Clock::new(256),
-            tma_clock: Clock::new(1024),
+            counter: 0x0000,
+            tima: 0x00,
+            tma: 0x00,
+            tac: 0x00,
+            reload: 0,
+        }
+    }
+
+    /// Set the internal 16-bit divider counter, DIV is its upper byte.
+    pub fn set_div_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
+    /// Input of TIMA's falling edge detector, the counter bit selected by TAC, if the timer is enabled.
+    fn input(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0x00 => 9,
+            0x01 => 3,
+            0x02 => 5,
+            _ => 7,
+        };
+        self.tac & 0x04 != 0 && self.counter & (1 << bit) != 0
+    }
+
+    /// Change the counter, or TAC, through set, incrementing TIMA if its input falls.
+    fn update(&mut self, set: impl FnOnce(&mut Self)) {
+        let before = self.input();
+        set(self);
+        if before && !self.input() {
+            self.tima = self.tima.wrapping_add(1);
+            if self.tima == 0x00 {
+                self.reload = RELOAD_DELAY;
+            }
         }
     }
 
     pub fn get(&self, a: u16) -> u8 {
         match a {
-            0xff04 => self.reg.div,
-            0xff05 => self.reg.tima,
-            0xff06 => self.reg.tma,
-            0xff07 => self.reg.tac,
+            0xff04 => (self.counter >> 8) as u8,
+            0xff05 => self.tima,
+            0xff06 => self.tma,
+            0xff07 => self.tac,
             _ => panic!("Unsupported address"),
         }
     }
 
     pub fn set(&mut self, a: u16, v: u8) {
         match a {
-            0xff04 => {
-                self.reg.div = 0x00;
-                self.div_clock.n = 0x00;
-            }
-            0xff05 => self.reg.tima = v,
-            0xff06 => self.reg.tma = v,
-            0xff07 => {
-                if (self.reg.tac & 0x03) != (v & 0x03) {
-                    self.tma_clock.n = 0x00;
-                    self.tma_clock.period = match v & 0x03 {
-                        0x00 => 1024,
-                        0x01 => 16,
-                        0x02 => 64,
-                        0x03 => 256,
-                        _ => panic!(""),
-                    };
-                    self.reg.tima = self.reg.tma;
-                }
-                self.reg.tac = v;
+            0xff04 => self.update(|timer| timer.counter = 0x0000),
+            0xff05 => {
+                self.tima = v;
+                self.reload = 0;
             }
+            0xff06 => self.tma = v,
+            0xff07 => self.update(|timer| timer.tac = v),
             _ => panic!("Unsupported address"),
         }
     }
 
     pub fn cycle(&mut self, cycles: u32) {
-        // Increment div at rate of 16384Hz. Because the clock cycles is 4194304, so div increment every 256 cycles (4194304/256).
-        self.reg.div = self
-            .reg
-            .div
-            .wrapping_add(self.div_clock.cycle(cycles) as u8);
-
-        // Timer Enabled?
-        if (self.reg.tac & 0x04) != 0x00 {
-            // Increment tima at rate of Clock / freq
-            let n = self.tma_clock.cycle(cycles);
-            for _ in 0..n {
-                self.reg.tima = self.reg.tima.wrapping_add(1);
-                if self.reg.tima == 0x00 {
-                    self.reg.tima = self.reg.tma;
+        for _ in 0..cycles {
+            if self.reload > 0 {
+                self.reload -= 1;
+                if self.reload == 0 {
+                    self.tima = self.tma;
                     self.if_.borrow_mut().set(Flags::Timer);
                 }
             }
+            self.update(|timer| timer.counter = timer.counter.wrapping_add(1));
+        }
+    }
+}
+
+impl Savestate for Timer {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.counter);
+        w.u8(self.tima);
+        w.u8(self.tma);
+        w.u8(self.tac);
+        w.u8(self.reload);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> state::Result<()> {
+        self.counter = r.u16()?;
+        self.tima = r.u8()?;
+        self.tma = r.u8()?;
+        self.tac = r.u8()?;
+        self.reload = match r.u8()? {
+            reload @ 0..=RELOAD_DELAY => reload,
+            _ => return Err(state::StateError::Invalid("timer reload")),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMA: u16 = 0xFF05;
+    const TMA: u16 = 0xFF06;
+    const TAC: u16 = 0xFF07;
+
+    /// Timer interrupt requested.
+    fn requested(if_: &Rc<RefCell<InterruptFlags>>) -> bool {
+        if_.borrow().data & (1 << Flags::Timer as u8) != 0
+    }
+
+    /// A timer counting every 16 T-cycles (bit 3), with TIMA at $FF and TMA at $80, so it overflows in 16.
+    fn overflowing() -> (Timer, Rc<RefCell<InterruptFlags>>) {
+        let if_ = Rc::new(RefCell::new(InterruptFlags::new()));
+        let mut timer = Timer::new(if_.clone());
+        timer.set(TAC, 0x05);
+        timer.set(TIMA, 0xFF);
+        timer.set(TMA, 0x80);
+        (timer, if_)
+    }
+
+    /// An overflowed TIMA reads $00 for 4 T-cycles, then it's reloaded from TMA, and the interrupt requested.
+    #[test]
+    fn reload_delay() {
+        let (mut timer, if_) = overflowing();
+        timer.cycle(16);
+        assert_eq!((timer.get(TIMA), requested(&if_)), (0x00, false));
+        timer.cycle(3);
+        assert_eq!((timer.get(TIMA), requested(&if_)), (0x00, false));
+        timer.cycle(1);
+        assert_eq!((timer.get(TIMA), requested(&if_)), (0x80, true));
+    }
+
+    /// Writing TIMA during the delay cancels the reload, and the interrupt. TMA written then is what's reloaded.
+    #[test]
+    fn write_during_reload() {
+        let (mut timer, if_) = overflowing();
+        timer.cycle(17);
+        timer.set(TIMA, 0x42);
+        timer.cycle(3);
+        assert_eq!((timer.get(TIMA), requested(&if_)), (0x42, false));
+
+        let (mut timer, if_) = overflowing();
+        timer.cycle(17);
+        timer.set(TMA, 0x99);
+        timer.cycle(3);
+        assert_eq!((timer.get(TIMA), requested(&if_)), (0x99, true));
+    }
+
+    /// Resetting DIV, or disabling the timer, while the selected counter bit is 1 makes TIMA's input fall,
+    /// which increments it. While the bit is 0, nothing falls.
+    #[test]
+    fn falling_edges() {
+        for (counter, write, expected) in [
+            (0x0008, (0xFF04, 0x00), 0x01),
+            (0x0007, (0xFF04, 0x00), 0x00),
+            (0x0008, (TAC, 0x01), 0x01),
+            (0x0008, (TAC, 0x06), 0x01),
+            (0x0028, (TAC, 0x06), 0x00),
+        ] {
+            let if_ = Rc::new(RefCell::new(InterruptFlags::new()));
+            let mut timer = Timer::new(if_);
+            timer.set(TAC, 0x05);
+            timer.set_div_counter(counter);
+            timer.set(write.0, write.1);
+            assert_eq!(
+                timer.get(TIMA),
+                expected,
+                "counter ${:04X}, wrote ${:02X} to ${:04X}",
+                counter,
+                write.1,
+                write.0
+            );
         }
     }
 }
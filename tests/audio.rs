@@ -0,0 +1,200 @@
+//! The sound the APU makes, through the mixer and what the host does with it.
+//! https://gbdev.io/pandocs/Audio_Registers.html
+
+mod common;
+
+use common::{lcd_off, round_trip};
+use ferrum::audio::{self, BufferStats, CaptureSink, DEFAULT_SAMPLE_RATE};
+use ferrum::gb::{FastForwardAudio, FastForwardSpeed, GameBoy};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Play a 512 Hz square wave on pulse channel 2 for frames, after setup, and return the samples.
+fn pulse(gb: &mut GameBoy, frames: u32) -> Vec<i16> {
+    gb.poke(0xFF16, 0x80);
+    gb.poke(0xFF17, 0xF0);
+    gb.poke(0xFF18, 0x00);
+    gb.poke(0xFF19, 0x87);
+
+    let capture = Rc::new(RefCell::new(CaptureSink::new(DEFAULT_SAMPLE_RATE)));
+    gb.set_audio_sink(Box::new(capture.clone()));
+    for _ in 0..frames {
+        gb.run_frame();
+    }
+    gb.take_audio_sink();
+    let samples = std::mem::take(&mut capture.borrow_mut().samples);
+    samples
+}
+
+/// Play pulse channel 2 with the given NR50 and NR51 for 10 frames,
+/// and return the loudest sample on the left and right over the last 5, once the high-pass filter settled.
+fn play(gb: &mut GameBoy, nr50: u8, nr51: u8) -> (i32, i32) {
+    gb.poke(0xFF24, nr50);
+    gb.poke(0xFF25, nr51);
+    let samples = pulse(gb, 10);
+    let settled = &samples[(samples.len() / 2) & !1..];
+    let peak = |side: usize| {
+        settled
+            .iter()
+            .skip(side)
+            .step_by(2)
+            .map(|&sample| (sample as i32).abs())
+            .max()
+            .unwrap_or(0)
+    };
+    (peak(0), peak(1))
+}
+
+/// Sound registers NR50 and NR51 read back in full, VIN bits included, and route the channels to each side.
+/// https://gbdev.io/pandocs/Audio_Registers.html#global-control-registers
+#[test]
+fn stereo() {
+    // The boot ROM leaves the APU on.
+    let mut gb = lcd_off();
+    round_trip(&mut gb, 0xFF24, 0xFF, 0x00);
+    round_trip(&mut gb, 0xFF25, 0xFF, 0x00);
+
+    // Nothing is routed to the other side, so it's silent.
+    let (left, right) = play(&mut gb, 0x77, 0x20);
+    assert!(
+        left > 1000 && right == 0,
+        "panned left, peaks were {} left and {} right",
+        left,
+        right
+    );
+    let (left, right) = play(&mut gb, 0x77, 0x02);
+    assert!(
+        right > 1000 && left == 0,
+        "panned right, peaks were {} left and {} right",
+        left,
+        right
+    );
+
+    // Volume 7 is 8/8, volume 0 is 1/8.
+    let (left, right) = play(&mut gb, 0x70, 0x22);
+    assert!(
+        right > 0 && (7..=9).contains(&(left / right)),
+        "left at 8/8 peaked at {}, right at 1/8 at {}",
+        left,
+        right
+    );
+}
+
+/// Rate control leaves a buffer at its target alone, and speeds up (or slows down) by up to 0.5% as it empties
+/// (or fills up).
+#[test]
+fn rate_control() {
+    let cases = [
+        (2400, 1.0),
+        (0, 1.005),
+        (1200, 1.0025),
+        (4800, 0.995),
+        (9600, 0.995),
+    ];
+    for (queued, expected) in cases {
+        let stats = BufferStats {
+            queued,
+            target: 2400,
+            ..Default::default()
+        };
+        let nudge = audio::rate_nudge(&stats);
+        assert!(
+            (nudge - expected).abs() < 1e-9,
+            "{} frames queued of 2400 nudge by {}, not {}",
+            queued,
+            nudge,
+            expected
+        );
+    }
+}
+
+/// The samples of a second of a square wave on pulse channel 2, fast-forwarding at 4x with the given sound, if any.
+/// The pacer would run the frames 4 times as fast, the mixer doesn't know the difference.
+fn fast_forward_samples(audio: Option<FastForwardAudio>) -> Vec<i16> {
+    let mut gb = lcd_off();
+    gb.set_fast_forward(FastForwardSpeed::X4, audio.unwrap_or_default());
+    gb.set_fast_forwarding(audio.is_some());
+    gb.poke(0xFF24, 0x77);
+    gb.poke(0xFF25, 0x22);
+    pulse(&mut gb, 60)
+}
+
+fn loud(samples: &[i16]) -> bool {
+    samples.iter().any(|&sample| sample.abs() > 1000)
+}
+
+/// Fast-forwarding with the pitch kept plays a quarter of the sound at 4x, in chunks.
+#[test]
+fn fast_forward_keeping_pitch() {
+    let normal = fast_forward_samples(None);
+    let pitch = fast_forward_samples(Some(FastForwardAudio::Pitch));
+    // Chunks are 1/20 s, so the last one kept can be cut short.
+    let chunk = DEFAULT_SAMPLE_RATE as usize / 20 * 2;
+    assert!(
+        pitch.len().abs_diff(normal.len() / 4) <= chunk,
+        "kept {} of {} samples, not about a quarter",
+        pitch.len(),
+        normal.len()
+    );
+    assert!(loud(&pitch), "the kept chunks are silent");
+}
+
+/// Fast-forwarding muted plays silence, as long as the sound would have been.
+#[test]
+fn fast_forward_muted() {
+    let normal = fast_forward_samples(None);
+    let muted = fast_forward_samples(Some(FastForwardAudio::Mute));
+    assert_eq!(muted.len(), normal.len());
+    assert!(!loud(&muted), "the sound played");
+}
+
+/// Fill wave RAM with $00, $11, ... $FF, and start the wave channel with a period of 11 bits.
+fn play_wave(gb: &mut GameBoy, period: u16) {
+    for i in 0..16 {
+        gb.poke(0xFF30 + i, i as u8 * 0x11);
+    }
+    gb.poke(0xFF1A, 0x80);
+    gb.poke(0xFF1C, 0x00);
+    gb.poke(0xFF1D, period as u8);
+    gb.poke(0xFF1E, 0x80 | (period >> 8) as u8);
+}
+
+/// The wave RAM written while the channel played, read back once it's stopped.
+fn stopped_wave(gb: &mut GameBoy) -> Vec<u8> {
+    gb.poke(0xFF1A, 0x00);
+    (0xFF30..=0xFF3F).map(|addr| gb.peek(addr)).collect()
+}
+
+/// While the wave channel plays, the DMG's CPU only reaches wave RAM as the channel reads a byte,
+/// and then reaches that byte. https://gbdev.io/pandocs/Audio_details.html#obscure-behavior
+/// Slow, the channel reads a byte every 4096 T-cycles, so it holds the bus.
+#[test]
+fn wave_ram_locked_while_playing() {
+    let mut gb = lcd_off();
+    play_wave(&mut gb, 0x000);
+    gb.step();
+    let read = gb.peek(0xFF35);
+    gb.poke(0xFF35, 0x77);
+    assert_eq!(read, 0xFF);
+    assert_eq!(stopped_wave(&mut gb)[5], 0x55, "the write went through");
+}
+
+/// Fast, the channel reads a byte every 2 T-cycles, so the CPU always gets through, to the byte being read.
+#[test]
+fn wave_ram_redirected_while_playing() {
+    let mut gb = lcd_off();
+    play_wave(&mut gb, 0x7FF);
+    gb.step();
+    gb.poke(0xFF3F, 0xF7);
+    let written: Vec<usize> = stopped_wave(&mut gb)
+        .iter()
+        .enumerate()
+        .filter(|&(i, &byte)| byte != i as u8 * 0x11)
+        .map(|(i, _)| i)
+        .collect();
+    assert!(
+        matches!(written[..], [i] if i != 15),
+        "wrote $FF3F while playing, bytes {:?} of wave RAM changed, instead of the one being read",
+        written
+    );
+}
@@ -0,0 +1,372 @@
+//! Mappers, their save data and save states, and what the debugger shows of them.
+
+mod common;
+
+use common::{banked_rom, banked_rom_with_ram};
+use ferrum::cartridge::banks::BankOverride;
+use ferrum::cartridge::rtc::{RtcTime, FOOTER_SIZE};
+use ferrum::cartridge::Mapper;
+use ferrum::gb::GameBoy;
+use ferrum::testrom::fix_checksums;
+
+/// Select ROM bank 3 through MBC1's bank register, and check it's mapped at $4000.
+fn switch_bank(gb: &mut GameBoy) {
+    gb.poke(0x2000, 0x03);
+    assert_eq!(gb.peek(0x4000), 0x03, "selected ROM bank 3");
+}
+
+/// A ROM only cartridge too large for the ROM only space is taken as MBC1.
+#[test]
+fn mapper_detection() {
+    switch_bank(&mut GameBoy::from_rom(banked_rom(0x00), None));
+}
+
+/// A forced mapper maps the whole image, whatever the ROM size in the header.
+#[test]
+fn forced_mapper() {
+    switch_bank(&mut GameBoy::from_rom_as(
+        banked_rom(0x01),
+        None,
+        Mapper::Mbc1,
+    ));
+}
+
+/// An MBC1 cartridge with 8 KiB of battery backed RAM.
+fn battery_rom() -> Vec<u8> {
+    let mut rom = banked_rom(0x03);
+    rom[0x149] = 0x02;
+    fix_checksums(&mut rom);
+    rom
+}
+
+fn ram_image() -> Vec<u8> {
+    (0..0x2000).map(|i| (i * 7) as u8).collect()
+}
+
+/// A RAM image loaded into the cartridge is what the CPU reads, and is saved back the same.
+#[test]
+fn ram_load() {
+    let ram = ram_image();
+    let mut gb = GameBoy::from_rom(battery_rom(), None);
+    gb.load_ram(&ram);
+    gb.poke(0x0000, 0x0A);
+    assert_eq!(gb.peek(0xA123), ram[0x123]);
+    assert!(
+        gb.battery_save() == Some(ram),
+        "the saved RAM isn't the loaded RAM"
+    );
+}
+
+/// Saves from other emulators, with an RTC footer, or of the wrong size, load fitted to the cartridge's RAM.
+#[test]
+fn save_fitting() {
+    let ram = ram_image();
+    for len in [0x2000 + 48, 0x4000, 0x1000] {
+        let mut save = ram.clone();
+        save.resize(len, 0xFF);
+        let mut expected = ram[..len.min(0x2000)].to_vec();
+        expected.resize(0x2000, 0x00);
+        let saved = GameBoy::from_rom(battery_rom(), Some(save)).battery_save();
+        assert!(
+            saved.as_ref() == Some(&expected),
+            "a {} byte save saved back as {:?} bytes",
+            len,
+            saved.map(|ram| ram.len())
+        );
+    }
+}
+
+/// Set the clock of an MBC3 with a timer to time, halted or not, save it, and load the save into a fresh machine
+/// as if the game had been off for the given seconds. Returns the size of the save and the clock after loading.
+fn rtc_reloaded(rom: &[u8], time: RtcTime, halt: bool, off: u64) -> (usize, Option<RtcTime>) {
+    let mut gb = GameBoy::from_rom(rom.to_vec(), None);
+    gb.set_rtc_time(time);
+    if halt {
+        gb.poke(0x0000, 0x0A);
+        gb.poke(0x4000, 0x0C);
+        gb.poke(0xA000, 0x40);
+    }
+    let mut save = gb.battery_save().expect("no save");
+    let stamp = save.len() - 8;
+    let saved = u64::from_le_bytes(save[stamp..].try_into().unwrap());
+    save[stamp..].copy_from_slice(&(saved - off).to_le_bytes());
+    (
+        save.len(),
+        GameBoy::from_rom(rom.to_vec(), Some(save)).rtc_time(),
+    )
+}
+
+/// The clock is saved in the RTC footer after the RAM, with or without RAM, and runs on while the game is off,
+/// whole days included, unless it's halted.
+#[test]
+fn rtc_save() {
+    let time = RtcTime {
+        days: 3,
+        hours: 10,
+        minutes: 20,
+        seconds: 30,
+    };
+    let later = RtcTime {
+        days: 5,
+        hours: 10,
+        minutes: 22,
+        seconds: 0,
+    };
+    let mut no_ram = banked_rom(0x0F);
+    no_ram[0x149] = 0x00;
+    fix_checksums(&mut no_ram);
+    for (rom, ram) in [(banked_rom_with_ram(0x10), 0x8000), (no_ram, 0)] {
+        let (len, loaded) = rtc_reloaded(&rom, time, false, 2 * 24 * 60 * 60 + 90);
+        assert_eq!(len, ram + FOOTER_SIZE, "saved the wrong size");
+        // A second may pass between saving and loading.
+        let loaded = loaded.expect("no clock");
+        assert!(
+            loaded == later
+                || loaded
+                    == RtcTime {
+                        seconds: 1,
+                        ..later
+                    },
+            "the clock read {} after two days and 90 seconds off",
+            loaded
+        );
+        let (_, halted) = rtc_reloaded(&rom, time, true, 24 * 60 * 60);
+        assert_eq!(halted, Some(time), "the halted clock ran");
+    }
+}
+
+/// An MMM01 cartridge with 8 ROM banks, each ending with its number, the menu is in the last two.
+/// Boot the menu, map the game in banks 2-3, and check the game can't bank switch out of them.
+#[test]
+fn mmm01_multi_game() {
+    let mut rom = banked_rom(0x0B);
+    rom[0x148] = 0x02;
+    for bank in 0..8 {
+        rom[bank * 0x4000 + 0x3FFF] = bank as u8;
+    }
+    fix_checksums(&mut rom);
+    let header = rom[0x100..0x150].to_vec();
+    rom[0x18100..0x18150].copy_from_slice(&header);
+    let mut gb = GameBoy::from_rom(rom, None);
+
+    let banks = |gb: &GameBoy| (gb.peek(0x3FFF), gb.peek(0x7FFF));
+    assert_eq!(banks(&gb), (0x06, 0x07), "unmapped");
+    gb.poke(0x2000, 0x02);
+    gb.poke(0x6000, 0x3C);
+    gb.poke(0x0000, 0x40);
+    assert_eq!(banks(&gb), (0x02, 0x03), "mapped the game at bank 2");
+    gb.poke(0x2000, 0x05);
+    assert_eq!(banks(&gb), (0x02, 0x03), "selected bank 5 from the game");
+}
+
+/// MBC5 maps ROM bank 0 at $4000, which MBC1 and MBC3 can't, and on a rumble cartridge bit 3 of the RAM bank
+/// runs the motor instead of selecting a bank, for as much of a frame as the game leaves it on.
+#[test]
+fn mbc5_rumble() {
+    let mut plain = GameBoy::from_rom(banked_rom_with_ram(0x1B), None);
+    plain.run_frame();
+    assert_eq!(plain.rumble(), None, "an MBC5 without a motor rumbles");
+
+    let mut gb = GameBoy::from_rom(banked_rom_with_ram(0x1D), None);
+    gb.poke(0x2000, 0x00);
+    assert_eq!(gb.peek(0x7FFF), 0x00, "selected ROM bank 0");
+    let mut strengths = Vec::new();
+    for bank in [0x01, 0x09, 0x01] {
+        gb.poke(0x4000, bank);
+        gb.run_frame();
+        strengths.push(gb.rumble());
+    }
+    assert_eq!(
+        strengths,
+        [Some(0.0), Some(1.0), Some(0.0)],
+        "the motor with it off, on, off"
+    );
+    assert_eq!(gb.banks().ram, Some(1), "the motor bit selected a RAM bank");
+}
+
+/// Override MBC1's ROM bank and RAM enable, then clear the override, and check the mapper's own selection is back.
+#[test]
+fn bank_override() {
+    let mut rom = banked_rom(0x03);
+    rom[0x148] = 0x02;
+    rom[0x149] = 0x02;
+    fix_checksums(&mut rom);
+    let mut gb = GameBoy::from_rom(rom, None);
+    gb.poke(0x2000, 0x03);
+    gb.set_bank_override(BankOverride {
+        rom: Some(5),
+        ram_enabled: Some(true),
+        ..Default::default()
+    });
+    gb.poke(0xA000, 0x42);
+    assert_eq!(
+        (gb.peek(0x4000), gb.banks().rom, gb.peek(0xA000)),
+        (0x05, 5, 0x42),
+        "overridden"
+    );
+    gb.set_bank_override(BankOverride::default());
+    assert_eq!((gb.peek(0x4000), gb.peek(0xA000)), (0x03, 0xFF), "cleared");
+}
+
+/// Map an MBC1 cartridge with bank 3 and RAM switched in, and check the map covers the address space,
+/// and shows the boot ROM, the bank, and RAM enabled.
+#[test]
+fn memory_map() {
+    let mut rom = banked_rom(0x03);
+    rom[0x148] = 0x02;
+    rom[0x149] = 0x02;
+    fix_checksums(&mut rom);
+    let mut gb = GameBoy::from_rom(rom, None);
+    let detail = |gb: &GameBoy, name: &str| {
+        gb.memory_map()
+            .entries
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.detail)
+            .unwrap_or_default()
+    };
+    assert!(
+        detail(&gb, "Boot ROM").starts_with("built-in"),
+        "at power on, the boot ROM isn't mapped"
+    );
+    gb.skip_boot();
+    gb.poke(0x2000, 0x03);
+    gb.poke(0x0000, 0x0A);
+
+    let mut next = 0x0000;
+    for entry in gb.memory_map().entries {
+        assert_eq!(entry.start as u32, next, "{} starts elsewhere", entry.name);
+        next = entry.end as u32 + 1;
+    }
+    assert_eq!(next, 0x10000, "the map doesn't end at $FFFF");
+    assert_eq!(detail(&gb, "ROM X"), "ROM bank 03 of 08");
+    assert_eq!(detail(&gb, "Cart RAM"), "RAM bank 00 of 01, enabled");
+}
+
+/// The ROM info of an MBC1 cartridge with battery backed RAM, and of the same ROM once its header is corrupted.
+#[test]
+fn rom_info() {
+    let mut rom = battery_rom();
+    let info = ferrum::rom_info(&rom).expect("no ROM info");
+    assert_eq!(
+        (
+            info.title.as_str(),
+            info.mapper,
+            info.battery,
+            info.rom_size,
+            info.ram_size,
+            info.header_checksum_valid && info.global_checksum_valid,
+        ),
+        (
+            "TEST",
+            Some(Mapper::Mbc1),
+            true,
+            Some(0x8000),
+            Some(0x2000),
+            true
+        ),
+        "decoded {:?}",
+        info
+    );
+    rom[0x134] = b't';
+    let corrupted = ferrum::rom_info(&rom).expect("no ROM info");
+    assert!(
+        !corrupted.header_checksum_valid && !corrupted.global_checksum_valid,
+        "a changed title still passes the checksums"
+    );
+    assert!(
+        ferrum::rom_info(&rom[..0x100]).is_none(),
+        "decoded a header from a ROM too short to hold one"
+    );
+}
+
+/// Set up the mapper with configure, save a state, and check that loading it, over the state at power on,
+/// gives back the same banks, reads the same bytes from ROM and RAM, and finishes the same RTC latch.
+fn mapper_state(rom: Vec<u8>, configure: impl Fn(&mut GameBoy)) {
+    let observe = |gb: &mut GameBoy| {
+        let banks = gb.banks();
+        let reads = [gb.peek(0x3FFF), gb.peek(0x7FFF), gb.peek(0xA000)];
+        // $01 latches the clock if the state had the latch armed.
+        gb.poke(0x6000, 0x01);
+        (banks, reads, gb.peek(0xA000))
+    };
+    let mut gb = GameBoy::from_rom(rom, None);
+    let power_on = gb.save_state();
+    configure(&mut gb);
+    let state = gb.save_state();
+    let expected = observe(&mut gb);
+    gb.load_state(&power_on).unwrap();
+    gb.load_state(&state).unwrap();
+    assert_eq!(observe(&mut gb), expected);
+}
+
+/// Save states restore every mapper's registers: banks, RAM enable, banking mode, the RTC and its latch.
+#[test]
+fn rom_only_save_state() {
+    let mut rom = banked_rom_with_ram(0x00);
+    rom.truncate(0x8000);
+    rom[0x148] = 0x00;
+    rom[0x149] = 0x00;
+    fix_checksums(&mut rom);
+    mapper_state(rom, |_| ());
+}
+
+#[test]
+fn mbc1_save_state() {
+    mapper_state(banked_rom_with_ram(0x03), |gb| {
+        gb.poke(0x0000, 0x0A);
+        gb.poke(0x6000, 0x01);
+        for bank in 0..4 {
+            gb.poke(0x4000, bank);
+            gb.poke(0xA000, 0x10 + bank);
+        }
+        gb.poke(0x2000, 0x05);
+        gb.poke(0x4000, 0x02);
+    });
+}
+
+#[test]
+fn mbc3_save_state() {
+    mapper_state(banked_rom_with_ram(0x10), |gb| {
+        gb.poke(0x0000, 0x0A);
+        for bank in 0..4 {
+            gb.poke(0x4000, bank);
+            gb.poke(0xA000, 0x30 + bank);
+        }
+        // Latch 42 seconds, set the clock to 21, and arm the latch again.
+        gb.poke(0x4000, 0x08);
+        gb.poke(0xA000, 0x2A);
+        gb.poke(0x6000, 0x00);
+        gb.poke(0x6000, 0x01);
+        gb.poke(0xA000, 0x15);
+        gb.poke(0x6000, 0x00);
+        gb.poke(0x2000, 0x06);
+    });
+}
+
+#[test]
+fn mbc5_save_state() {
+    mapper_state(banked_rom_with_ram(0x1E), |gb| {
+        gb.poke(0x0000, 0x0A);
+        for bank in 0..4 {
+            gb.poke(0x4000, bank);
+            gb.poke(0xA000, 0x50 + bank);
+        }
+        gb.poke(0x2000, 0x00);
+        gb.poke(0x4000, 0x0A);
+    });
+}
+
+#[test]
+fn mmm01_save_state() {
+    let mut rom = banked_rom_with_ram(0x0D);
+    let header = rom[0x100..0x150].to_vec();
+    rom[0x18100..0x18150].copy_from_slice(&header);
+    mapper_state(rom, |gb| {
+        gb.poke(0x2000, 0x02);
+        gb.poke(0x6000, 0x3C);
+        gb.poke(0x0000, 0x4A);
+        gb.poke(0x4000, 0x01);
+        gb.poke(0xA000, 0x5A);
+    });
+}
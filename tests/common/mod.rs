@@ -0,0 +1,104 @@
+//! Machines and ROMs shared by the tests, built with TestRom, so they need no test ROM files.
+#![allow(dead_code)]
+
+use ferrum::gb::GameBoy;
+use ferrum::ppu::LcdOffPolicy;
+use ferrum::testrom::{fix_checksums, TestRom};
+
+/// Most frames to run before the test ROM's code is done, the boot ROM scrolls the logo first.
+pub const MAX_FRAMES: u32 = 1000;
+
+/// Written to $C000-$C001 by the test ROM once it's done.
+pub const DONE: [u8; 2] = *b"OK";
+
+/// Write DONE, for run_until_done to see.
+pub fn done(rom: &mut TestRom) {
+    for (i, byte) in DONE.into_iter().enumerate() {
+        rom.ld_a(byte);
+        rom.ld_mem_a(0xC000 + i as u16);
+    }
+}
+
+/// Run frames until the test ROM wrote DONE, false if it never did.
+pub fn run_until_done(gb: &mut GameBoy) -> bool {
+    for _ in 0..MAX_FRAMES {
+        gb.run_frame();
+        if [gb.peek(0xC000), gb.peek(0xC001)] == DONE {
+            return true;
+        }
+    }
+    false
+}
+
+/// A Gameboy that ran the boot ROM, then turned the LCD off and stopped, so the PPU sits still.
+pub fn lcd_off() -> GameBoy {
+    lcd_off_as(LcdOffPolicy::default())
+}
+
+/// lcd_off, showing what the policy says while the LCD is off.
+pub fn lcd_off_as(policy: LcdOffPolicy) -> GameBoy {
+    let mut rom = TestRom::new("TEST");
+    rom.di();
+    rom.ld_a(0x00);
+    rom.ldh_write(0x40);
+    done(&mut rom);
+    rom.end();
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.set_lcd_off_policy(policy);
+    assert!(run_until_done(&mut gb), "the test ROM never finished");
+    // One more frame, for the PPU to notice the LCD is off.
+    gb.run_frame();
+    assert_eq!(
+        gb.peek(0xFF40),
+        0x00,
+        "the test ROM didn't turn the LCD off"
+    );
+    gb
+}
+
+/// Write every value to a register, and check that it reads back through mask,
+/// the bits outside mask read back as they were.
+pub fn round_trip(gb: &mut GameBoy, addr: u16, mask: u8, fixed: u8) {
+    for val in 0..=0xFF {
+        let before = gb.peek(addr);
+        gb.poke(addr, val);
+        let expected = (val & mask) | (before & !mask & !fixed) | fixed;
+        assert_eq!(
+            gb.peek(addr),
+            expected,
+            "${:04X}: wrote ${:02X}, read back the wrong value",
+            addr,
+            val
+        );
+    }
+}
+
+/// A 128 KiB ROM image with the given cartridge type in its header, but a 32 KiB ROM size,
+/// each bank starting with its number.
+pub fn banked_rom(cart_type: u8) -> Vec<u8> {
+    let mut rom = TestRom::new("TEST");
+    rom.end();
+    let mut rom = rom.build();
+    rom.resize(0x20000, 0x00);
+    for bank in 1..8 {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    rom[0x147] = cart_type;
+    rom[0x148] = 0x00;
+    fix_checksums(&mut rom);
+    rom
+}
+
+/// A 128 KiB ROM of a cartridge type, with 32 KiB of RAM, each bank ending with its number.
+pub fn banked_rom_with_ram(cart_type: u8) -> Vec<u8> {
+    let mut rom = banked_rom(cart_type);
+    rom[0x148] = 0x02;
+    rom[0x149] = 0x03;
+    for bank in 0..8 {
+        rom[bank * 0x4000 + 0x3FFF] = bank as u8;
+    }
+    fix_checksums(&mut rom);
+    rom
+}
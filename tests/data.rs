@@ -0,0 +1,84 @@
+//! Files kept in the data directory: the save journal, and the time played.
+
+use ferrum::data::{format_playtime, Playtime, PlaytimeClock, SaveJournal, PAGE_SIZE};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A file in the temporary directory, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("ferrum-test-{}-{}", std::process::id(), name)))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Run the journal frame by frame, with RAM written on the first, until it saves, returns the frame and the pages.
+fn settle(journal: &mut SaveJournal, ram: &[u8], writing: bool) -> (u32, usize) {
+    for frame in 0..1000 {
+        match journal.frame(frame == 0 || writing, ram).unwrap() {
+            0 => (),
+            pages => return (frame, pages),
+        }
+    }
+    panic!("never saved");
+}
+
+/// The save journal waits for the game to stop writing, then writes the pages it changed, and only them.
+#[test]
+fn save_journal() {
+    let file = TempFile::new("journal.sav");
+    let mut ram = vec![0u8; 0x2000];
+    let mut journal = SaveJournal::new(file.0.clone(), &ram);
+    // There's no save file yet, it's written whole.
+    ram[0x10] = 0x01;
+    assert_eq!(settle(&mut journal, &ram, false), (30, 16), "first save");
+
+    // Mark a page on disk, then change another in RAM, the marked one isn't written over.
+    let mut saved = std::fs::read(&file.0).unwrap();
+    saved[5 * PAGE_SIZE] = 0xEE;
+    std::fs::write(&file.0, &saved).unwrap();
+    ram[3 * PAGE_SIZE] = 0x02;
+    assert_eq!(
+        settle(&mut journal, &ram, false).1,
+        1,
+        "saved more than the page that changed"
+    );
+    let saved = std::fs::read(&file.0).unwrap();
+    assert_eq!((saved[3 * PAGE_SIZE], saved[5 * PAGE_SIZE]), (0x02, 0xEE));
+
+    // A game that never stops writing is saved every 10 seconds.
+    ram[0] = 0x03;
+    assert_eq!(settle(&mut journal, &ram, true), (600, 1));
+}
+
+/// Play 40 seconds at 2x counted as real time, then as emulated time, and check the playtime is written each time
+/// a minute builds up, and reads back.
+#[test]
+fn playtime() {
+    let file = TempFile::new("playtime.toml");
+    let second = Duration::from_secs(1);
+    let mut playtime = Playtime::load(file.0.clone(), PlaytimeClock::Wall).unwrap();
+    for _ in 0..40 {
+        assert!(
+            !playtime.add(second, 2 * second).unwrap(),
+            "written before a minute was played"
+        );
+    }
+    playtime.set_clock(PlaytimeClock::Emulated);
+    let mut written = 0;
+    for _ in 0..40 {
+        written += playtime.add(second, 2 * second).unwrap() as u32;
+    }
+    assert_eq!(playtime.total(), Duration::from_secs(120));
+    assert_eq!(written, 2);
+    playtime.save().unwrap();
+    let reloaded = Playtime::load(file.0.clone(), PlaytimeClock::Wall).unwrap();
+    assert_eq!(format_playtime(reloaded.total()), "2m");
+}
@@ -0,0 +1,91 @@
+//! What the debugger watches for: VRAM watchpoints, and the game stalling.
+
+mod common;
+
+use common::lcd_off;
+use ferrum::gb::{GameBoy, Stall};
+use ferrum::ppu::watchpoint::{WatchHit, Watchpoint};
+use ferrum::testrom::TestRom;
+
+/// Run a ROM turning the LCD off, so VRAM is free, then writing $FF to addr, with a watchpoint,
+/// and return the hit that stopped it, and where the write is in the ROM, None if nothing did within 10 frames.
+fn watch_write(addr: u16, watchpoint: Watchpoint) -> Option<(WatchHit, u16)> {
+    let mut rom = TestRom::new("TEST");
+    rom.di();
+    rom.ld_a(0x00);
+    rom.ldh_write(0x40);
+    rom.ld_a(0xFF);
+    let write = rom.here();
+    rom.ld_mem_a(addr);
+    rom.end();
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.skip_boot();
+    gb.add_watchpoint(watchpoint);
+    (0..10).find_map(|_| {
+        gb.run_frame();
+        gb.watch_hit().map(|hit| (hit, write))
+    })
+}
+
+/// A tile watchpoint stops right after the instruction writing the watched tile.
+#[test]
+fn tile_watchpoint() {
+    let (hit, write) = watch_write(0x8FF5, "tile8800:$FF".parse().unwrap())
+        .expect("the watchpoint never triggered");
+    assert_eq!((hit.addr, hit.val, hit.pc.addr), (0x8FF5, 0xFF, write));
+}
+
+/// A map cell watchpoint stops on a write to that cell, and only to it.
+#[test]
+fn map_cell_watchpoint() {
+    let cell = "map1:3,4".parse().unwrap();
+    assert!(
+        watch_write(0x9C83, cell).is_some(),
+        "the watchpoint never triggered"
+    );
+    assert!(
+        watch_write(0x9C84, cell).is_none(),
+        "$9C84 triggered {}",
+        cell
+    );
+}
+
+/// Run frames until a stall is reported, returning it and the frames it took, None if none is within frames.
+fn run_to_stall(gb: &mut GameBoy, frames: u32) -> Option<(Stall, u32)> {
+    (1..=frames).find_map(|frame| {
+        gb.run_frame();
+        gb.stall().map(|report| (report.stall, frame))
+    })
+}
+
+/// The LCD left off is a stall after 5 seconds, reported once.
+#[test]
+fn lcd_off_stall() {
+    let mut gb = lcd_off();
+    let (stall, frame) = run_to_stall(&mut gb, 400).expect("not reported");
+    assert!(
+        stall == Stall::LcdOff(300),
+        "{} after {} frames",
+        stall,
+        frame
+    );
+    assert!(run_to_stall(&mut gb, 100).is_none(), "reported again");
+}
+
+/// A HALT with no interrupt enabled is a stall right away.
+#[test]
+fn halt_stall() {
+    let mut rom = TestRom::new("TEST");
+    rom.di();
+    rom.ld_a(0x00);
+    rom.ldh_write(0xFF);
+    rom.halt();
+    rom.end();
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.skip_boot();
+    let (stall, _) = run_to_stall(&mut gb, 2).expect("not reported");
+    assert!(stall == Stall::Halted, "{} instead of a HALT", stall);
+}
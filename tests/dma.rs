@@ -0,0 +1,78 @@
+//! OAM DMA, run from HRAM the way games do.
+
+mod common;
+
+use common::{done, run_until_done};
+use ferrum::bus::DmaBusPolicy;
+use ferrum::gb::GameBoy;
+use ferrum::testrom::TestRom;
+
+/// Where the DMA test ROM keeps its OAM image, and where its DMA routine stores the byte it read during the transfer.
+const DMA_SOURCE: u16 = 0x4000;
+const DMA_READ: u16 = 0xFFF0;
+
+/// The OAM image, no byte is $00 like the WRAM byte read during the transfer.
+fn dma_pattern() -> Vec<u8> {
+    (0..0xA0).map(|i| 0x10 + i as u8).collect()
+}
+
+/// Run a DMA routine from HRAM: it starts the transfer, reads $C000 (holding $00) partway through,
+/// and waits out the rest of the transfer before returning.
+fn run_dma(policy: DmaBusPolicy) -> GameBoy {
+    let mut rom = TestRom::new("TEST");
+    let start = rom.here();
+    rom.org(DMA_SOURCE);
+    rom.bytes(&dma_pattern());
+    rom.org(start);
+
+    rom.di();
+    rom.ld_a(0x00);
+    rom.ld_mem_a(0xC000);
+    let routine = [
+        &[0xE0, 0x46][..],                     // LDH (DMA), A
+        &[0x3E, 0x08, 0x3D, 0x20, 0xFD],       // LD A, 8; wait
+        &[0xFA, 0x00, 0xC0],                   // LD A, ($C000)
+        &[0xE0, DMA_READ as u8],               // LDH (DMA_READ), A
+        &[0x3E, 0x28, 0x3D, 0x20, 0xFD, 0xC9], // LD A, 40; wait; RET
+    ]
+    .concat();
+    for (i, byte) in routine.into_iter().enumerate() {
+        rom.ld_a(byte);
+        rom.ldh_write(0x80 + i as u8);
+    }
+    rom.ld_a((DMA_SOURCE >> 8) as u8);
+    rom.call(0xFF80);
+    done(&mut rom);
+    rom.end();
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.set_dma_bus_policy(policy);
+    assert!(run_until_done(&mut gb), "the DMA routine didn't return");
+    gb
+}
+
+/// OAM DMA copies the source to OAM, while a routine in HRAM keeps running.
+#[test]
+fn oam_dma_from_hram() {
+    let gb = run_dma(DmaBusPolicy::Restricted);
+    let oam: Vec<u8> = (0xFE00..0xFEA0).map(|addr| gb.inspect(addr)).collect();
+    assert_eq!(oam, dma_pattern());
+}
+
+/// Reads from WRAM during the transfer see the byte being copied.
+#[test]
+fn dma_bus_restricted() {
+    let read = run_dma(DmaBusPolicy::Restricted).peek(DMA_READ);
+    assert!(
+        dma_pattern().contains(&read),
+        "read ${:02X}, not a byte being copied",
+        read
+    );
+}
+
+/// Unless the bus is left free.
+#[test]
+fn dma_bus_free() {
+    assert_eq!(run_dma(DmaBusPolicy::Free).peek(DMA_READ), 0x00);
+}
@@ -0,0 +1,148 @@
+//! Running the emulator as a library: frame by frame, to screenshots, in race mode, and many at once.
+
+mod common;
+
+use common::MAX_FRAMES;
+use ferrum::gb::GameBoy;
+use ferrum::shots::{self, Shot};
+use ferrum::testrom::{self, TestRom};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+
+/// Serial output captured in memory.
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A machine running a test ROM printing text over the serial port.
+fn printing(text: &str) -> GameBoy {
+    let mut rom = TestRom::new("TEST");
+    rom.print(text);
+    rom.end();
+    GameBoy::from_rom(rom.build(), None)
+}
+
+/// Run a test ROM printing text over the serial port, returning what it printed.
+fn print_rom(text: &str) -> String {
+    let mut gb = printing(text);
+    let capture = Capture::default();
+    gb.set_serial_writer(Box::new(capture.clone()));
+    for _ in 0..MAX_FRAMES {
+        gb.run_frame();
+        if capture.0.borrow().len() >= text.len() {
+            break;
+        }
+    }
+    let output = capture.0.borrow();
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// The frame iterator yields what the machine printed over the serial port, frame by frame.
+#[test]
+fn frame_iterator() {
+    let text = "frame by frame";
+    let mut gb = printing(text);
+    gb.set_serial_output(false);
+    let mut serial = Vec::new();
+    for (i, frame) in gb.frames().take(MAX_FRAMES as usize).enumerate() {
+        assert_eq!(frame.counters.frames, i as u64 + 1, "frame counted wrong");
+        serial.extend(frame.serial);
+        if serial.len() >= text.len() {
+            break;
+        }
+    }
+    assert_eq!(String::from_utf8_lossy(&serial), text);
+}
+
+/// Take the shots of a script from the demo cartridge, twice, and check both runs wrote the same PNGs.
+#[test]
+fn screenshot_script() {
+    let dir = std::env::temp_dir().join(format!("ferrum-test-{}-shots", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("shots.txt");
+    std::fs::write(
+        &script,
+        "# The logo, then the demo\n100 logo.png\n\n400 demo.png\n",
+    )
+    .unwrap();
+    let listed = shots::load_script(&script).unwrap();
+    let read: Vec<_> = listed
+        .iter()
+        .map(|shot| (shot.frame, shot.path.clone()))
+        .collect();
+    assert_eq!(
+        read,
+        [(100, dir.join("logo.png")), (400, dir.join("demo.png"))]
+    );
+
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let mut gb = GameBoy::from_rom(testrom::demo(), None);
+        gb.set_serial_output(false);
+        shots::capture(&mut gb, &listed).unwrap();
+        let pngs: Vec<_> = listed
+            .iter()
+            .map(|shot| std::fs::read(&shot.path).unwrap())
+            .collect();
+        runs.push(pngs);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(runs[0] == runs[1], "two runs took different screenshots");
+    assert!(runs[0][0] != runs[0][1], "frames 100 and 400 look the same");
+    assert!(Shot::parse("0:title.png").is_err(), "took frame 0");
+}
+
+/// Race mode resets to the anchor with the timer cleared, and counts the attempts.
+#[test]
+fn race_mode() {
+    let mut gb = GameBoy::from_rom(testrom::demo(), None);
+    gb.set_serial_output(false);
+    gb.skip_boot();
+    gb.run_frame();
+    let anchor = gb.save_state();
+    gb.set_race_anchor(anchor.clone()).unwrap();
+    for _ in 0..2 {
+        for _ in 0..60 {
+            gb.run_frame();
+        }
+        gb.race_reset().unwrap();
+    }
+    assert!(
+        gb.save_state() == anchor,
+        "the reset machine isn't the anchor"
+    );
+    for _ in 0..30 {
+        gb.run_frame();
+    }
+    let race = gb.race().expect("race mode is off");
+    assert_eq!(
+        (race.attempts(), race.elapsed(gb.counters()).frames),
+        (3, 30)
+    );
+}
+
+/// Two machines running different ROMs at once, on their own threads, don't see each other's output.
+/// The core keeps no global state, so a host can run as many machines as it likes.
+#[test]
+fn parallel_instances() {
+    let texts = ["first machine", "second machine"];
+    let threads: Vec<_> = texts
+        .iter()
+        .map(|&text| thread::spawn(move || print_rom(text)))
+        .collect();
+    for (thread, text) in threads.into_iter().zip(texts) {
+        assert_eq!(thread.join().unwrap(), text);
+    }
+}
@@ -0,0 +1,29 @@
+//! Hotkeys and key bindings.
+
+use ferrum::frontend::Hotkey;
+use ferrum::input::{self, Binding, Chord, HotkeyMap, Modifiers};
+
+/// Rebind pause to a chord from a key bindings file, and check it only triggers with its modifiers,
+/// and that a key bound twice, or to a Joypad button as well, is reported.
+#[test]
+fn hotkey_chords() {
+    let parse_key = |name: &str| name.chars().next().filter(|_| name.len() == 1);
+    let mut hotkeys = HotkeyMap::new();
+    hotkeys.bind(Chord::key('p'), Hotkey::Pause);
+    hotkeys.bind(Chord::key('m'), Hotkey::Mute);
+    let bindings =
+        input::parse_bindings("pause = \"Ctrl+Shift+p\"\nmute = \"x\"\nscale = \"x\"\n").unwrap();
+    for (name, binding) in bindings {
+        let Binding::Hotkey(hotkey) = binding else {
+            panic!("{} isn't bound to a hotkey", name);
+        };
+        hotkeys.rebind(Chord::parse(&name, parse_key).unwrap(), hotkey);
+    }
+
+    let chorded = Modifiers::CTRL | Modifiers::SHIFT;
+    assert_eq!(hotkeys.resolve(&['p'], Modifiers::empty()), []);
+    assert_eq!(hotkeys.resolve(&['p'], Modifiers::CTRL), []);
+    assert_eq!(hotkeys.resolve(&['p'], chorded), [Hotkey::Pause]);
+    // X bound to mute and scale, and each to a Joypad button.
+    assert_eq!(hotkeys.conflicts(&['x']).len(), 3);
+}
@@ -0,0 +1,158 @@
+//! IO registers: what they read back, the serial port, and what the debugger sees of them.
+//! https://gbdev.io/pandocs/Hardware_Reg_List.html
+
+mod common;
+
+use common::{done, lcd_off, round_trip, run_until_done};
+use ferrum::gb::{GameBoy, IoSupport};
+use ferrum::serial::{Clock, SerialDevice};
+use ferrum::testrom::TestRom;
+
+#[test]
+fn stat_mode_with_lcd_off() {
+    let gb = lcd_off();
+    assert_eq!(gb.peek(0xFF41) & 0x03, 0, "STAT doesn't read mode 0");
+}
+
+/// PPU registers the CPU can read and write in full.
+#[test]
+fn ppu_registers() {
+    let mut gb = lcd_off();
+    // SCY, SCX, LYC, DMA, BGP, OBP0, OBP1, WY, WX.
+    for addr in [
+        0xFF42, 0xFF43, 0xFF45, 0xFF46, 0xFF47, 0xFF48, 0xFF49, 0xFF4A, 0xFF4B,
+    ] {
+        round_trip(&mut gb, addr, 0xFF, 0x00);
+    }
+}
+
+/// STAT.7 always reads 1, the coincidence flag and mode (STAT.2-0) are read-only.
+#[test]
+fn stat() {
+    round_trip(&mut lcd_off(), 0xFF41, 0x78, 0x80);
+}
+
+/// LY is read-only, and stays at 0 while the LCD is off.
+#[test]
+fn ly() {
+    round_trip(&mut lcd_off(), 0xFF44, 0x00, 0x00);
+}
+
+/// Writing LCDC turns the LCD on and off.
+#[test]
+fn lcdc() {
+    round_trip(&mut lcd_off(), 0xFF40, 0xFF, 0x00);
+}
+
+/// IO addresses read back as on DMG after writing $00: unused bits as 1, and CGB registers,
+/// undocumented ones, and unmapped addresses as $FF.
+#[test]
+fn io_read_back() {
+    let mut gb = lcd_off();
+    let expected = [
+        (0xFF03, 0xFF),
+        (0xFF07, 0xF8),
+        (0xFF0F, 0xE0),
+        (0xFF4D, 0xFF),
+        (0xFF4F, 0xFF),
+        (0xFF55, 0xFF),
+        (0xFF6C, 0xFF),
+        (0xFF70, 0xFF),
+        (0xFF72, 0xFF),
+        (0xFF7F, 0xFF),
+    ];
+    for (addr, expected) in expected {
+        gb.poke(addr, 0x00);
+        assert_eq!(gb.peek(addr), expected, "${:04X} reads back wrong", addr);
+    }
+}
+
+/// Answers every bit with its complement, through the bit-level half of SerialDevice.
+struct Inverter;
+
+impl SerialDevice for Inverter {
+    fn exchange_bit(&mut self, bit: bool, _clock: Clock) -> bool {
+        !bit
+    }
+}
+
+/// Send a byte with the internal clock, and check what came back, and that the transfer finished.
+fn transfer(gb: &mut GameBoy, byte: u8, expected: u8) {
+    gb.poke(0xFF0F, 0x00);
+    gb.poke(0xFF01, byte);
+    gb.poke(0xFF02, 0x81);
+    gb.run_frame();
+    assert_eq!(
+        gb.peek(0xFF02) & 0x80,
+        0,
+        "the transfer didn't finish within a frame"
+    );
+    assert_ne!(
+        gb.peek(0xFF0F) & 0x08,
+        0,
+        "the serial interrupt wasn't requested"
+    );
+    assert_eq!(gb.peek(0xFF01), expected, "sent ${:02X}", byte);
+}
+
+/// Serial port registers, and transfers with nothing, or a custom device, plugged in.
+/// https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+#[test]
+fn serial() {
+    let mut gb = lcd_off();
+    round_trip(&mut gb, 0xFF01, 0xFF, 0x00);
+
+    // Only the transfer enable and clock select bits (SC.7, SC.0) are there, the rest read 1.
+    round_trip(&mut gb, 0xFF02, 0x81, 0x7E);
+
+    // Nothing plugged in, the line reads 1.
+    transfer(&mut gb, 0x5A, 0xFF);
+
+    gb.set_serial_device(Box::new(Inverter));
+    transfer(&mut gb, 0x5A, 0xA5);
+}
+
+/// An IO snapshot lists the registers written since the last one, and only them.
+#[test]
+fn io_snapshot() {
+    let mut gb = lcd_off();
+    let before = gb.io_snapshot();
+    gb.poke(0xFF42, before.get(0xFF42) ^ 0xFF);
+    gb.poke(0xFF47, before.get(0xFF47) ^ 0xFF);
+    assert_eq!(gb.io_snapshot().changed(&before), [0xFF42, 0xFF47]);
+}
+
+/// IO registers the game uses that aren't fully emulated are listed once each, the debugger's peeks and pokes aren't.
+#[test]
+fn unsupported_io() {
+    let mut rom = TestRom::new("TEST");
+    for _ in 0..2 {
+        rom.ldh_read(0x4D);
+        rom.ld_a(0x00);
+        rom.ldh_write(0x56);
+        rom.ldh_write(0x41);
+    }
+    done(&mut rom);
+    rom.end();
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    assert!(run_until_done(&mut gb), "the test ROM never finished");
+    gb.peek(0xFF70);
+    gb.poke(0xFF6C, 0x00);
+    assert_eq!(
+        gb.unsupported_io(),
+        [
+            (
+                0xFF41,
+                IoSupport::Partial("the spurious STAT interrupt on writes"),
+            ),
+            (0xFF4D, IoSupport::Unimplemented("KEY1")),
+            (0xFF56, IoSupport::Unimplemented("RP")),
+        ]
+    );
+    assert_eq!(
+        gb.unsupported_io_summary().first().map(String::as_str),
+        Some("This game used: FF4D (KEY1), FF56 (RP) — unimplemented")
+    );
+}
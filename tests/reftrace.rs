@@ -0,0 +1,69 @@
+//! Comparing ferrum against a reference trace, line by line.
+
+use ferrum::gb::GameBoy;
+use ferrum::reftrace::{self, Outcome, TraceLine};
+use ferrum::testrom::TestRom;
+
+/// Instructions in the reference trace, and the line given a wrong A.
+const TRACE_LINES: usize = 200;
+const TRACE_WRONG: usize = 150;
+
+/// A machine at the cartridge's entry point, with LY stubbed, running a ROM that waits for LY=$90, then counts in A.
+fn trace_machine() -> GameBoy {
+    let mut rom = TestRom::new("TEST");
+    let wait = rom.here();
+    rom.ldh_read(0x44);
+    rom.cp(0x90);
+    rom.jr_nz(wait);
+    let count = rom.here();
+    rom.inc_a();
+    rom.jr(count);
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.skip_boot();
+    gb.set_ly_stub(Some(0x90));
+    gb
+}
+
+/// A trace of ferrum itself.
+fn own_trace() -> Vec<String> {
+    let mut gb = trace_machine();
+    let mut lines = Vec::new();
+    for _ in 0..TRACE_LINES {
+        lines.push(TraceLine::capture(&gb).to_string());
+        gb.step();
+    }
+    lines
+}
+
+fn compare(lines: &[String]) -> Outcome {
+    reftrace::compare(&mut trace_machine(), lines.join("\n").as_bytes()).unwrap()
+}
+
+/// A trace of ferrum itself matches itself, line by line.
+#[test]
+fn matches_itself() {
+    match compare(&own_trace()) {
+        Outcome::Matched(TRACE_LINES) => (),
+        Outcome::Matched(n) => panic!("matched {} of {} lines", n, TRACE_LINES),
+        Outcome::Diverged(d) => panic!("diverged at line {}", d.line),
+        Outcome::Malformed { line, error } => panic!("line {}: {}", line, error),
+    }
+}
+
+/// One wrong register stops the comparison on its line.
+#[test]
+fn divergence() {
+    let mut lines = own_trace();
+    let mut wrong: TraceLine = lines[TRACE_WRONG - 1].parse().unwrap();
+    wrong.a ^= 0xFF;
+    lines[TRACE_WRONG - 1] = wrong.to_string();
+    match compare(&lines) {
+        Outcome::Diverged(d) => {
+            assert_eq!(d.line, TRACE_WRONG);
+            assert_eq!(d.fields, ["A"]);
+        }
+        _ => panic!("didn't diverge at line {}", TRACE_WRONG),
+    }
+}
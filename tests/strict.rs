@@ -0,0 +1,119 @@
+//! Strict mode, stopping on what real hardware wouldn't do as the game expects, and the trace leading up to it.
+
+mod common;
+
+use common::MAX_FRAMES;
+use ferrum::fault::{Fault, FaultPolicy};
+use ferrum::gb::GameBoy;
+use ferrum::testrom::TestRom;
+
+/// Run code until it faults in strict mode, returning the fault and the machine.
+fn run_to_fault(code: impl Fn(&mut TestRom)) -> (Fault, GameBoy) {
+    let mut rom = TestRom::new("TEST");
+    code(&mut rom);
+    rom.end();
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.set_fault_policy(FaultPolicy::Exit);
+    gb.set_stack_check(true);
+    for _ in 0..MAX_FRAMES {
+        gb.run_frame();
+        if let Some(fault) = gb.fault() {
+            return (fault, gb);
+        }
+    }
+    panic!("didn't stop on a fault");
+}
+
+/// Run code that faults, and check emulation stops on that fault, and nothing before it.
+fn fault(code: impl Fn(&mut TestRom), expected: Fault) {
+    assert_eq!(run_to_fault(code).0, expected);
+}
+
+#[test]
+fn illegal_opcode() {
+    fault(
+        |rom| rom.bytes(&[0xD3]),
+        Fault::IllegalOpcode {
+            pc: 0x0150,
+            opcode: 0xD3,
+        },
+    );
+}
+
+#[test]
+fn prohibited_write() {
+    fault(
+        |rom| {
+            rom.ld_a(0x00);
+            rom.ld_mem_a(0xFEA0);
+        },
+        Fault::ProhibitedWrite(0xFEA0),
+    );
+}
+
+#[test]
+fn dma_outside_hram() {
+    fault(
+        |rom| {
+            rom.ld_a(0xC0);
+            rom.ldh_write(0x46);
+        },
+        Fault::DmaConflict(0x0154),
+    );
+}
+
+#[test]
+fn unknown_io() {
+    fault(|rom| rom.ldh_read(0x03), Fault::UnknownIoRead(0xFF03));
+}
+
+#[test]
+fn sp_in_rom() {
+    fault(
+        |rom| rom.ld_sp(0x4000),
+        Fault::StackPointer {
+            pc: 0x0150,
+            sp: 0x4000,
+        },
+    );
+}
+
+#[test]
+fn push_over_ie() {
+    fault(
+        |rom| {
+            rom.ld_sp(0x0000);
+            // PUSH BC
+            rom.bytes(&[0xC5]);
+        },
+        Fault::StackOverIe { pc: 0x0153 },
+    );
+}
+
+/// The trace ring holds the instructions up to an illegal opcode, with the registers before each.
+#[test]
+fn trace_ring() {
+    let (_, gb) = run_to_fault(|rom| {
+        rom.nop();
+        rom.ld_a(0x12);
+        rom.bytes(&[0xD3]);
+    });
+    let traced: Vec<_> = gb
+        .recent_instructions()
+        .iter()
+        .rev()
+        .take(3)
+        .rev()
+        .map(|executed| (executed.state.pc, executed.opcode, executed.state.af >> 8))
+        .collect();
+    assert_eq!(
+        traced,
+        [
+            (0x0150, 0x00, traced[0].2),
+            (0x0151, 0x3E, traced[0].2),
+            (0x0153, 0xD3, 0x12),
+        ]
+    );
+}
@@ -0,0 +1,306 @@
+//! What the PPU draws, how long it takes about it, and the ways the frame is shown.
+
+mod common;
+
+use common::{done, lcd_off_as, run_until_done};
+use ferrum::frontend::{terminal, PixelFormat};
+use ferrum::gb::GameBoy;
+use ferrum::ppu::{LcdOffPolicy, PpuAccuracy, SCREEN_WIDTH, WIDESCREEN_BORDER, WIDESCREEN_WIDTH};
+use ferrum::testrom::TestRom;
+
+/// Run the boot ROM, then scroll the logo 64 pixels left, halfway off the screen, with or without widescreen
+/// and interlacing.
+fn scrolled_logo(widescreen: bool, interlaced: bool) -> GameBoy {
+    let mut rom = TestRom::new("TEST");
+    rom.di();
+    rom.ld_a(0x40);
+    rom.ldh_write(0x43);
+    done(&mut rom);
+    rom.end();
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.set_ppu_accuracy(PpuAccuracy::Scanline);
+    gb.set_widescreen(widescreen);
+    gb.set_interlaced(interlaced);
+    assert!(run_until_done(&mut gb), "the test ROM never finished");
+    // Two more frames, for a whole one with the logo scrolled.
+    gb.run_frame();
+    gb.run_frame();
+    gb
+}
+
+/// Widescreen shows the background beside the screen, and leaves the screen itself as it was.
+#[test]
+fn widescreen() {
+    let narrow = scrolled_logo(false, false);
+    let wide = scrolled_logo(true, false);
+    let frame = wide.widescreen().expect("no widescreen frame");
+    assert!(
+        narrow.viewport() == wide.viewport(),
+        "the screen changed with widescreen on"
+    );
+    let middle: Vec<u32> = frame
+        .chunks_exact(WIDESCREEN_WIDTH)
+        .flat_map(|row| &row[WIDESCREEN_BORDER..WIDESCREEN_BORDER + SCREEN_WIDTH])
+        .copied()
+        .collect();
+    assert!(
+        middle == *wide.viewport(),
+        "the middle of the widescreen frame isn't the screen"
+    );
+    // The logo starts 32 pixels into the map, so scrolled by 64, its left part is beside the screen.
+    assert!(
+        frame
+            .chunks_exact(WIDESCREEN_WIDTH)
+            .any(|row| row[..WIDESCREEN_BORDER]
+                .iter()
+                .any(|&pixel| pixel != frame[0])),
+        "nothing of the logo left of the screen"
+    );
+}
+
+/// Interlacing draws the same still picture over two frames, one half of the lines per frame.
+#[test]
+fn interlaced() {
+    let full = scrolled_logo(false, false);
+    let mut gb = scrolled_logo(false, true);
+    assert!(
+        full.viewport() == gb.viewport(),
+        "the picture differs from the one drawn without interlacing"
+    );
+
+    // Inverting the palette changes every pixel, but only on the lines being drawn.
+    let before = gb.viewport();
+    gb.poke(0xFF47, !gb.peek(0xFF47));
+    let mut changed = Vec::new();
+    for _ in 0..2 {
+        gb.run_frame();
+        let rows = gb
+            .viewport()
+            .chunks_exact(SCREEN_WIDTH)
+            .zip(before.chunks_exact(SCREEN_WIDTH))
+            .filter(|(row, old)| row != old)
+            .count();
+        changed.push(rows);
+    }
+    assert_eq!(changed, [72, 144], "lines changed over two frames");
+}
+
+/// A save state keeps which half of the lines the next interlaced frame draws.
+#[test]
+fn interlaced_state() {
+    let mut gb = scrolled_logo(false, true);
+    gb.poke(0xFF47, !gb.peek(0xFF47));
+    let state = gb.save_state();
+    gb.run_frame();
+
+    // A frame further along, so it would draw the other half.
+    let mut loaded = scrolled_logo(false, true);
+    loaded.run_frame();
+    loaded.load_state(&state).unwrap();
+    loaded.run_frame();
+    assert!(
+        loaded.viewport() == gb.viewport(),
+        "drew the other half of the lines after loading"
+    );
+}
+
+/// Loading a state puts its screen in the middle of the widescreen frame, the sides wait for the next frame.
+#[test]
+fn widescreen_state() {
+    let state = scrolled_logo(false, false).save_state();
+    let mut gb = scrolled_logo(true, false);
+    gb.poke(0xFF47, !gb.peek(0xFF47));
+    gb.run_frame();
+    gb.load_state(&state).unwrap();
+    let frame = gb.widescreen().expect("no widescreen frame");
+    let middle: Vec<u32> = frame
+        .chunks_exact(WIDESCREEN_WIDTH)
+        .flat_map(|row| &row[WIDESCREEN_BORDER..WIDESCREEN_BORDER + SCREEN_WIDTH])
+        .copied()
+        .collect();
+    assert!(
+        middle == *gb.viewport(),
+        "the middle of the widescreen frame isn't the loaded screen"
+    );
+}
+
+fn white(gb: &GameBoy) -> bool {
+    gb.viewport().iter().all(|&pixel| pixel == 0x00FFFFFF)
+}
+
+/// With the LCD off the screen is white.
+#[test]
+fn lcd_off_blank_screen() {
+    assert!(white(&lcd_off_as(LcdOffPolicy::Blank)));
+}
+
+/// With the hold policy, it still shows the boot ROM's logo.
+#[test]
+fn lcd_off_hold_frame() {
+    assert!(
+        !white(&lcd_off_as(LcdOffPolicy::Hold)),
+        "the logo went blank with the LCD off"
+    );
+}
+
+/// Frames encode into each pixel format byte for byte.
+#[test]
+fn pixel_formats() {
+    let formats = [
+        (PixelFormat::Xrgb8888, vec![0x56, 0x34, 0x12, 0x00]),
+        (PixelFormat::Rgba8888, vec![0x12, 0x34, 0x56, 0xFF]),
+        (PixelFormat::Bgra8888, vec![0x56, 0x34, 0x12, 0xFF]),
+        // 00010 001101 01010
+        (PixelFormat::Rgb565, vec![0xAA, 0x11]),
+    ];
+    for (format, expected) in formats {
+        let mut pixels = Vec::new();
+        format.encode(&[0x00123456], &mut pixels);
+        assert_eq!(pixels, expected, "{:?}", format);
+    }
+}
+
+/// The viewport as RGBA8888 is the viewport.
+#[test]
+fn viewport_encoded() {
+    let gb = scrolled_logo(false, false);
+    let expected: Vec<u8> = gb
+        .viewport()
+        .iter()
+        .flat_map(|pixel| {
+            let [b, g, r, _] = pixel.to_le_bytes();
+            [r, g, b, 0xFF]
+        })
+        .collect();
+    assert!(gb.viewport_encoded(PixelFormat::Rgba8888) == expected);
+}
+
+/// A 2x3 frame in half blocks: the top pixels in the foreground, the bottom ones in the background,
+/// colors only given when they change, and the odd row out over black.
+#[test]
+fn terminal_half_blocks() {
+    let frame = [0xFF0000, 0xFF0000, 0x0000FF, 0x00FF00, 0x123456, 0x123456];
+    let mut text = String::new();
+    terminal::half_blocks(&frame, 2, &mut text);
+    assert_eq!(
+        text,
+        concat!(
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[48;2;0;255;0m▀\x1b[0m\n",
+            "\x1b[38;2;18;52;86m\x1b[48;2;0;0;0m▀▀\x1b[0m\n",
+        )
+    );
+}
+
+/// How long Mode 3 lasts on lines 10-25, in dots, give or take the 4 of an instruction.
+/// The machine turns the LCD off, setup writes OAM and the registers, then the LCD is turned on with LCDC
+/// and the CPU runs NOPs, reading STAT after every one.
+fn mode3_dots(accuracy: PpuAccuracy, lcdc: u8, setup: impl Fn(&mut GameBoy)) -> u32 {
+    let mut rom = TestRom::new("TEST");
+    rom.di();
+    rom.ld_a(0x00);
+    rom.ldh_write(0x40);
+    let sled = rom.here();
+    for _ in 0..1000 {
+        rom.nop();
+    }
+    rom.jp(sled);
+
+    let mut gb = GameBoy::from_rom(rom.build(), None);
+    gb.set_serial_output(false);
+    gb.set_ppu_accuracy(accuracy);
+    gb.skip_boot();
+    while gb.peek(0xFF40) & 0x80 != 0 {
+        gb.step();
+    }
+    setup(&mut gb);
+    gb.poke(0xFF40, lcdc);
+
+    let mut steps = [0u32; 26];
+    loop {
+        gb.step();
+        let ly = gb.peek(0xFF44) as usize;
+        if ly == steps.len() {
+            break;
+        }
+        if ly >= 10 && gb.peek(0xFF41) & 0x03 == 3 {
+            steps[ly] += 1;
+        }
+    }
+    // The most common count, a JP back to the start of the NOPs takes longer.
+    let lines = &steps[10..];
+    let most = lines
+        .iter()
+        .max_by_key(|&&n| lines.iter().filter(|&&m| m == n).count())
+        .copied()
+        .unwrap_or_default();
+    most * 4
+}
+
+/// Sprites 8x16 pixels tall at Y=26, on lines 10-25, at these X.
+fn sprites_at(xs: &[u8]) -> impl Fn(&mut GameBoy) + '_ {
+    move |gb| {
+        for (i, &x) in xs.iter().enumerate() {
+            let addr = 0xFE00 + i as u16 * 4;
+            gb.poke(addr, 26);
+            gb.poke(addr + 1, x);
+        }
+    }
+}
+
+/// LCD and BG on, 8x16 sprites on, the window on (or not), its map at $9C00.
+const LCDC: u8 = 0x87;
+const LCDC_WINDOW: u8 = 0xE7;
+
+/// Mode 3 lasts expected dots with the given setup, within an instruction.
+fn mode3(accuracy: PpuAccuracy, lcdc: u8, setup: impl Fn(&mut GameBoy), expected: u32) {
+    let dots = mode3_dots(accuracy, lcdc, setup);
+    assert!(
+        dots.abs_diff(expected) < 4,
+        "{} dots, not {}",
+        dots,
+        expected
+    );
+}
+
+// Mode 3 is longer by SCX % 8, the window, and sprites (the first 10 on the line only), so the mode 0 STAT
+// interrupt fires when it does on hardware, with both pipelines. Mooneye's intr_2_mode0_timing_sprites has the
+// same cases, the timings are from Pan Docs.
+
+#[test]
+fn mode3_length() {
+    mode3(PpuAccuracy::Scanline, LCDC, |_| (), 172);
+}
+
+#[test]
+fn mode3_fine_scroll() {
+    mode3(PpuAccuracy::Scanline, LCDC, |gb| gb.poke(0xFF43, 5), 177);
+}
+
+#[test]
+fn mode3_window() {
+    let window = |gb: &mut GameBoy| {
+        gb.poke(0xFF4A, 0);
+        gb.poke(0xFF4B, 87);
+    };
+    mode3(PpuAccuracy::Scanline, LCDC_WINDOW, window, 178);
+}
+
+#[test]
+fn mode3_sprites() {
+    mode3(PpuAccuracy::Scanline, LCDC, sprites_at(&[0; 10]), 282);
+    mode3(PpuAccuracy::Fifo, LCDC, sprites_at(&[0; 10]), 282);
+}
+
+#[test]
+fn mode3_sprite_overflow() {
+    mode3(PpuAccuracy::Scanline, LCDC, sprites_at(&[0; 12]), 282);
+    mode3(PpuAccuracy::Fifo, LCDC, sprites_at(&[0; 12]), 282);
+}
+
+/// 11 for the first sprite on the tile under X=8, 6 for the second.
+#[test]
+fn mode3_sprites_on_a_tile() {
+    mode3(PpuAccuracy::Scanline, LCDC, sprites_at(&[8, 10]), 189);
+}